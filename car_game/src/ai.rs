@@ -0,0 +1,82 @@
+use car_sim::gym::{Simulator, SimConfig};
+use car_sim::physics::CarState;
+use car_sim::map::Road;
+use car_sim::policy::Policy;
+use car_sim::recorder::Demonstrations;
+
+
+/// A car driven by a policy in its own simulator, stepped in real time with the fixed time step
+/// of the simulator config. The car restarts at the start of the track whenever it crashes.
+pub struct AiDriver<R: Road, P: Policy> {
+    sim: Simulator<R>,
+    policy: P,
+    observation: Vec<f32>,
+    /// Time not yet simulated
+    pending_time: f32,
+    demonstrations: Option<Demonstrations>,
+}
+
+
+impl<R: Road, P: Policy> AiDriver<R, P> {
+    pub fn new(config: SimConfig, road: R, policy: P) -> Self {
+        let sim = Simulator::new(config, road, None);
+        let observation = vec![0.0; sim.observation_dim()];
+        let mut this = Self { sim, policy, observation, pending_time: 0.0, demonstrations: None };
+        this.restart();
+        this
+    }
+
+    pub fn observation_dim(&self) -> usize {
+        self.observation.len()
+    }
+
+    /// Place the car at the start of the track
+    pub fn restart(&mut self) {
+        self.restart_at(0.0);
+    }
+
+    /// Start a new episode with the car on the centerline at a distance 'progress' along it
+    pub fn restart_at(&mut self, progress: f32) {
+        self.sim.reset(None);
+        let (position, unit_forward) = self.sim.road.pose_at(progress);
+        self.sim.state = CarState { position, unit_forward, ..CarState::default() };
+    }
+
+    /// Advance the simulation by 'dt' of real time, returning whether the car restarted
+    pub fn update(&mut self, dt: f32) -> bool {
+        let mut restarted = false;
+        self.pending_time += dt;
+        while self.pending_time >= self.sim.config.dt {
+            self.pending_time -= self.sim.config.dt;
+            self.sim.observe_into(&mut self.observation);
+            let action = self.policy.act(&self.observation);
+            let transition = self.sim.step(action);
+            if let Some(demonstrations) = &mut self.demonstrations {
+                demonstrations.push(self.observation.clone(), action as u8, transition.reward, transition.done);
+            }
+            if transition.done {
+                self.restart();
+                restarted = true;
+            }
+        }
+        restarted
+    }
+
+    /// Record the observations and actions of the following steps, e.g. to learn from a person
+    /// driving with 'KeyboardPolicy'
+    pub fn start_demonstrations(&mut self) {
+        self.demonstrations = Some(Demonstrations::default());
+    }
+
+    pub fn demonstrations(&self) -> Option<&Demonstrations> {
+        self.demonstrations.as_ref()
+    }
+
+    pub fn state(&self) -> &CarState {
+        &self.sim.state
+    }
+
+    pub fn sim(&self) -> &Simulator<R> {
+        &self.sim
+    }
+}
@@ -0,0 +1,109 @@
+use std::f32::consts::FRAC_PI_2;
+
+use macroquad::prelude as mq;
+
+use graphics_utils::ScreenTransform;
+use car_sim::physics::CarState;
+use car_sim::map::Road;
+
+
+/// Fraction of the screen covered by the road in the fixed overhead view
+const FIXED_MARGIN: f32 = 0.95;
+/// Zoom factor per step of the mouse wheel in the free view
+const ZOOM_STEP: f32 = 1.1;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// An overhead view of the whole road
+    Fixed,
+    /// Centered on the car, with north up
+    Follow,
+    /// Centered on the car, rotating so that the car points up
+    CarUp,
+    /// Panned by dragging with the left mouse button and zoomed with the mouse wheel
+    Free,
+}
+
+
+impl CameraMode {
+    pub fn next(self) -> Self {
+        match self {
+            CameraMode::Fixed => CameraMode::Follow,
+            CameraMode::Follow => CameraMode::CarUp,
+            CameraMode::CarUp => CameraMode::Free,
+            CameraMode::Free => CameraMode::Fixed,
+        }
+    }
+}
+
+
+/// Moves the screen transform each frame according to a camera mode
+pub struct Camera {
+    pub mode: CameraMode,
+    /// Zoom of the views following the car
+    follow_px_per_m: f32,
+    /// Mouse position of the last frame in the free view while dragging
+    last_mouse: Option<mq::Vec2>,
+}
+
+
+impl Camera {
+    pub fn new(mode: CameraMode, follow_px_per_m: f32) -> Self {
+        Self { mode, follow_px_per_m, last_mouse: None }
+    }
+
+    /// Switch to the next camera mode. The free view starts from the current view.
+    pub fn cycle_mode(&mut self) {
+        self.mode = self.mode.next();
+        self.last_mouse = None;
+    }
+
+    pub fn update<R: Road>(&mut self, transform: &mut ScreenTransform, state: &CarState, road: &R) {
+        match self.mode {
+            CameraMode::Fixed => {
+                let (min, max) = road.bounds();
+                let size = max - min;
+                let px_per_m = (mq::screen_width() / size.0).min(mq::screen_height() / size.1) * FIXED_MARGIN;
+                transform.set_center((min + max)*0.5);
+                transform.set_px_per_m(px_per_m);
+                transform.set_rotation(0.0);
+            },
+            CameraMode::Follow => {
+                transform.set_center(state.position);
+                transform.set_px_per_m(self.follow_px_per_m);
+                transform.set_rotation(0.0);
+            },
+            CameraMode::CarUp => {
+                transform.set_center(state.position);
+                transform.set_px_per_m(self.follow_px_per_m);
+                transform.set_rotation(state.unit_forward.angle() - FRAC_PI_2);
+            },
+            CameraMode::Free => self.update_free(transform),
+        }
+    }
+
+    fn update_free(&mut self, transform: &mut ScreenTransform) {
+        let mouse = mq::Vec2::from(mq::mouse_position());
+
+        // Keep the world point under the mouse fixed while dragging
+        if mq::is_mouse_button_down(mq::MouseButton::Left) {
+            if let Some(last) = self.last_mouse {
+                let shift = transform.to_world(mouse) - transform.to_world(last);
+                transform.set_center(transform.center() - shift);
+            }
+            self.last_mouse = Some(mouse);
+        } else {
+            self.last_mouse = None;
+        }
+
+        // Zoom about the point under the mouse
+        let (_, wheel) = mq::mouse_wheel();
+        if wheel != 0.0 {
+            let before = transform.to_world(mouse);
+            transform.set_px_per_m(transform.px_per_m() * ZOOM_STEP.powf(wheel.signum()));
+            let after = transform.to_world(mouse);
+            transform.set_center(transform.center() + before - after);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+
+use macroquad::prelude as mq;
+
+
+/// Records the screen to an animated GIF. Frames are encoded on a background thread while
+/// recording, so that the game keeps running smoothly.
+pub struct GifRecorder {
+    path: PathBuf,
+    sender: mpsc::Sender<mq::Image>,
+    worker: JoinHandle<std::io::Result<()>>,
+    /// Time between captured frames, in seconds
+    frame_interval: f32,
+    /// Time since the last captured frame
+    elapsed: f32,
+}
+
+
+impl GifRecorder {
+    /// Start recording to a GIF file at 'path' with 'fps' frames per second
+    pub fn start<P: AsRef<Path>>(path: P, fps: f32) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let (sender, receiver) = mpsc::channel();
+        // GIF frame delays are in hundredths of a second
+        let delay = (100.0 / fps).round() as u16;
+        let file_path = path.clone();
+        let worker = std::thread::spawn(move || encode_gif(&file_path, receiver, delay));
+        Self { path, sender, worker, frame_interval: 1.0 / fps, elapsed: 0.0 }
+    }
+
+    /// Advance the clock of the recording by 'dt', capturing the screen when a frame is due. Call
+    /// after drawing a frame and before 'next_frame'.
+    pub fn capture(&mut self, dt: f32) {
+        self.elapsed += dt;
+        if self.elapsed < self.frame_interval {
+            return;
+        }
+        self.elapsed %= self.frame_interval;
+        // A failed encoder has dropped its receiver and reports its error in 'finish'
+        let _ = self.sender.send(mq::get_screen_data());
+    }
+
+    /// Stop recording and wait for the remaining frames to be encoded, returning the path of the
+    /// file
+    pub fn finish(self) -> std::io::Result<PathBuf> {
+        drop(self.sender);
+        self.worker.join().expect("the encoder thread not to panic")?;
+        Ok(self.path)
+    }
+}
+
+
+/// Encode the screen images from 'receiver' until the channel closes. The size of the GIF is
+/// given by the first frame, and frames of another size, e.g. after resizing the window, are
+/// skipped.
+fn encode_gif(path: &Path, receiver: mpsc::Receiver<mq::Image>, delay: u16) -> std::io::Result<()> {
+    let mut encoder = None;
+    for image in receiver {
+        let (width, height) = (image.width, image.height);
+        let encoder = match &mut encoder {
+            Some(encoder) => encoder,
+            None => {
+                let file = BufWriter::new(File::create(path)?);
+                let mut new_encoder = gif::Encoder::new(file, width, height, &[]).map_err(std::io::Error::other)?;
+                new_encoder.set_repeat(gif::Repeat::Infinite).map_err(std::io::Error::other)?;
+                encoder.insert((new_encoder, width, height))
+            },
+        };
+        if (encoder.1, encoder.2) != (width, height) {
+            continue;
+        }
+
+        // The screen is read bottom row first
+        let row_bytes = 4*width as usize;
+        let mut pixels: Vec<u8> = image.bytes.chunks_exact(row_bytes).rev().flatten().copied().collect();
+        // Transparent pixels would show the background of the viewer instead
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel[3] = 255;
+        }
+        let mut frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+        frame.delay = delay;
+        encoder.0.write_frame(&frame).map_err(std::io::Error::other)?;
+    }
+    Ok(())
+}
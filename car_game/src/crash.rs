@@ -0,0 +1,56 @@
+use macroquad::prelude as mq;
+
+use graphics_utils::{ScreenTransform, draw_world_circle_lines, draw_world_text};
+use math_utils::Vec2;
+
+
+/// Time after a crash during which the game is frozen
+const FREEZE_TIME: f32 = 0.4;
+/// Time over which the red flash of a crash fades out
+const FLASH_TIME: f32 = 0.3;
+/// Time after a crash until the car respawns and the crash marker disappears
+const RESPAWN_DELAY: f32 = 1.5;
+/// Radius of the marker around the crash location, in meters
+const MARKER_RADIUS: f32 = 3.0;
+
+
+/// Feedback for a crash of the player: a red flash, a brief freeze of the game and a marker at
+/// the crash location until the car respawns
+pub struct Crash {
+    pub position: Vec2,
+    /// Whether the game should respawn the car once the crash is over, instead of the car being
+    /// respawned already, e.g. by its simulator
+    pub respawns: bool,
+    /// Game time since the crash
+    elapsed: f32,
+}
+
+
+impl Crash {
+    pub fn new(position: Vec2, respawns: bool) -> Self {
+        Self { position, respawns, elapsed: 0.0 }
+    }
+
+    /// Advance the crash by 'dt' of game time
+    pub fn update(&mut self, dt: f32) {
+        self.elapsed += dt;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.elapsed < FREEZE_TIME
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.elapsed >= RESPAWN_DELAY
+    }
+
+    pub fn draw(&self, transform: &ScreenTransform) {
+        draw_world_circle_lines(self.position, MARKER_RADIUS, 3.0, transform, mq::RED);
+        draw_world_text("CRASH", self.position + Vec2(0.0, 1.5*MARKER_RADIUS), transform, 30.0, mq::RED);
+
+        let flash = 1.0 - self.elapsed / FLASH_TIME;
+        if flash > 0.0 {
+            mq::draw_rectangle(0.0, 0.0, mq::screen_width(), mq::screen_height(), mq::Color { a: 0.5*flash, ..mq::RED });
+        }
+    }
+}
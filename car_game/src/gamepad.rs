@@ -0,0 +1,84 @@
+use std::cell::RefCell;
+
+use gilrs::{Gilrs, Axis, Button};
+
+use car_sim::physics::{CarInput, CarConfig};
+
+use crate::input::CarInputSource;
+
+
+pub struct GamepadConfig {
+    /// Stick deflection below which the steering is zero
+    pub steer_dead_zone: f32,
+    /// Trigger pressure below which throttle and brake are released
+    pub trigger_dead_zone: f32,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self { steer_dead_zone: 0.1, trigger_dead_zone: 0.05 }
+    }
+}
+
+
+/// Map a value in [-1, 1] to zero inside the dead zone, and linearly to [-1, 1] outside it
+fn apply_dead_zone(value: f32, dead_zone: f32) -> f32 {
+    if value.abs() <= dead_zone {
+        0.0
+    } else {
+        value.signum() * (value.abs() - dead_zone) / (1.0 - dead_zone)
+    }
+}
+
+
+/// Analog input from the first connected gamepad, steering with the left stick, accelerating with
+/// the right trigger and braking with the left trigger. Falls back to the keyboard when no
+/// gamepad is connected.
+pub struct GamepadInput<K: CarInputSource> {
+    /// Missing if gamepads are not supported on this system
+    gilrs: Option<RefCell<Gilrs>>,
+    config: GamepadConfig,
+    keyboard: K,
+}
+
+impl<K: CarInputSource> GamepadInput<K> {
+    pub fn new(config: GamepadConfig, keyboard: K) -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(RefCell::new(gilrs)),
+            Err(err) => {
+                eprintln!("Gamepads unavailable, using the keyboard: {}", err);
+                None
+            },
+        };
+        Self { gilrs, config, keyboard }
+    }
+}
+
+
+impl<K: CarInputSource> CarInputSource for GamepadInput<K> {
+    fn read(&self, config: &CarConfig) -> CarInput {
+        let Some(gilrs) = &self.gilrs else {
+            return self.keyboard.read(config);
+        };
+        let mut gilrs = gilrs.borrow_mut();
+
+        // Process the pending events, which updates the state of the gamepads
+        while gilrs.next_event().is_some() {}
+        let Some((_, gamepad)) = gilrs.gamepads().next() else {
+            return self.keyboard.read(config);
+        };
+
+        let trigger = |button: Button| {
+            let value = gamepad.button_data(button).map_or(0.0, |data| data.value());
+            apply_dead_zone(value, self.config.trigger_dead_zone)
+        };
+        // Pushing the stick right steers right, which is a negative steering angle
+        let steer = apply_dead_zone(gamepad.value(Axis::LeftStickX), self.config.steer_dead_zone);
+
+        CarInput {
+            target_delta: -steer*config.max_delta,
+            forward_acc: trigger(Button::RightTrigger2)*config.acceleration,
+            braking: trigger(Button::LeftTrigger2) > 0.0,
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use car_sim::physics::CarState;
+
+
+/// Records the states of the car through each lap, and keeps the states of the fastest lap to
+/// replay as a ghost car on the following laps
+#[derive(Default)]
+pub struct Ghost {
+    /// (lap time, state) of the current lap, by increasing time
+    current: Vec<(f32, CarState)>,
+    best: Vec<(f32, CarState)>,
+    best_time: Option<f32>,
+}
+
+
+impl Ghost {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the state of the car at time 'lap_time' into the current lap
+    pub fn record(&mut self, lap_time: f32, state: &CarState) {
+        self.current.push((lap_time, state.clone()));
+    }
+
+    /// End the current lap, which took 'lap_time', keeping it if it is the fastest so far
+    pub fn finish_lap(&mut self, lap_time: f32) {
+        if self.best_time.is_none_or(|best_time| lap_time < best_time) {
+            self.best_time = Some(lap_time);
+            std::mem::swap(&mut self.current, &mut self.best);
+        }
+        self.current.clear();
+    }
+
+    /// Drop the current lap without finishing it, e.g. when the car respawns
+    pub fn abandon_lap(&mut self) {
+        self.current.clear();
+    }
+
+    /// The state of the best lap at time 'lap_time' into it, if a lap has been finished. The
+    /// ghost stays at the finish line once its lap is over.
+    pub fn state_at(&self, lap_time: f32) -> Option<&CarState> {
+        let idx = self.best.partition_point(|&(t, _)| t < lap_time);
+        self.best.get(idx.min(self.best.len().checked_sub(1)?)).map(|(_, state)| state)
+    }
+}
@@ -1,10 +1,10 @@
 use macroquad::prelude as mq;
 
 use car_sim::physics::{CarState, CarConfig};
-use car_sim::map::{CellMap, SplineMap, Road};
+use car_sim::map::{Cell, CellMap, SplineMap, WallMap, Road, Obstacle, ObstacleShape, RoadSamples};
 use car_sim::lidar::{LidarArray};
 use math_utils::Vec2;
-use graphics_utils::{ScreenTransform, draw_spline};
+use graphics_utils::{ScreenTransform, draw_spline, draw_world_circle};
 
 // Ratio width/length of car graphic
 const WIDTH_RATIO: f32 = 0.5;
@@ -12,6 +12,14 @@ const WIDTH_RATIO: f32 = 0.5;
 // Relative length of wheel to display
 const WHEEL_LENGTH: f32 = 0.2;
 
+// Size of the kerb stripes along the road edges, in meters
+const KERB_LENGTH: f32 = 2.0;
+const KERB_WIDTH: f32 = 0.5;
+
+// Number of checker rows of the start line, and the approximate size of its squares in meters
+const START_LINE_ROWS: usize = 2;
+const CHECKER_SIZE: f32 = 1.0;
+
 
 /// A trait for Road implentations that can be drawn to screen
 pub trait DrawRoad: Road {
@@ -20,9 +28,17 @@ pub trait DrawRoad: Road {
 
 
 pub fn draw_car(state: &CarState, config: &CarConfig, transform: &ScreenTransform) {
+    draw_car_with_color(state, config, transform, mq::BLUE);
+}
+
+
+pub fn draw_car_with_color(state: &CarState, config: &CarConfig, transform: &ScreenTransform, color: mq::Color) {
     // Car position represents the position of the center of the back axle
     // A physical coordinate of (0,0) should be at the center of the screen
 
+    // Wheels and outline are as translucent as the body, e.g. for a ghost car
+    let with_alpha = |other: mq::Color| mq::Color { a: color.a, ..other };
+
     let back_axle_center = state.position;
     let back_center = state.position - state.unit_forward * config.back_axle;
     let unit_left = state.unit_forward.rotate90();
@@ -48,7 +64,7 @@ pub fn draw_car(state: &CarState, config: &CarConfig, transform: &ScreenTransfor
 
         let back_point = transform.to_screen(center - half_wheel);
         let front_point = transform.to_screen(center + half_wheel);
-        mq::draw_line(back_point.x, back_point.y, front_point.x, front_point.y, 5.0, mq::BLACK);
+        mq::draw_line(back_point.x, back_point.y, front_point.x, front_point.y, 5.0, with_alpha(mq::BLACK));
     };
 
     // Draw the wheels
@@ -64,36 +80,39 @@ pub fn draw_car(state: &CarState, config: &CarConfig, transform: &ScreenTransfor
     let fr_corner = transform.to_screen(fr_corner);
     mq::draw_triangle(bl_corner,
                       fl_corner,
-                      fr_corner, mq::BLUE);
+                      fr_corner, color);
     mq::draw_triangle(fr_corner,
                       br_corner,
-                      bl_corner, mq::BLUE);
+                      bl_corner, color);
 
-    mq::draw_line(bl_corner.x, bl_corner.y, fl_corner.x, fl_corner.y, 3.0, mq::RED);
-    mq::draw_line(fl_corner.x, fl_corner.y, fr_corner.x, fr_corner.y, 3.0, mq::GREEN);
-    mq::draw_line(fr_corner.x, fr_corner.y, br_corner.x, br_corner.y, 3.0, mq::RED);
-    mq::draw_line(br_corner.x, br_corner.y, bl_corner.x, bl_corner.y, 3.0, mq::GREEN);
+    mq::draw_line(bl_corner.x, bl_corner.y, fl_corner.x, fl_corner.y, 3.0, with_alpha(mq::RED));
+    mq::draw_line(fl_corner.x, fl_corner.y, fr_corner.x, fr_corner.y, 3.0, with_alpha(mq::GREEN));
+    mq::draw_line(fr_corner.x, fr_corner.y, br_corner.x, br_corner.y, 3.0, with_alpha(mq::RED));
+    mq::draw_line(br_corner.x, br_corner.y, bl_corner.x, bl_corner.y, 3.0, with_alpha(mq::GREEN));
 }
 
 
 impl DrawRoad for CellMap {
     fn draw_road(&self, transform: &ScreenTransform) {
-        for i in 0 .. &self.cells.len()-1 {
-            let cell1 = &self.cells[i];
-            let cell2 = &self.cells[i+1];
-
-            let left = cell1.0.min(cell2.0) as f32 - 0.45;
-            let right = cell1.0.max(cell2.0) as f32 + 0.45;
-            let bottom = cell1.1.min(cell2.1) as f32 - 0.45;
-            let top = cell1.1.max(cell2.1) as f32 + 0.45;
-
-            let top_left = transform.to_screen(Vec2(left, top)*self.cell_size);
-            let top_right = transform.to_screen(Vec2(right, top)*self.cell_size);
-            let bottom_left = transform.to_screen(Vec2(left, bottom)*self.cell_size);
-            let bottom_right = transform.to_screen(Vec2(right, bottom)*self.cell_size);
-
-            mq::draw_triangle(top_left, top_right, bottom_right, mq::GRAY);
-            mq::draw_triangle(bottom_right, bottom_left, top_left, mq::GRAY);
+        let draw_quad = |corners: [Vec2; 4]| {
+            let [a, b, c, d] = corners.map(|corner| transform.to_screen(corner*self.cell_size));
+            mq::draw_triangle(a, b, c, mq::GRAY);
+            mq::draw_triangle(c, d, a, mq::GRAY);
+        };
+        let center = |Cell(x, y): Cell| Vec2(x as f32, y as f32);
+
+        // Draw a square in each cell, with gaps between cells that are not linked
+        for cell in self.all_cells() {
+            let c = center(cell);
+            draw_quad([c + Vec2(-0.45, -0.45), c + Vec2(0.45, -0.45), c + Vec2(0.45, 0.45), c + Vec2(-0.45, 0.45)]);
+        }
+
+        // Close the gaps between linked cells, with a band through the shared corner of diagonal ones
+        for (cell1, cell2) in self.links() {
+            let (c1, c2) = (center(cell1), center(cell2));
+            let step = c2 - c1;
+            let half_width = if step.0 != 0.0 && step.1 != 0.0 { Vec2(step.0, -step.1)*0.25 } else { step.rotate90()*0.45 };
+            draw_quad([c1 + half_width, c2 + half_width, c2 - half_width, c1 - half_width]);
         }
     }
 }
@@ -101,10 +120,93 @@ impl DrawRoad for CellMap {
 
 impl DrawRoad for SplineMap {
     fn draw_road(&self, transform: &ScreenTransform) {
-        let segments = 128;
         let color = mq::Color { r: 0.3, g: 0.3, b: 0.3, a: 1.0 };
-        draw_spline(&self.spline, transform, self.width, segments, color);
-        draw_spline(&self.spline, transform, 0.1, segments, mq::WHITE);
+        draw_spline(&self.spline, transform, self.width, color);
+        draw_kerbs(self, transform);
+        draw_start_line(self, transform);
+        draw_spline(&self.spline, transform, 0.1, mq::WHITE);
+        for obstacle in self.all_obstacles() {
+            draw_obstacle(obstacle, transform);
+        }
+    }
+}
+
+
+/// Draw a quadrilateral given by its corners in order around its edge
+fn draw_quad(corners: [Vec2; 4], transform: &ScreenTransform, color: mq::Color) {
+    let [a, b, c, d] = corners.map(|corner| transform.to_screen(corner));
+    mq::draw_triangle(a, b, c, color);
+    mq::draw_triangle(c, d, a, color);
+}
+
+
+/// Draw alternating red and white stripes along both edges of a spline road
+fn draw_kerbs(road: &SplineMap, transform: &ScreenTransform) {
+    let n_segments = (road.spline.total_length() / KERB_LENGTH).ceil().max(1.0) as usize;
+    let RoadSamples { center, left, right } = road.sample_road(n_segments);
+    let n_stripes = if road.spline.is_closed() { center.len() } else { center.len() - 1 };
+
+    for edge in [&left, &right] {
+        // Move a point on the edge the kerb width towards the center
+        let inset = |i: usize| edge[i] + (center[i] - edge[i]).normalized()*KERB_WIDTH;
+        for i in 0 .. n_stripes {
+            let next = (i + 1) % center.len();
+            let color = if i % 2 == 0 { mq::RED } else { mq::WHITE };
+            draw_quad([edge[i], edge[next], inset(next), inset(i)], transform, color);
+        }
+    }
+}
+
+
+/// Draw a checkered start and finish line across a spline road at its start
+fn draw_start_line(road: &SplineMap, transform: &ScreenTransform) {
+    let start = road.spline.get(0.0);
+    let forward = road.spline.tangent(0.0);
+    let lateral = forward.rotate90();
+
+    let n_columns = (road.width / CHECKER_SIZE).round().max(1.0) as usize;
+    let size = road.width / n_columns as f32;
+    let corner = start + lateral*0.5*road.width;
+    for row in 0 .. START_LINE_ROWS {
+        for column in 0 .. n_columns {
+            let color = if (row + column) % 2 == 0 { mq::WHITE } else { mq::BLACK };
+            let a = corner + forward*(row as f32*size) - lateral*(column as f32*size);
+            let b = a + forward*size;
+            let c = b - lateral*size;
+            let d = a - lateral*size;
+            draw_quad([a, b, c, d], transform, color);
+        }
+    }
+}
+
+
+impl DrawRoad for WallMap {
+    fn draw_road(&self, transform: &ScreenTransform) {
+        for wall in [&self.inner, &self.outer] {
+            let points: Vec<mq::Vec2> = wall.points().iter().map(|&point| transform.to_screen(point)).collect();
+            for (i, start) in points.iter().enumerate() {
+                let end = points[(i+1) % points.len()];
+                mq::draw_line(start.x, start.y, end.x, end.y, 3.0, mq::WHITE);
+            }
+        }
+    }
+}
+
+
+pub fn draw_obstacle(obstacle: &Obstacle, transform: &ScreenTransform) {
+    let color = mq::ORANGE;
+    match obstacle.shape {
+        ObstacleShape::Circle { radius } => draw_world_circle(obstacle.center, radius, transform, color),
+        ObstacleShape::Rectangle { length, width } => {
+            let half_forward = obstacle.unit_forward*0.5*length;
+            let half_lateral = obstacle.unit_forward.rotate90()*0.5*width;
+            let bl_corner = transform.to_screen(obstacle.center - half_forward + half_lateral);
+            let br_corner = transform.to_screen(obstacle.center - half_forward - half_lateral);
+            let fl_corner = transform.to_screen(obstacle.center + half_forward + half_lateral);
+            let fr_corner = transform.to_screen(obstacle.center + half_forward - half_lateral);
+            mq::draw_triangle(bl_corner, fl_corner, fr_corner, color);
+            mq::draw_triangle(fr_corner, br_corner, bl_corner, color);
+        }
     }
 }
 
@@ -118,7 +220,7 @@ pub fn draw_lidar(state: &CarState, lidar: &LidarArray, readings: &[f32], transf
     let points = lidar.get_angles().iter().zip(readings)
         .map(|(&angle, &reading)| {
             let direction = state.unit_forward.rotate(angle);
-            transform.to_screen(lidar_pos + direction*reading)
+            transform.to_screen(lidar_pos + direction*reading.abs())
         });
 
     for point in points {
@@ -0,0 +1,82 @@
+use macroquad::prelude as mq;
+
+use car_sim::physics::CarState;
+
+
+const FONT_SIZE: f32 = 24.0;
+const LINE_HEIGHT: f32 = 26.0;
+const MARGIN: f32 = 10.0;
+
+
+/// Times laps by the distance driven along the track, so that driving backwards across the start
+/// line does not count as a lap
+pub struct LapTimer {
+    track_length: f32,
+    /// Distance driven along the track in the current lap
+    distance: f32,
+    lap_start: f32,
+    pub last_lap: Option<f32>,
+    pub best_lap: Option<f32>,
+}
+
+
+impl LapTimer {
+    pub fn new(track_length: f32) -> Self {
+        Self { track_length, distance: 0.0, lap_start: 0.0, last_lap: None, best_lap: None }
+    }
+
+    /// Restart the current lap at time 't'
+    pub fn restart(&mut self, t: f32) {
+        self.distance = 0.0;
+        self.lap_start = t;
+    }
+
+    /// Advance the timer by a step that moves 'progress_delta' along the track and ends at time
+    /// 't', returning the lap time if the step completes a lap
+    pub fn update(&mut self, progress_delta: f32, t: f32) -> Option<f32> {
+        self.distance += progress_delta;
+        if self.distance >= self.track_length {
+            let lap = t - self.lap_start;
+            self.last_lap = Some(lap);
+            self.best_lap = Some(self.best_lap.map_or(lap, |best| best.min(lap)));
+            self.distance -= self.track_length;
+            self.lap_start = t;
+            Some(lap)
+        } else {
+            None
+        }
+    }
+
+    pub fn lap_time(&self, t: f32) -> f32 {
+        t - self.lap_start
+    }
+}
+
+
+/// Format a duration in seconds as minutes, seconds and hundredths
+fn format_time(seconds: f32) -> String {
+    let minutes = (seconds / 60.0).floor();
+    format!("{}:{:05.2}", minutes, seconds - 60.0*minutes)
+}
+
+
+/// Draw the state of the car, the clock and the lap times in the bottom left corner of the
+/// screen, and the reward of the last step if given
+pub fn draw_hud(state: &CarState, t: f32, timer: &LapTimer, reward: Option<f32>) {
+    let mut lines = vec![
+        format!("Speed: {:.0} km/h", state.speed*3.6),
+        format!("Steering: {:+.1} deg", state.steer_delta.to_degrees()),
+        format!("Time: {}", format_time(t)),
+        format!("Lap: {}", format_time(timer.lap_time(t))),
+        format!("Last lap: {}", timer.last_lap.map_or("-".to_string(), format_time)),
+        format!("Best lap: {}", timer.best_lap.map_or("-".to_string(), format_time)),
+    ];
+    if let Some(reward) = reward {
+        lines.push(format!("Reward: {:+.3}", reward));
+    }
+
+    let top = mq::screen_height() - MARGIN - LINE_HEIGHT*lines.len() as f32;
+    for (i, line) in lines.iter().enumerate() {
+        mq::draw_text(line, MARGIN, top + LINE_HEIGHT*(i + 1) as f32, FONT_SIZE, mq::WHITE);
+    }
+}
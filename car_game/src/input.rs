@@ -1,6 +1,10 @@
+use std::cell::Cell;
+
 use macroquad::prelude as mq;
 
 use car_sim::physics::{CarInput, CarConfig};
+use car_sim::gym::Action;
+use car_sim::policy::Policy;
 
 
 pub struct InputKeycodes {
@@ -64,3 +68,71 @@ impl CarInputSource for KeyboardInput {
     }
 }
 
+
+
+/// Moves the steering of another input source towards its target at a limited rate, so that
+/// digital keys give smooth steering ramps instead of jumping between full left and right
+pub struct SlidingInput<S: CarInputSource> {
+    source: S,
+    /// Rate of change of the steering target, in radians per second
+    steer_rate: f32,
+    target_delta: Cell<f32>,
+}
+
+impl<S: CarInputSource> SlidingInput<S> {
+    pub fn new(source: S, steer_rate: f32) -> Self {
+        Self { source, steer_rate, target_delta: Cell::new(0.0) }
+    }
+}
+
+
+impl<S: CarInputSource> CarInputSource for SlidingInput<S> {
+    fn read(&self, config: &CarConfig) -> CarInput {
+        let input = self.source.read(config);
+        let max_change = self.steer_rate * mq::get_frame_time();
+        let current = self.target_delta.get();
+        let target_delta = current + (input.target_delta - current).clamp(-max_change, max_change);
+        self.target_delta.set(target_delta);
+        CarInput { target_delta, ..input }
+    }
+}
+
+
+/// Drives with the discrete actions of the gym environment from the keyboard, so that a person
+/// can give demonstrations for a policy. Steering takes precedence over the pedals, and the car
+/// coasts when no key is held.
+pub struct KeyboardPolicy {
+    keycodes: InputKeycodes,
+}
+
+impl KeyboardPolicy {
+    pub fn new(keycodes: InputKeycodes) -> Self {
+        Self { keycodes }
+    }
+}
+
+impl Default for KeyboardPolicy {
+    fn default() -> Self {
+        let KeyboardInput { keycodes } = KeyboardInput::default();
+        Self { keycodes }
+    }
+}
+
+
+impl Policy for KeyboardPolicy {
+    fn act(&mut self, _observation: &[f32]) -> Action {
+        let left = mq::is_key_down(self.keycodes.left);
+        let right = mq::is_key_down(self.keycodes.right);
+        if left && !right {
+            Action::Left
+        } else if right && !left {
+            Action::Right
+        } else if mq::is_key_down(self.keycodes.accelerate) {
+            Action::Accelerate
+        } else if mq::is_key_down(self.keycodes.brake) {
+            Action::Brake
+        } else {
+            Action::Coast
+        }
+    }
+}
@@ -1,2 +1,11 @@
 pub mod graphics;
 pub mod input;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod capture;
+pub mod camera;
+pub mod hud;
+pub mod ai;
+pub mod ghost;
+pub mod time_control;
+pub mod crash;
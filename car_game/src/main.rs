@@ -5,65 +5,334 @@ use graphics_utils::{ScreenTransform};
 
 use car_sim::physics::{CarState, CarConfig};
 use car_sim::lidar::{LidarArray};
-use car_sim::map::{Road};
+use car_sim::map::{CellMap, WallMap, ObstacleShape};
 use car_sim::map;
+use car_sim::gym::{RewardConfig, SimConfig};
+use car_sim::policy::{Policy, QTablePolicy};
+use math_utils::spline::OFFSET_TOLERANCE;
 
-use car_game::graphics::{draw_car, draw_lidar, DrawRoad};
-use car_game::input::{KeyboardInput, CarInputSource};
+use car_game::graphics::{draw_car, draw_car_with_color, draw_lidar, DrawRoad};
+use car_game::input::{KeyboardInput, KeyboardPolicy, SlidingInput, CarInputSource};
+use car_game::capture::GifRecorder;
+use car_game::camera::{Camera, CameraMode};
+use car_game::hud::{LapTimer, draw_hud};
+use car_game::ai::AiDriver;
+use car_game::ghost::Ghost;
+use car_game::time_control::TimeControl;
+use car_game::crash::Crash;
+
+
+/// Frames per second of GIF recordings
+const RECORDING_FPS: f32 = 20.0;
+/// Rate at which the steering keys move the steering target, in radians per second
+const STEER_RATE: f32 = 2.0;
+/// Color of the ghost car replaying the best lap
+const GHOST_COLOR: mq::Color = mq::Color { r: 1.0, g: 1.0, b: 1.0, a: 0.35 };
+/// Duration of a single physics tick when stepping frame by frame
+const STEP_DT: f32 = 1.0 / 60.0;
+
+
+/// A policy to drive a second car with, and the simulator config it was trained with
+struct AiSetup {
+    config: SimConfig,
+    policy: Box<dyn Policy>,
+}
+
+
+/// Where to save the demonstrations of the player, who then drives with the discrete actions of
+/// the simulator config
+struct DemoSetup {
+    config: SimConfig,
+    path: String,
+}
+
+
+/// Get the value of the command line option 'name', if given
+fn option<'a>(args: &'a [String], name: &str) -> Result<Option<&'a String>, String> {
+    args.iter().position(|arg| arg == name).map(|idx| {
+        args.get(idx + 1).ok_or_else(|| format!("Missing value for option '{}'", name))
+    }).transpose()
+}
+
+
+/// Load the simulator config given by the option '--config <file>', or the default one
+fn load_config(args: &[String]) -> Result<SimConfig, String> {
+    match option(args, "--config")? {
+        Some(path) => SimConfig::from_file(path).map_err(|err| format!("Could not load config '{}': {}", path, err)),
+        None => Ok(SimConfig::default()),
+    }
+}
+
+
+/// Load the AI driver given by the options '--q-table <file>' or '--onnx <file>', if any
+fn load_ai_setup(args: &[String], config: &SimConfig) -> Result<Option<AiSetup>, String> {
+    let config = config.clone();
+    let dim = config.observation_dim();
+
+    if let Some(path) = option(args, "--q-table")? {
+        let policy = QTablePolicy::load(path).map_err(|err| format!("Could not load Q-table '{}': {}", path, err))?;
+        if policy.chunking.observation_dim() != dim {
+            return Err(format!("The Q-table expects observations of length {}, but the config gives {}", policy.chunking.observation_dim(), dim));
+        }
+        return Ok(Some(AiSetup { config, policy: Box::new(policy) }));
+    }
+    if let Some(path) = option(args, "--onnx")? {
+        #[cfg(feature = "onnx")]
+        {
+            let policy = car_sim::policy::OnnxPolicy::load(path, dim).map_err(|err| format!("Could not load ONNX policy '{}': {}", path, err))?;
+            return Ok(Some(AiSetup { config, policy: Box::new(policy) }));
+        }
+        #[cfg(not(feature = "onnx"))]
+        return Err(format!("Cannot load ONNX policy '{}' without the 'onnx' feature", path));
+    }
+    Ok(None)
+}
 
 
 #[macroquad::main("Car RL")]
 async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let setup = load_config(&args).and_then(|config| {
+        let ai = load_ai_setup(&args, &config)?;
+        let demo = option(&args, "--demo")?.map(|path| DemoSetup { config, path: path.clone() });
+        Ok((ai, demo))
+    });
+    let (ai, demo) = match setup {
+        Ok(setup) => setup,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    // Select the race map from the first command line argument
+    match args.get(1).map(String::as_str) {
+        Some("circuit") => run(CellMap::new(map::CIRCUIT, 20.0), ai, demo).await,
+        Some("fold") => run(CellMap::new(map::FOLD, 20.0), ai, demo).await,
+        Some("walls") => run(WallMap::from_spline_map(&map::make_racetrack(), OFFSET_TOLERANCE), ai, demo).await,
+        _ => {
+            // Create the race map, with a handful of obstacles along the track
+            let mut road = map::make_racetrack();
+            road.place_obstacle(ObstacleShape::Circle { radius: 0.5 }, 100.0, 2.0);
+            road.place_obstacle(ObstacleShape::Circle { radius: 0.5 }, 130.0, -2.0);
+            road.place_obstacle(ObstacleShape::Rectangle { length: 1.0, width: 3.0 }, 300.0, 1.5);
+
+            // Add a pace car driving ahead of the player
+            road.add_moving_obstacle(ObstacleShape::Rectangle { length: 4.0, width: 1.6 }, 30.0, 0.0, 15.0);
+            run(road, ai, demo).await
+        }
+    }
+}
+
+
+async fn run<R: DrawRoad + Clone>(mut road: R, ai: Option<AiSetup>, demo: Option<DemoSetup>) {
 
     // Create an object tracking coordinate transformations for drawing
     let mut transform = ScreenTransform::new(10.0);
-
-    // Create the race map
-    let road = map::make_racetrack();
+    let mut camera = Camera::new(CameraMode::Follow, 10.0);
 
     // Create a LiDAR array
     let lidar_array = LidarArray::default();
 
     // Set physical settings for car
-    let config = CarConfig::default();
+    let config = demo.as_ref().map_or_else(CarConfig::default, |demo| demo.config.car.clone());
 
     // Create an object for managing user input
-    let keyboard_input = KeyboardInput::default();
+    let keyboard_input = SlidingInput::new(KeyboardInput::default(), STEER_RATE);
+    #[cfg(feature = "gamepad")]
+    let input_source = car_game::gamepad::GamepadInput::new(car_game::gamepad::GamepadConfig::default(), keyboard_input);
+    #[cfg(not(feature = "gamepad"))]
+    let input_source = keyboard_input;
     let mut do_draw_road = true;
     let mut do_draw_lidar = true;
+    let mut do_draw_hud = true;
+    let mut recorder: Option<GifRecorder> = None;
+
+    // Score the driving with the reward of the gym environment
+    let reward_config = RewardConfig::default();
+
+    // Intialize simulator state at the start of the track
+    let (position, unit_forward) = road.pose_at(0.0);
+    let mut state = CarState { position, unit_forward, ..CarState::default() };
+    road.reset_obstacles(0.0);
+    let mut projection = road.project(state.position);
+    let mut t = 0.0;
+    let mut lap_timer = LapTimer::new(road.track_length());
+    let mut ghost = Ghost::new();
+    let mut time_control = TimeControl::new(STEP_DT);
+    let mut reward = 0.0;
+    let mut crash: Option<Crash> = None;
+
+    // Let the AI driver race on its own copy of the road
+    let mut ai_driver = ai.map(|AiSetup { config, policy }| AiDriver::new(config, road.clone(), policy));
 
-    // Intialize simulator state
-    let mut state = CarState::default();
+    // In demo mode the player drives the simulator with discrete actions, which are recorded
+    let mut demo_driver = demo.map(|DemoSetup { config, path }| {
+        let mut driver = AiDriver::new(config, road.clone(), KeyboardPolicy::default());
+        driver.start_demonstrations();
+        (driver, path)
+    });
 
     loop {
 
-        let dt = mq::get_frame_time();
+        let frame_time = mq::get_frame_time();
 
         // Handle user input
-        let input = keyboard_input.read(&config);
+        let input = input_source.read(&config);
+        if mq::is_key_pressed(KeyCode::P) {
+            time_control.toggle_pause();
+        }
+        if mq::is_key_pressed(KeyCode::T) {
+            time_control.cycle_scale();
+        }
+        if mq::is_key_pressed(KeyCode::N) {
+            time_control.step();
+        }
+        let mut dt = time_control.advance(frame_time);
+
+        // The game freezes briefly after a crash, and then the crashed car waits to respawn
+        let mut respawn_to_start = None;
+        if let Some(current) = &mut crash {
+            current.update(dt);
+            if current.is_frozen() {
+                dt = 0.0;
+            }
+            if current.is_over() {
+                // Crashed laps do not count, so the car starts over
+                if current.respawns {
+                    respawn_to_start = Some(true);
+                }
+                crash = None;
+            }
+        }
+
+        // Respawn at the start line, or with shift at the closest point on the centerline
+        if mq::is_key_pressed(KeyCode::R) {
+            respawn_to_start = Some(!mq::is_key_down(KeyCode::LeftShift) && !mq::is_key_down(KeyCode::RightShift));
+        }
+        if let Some(to_start) = respawn_to_start {
+            crash = None;
+            let progress = if to_start { 0.0 } else { projection.progress };
+            match &mut demo_driver {
+                Some((driver, _)) => {
+                    driver.restart_at(progress);
+                    state = driver.state().clone();
+                },
+                None => {
+                    let (position, unit_forward) = road.pose_at(progress);
+                    state = CarState { position, unit_forward, ..CarState::default() };
+                },
+            }
+            projection = road.project_near(state.position, progress);
+            if to_start {
+                road.reset_obstacles(0.0);
+                lap_timer.restart(t);
+                ghost.abandon_lap();
+            }
+        }
         if mq::is_key_pressed(KeyCode::Z) {
             do_draw_lidar = !do_draw_lidar;
         }
         if mq::is_key_pressed(KeyCode::M) {
             do_draw_road = !do_draw_road;
         }
+        if mq::is_key_pressed(KeyCode::H) {
+            do_draw_hud = !do_draw_hud;
+        }
+        if mq::is_key_pressed(KeyCode::C) {
+            camera.cycle_mode();
+        }
+        if mq::is_key_pressed(KeyCode::G) {
+            recorder = match recorder.take() {
+                Some(recorder) => {
+                    match recorder.finish() {
+                        Ok(path) => println!("Saved recording to {}", path.display()),
+                        Err(err) => eprintln!("Could not save recording: {}", err),
+                    }
+                    None
+                },
+                None => {
+                    let seconds = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |time| time.as_secs());
+                    Some(GifRecorder::start(format!("car_game_{}.gif", seconds), RECORDING_FPS))
+                },
+            };
+        }
+        if mq::is_key_pressed(KeyCode::S) && let Some((driver, path)) = &demo_driver {
+            let demonstrations = driver.demonstrations().expect("the demo driver to record");
+            match demonstrations.save(path) {
+                Ok(()) => println!("Saved {} demonstration steps to {}", demonstrations.len(), path),
+                Err(err) => eprintln!("Could not save demonstrations: {}", err),
+            }
+        }
         
-        // Run physics
-        state = state.update(&input, dt, &config);
+        // Run physics, unless paused
+        if dt > 0.0 {
+            match &mut demo_driver {
+                Some((driver, _)) => {
+                    // The simulator restarts the episode right away, so the crash only shows
+                    if driver.update(dt) {
+                        crash = Some(Crash::new(state.position, false));
+                        projection = road.project(driver.state().position);
+                        lap_timer.restart(t + dt);
+                        ghost.abandon_lap();
+                    }
+                    state = driver.state().clone();
+                },
+                None => if crash.is_none() {
+                    state = state.update(&input, dt, &config);
+                },
+            }
+            road.advance_obstacles(dt);
+            if let Some(ai_driver) = &mut ai_driver {
+                ai_driver.update(dt);
+            }
+
+            t += dt;
 
-        // Check if we have crashed
-        let _crashed = road.is_crashed(&state, &config);
+            // Check if we have crashed
+            let crashed = road.is_crashed(&state, &config);
+            let on_shoulder = road.is_on_shoulder(&state, &config);
+            if crashed && crash.is_none() {
+                crash = Some(Crash::new(state.position, true));
+            }
+
+            // Track the progress along the road for the lap times and the reward
+            let new_projection = road.project_near(state.position, projection.progress);
+            if let Some(lap) = lap_timer.update(road.progress_delta(projection.progress, new_projection.progress), t) {
+                ghost.finish_lap(lap);
+            }
+            ghost.record(lap_timer.lap_time(t), &state);
+            reward = reward_config.reward(&reward_config.terms(&road, &projection, &new_projection, crashed, on_shoulder, dt));
+            projection = new_projection;
+        }
 
         // Get LIDAR
         let readings = road.read_lidar(&state, &lidar_array);
 
         // Draw
-        transform.set_center(state.position);
+        camera.update(&mut transform, &state, &road);
 
         mq::clear_background(mq::Color{ r: 0.3, g: 0.8, b: 0.4, a: 0.5 });
         if do_draw_road { road.draw_road(&transform); }
         if do_draw_lidar { draw_lidar(&state, &lidar_array, &readings, &transform); }
+        if let Some(ghost_state) = ghost.state_at(lap_timer.lap_time(t)) {
+            draw_car_with_color(ghost_state, &config, &transform, GHOST_COLOR);
+        }
+        if let Some(ai_driver) = &ai_driver {
+            draw_car_with_color(ai_driver.state(), &ai_driver.sim().config.car, &transform, mq::PURPLE);
+        }
         draw_car(&state, &config, &transform);
+        if let Some(crash) = &crash { crash.draw(&transform); }
+        if do_draw_hud { draw_hud(&state, t, &lap_timer, Some(reward)); }
+
+        // Capture the frame before drawing the recording indicator
+        if let Some(recorder) = &mut recorder {
+            recorder.capture(frame_time);
+            mq::draw_text("REC", 10.0, 30.0, 30.0, mq::RED);
+        }
+        if let Some(label) = time_control.label() {
+            mq::draw_text(&label, 10.0, 60.0, 30.0, mq::YELLOW);
+        }
 
         mq::next_frame().await
     }
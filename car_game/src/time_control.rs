@@ -0,0 +1,63 @@
+/// Speeds at which the simulation can run, relative to real time
+const TIME_SCALES: [f32; 4] = [1.0, 0.5, 0.25, 0.1];
+
+
+/// Pauses, slows down and single-steps the simulation, e.g. to inspect crashes and LiDAR readings
+/// frame by frame
+pub struct TimeControl {
+    pub paused: bool,
+    /// Index into 'TIME_SCALES'
+    scale_idx: usize,
+    /// Duration of a single physics tick when stepping while paused
+    step_dt: f32,
+    step_pending: bool,
+}
+
+
+impl TimeControl {
+    pub fn new(step_dt: f32) -> Self {
+        Self { paused: false, scale_idx: 0, step_dt, step_pending: false }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Switch to the next slower speed, wrapping around to real time
+    pub fn cycle_scale(&mut self) {
+        self.scale_idx = (self.scale_idx + 1) % TIME_SCALES.len();
+    }
+
+    pub fn scale(&self) -> f32 {
+        TIME_SCALES[self.scale_idx]
+    }
+
+    /// Pause, and run a single physics tick on the next frame
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.step_pending = true;
+    }
+
+    /// Get the time to simulate in a frame that took 'frame_time' of real time, which is zero
+    /// while paused
+    pub fn advance(&mut self, frame_time: f32) -> f32 {
+        if !self.paused {
+            frame_time*self.scale()
+        } else if std::mem::take(&mut self.step_pending) {
+            self.step_dt
+        } else {
+            0.0
+        }
+    }
+
+    /// Describe the speed of the simulation, unless it runs in real time
+    pub fn label(&self) -> Option<String> {
+        if self.paused {
+            Some("PAUSED".to_string())
+        } else if self.scale_idx != 0 {
+            Some(format!("x{}", self.scale()))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::physics::{CarState, CarInput, CarConfig};
+use crate::map::{Road, RoadContact};
+
+
+/// A classical driver computing the car input directly from the car state and the road, e.g. as
+/// a baseline for learned agents
+pub trait Controller {
+    /// Get the input for the next step of duration 'dt'
+    fn control<R: Road>(&mut self, road: &R, state: &CarState, config: &CarConfig, dt: f32) -> CarInput;
+
+    /// Forget any state from previous steps, e.g. integrated errors
+    fn reset(&mut self) {}
+}
+
+
+/// Tunable gains of a 'StanleyController'
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StanleyGains {
+    /// Steering per cross-track error relative to speed
+    pub cross_track: f32,
+    /// Speed added to the actual speed in the cross-track term, keeping it bounded at low speed
+    pub softening: f32,
+    /// Proportional gain of the speed controller, in acceleration per speed error
+    pub speed_p: f32,
+    /// Integral gain of the speed controller
+    pub speed_i: f32,
+}
+
+impl Default for StanleyGains {
+    fn default() -> Self {
+        Self { cross_track: 1.5, softening: 1.0, speed_p: 1.0, speed_i: 0.1 }
+    }
+}
+
+
+/// Follows the centerline of the road with the Stanley steering law, which steers the front axle
+/// to cancel the heading error and the cross-track error, and holds a target speed with a PI
+/// controller
+#[derive(Debug, Clone)]
+pub struct StanleyController {
+    pub gains: StanleyGains,
+    pub target_speed: f32,
+    /// Integrated speed error of the PI controller
+    speed_integral: f32,
+    /// Progress along the road of the last step, to warm start the projection
+    progress: Option<f32>,
+}
+
+
+impl StanleyController {
+    pub fn new(gains: StanleyGains, target_speed: f32) -> Self {
+        Self { gains, target_speed, speed_integral: 0.0, progress: None }
+    }
+}
+
+
+impl Controller for StanleyController {
+    fn control<R: Road>(&mut self, road: &R, state: &CarState, config: &CarConfig, dt: f32) -> CarInput {
+        // Errors of the front axle relative to the closest point on the centerline, positive to
+        // the left of it
+        let front = state.position + state.unit_forward*(config.front_axle - config.back_axle);
+        let projection = match self.progress {
+            Some(progress) => road.project_near(front, progress),
+            None => road.project(front),
+        };
+        self.progress = Some(projection.progress);
+        let (center, tangent) = road.pose_at(projection.progress);
+        let cross_track_error = (front - center).dot(tangent.rotate90());
+        let heading_error = tangent.angle_between(state.unit_forward);
+
+        let gains = &self.gains;
+        let target_delta = -heading_error - (gains.cross_track*cross_track_error / (gains.softening + state.speed.abs())).atan();
+
+        // Brake when clearly too fast, and otherwise accelerate as needed
+        let speed_error = self.target_speed - state.speed;
+        self.speed_integral = (self.speed_integral + speed_error*dt).clamp(-self.target_speed, self.target_speed);
+        let acceleration = gains.speed_p*speed_error + gains.speed_i*self.speed_integral;
+        let braking = speed_error < -0.1*self.target_speed;
+
+        CarInput {
+            forward_acc: if braking { 0.0 } else { acceleration.clamp(0.0, config.acceleration) },
+            target_delta: target_delta.clamp(-config.max_delta, config.max_delta),
+            braking,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.speed_integral = 0.0;
+        self.progress = None;
+    }
+}
+
+
+/// Drive one lap from the start of the road with a controller in steps of 'dt', returning the
+/// lap time, or None if the car crashes or does not finish within 'max_time'. Like the
+/// simulator, driving on the shoulder slows the car down. Dynamic obstacles are not moved.
+pub fn time_lap<R: Road, C: Controller>(road: &R, config: &CarConfig, controller: &mut C, dt: f32, max_time: f32) -> Option<f32> {
+    let (position, unit_forward) = road.pose_at(0.0);
+    let mut state = CarState { position, unit_forward, ..CarState::default() };
+    let mut progress = road.project(position).progress;
+    let mut distance = 0.0;
+    let mut t = 0.0;
+    controller.reset();
+
+    while t < max_time {
+        let input = controller.control(road, &state, config, dt);
+        state = state.update(&input, dt, config);
+        t += dt;
+        let RoadContact { on_shoulder, crashed } = road.contact(&state, config);
+        if crashed {
+            return None;
+        }
+        if on_shoulder {
+            state.speed = (state.speed - config.shoulder_deceleration*dt).max(0.0);
+        }
+
+        let new_progress = road.project_near(state.position, progress).progress;
+        distance += road.progress_delta(progress, new_progress);
+        progress = new_progress;
+        if distance >= road.track_length() {
+            return Some(t);
+        }
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map;
+
+    #[test]
+    fn test_stanley() {
+        let road = map::make_oval();
+        let config = CarConfig::default();
+        let mut controller = StanleyController::new(StanleyGains::default(), 10.0);
+
+        // A car left of the centerline steers further right than a car right of it
+        let (position, unit_forward) = road.pose_at(10.0);
+        let mut target_delta = |offset: f32| {
+            let state = CarState { position: position + unit_forward.rotate90()*offset, unit_forward, ..CarState::default() };
+            controller.reset();
+            controller.control(&road, &state, &config, 0.1).target_delta
+        };
+        assert!(target_delta(1.0) < target_delta(-1.0));
+
+        let lap_time = time_lap(&road, &config, &mut controller, 0.1, 300.0).expect("the controller to finish a lap");
+        // Not much slower than driving the whole centerline at the target speed
+        assert!(lap_time > road.track_length() / 10.0 - 1.0);
+        assert!(lap_time < 1.5 * road.track_length() / 10.0);
+    }
+}
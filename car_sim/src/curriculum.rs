@@ -0,0 +1,187 @@
+use std::collections::VecDeque;
+
+use rand::SeedableRng;
+
+use crate::map::{self, SplineMap};
+
+
+/// The track driven in a stage of a curriculum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurriculumTrack {
+    Oval,
+    SimpleRacetrack,
+    Racetrack,
+    /// A new random track for every episode, see 'map::make_random_track'
+    Random,
+}
+
+impl CurriculumTrack {
+    /// All tracks, from the easiest to the hardest
+    pub const ALL: [CurriculumTrack; 4] = [CurriculumTrack::Oval, CurriculumTrack::SimpleRacetrack, CurriculumTrack::Racetrack, CurriculumTrack::Random];
+
+    /// A lowercase name of the track, e.g. for parsing
+    pub fn name(self) -> &'static str {
+        match self {
+            CurriculumTrack::Oval => "oval",
+            CurriculumTrack::SimpleRacetrack => "simple_racetrack",
+            CurriculumTrack::Racetrack => "racetrack",
+            CurriculumTrack::Random => "random",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|track| track.name() == name)
+    }
+}
+
+
+/// A stage of a curriculum, i.e. a track with its road width scaled by 'width_factor'
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stage {
+    pub track: CurriculumTrack,
+    pub width_factor: f32,
+}
+
+
+/// Moves through stages of increasing difficulty by the success rate of the latest episodes.
+/// Once 'window' episodes are reported in a stage, a success rate of at least 'promote_rate'
+/// moves on to the next stage, and one of at most 'demote_rate' back to the previous one.
+pub struct Curriculum {
+    stages: Vec<Stage>,
+    stage: usize,
+    /// Whether each of the latest episodes of the current stage succeeded, oldest first
+    results: VecDeque<bool>,
+    pub window: usize,
+    pub promote_rate: f32,
+    pub demote_rate: f32,
+    /// The stage of the road last given by 'next_road', if any
+    road_stage: Option<usize>,
+    /// Source of the random tracks, seeded separately from the simulator
+    rng: rand_pcg::Pcg64,
+}
+
+
+impl Curriculum {
+    /// Create a curriculum starting at the first of 'stages', drawing random tracks from a
+    /// generator seeded with 'seed', or else seeded randomly
+    pub fn new(stages: Vec<Stage>, seed: Option<u64>) -> Self {
+        assert!(!stages.is_empty(), "A curriculum needs at least one stage");
+        let rng = match seed {
+            Some(seed) => rand_pcg::Pcg64::seed_from_u64(seed),
+            None => rand_pcg::Pcg64::from_rng(&mut rand::rng()),
+        };
+        Self { stages, stage: 0, results: VecDeque::new(), window: 20, promote_rate: 0.8, demote_rate: 0.2, road_stage: None, rng }
+    }
+
+    /// The tracks from the oval to random tracks, at their own widths
+    pub fn default_stages() -> Vec<Stage> {
+        CurriculumTrack::ALL.into_iter().map(|track| Stage { track, width_factor: 1.0 }).collect()
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// Get the index of the current stage
+    pub fn stage_index(&self) -> usize {
+        self.stage
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stages[self.stage]
+    }
+
+    /// Get the success rate of the episodes reported in the current stage, within the window, or
+    /// None if there are none
+    pub fn success_rate(&self) -> Option<f32> {
+        let n = self.results.len();
+        (n > 0).then(|| self.results.iter().filter(|&&success| success).count() as f32 / n as f32)
+    }
+
+    /// Report whether an episode succeeded, e.g. completed a lap, and move to another stage if
+    /// the success rate calls for it. Returns whether the stage changed.
+    pub fn report(&mut self, success: bool) -> bool {
+        self.results.push_back(success);
+        while self.results.len() > self.window {
+            self.results.pop_front();
+        }
+        if self.results.len() < self.window {
+            return false;
+        }
+
+        let rate = self.success_rate().expect("results to be nonempty");
+        let stage = if rate >= self.promote_rate && self.stage + 1 < self.stages.len() {
+            self.stage + 1
+        } else if rate <= self.demote_rate && self.stage > 0 {
+            self.stage - 1
+        } else {
+            return false;
+        };
+        self.stage = stage;
+        self.results.clear();
+        true
+    }
+
+    /// Build the road of the current stage
+    pub fn make_road(&mut self) -> SplineMap {
+        let Stage { track, width_factor } = self.stage();
+        let road = match track {
+            CurriculumTrack::Oval => map::make_oval(),
+            CurriculumTrack::SimpleRacetrack => map::make_simple_racetrack(),
+            CurriculumTrack::Racetrack => map::make_racetrack(),
+            CurriculumTrack::Random => map::make_random_track(&mut self.rng),
+        };
+        if width_factor == 1.0 {
+            road
+        } else {
+            SplineMap::new(road.spline, road.width*width_factor)
+        }
+    }
+
+    /// Get the road for the next episode, if it is not the last one given: when the stage has
+    /// changed since, and for every episode of a random track
+    pub fn next_road(&mut self) -> Option<SplineMap> {
+        if self.road_stage == Some(self.stage) && self.stage().track != CurriculumTrack::Random {
+            return None;
+        }
+        self.road_stage = Some(self.stage);
+        Some(self.make_road())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curriculum() {
+        let mut curriculum = Curriculum::new(Curriculum::default_stages(), Some(0));
+        curriculum.window = 4;
+        curriculum.promote_rate = 0.75;
+        assert!(curriculum.next_road().is_some());
+        assert!(curriculum.next_road().is_none());
+
+        // A full window with enough successes moves on, and the window starts over
+        assert!(!curriculum.report(true));
+        assert!(!curriculum.report(true));
+        assert!(!curriculum.report(false));
+        assert!(curriculum.report(true));
+        assert_eq!(curriculum.stage().track, CurriculumTrack::SimpleRacetrack);
+        assert_eq!(curriculum.success_rate(), None);
+        assert!(curriculum.next_road().is_some());
+
+        // Failing moves back, but not before the first stage
+        for _ in 0 .. 8 {
+            curriculum.report(false);
+        }
+        assert_eq!(curriculum.stage_index(), 0);
+
+        // Random tracks are new for every episode
+        let mut random = Curriculum::new(vec![Stage { track: CurriculumTrack::Random, width_factor: 1.5 }], Some(0));
+        let first = random.next_road().unwrap();
+        let second = random.next_road().unwrap();
+        assert_eq!(first.width, 15.0);
+        assert_ne!(first.spline.get(0.5), second.spline.get(0.5));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::gym::{Simulator, TransitionObservation};
+use crate::map::Road;
+use crate::policy::Policy;
+
+
+/// The outcomes of a number of evaluation episodes, one entry per episode
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Evaluation {
+    pub returns: Vec<f32>,
+    pub lengths: Vec<usize>,
+    /// Whether the car drove at least one lap
+    pub completed_lap: Vec<bool>,
+    /// Whether the episode ended with a crash, rather than running out of steps
+    pub crashed: Vec<bool>,
+}
+
+
+fn mean(values: impl ExactSizeIterator<Item = f32>) -> f32 {
+    let n = values.len();
+    if n == 0 { f32::NAN } else { values.sum::<f32>() / n as f32 }
+}
+
+
+impl Evaluation {
+    pub fn n_episodes(&self) -> usize {
+        self.returns.len()
+    }
+
+    pub fn mean_return(&self) -> f32 {
+        mean(self.returns.iter().copied())
+    }
+
+    pub fn mean_length(&self) -> f32 {
+        mean(self.lengths.iter().map(|&length| length as f32))
+    }
+
+    pub fn lap_completion_rate(&self) -> f32 {
+        mean(self.completed_lap.iter().map(|&completed| completed as i32 as f32))
+    }
+
+    pub fn crash_rate(&self) -> f32 {
+        mean(self.crashed.iter().map(|&crashed| crashed as i32 as f32))
+    }
+}
+
+
+/// Follows a single episode of a simulator step by step, for evaluation loops that choose the
+/// actions themselves
+pub struct EpisodeTracker {
+    episode_return: f32,
+    length: usize,
+    /// Distance driven along the track since the start of the episode, in the direction of the
+    /// episode
+    distance: f32,
+    progress: f32,
+}
+
+
+impl EpisodeTracker {
+    /// Start tracking from the current state of a simulator, e.g. right after a reset
+    pub fn new<R: Road>(sim: &Simulator<R>) -> Self {
+        Self { episode_return: 0.0, length: 0, distance: 0.0, progress: sim.progress() }
+    }
+
+    /// Record a step that 'sim' has just taken
+    pub fn step<R: Road>(&mut self, sim: &Simulator<R>, transition: &TransitionObservation) {
+        self.episode_return += transition.reward;
+        self.length += 1;
+        let progress = sim.progress();
+        let delta = sim.road.progress_delta(self.progress, progress);
+        self.distance += if sim.is_reversed() { -delta } else { delta };
+        self.progress = progress;
+    }
+
+    /// End the episode, which is a crash if 'crashed', and add its outcome to 'evaluation'
+    pub fn finish<R: Road>(self, sim: &Simulator<R>, crashed: bool, evaluation: &mut Evaluation) {
+        evaluation.returns.push(self.episode_return);
+        evaluation.lengths.push(self.length);
+        evaluation.completed_lap.push(self.distance >= sim.road.track_length());
+        evaluation.crashed.push(crashed);
+    }
+}
+
+
+impl<R: Road> Simulator<R> {
+    /// Run 'episodes' episodes from resets with actions from 'policy', each ending with a crash or
+    /// after 'max_steps' steps
+    pub fn evaluate<P: Policy + ?Sized>(&mut self, policy: &mut P, episodes: usize, max_steps: usize) -> Evaluation {
+        let mut evaluation = Evaluation::default();
+        let mut observation = vec![0.0; self.observation_dim()];
+        for _ in 0 .. episodes {
+            self.reset(None);
+            let mut tracker = EpisodeTracker::new(self);
+            let mut crashed = false;
+            for _ in 0 .. max_steps {
+                self.observe_into(&mut observation);
+                let transition = self.step(policy.act(&observation));
+                tracker.step(self, &transition);
+                if transition.done {
+                    crashed = true;
+                    break;
+                }
+            }
+            tracker.finish(self, crashed, &mut evaluation);
+        }
+        evaluation
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gym::{Action, SimConfig};
+    use crate::map::make_oval;
+
+    /// Always takes the same action
+    struct Constant(Action);
+
+    impl Policy for Constant {
+        fn act(&mut self, _observation: &[f32]) -> Action {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut sim = Simulator::new(SimConfig::default(), make_oval(), Some(0));
+
+        // Steering left all the time crashes, without reaching a lap
+        let evaluation = sim.evaluate(&mut Constant(Action::Left), 3, 1000);
+        assert_eq!(evaluation.n_episodes(), 3);
+        assert_eq!(evaluation.crash_rate(), 1.0);
+        assert_eq!(evaluation.lap_completion_rate(), 0.0);
+        assert!(evaluation.lengths.iter().all(|&length| length < 1000));
+        assert_eq!(evaluation.mean_length(), evaluation.lengths.iter().sum::<usize>() as f32 / 3.0);
+
+        // Braking stops the car before it crashes, so episodes run out of steps
+        let evaluation = sim.evaluate(&mut Constant(Action::Brake), 2, 50);
+        assert_eq!(evaluation.crash_rate(), 0.0);
+        assert_eq!(evaluation.lengths, [50, 50]);
+        assert!(Evaluation::default().mean_return().is_nan());
+    }
+}
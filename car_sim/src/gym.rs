@@ -1,14 +1,24 @@
-use crate::physics::{CarState, CarInput, CarConfig};
-use crate::map::{Road, SplineMap};
+use crate::physics::{CarState, CarInput, CarConfig, TireUsage};
+use crate::map::{Road, RoadContact, TrackProjection, RayMarch};
 use crate::lidar::LidarArray;
-use math_utils::spline::ClosestPointOutput;
-use math_utils::root::find_root;
+use crate::recorder::{Recorder, Recording, StepRecord, EpisodeRecord, Trajectory, Demonstrations};
+use crate::policy::Policy;
+use crate::telemetry::{TelemetryLogger, TelemetryRow};
+#[cfg(feature = "stream")]
+use crate::stream::{Frame, FramePublisher};
+use math_utils::Vec2;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
 
 use rand::{Rng, SeedableRng};
 use rand_pcg;
+use serde::{Serialize, Deserialize};
 
 
 #[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Action {
     Left = 0,
     Right = 1,
@@ -17,6 +27,7 @@ pub enum Action {
     Coast = 4,
 }
 
+#[derive(Debug)]
 pub struct InvalidActionError;
 
 impl TryFrom<u8> for Action {
@@ -35,44 +46,380 @@ impl TryFrom<u8> for Action {
 }
 
 
+impl Action {
+    /// All actions, in the order of their integer values
+    pub const ALL: [Action; 5] = [Action::Left, Action::Right, Action::Accelerate, Action::Brake, Action::Coast];
+
+    /// A lowercase name of the action, for labels
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Accelerate => "accelerate",
+            Action::Brake => "brake",
+            Action::Coast => "coast",
+        }
+    }
+
+    /// Get the car input of the action. The steering of 'Left' and 'Right' decreases with speed
+    /// above 5 m/s, to keep the car controllable.
+    pub fn to_input(self, state: &CarState, config: &CarConfig) -> CarInput {
+        let delta_factor = 5.0 / state.speed.max(5.0);
+        match self {
+            Action::Left => CarInput { forward_acc: 0.0, target_delta: config.max_delta*delta_factor, braking: false },
+            Action::Right => CarInput { forward_acc: 0.0, target_delta: -config.max_delta*delta_factor, braking: false },
+            Action::Accelerate => CarInput { forward_acc: config.acceleration, target_delta: 0.0, braking: false },
+            Action::Brake => CarInput { forward_acc: 0.0, target_delta: 0.0, braking: true },
+            Action::Coast => CarInput { forward_acc: 0.0, target_delta: 0.0, braking: false },
+        }
+    }
+}
+
+
+/// The change of speed of a 'SteerThrottle' action
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Throttle {
+    Brake = 0,
+    Coast = 1,
+    Accelerate = 2,
+}
+
+impl Throttle {
+    /// All throttle levels, in the order of their integer values
+    pub const ALL: [Throttle; 3] = [Throttle::Brake, Throttle::Coast, Throttle::Accelerate];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Throttle::Brake => "brake",
+            Throttle::Coast => "coast",
+            Throttle::Accelerate => "accelerate",
+        }
+    }
+}
+
+
+/// An action which steers and changes the speed at once. The steering level is between
+/// '-MAX_STEER' and 'MAX_STEER', where positive levels steer left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SteerThrottle {
+    pub steer: i8,
+    pub throttle: Throttle,
+}
+
+impl SteerThrottle {
+    pub const MAX_STEER: i8 = 2;
+    /// The number of steering levels
+    pub const N_STEER: usize = 2*Self::MAX_STEER as usize + 1;
+    /// The number of actions, i.e. of combinations of a steering and a throttle level
+    pub const N: usize = Self::N_STEER*Throttle::ALL.len();
+
+    /// Get the action of a flattened integer value, '3*(steer + MAX_STEER) + throttle'
+    pub fn from_index(index: u8) -> Option<Self> {
+        let (steer, throttle) = (index as usize / Throttle::ALL.len(), index as usize % Throttle::ALL.len());
+        (steer < Self::N_STEER).then(|| Self { steer: steer as i8 - Self::MAX_STEER, throttle: Throttle::ALL[throttle] })
+    }
+
+    /// Get the flattened integer value of the action, see 'from_index'
+    pub fn index(self) -> u8 {
+        (self.steer + Self::MAX_STEER) as u8*Throttle::ALL.len() as u8 + self.throttle as u8
+    }
+
+    /// A lowercase name of the action, for labels, e.g. 'steer_-1_accelerate'
+    pub fn name(self) -> String {
+        format!("steer_{}_{}", self.steer, self.throttle.name())
+    }
+
+    /// Get the car input of the action. As for 'Action', the steering decreases with speed above
+    /// 5 m/s, and the largest steering level steers as much as 'Action::Left' and 'Action::Right'.
+    pub fn to_input(self, state: &CarState, config: &CarConfig) -> CarInput {
+        let delta_factor = 5.0 / state.speed.max(5.0);
+        let target_delta = config.max_delta*delta_factor*self.steer as f32/Self::MAX_STEER as f32;
+        match self.throttle {
+            Throttle::Brake => CarInput { forward_acc: 0.0, target_delta, braking: true },
+            Throttle::Coast => CarInput { forward_acc: 0.0, target_delta, braking: false },
+            Throttle::Accelerate => CarInput { forward_acc: config.acceleration, target_delta, braking: false },
+        }
+    }
+}
+
+/// The steer and throttle action with the same car input as a discrete action
+impl From<Action> for SteerThrottle {
+    fn from(action: Action) -> Self {
+        let (steer, throttle) = match action {
+            Action::Left => (Self::MAX_STEER, Throttle::Coast),
+            Action::Right => (-Self::MAX_STEER, Throttle::Coast),
+            Action::Accelerate => (0, Throttle::Accelerate),
+            Action::Brake => (0, Throttle::Brake),
+            Action::Coast => (0, Throttle::Coast),
+        };
+        Self { steer, throttle }
+    }
+}
+
+
+/// The action recorded for a step with a raw car input, see 'Simulator::step_input'. It is not a
+/// valid action in any action mode.
+pub const RAW_INPUT_ACTION: u8 = u8::MAX;
+
+
+/// How the integer values of actions are interpreted, e.g. by 'Simulator::step_index' and in
+/// recordings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionMode {
+    /// One of the five 'Action's, which either steer or change the speed
+    #[default]
+    Discrete,
+    /// A flattened 'SteerThrottle', which steers and changes the speed at once
+    SteerThrottle,
+}
+
+impl ActionMode {
+    /// Get the number of actions, i.e. of valid integer values
+    pub fn n_actions(self) -> usize {
+        match self {
+            ActionMode::Discrete => Action::ALL.len(),
+            ActionMode::SteerThrottle => SteerThrottle::N,
+        }
+    }
+
+    /// Get the names of the actions, in the order of their integer values
+    pub fn labels(self) -> Vec<String> {
+        match self {
+            ActionMode::Discrete => Action::ALL.iter().map(|action| action.name().to_string()).collect(),
+            ActionMode::SteerThrottle => (0 .. SteerThrottle::N as u8)
+                .map(|index| SteerThrottle::from_index(index).expect("index to be in range").name())
+                .collect(),
+        }
+    }
+
+    /// Get the integer value of a discrete action in this mode
+    pub fn index_of(self, action: Action) -> u8 {
+        match self {
+            ActionMode::Discrete => action as u8,
+            ActionMode::SteerThrottle => SteerThrottle::from(action).index(),
+        }
+    }
+
+    /// Get the action with integer value 'action' in this mode, as the steer and throttle action
+    /// with the same car input
+    pub fn to_steer_throttle(self, action: u8) -> Result<SteerThrottle, InvalidActionError> {
+        match self {
+            ActionMode::Discrete => Ok(Action::try_from(action)?.into()),
+            ActionMode::SteerThrottle => SteerThrottle::from_index(action).ok_or(InvalidActionError),
+        }
+    }
+}
+
+
 #[derive(Debug)]
 pub struct TransitionObservation {
     pub reward: f32,
-    pub done: bool
+    pub done: bool,
+    pub on_shoulder: bool,
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StateObservation {
     pub lidar_readings: Vec<f32>,
     pub steer_delta: f32,
     pub speed: f32,
+    pub on_shoulder: bool,
+    pub energy: Option<f32>,
+    #[serde(default)]
+    pub grip_utilization: Option<f32>,
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RewardConfig {
     pub travel_coeff: f32,
     pub center_coeff: f32,
     pub crash_reward: f32,
     pub center_integral_coeff: f32,
+    pub shoulder_coeff: f32,
+    /// Factor that the sum of the reward terms is multiplied by
+    pub reward_scale: f32,
+    /// Bound on the absolute value of the scaled reward, if any
+    pub reward_clip: Option<f32>,
 }
 
 impl Default for RewardConfig {
     fn default() -> Self {
         Self { 
             travel_coeff: 1.0, center_coeff: 2.0, crash_reward: -100.0,
-            center_integral_coeff: 1.0
+            center_integral_coeff: 1.0, shoulder_coeff: 5.0,
+            reward_scale: 1.0, reward_clip: None,
         }
     }
 }
 
-#[derive(Debug)]
+/// The terms of the reward of a single step, see 'RewardConfig'
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RewardTerms {
+    pub travel: f32,
+    pub center: f32,
+    pub center_integral: f32,
+    pub crash: f32,
+    pub shoulder: f32,
+}
+
+impl RewardTerms {
+    pub fn total(&self) -> f32 {
+        self.travel + self.center + self.center_integral + self.crash + self.shoulder
+    }
+}
+
+impl RewardConfig {
+    /// Get the reward of a step from its terms, i.e. their sum multiplied by 'reward_scale' and
+    /// clipped to 'reward_clip'
+    pub fn reward(&self, terms: &RewardTerms) -> f32 {
+        let reward = self.reward_scale*terms.total();
+        match self.reward_clip {
+            Some(clip) => reward.clamp(-clip, clip),
+            None => reward,
+        }
+    }
+
+    /// Get the reward terms of a step of duration 'dt' that moves the projection of the car onto
+    /// 'road' from 'projection' to 'new_projection'
+    pub fn terms<R: Road>(&self, road: &R, projection: &TrackProjection, new_projection: &TrackProjection, is_crashed: bool, on_shoulder: bool, dt: f32) -> RewardTerms {
+        let &TrackProjection { progress: travel1, distance_sq: d1_sq } = projection;
+        let &TrackProjection { progress: travel2, distance_sq: d2_sq } = new_projection;
+
+        let travel = road.progress_delta(travel1, travel2);
+        let d_sq_decrease = d2_sq - d1_sq;
+        RewardTerms {
+            travel: self.travel_coeff * travel,
+            center: self.center_coeff * d_sq_decrease,
+            center_integral: -self.center_integral_coeff * d2_sq * dt,
+            crash: self.crash_reward*(is_crashed as i32 as f32),
+            shoulder: -self.shoulder_coeff * dt * (on_shoulder as i32 as f32),
+        }
+    }
+}
+
+/// Which features to include in the observation vector of 'Simulator::observe_into', after the
+/// LiDAR readings
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObservationConfig {
+    pub delta: bool,
+    pub speed: bool,
+    pub shoulder: bool,
+    /// The energy left, infinite if it is unlimited
+    pub energy: bool,
+    /// The fraction of the grip used at the last step, zero if the tyres have no grip limit, see
+    /// 'TireUsage::grip_utilization'
+    pub grip: bool,
+}
+
+impl Default for ObservationConfig {
+    fn default() -> Self {
+        Self { delta: true, speed: true, shoulder: false, energy: false, grip: false }
+    }
+}
+
+/// Perturbations of the actions and observations, to evaluate how robust a policy is. All are off
+/// by default.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PerturbationConfig {
+    /// Probability that the car repeats its previous action instead of the one taken
+    pub sticky_action_prob: f32,
+    /// Number of steps before an action reaches the car. Until then, the car coasts.
+    pub action_delay: usize,
+    /// Number of steps that observations lag behind, as for sensors with latency. Until an episode
+    /// is that long, the observation is the first one of the episode.
+    pub observation_delay: usize,
+    /// Standard deviation of random turns of the heading in radians per square root of a second,
+    /// as from bumps in the road
+    pub heading_noise: f32,
+    /// Standard deviation of random sideways displacements in meters per square root of a second,
+    /// as from gusts of wind
+    pub lateral_noise: f32,
+}
+
+/// What happens when the car crashes. It is given the crash reward either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrashMode {
+    /// The episode ends
+    #[default]
+    Terminate,
+    /// The car bounces off the edge of the road and drives on. Its heading is mirrored about the
+    /// direction of the road where it crashed, its speed is scaled by the restitution, and it is
+    /// moved back to where it was before the step.
+    Bounce,
+}
+
+/// Draw from the standard normal distribution, by the Box-Muller transform
+fn standard_normal(rng: &mut impl Rng) -> f32 {
+    let radius = (-2.0*(1.0 - rng.random::<f32>()).ln()).sqrt();
+    radius*(std::f32::consts::TAU*rng.random::<f32>()).cos()
+}
+
+/// Where along the centerline of the road episodes start, facing along the road
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartDistribution {
+    /// A point drawn uniformly along the whole track, e.g. for training
+    #[default]
+    Uniform,
+    /// The same point in every episode, at arc length 'arc'
+    Fixed { arc: f32 },
+    /// One of 'n' evenly spaced points, starting from zero arc length and taking the next one at
+    /// every reset, e.g. to sweep the track deterministically in evaluation
+    Grid { n: usize },
+}
+
+impl StartDistribution {
+    /// Get the arc length to start the episode with index 'episode' at, on a track of length
+    /// 'track_length'
+    fn sample<G: Rng + ?Sized>(&self, track_length: f32, episode: usize, rng: &mut G) -> f32 {
+        match *self {
+            StartDistribution::Uniform => track_length * rng.random::<f32>(),
+            StartDistribution::Fixed { arc } => arc.rem_euclid(track_length),
+            StartDistribution::Grid { n } => track_length * (episode % n.max(1)) as f32 / n.max(1) as f32,
+        }
+    }
+}
+
+/// The name and range of a component of the observation vector, see
+/// 'SimConfig::observation_components'
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObservationComponent {
+    pub label: String,
+    pub low: f32,
+    pub high: f32,
+}
+
+/// The full configuration of a simulator. Serialized with all fields optional, where missing
+/// fields take their default values, so that config files only list what they change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SimConfig {
     pub car: CarConfig,
     pub reward: RewardConfig,
     pub lidar: LidarArray,
+    pub observation: ObservationConfig,
     pub dt: f32,
+    pub action_mode: ActionMode,
+    pub perturbation: PerturbationConfig,
+    pub start: StartDistribution,
+    /// The probability that an episode is driven in the reverse direction of the track, starting
+    /// turned around and rewarded for decreasing progress
+    pub reverse_prob: f32,
+    pub crash_mode: CrashMode,
+    /// The fraction of the speed that the car keeps when it bounces, see 'CrashMode::Bounce'
+    pub restitution: f32,
+    /// Limits on marching the LiDAR rays, which are given to the road
+    pub ray_march: RayMarch,
+    /// Whether to record all episodes from the start, see 'Simulator::start_recording'
+    pub record: bool,
 }
 
 impl Default for SimConfig {
@@ -81,110 +428,592 @@ impl Default for SimConfig {
             car: CarConfig::default(),
             reward: RewardConfig::default(),
             lidar: LidarArray::default(),
-            dt: 0.2
+            observation: ObservationConfig::default(),
+            dt: 0.2,
+            action_mode: ActionMode::default(),
+            perturbation: PerturbationConfig::default(),
+            start: StartDistribution::default(),
+            reverse_prob: 0.0,
+            crash_mode: CrashMode::default(),
+            restitution: 0.5,
+            ray_march: RayMarch::default(),
+            record: false,
         }
     }
 }
 
 
+#[derive(Debug)]
+pub enum ConfigLoadError {
+    Io(std::io::Error),
+    Parse(String),
+    UnsupportedFormat(String),
+}
+
+impl fmt::Display for ConfigLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigLoadError::Io(err) => write!(f, "Could not access config file: {}", err),
+            ConfigLoadError::Parse(msg) => write!(f, "Could not parse config file: {}", msg),
+            ConfigLoadError::UnsupportedFormat(extension) => write!(f, "Unsupported config file format '{}'. Expected 'json' or 'toml'.", extension),
+        }
+    }
+}
+
+impl std::error::Error for ConfigLoadError {}
+
+impl From<std::io::Error> for ConfigLoadError {
+    fn from(err: std::io::Error) -> Self {
+        ConfigLoadError::Io(err)
+    }
+}
+
+
+impl SimConfig {
+    /// Get the length of the observation vector of a simulator with this config
+    pub fn observation_dim(&self) -> usize {
+        let ObservationConfig { delta, speed, shoulder, energy, grip } = self.observation;
+        self.lidar.n_angles() + delta as usize + speed as usize + shoulder as usize + energy as usize + grip as usize
+    }
+
+    /// Describe each component of the observation vector of a simulator with this config. The
+    /// LiDAR readings are labelled by their angle in degrees, e.g. 'lidar_-30'.
+    pub fn observation_components(&self) -> Vec<ObservationComponent> {
+        let component = |label: String, low: f32, high: f32| ObservationComponent { label, low, high };
+        let ObservationConfig { delta, speed, shoulder, energy, grip } = self.observation;
+        let max_delta = self.car.max_delta;
+        let capacity = self.car.energy_capacity.unwrap_or(f32::INFINITY);
+        let min_speed = if self.car.reverse_acceleration.is_some() { -self.car.max_reverse_speed } else { 0.0 };
+
+        let lidar = self.lidar.get_angles().iter()
+            .map(|angle| component(format!("lidar_{:.0}", angle.to_degrees()), f32::NEG_INFINITY, f32::INFINITY));
+        let features = [
+            (delta, component("steer_delta".to_string(), -max_delta, max_delta)),
+            (speed, component("speed".to_string(), min_speed, f32::INFINITY)),
+            (shoulder, component("on_shoulder".to_string(), 0.0, 1.0)),
+            (energy, component("energy".to_string(), 0.0, capacity)),
+            (grip, component("grip_utilization".to_string(), 0.0, f32::INFINITY)),
+        ];
+        lidar.chain(features.into_iter().filter(|(included, _)| *included).map(|(_, component)| component)).collect()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, ConfigLoadError> {
+        serde_json::from_str(text).map_err(|err| ConfigLoadError::Parse(err.to_string()))
+    }
 
+    pub fn from_toml(text: &str) -> Result<Self, ConfigLoadError> {
+        toml::from_str(text).map_err(|err| ConfigLoadError::Parse(err.to_string()))
+    }
+
+    /// Load a config from a '.json' or '.toml' file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigLoadError> {
+        let path = path.as_ref();
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&std::fs::read_to_string(path)?),
+            Some("toml") => Self::from_toml(&std::fs::read_to_string(path)?),
+            extension => Err(ConfigLoadError::UnsupportedFormat(extension.unwrap_or("").to_string())),
+        }
+    }
+
+    /// Save the config, with all fields, to a '.json' or '.toml' file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigLoadError> {
+        let path = path.as_ref();
+        let text = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => serde_json::to_string_pretty(self).map_err(|err| ConfigLoadError::Parse(err.to_string()))?,
+            Some("toml") => toml::to_string(self).map_err(|err| ConfigLoadError::Parse(err.to_string()))?,
+            extension => return Err(ConfigLoadError::UnsupportedFormat(extension.unwrap_or("").to_string())),
+        };
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+
+/// Why a recorded episode was not reproduced, see 'Simulator::verify_episode'
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayError {
+    /// The recorded action at a step is not a valid action
+    InvalidAction { step: usize, action: u8 },
+    /// The car state after a step deviates from the recorded one by more than the tolerance, in
+    /// the largest absolute difference of any component
+    Mismatch { step: usize, deviation: f32 },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::InvalidAction { step, action } => write!(f, "Invalid action '{}' recorded at step {}", action, step),
+            ReplayError::Mismatch { step, deviation } => write!(f, "Replayed state deviates by {} from the recording at step {}", deviation, step),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+
+/// Get the largest absolute difference between any components of two car states, or NaN if any
+/// component is NaN
+fn state_deviation(a: &CarState, b: &CarState) -> f32 {
+    [
+        a.position.0 - b.position.0, a.position.1 - b.position.1,
+        a.unit_forward.0 - b.unit_forward.0, a.unit_forward.1 - b.unit_forward.1,
+        a.speed - b.speed, a.steer_delta - b.steer_delta,
+    ].into_iter().map(f32::abs).fold(0.0, |max, x| if x.is_nan() || x > max { x } else { max })
+}
+
+
+/// A snapshot of everything that changes while a simulator runs, i.e. the car, the clock, the
+/// moving obstacles and the random number generator. Restoring a snapshot with
+/// 'Simulator::set_state' replays the following steps exactly, e.g. for planning or resuming.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimState {
+    pub car: CarState,
+    pub on_shoulder: bool,
+    pub t: f32,
+    pub i: usize,
+    /// The steering target of the last step, see 'CarConfig::steer_target_rate'
+    #[serde(default)]
+    pub steer_target: f32,
+    #[serde(default)]
+    actuation: Actuation,
+    #[serde(default)]
+    observation_history: VecDeque<StateObservation>,
+    /// Whether the episode is driven in the reverse direction, see 'SimConfig::reverse_prob'
+    #[serde(default)]
+    pub reversed: bool,
+    /// The number of resets since the simulator was last seeded, see 'StartDistribution::Grid'
+    #[serde(default)]
+    pub episode: usize,
+    #[serde(default)]
+    laps: LapCounter,
+    #[serde(default)]
+    tire: TireUsage,
+    projection: (Vec2, TrackProjection),
+    obstacles: Vec<f32>,
+    rng: rand_pcg::Pcg64,
+}
+
+impl SimState {
+    /// Get the progress along the track of the car at the last step
+    pub fn progress(&self) -> f32 {
+        self.projection.1.progress
+    }
+
+    /// Get the state of the moving obstacles, see 'Road::obstacle_state'
+    pub fn obstacle_state(&self) -> &[f32] {
+        &self.obstacles
+    }
+}
+
+
+/// Counts the laps of an episode by the distance driven along the track
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct LapCounter {
+    /// Distance driven along the track since the reset, in the direction of the episode
+    distance: f32,
+    /// The number of laps completed
+    laps: usize,
+    /// The clock at the start of the current lap
+    lap_start: f32,
+}
+
+
+/// The actions on their way to the car, see 'PerturbationConfig'
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Actuation {
+    /// The actions taken in the last 'action_delay' steps, oldest first
+    pending: VecDeque<SteerThrottle>,
+    /// The action which reached the car at the last step
+    last: Option<SteerThrottle>,
+}
+
+
+/// Cloning a simulator forks it, e.g. for planning, where both copies continue independently
+/// with the same random number stream
+#[derive(Clone)]
 pub struct Simulator<R>
 {
     pub config: SimConfig,
     pub road: R,
     pub state: CarState,
+    on_shoulder: bool,
+    /// The steering target of the last step, which moves towards the one asked for by an action
+    /// at a limited rate, see 'CarConfig::steer_target_rate'
+    steer_target: f32,
+    actuation: Actuation,
+    /// The observations of the last 'observation_delay' steps and the current one, oldest first,
+    /// if observations are delayed
+    observation_history: VecDeque<StateObservation>,
+    /// Whether the episode is driven in the reverse direction, see 'SimConfig::reverse_prob'
+    reversed: bool,
+    /// The number of resets since the simulator was last seeded, see 'StartDistribution::Grid'
+    episode: usize,
+    laps: LapCounter,
+    /// The accelerations of the tyres at the last step
+    tire: TireUsage,
+    /// Projection of the car position onto the road, and the position it was computed for.
+    /// Used to warm start the projection at the next step.
+    projection: (Vec2, TrackProjection),
     t: f32,
     i: usize,
-    init_rng: rand_pcg::Pcg64,
+    /// Source of all randomness in the simulator, e.g. the start pose. Only reseeded by 'new' and
+    /// 'reset', so that a seed fixes all following episodes bit for bit.
+    rng: rand_pcg::Pcg64,
+    recorder: Option<Recorder>,
+    /// The steps since the last reset
+    trajectory: Trajectory,
+    telemetry: TelemetrySlot,
+    #[cfg(feature = "stream")]
+    stream: StreamSlot,
 }
 
 
+/// Holds the telemetry logger of a simulator. A clone of a simulator does not log, so that two
+/// simulators never write to the same file.
+#[derive(Default)]
+struct TelemetrySlot(Option<TelemetryLogger>);
 
-impl Simulator<SplineMap> {
-    pub fn reset(&mut self, seed: Option<u64>) {
+impl Clone for TelemetrySlot {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
+
+
+/// Holds the frame publisher of a simulator and the number of steps per frame. A clone of a
+/// simulator does not stream, since the publisher owns its port.
+#[cfg(feature = "stream")]
+#[derive(Default)]
+struct StreamSlot(Option<(FramePublisher, usize)>);
+
+#[cfg(feature = "stream")]
+impl Clone for StreamSlot {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
 
-        // Sample a point uniformly along the arc
-        let rng = match seed {
-            Some(seed) => &mut rand_pcg::Pcg64::seed_from_u64(seed),
-            None => &mut self.init_rng,
-        };
-        let arc = self.road.spline.total_length() * rng.random::<f32>();
 
-        // Find the parameter of the point
-        let f = |u| { self.road.spline.arc_length(u) - arc };
-        let u = find_root(f, 0.0, self.road.spline.total_length(), 0.05).expect("root to exist given curated range");
 
-        let position = self.road.spline.get(u);
-        let unit_forward = self.road.spline.tangent(u);
+impl<R: Road> Simulator<R> {
+    /// Start a new episode. Given a seed, the random number generator of the simulator is
+    /// reseeded, which fixes this and all following episodes until the next seed.
+    pub fn reset(&mut self, seed: Option<u64>) {
+        if let Some(seed) = seed {
+            self.rng = rand_pcg::Pcg64::seed_from_u64(seed);
+            self.episode = 0;
+        }
 
-        self.state = CarState { position, unit_forward, ..CarState::default() };
+        // The road or its config may have been replaced since the last episode
+        self.road.set_ray_march(self.config.ray_march);
+        let arc = self.config.start.sample(self.road.track_length(), self.episode, &mut self.rng);
+        self.episode += 1;
+        let (position, unit_forward) = self.road.pose_at(arc);
+
+        // Only draw when episodes may be reversed, so that the random stream is the same otherwise
+        let reverse_prob = self.config.reverse_prob;
+        self.reversed = reverse_prob > 0.0 && self.rng.random::<f32>() < reverse_prob;
+        let unit_forward = if self.reversed { -unit_forward } else { unit_forward };
+
+        self.state = CarState { position, unit_forward, energy: self.config.car.energy_capacity, ..CarState::default() };
+        self.projection = (position, self.road.project(position));
+        self.road.reset_obstacles(arc);
+        self.on_shoulder = false;
+        self.steer_target = 0.0;
+        self.actuation = Actuation::default();
+        self.laps = LapCounter::default();
+        self.tire = TireUsage::default();
         self.t = 0.0;
         self.i = 0;
+        self.trajectory.clear();
+        self.observation_history.clear();
+        self.remember_observation();
+        self.begin_recorded_episode();
     }
 
+    /// Step with a discrete action, which is valid in either action mode
     pub fn step(&mut self, action: Action) -> TransitionObservation {
+        self.step_action(action.into(), self.config.action_mode.index_of(action))
+    }
+
+    /// Step with an action which steers and changes the speed at once. Recorded by its flattened
+    /// integer value, so it should only be used in the 'SteerThrottle' action mode.
+    pub fn step_steer_throttle(&mut self, action: SteerThrottle) -> TransitionObservation {
+        self.step_action(action, action.index())
+    }
+
+    /// Step with the integer value of an action in the action mode of the config
+    pub fn step_index(&mut self, action: u8) -> Result<TransitionObservation, InvalidActionError> {
+        let steer_throttle = self.config.action_mode.to_steer_throttle(action)?;
+        Ok(self.step_action(steer_throttle, action))
+    }
+
+    /// Step with a raw car input, bypassing the actions and their perturbations, e.g. for
+    /// continuous controllers. The steering target is limited to 'max_delta' and the forward
+    /// acceleration to between zero and 'acceleration' of the car config, as for actions. Recorded
+    /// as the action 'RAW_INPUT_ACTION'.
+    pub fn step_input(&mut self, input: CarInput) -> TransitionObservation {
+        let CarConfig { max_delta, acceleration, .. } = self.config.car;
+        let input = CarInput {
+            forward_acc: input.forward_acc.clamp(0.0, acceleration),
+            target_delta: input.target_delta.clamp(-max_delta, max_delta),
+            braking: input.braking,
+        };
+        self.step_car_input(input, RAW_INPUT_ACTION)
+    }
+
+    /// Get the action which reaches the car when 'action' is taken, after the delay and the
+    /// sticky actions of the perturbation config
+    fn actuate(&mut self, action: SteerThrottle) -> SteerThrottle {
+        let PerturbationConfig { sticky_action_prob, action_delay, .. } = self.config.perturbation;
+        let Actuation { pending, last } = &mut self.actuation;
+
+        pending.push_back(action);
+        let delayed = if pending.len() > action_delay {
+            pending.pop_front().expect("queue to be nonempty")
+        } else {
+            Action::Coast.into()
+        };
+
+        // Only draw when sticky, so that the random stream is the same without perturbations
+        let actuated = match *last {
+            Some(last) if sticky_action_prob > 0.0 && self.rng.random::<f32>() < sticky_action_prob => last,
+            _ => delayed,
+        };
+        *last = Some(actuated);
+        actuated
+    }
+
+    /// Step with an action, recording 'action_id' as the action taken
+    fn step_action(&mut self, action: SteerThrottle, action_id: u8) -> TransitionObservation {
+        let action = self.actuate(action);
+        let input = action.to_input(&self.state, &self.config.car);
+        self.step_car_input(input, action_id)
+    }
+
+    /// Step with the input that reaches the car, recording 'action_id' as the action taken
+    fn step_car_input(&mut self, input: CarInput, action_id: u8) -> TransitionObservation {
         let SimConfig { dt, car: car_cfg, .. } = &self.config;
         let dt = *dt;
 
-        let delta_factor = 5.0 / self.state.speed.max(5.0);
-        let input = match action {
-            Action::Left => CarInput { forward_acc: 0.0, target_delta: car_cfg.max_delta*delta_factor, braking: false },
-            Action::Right => CarInput { forward_acc: 0.0, target_delta: -car_cfg.max_delta*delta_factor, braking: false },
-            Action::Accelerate => CarInput { forward_acc: car_cfg.acceleration, target_delta: 0.0, braking: false },
-            Action::Brake => CarInput { forward_acc: 0.0, target_delta: 0.0, braking: true },
-            Action::Coast => CarInput { forward_acc: 0.0, target_delta: 0.0, braking: false },
+        // Slew the steering target, as a person turning a wheel would, unless it may jump
+        let steer_target = match car_cfg.steer_target_rate {
+            Some(rate) => {
+                let max_change = rate*dt;
+                self.steer_target + (input.target_delta - self.steer_target).clamp(-max_change, max_change)
+            },
+            None => input.target_delta,
         };
-        let new_state = self.state.update(&input, dt, car_cfg);
+        let input = CarInput { target_delta: steer_target, forward_acc: input.forward_acc, braking: input.braking };
+        let mut new_state = self.state.update(&input, dt, car_cfg);
+        self.road.advance_obstacles(dt);
 
-        let is_crashed = self.road.is_crashed(&new_state, car_cfg);
+        // Disturb the car, drawing only when disturbances are enabled so that the random stream is
+        // the same otherwise
+        let PerturbationConfig { heading_noise, lateral_noise, .. } = self.config.perturbation;
+        if heading_noise > 0.0 {
+            new_state.unit_forward = new_state.unit_forward.rotate(heading_noise*dt.sqrt()*standard_normal(&mut self.rng));
+        }
+        if lateral_noise > 0.0 {
+            let offset = lateral_noise*dt.sqrt()*standard_normal(&mut self.rng);
+            new_state.position = new_state.position + new_state.unit_forward.rotate90()*offset;
+        }
+
+        // Driving on the shoulder slows the car down, in either direction, which does not move it.
+        // A car which passes off the road within the step crashes, even if it lands back on it.
+        let RoadContact { on_shoulder, crashed } = self.road.contact(&new_state, car_cfg);
+        let is_crashed = crashed || self.road.path_leaves_road(self.state.position, new_state.position);
+        if on_shoulder {
+            let speed = new_state.speed;
+            new_state.speed = speed.signum() * (speed.abs() - car_cfg.shoulder_deceleration*dt).max(0.0);
+        }
+
+        // A bouncing car reflects off the edge, whose direction is that of the centerline nearby
+        if is_crashed && self.config.crash_mode == CrashMode::Bounce {
+            let crash_progress = self.road.project(new_state.position).progress;
+            let normal = self.road.pose_at(crash_progress).1.rotate90();
+            let unit_forward = new_state.unit_forward - normal*(2.0*new_state.unit_forward.dot(normal));
+            new_state.unit_forward = unit_forward / unit_forward.norm();
+            new_state.speed *= self.config.restitution;
+            new_state.position = self.state.position;
+        }
 
-        let reward = self.reward(&self.state, &new_state, is_crashed);
+        // Reuse the projection of the previous step, unless the car has been moved since
+        let (projected_position, projection) = self.projection;
+        let projection = if projected_position == self.state.position {
+            projection
+        } else {
+            self.road.project_near(self.state.position, projection.progress)
+        };
+        let new_projection = self.road.project_near(new_state.position, projection.progress);
+        let travel = self.road.progress_delta(projection.progress, new_projection.progress);
+        let mut reward_terms = self.config.reward.terms(&self.road, &projection, &new_projection, is_crashed, on_shoulder, dt);
+        if self.reversed {
+            reward_terms.travel = -reward_terms.travel;
+        }
+        let reward = self.config.reward.reward(&reward_terms);
 
-        let done = is_crashed;
+        let done = is_crashed && self.config.crash_mode == CrashMode::Terminate;
 
         // Do the transition
+        self.tire = TireUsage::between(&self.state, &new_state, dt, &self.config.car);
         self.state = new_state;
+        self.on_shoulder = on_shoulder;
+        self.steer_target = steer_target;
+        self.projection = (self.state.position, new_projection);
         self.t += dt;
         self.i += 1;
+        self.laps.distance += if self.reversed { -travel } else { travel };
+        if self.laps.distance >= (self.laps.laps + 1) as f32 * self.road.track_length() {
+            self.laps.laps += 1;
+            self.laps.lap_start = self.t;
+        }
+        self.trajectory.push(&self.state, action_id, reward);
+        self.remember_observation();
+        let grip_utilization = self.grip_utilization();
+        if let Some(telemetry) = &mut self.telemetry.0 {
+            telemetry.log(&TelemetryRow {
+                t: self.t, x: self.state.position.0, y: self.state.position.1,
+                speed: self.state.speed, steer: self.state.steer_delta,
+                progress: new_projection.progress, reward: reward_terms, done,
+                tire: self.tire, grip_utilization,
+            });
+        }
+
+        if self.recorder.is_some() {
+            let mut observation = vec![0.0; self.observation_dim()];
+            self.observe_into(&mut observation);
+            let step = StepRecord { action: action_id, reward, done, state: self.state.clone(), observation };
+            if let Some(recorder) = &mut self.recorder {
+                recorder.record_step(step);
+            }
+        }
+        #[cfg(feature = "stream")]
+        if let Some((publisher, steps_per_frame)) = &self.stream.0 && self.i.is_multiple_of(*steps_per_frame) {
+            publisher.publish(&Frame::from_sim(self));
+        }
+
+        TransitionObservation { reward, done, on_shoulder }
+    }
 
-        TransitionObservation { reward, done }
+    /// Get the accelerations of the tyres at the last step
+    pub fn tire_usage(&self) -> TireUsage {
+        self.tire
     }
 
+    /// Get the fraction of the grip used at the last step, see 'TireUsage::grip_utilization'
+    pub fn grip_utilization(&self) -> Option<f32> {
+        self.tire.grip_utilization(&self.config.car)
+    }
+
+    /// Get the projection of the car position computed by the last step, unless the car has been
+    /// moved since
+    fn current_projection(&self) -> Option<&TrackProjection> {
+        let (projected_position, projection) = &self.projection;
+        (*projected_position == self.state.position).then_some(projection)
+    }
+
+    /// Get the observation, which lags behind by the observation delay of the perturbation config
     pub fn observe(&self) -> StateObservation {
-        let lidar_readings = self.road.read_lidar(&self.state, &self.config.lidar);
-        let CarState { steer_delta, speed, .. } = self.state;
-        StateObservation { lidar_readings, steer_delta, speed }
+        match self.observation_history.front() {
+            Some(observation) => observation.clone(),
+            None => self.observe_current(),
+        }
+    }
+
+    /// Get the observation of the current state without any delay, e.g. to draw the LiDAR rays
+    /// from the current pose of the car
+    pub fn observe_current(&self) -> StateObservation {
+        let mut lidar_readings = vec![0.0; self.config.lidar.n_angles()];
+        self.road.read_lidar_into(&self.state, &self.config.lidar, self.current_projection(), &mut lidar_readings);
+        let CarState { steer_delta, speed, energy, .. } = self.state;
+        let grip_utilization = self.grip_utilization();
+        StateObservation { lidar_readings, steer_delta, speed, on_shoulder: self.on_shoulder, energy, grip_utilization }
+    }
+
+    /// Add the current observation to the history of delayed observations, if observations are
+    /// delayed
+    fn remember_observation(&mut self) {
+        let delay = self.config.perturbation.observation_delay;
+        if delay == 0 {
+            self.observation_history.clear();
+            return;
+        }
+        self.observation_history.push_back(self.observe_current());
+        while self.observation_history.len() > delay + 1 {
+            self.observation_history.pop_front();
+        }
+    }
+
+    /// Get the length of the observation vector written by 'observe_into'
+    pub fn observation_dim(&self) -> usize {
+        self.config.observation_dim()
+    }
+
+    /// Write the observation vector, the LiDAR readings followed by the features selected in the
+    /// observation config, into 'out' without allocating
+    pub fn observe_into(&self, out: &mut [f32]) {
+        assert_eq!(out.len(), self.observation_dim(), "Observation buffer has the wrong length");
+        let (lidar_readings, features) = out.split_at_mut(self.config.lidar.n_angles());
+        let (steer_delta, car_speed, on_shoulder, car_energy, grip_utilization) = match self.observation_history.front() {
+            Some(observation) => {
+                lidar_readings.copy_from_slice(&observation.lidar_readings);
+                (observation.steer_delta, observation.speed, observation.on_shoulder, observation.energy, observation.grip_utilization)
+            },
+            None => {
+                self.road.read_lidar_into(&self.state, &self.config.lidar, self.current_projection(), lidar_readings);
+                (self.state.steer_delta, self.state.speed, self.on_shoulder, self.state.energy, self.grip_utilization())
+            },
+        };
+
+        let ObservationConfig { delta, speed, shoulder, energy, grip } = self.config.observation;
+        let values = [
+            (delta, steer_delta),
+            (speed, car_speed),
+            (shoulder, on_shoulder as i32 as f32),
+            (energy, car_energy.unwrap_or(f32::INFINITY)),
+            (grip, grip_utilization.unwrap_or(0.0)),
+        ];
+        for (feature, (_, value)) in features.iter_mut().zip(values.into_iter().filter(|&(included, _)| included)) {
+            *feature = value;
+        }
     }
 
 }
 
-impl Simulator<SplineMap> {
-    pub fn new(config: SimConfig, road: SplineMap, seed: Option<u64>) -> Self {
+impl<R: Road> Simulator<R> {
+    pub fn new(config: SimConfig, mut road: R, seed: Option<u64>) -> Self {
+        road.set_ray_march(config.ray_march);
         let state = CarState::default();
+        let projection = (state.position, road.project(state.position));
 
-        let init_rng = match seed {
+        // Only an unseeded simulator draws from the process-global generator, once
+        let rng = match seed {
             Some(seed) => rand_pcg::Pcg64::seed_from_u64(seed),
             None => rand_pcg::Pcg64::from_rng(&mut rand::rng()),
         };
 
-        Self { config, road, state, t: 0.0, i: 0, init_rng}
+        let record = config.record;
+        let mut this = Self { config, road, state, on_shoulder: false, steer_target: 0.0, actuation: Actuation::default(),
+            observation_history: VecDeque::new(), reversed: false, episode: 0, laps: LapCounter::default(), tire: TireUsage::default(), projection, t: 0.0, i: 0, rng, recorder: None,
+            trajectory: Trajectory::default(), telemetry: TelemetrySlot::default(),
+            #[cfg(feature = "stream")]
+            stream: StreamSlot::default() };
+        if record {
+            this.start_recording();
+        }
+        this
     }
 
-    fn reward(&self, state: &CarState, new_state: &CarState, is_crashed: bool) -> f32 {
-        let rcfg = &self.config.reward;
-
-        let ClosestPointOutput { parameter: p1, distance_sq: d1_sq } = self.road.spline.closest_point(state.position);
-        let ClosestPointOutput { parameter: p2, distance_sq: d2_sq } = self.road.spline.closest_point(new_state.position);
-        let travel1 = self.road.spline.arc_length(p1);
-        let travel2 = self.road.spline.arc_length(p2);
+    /// Reseed the random number generator, as 'reset' does given a seed
+    pub(crate) fn reseed(&mut self, seed: u64) {
+        self.rng = rand_pcg::Pcg64::seed_from_u64(seed);
+        self.episode = 0;
+    }
 
-        let total_length = self.road.spline.total_length();
-        let travel = (travel2 - travel1 + 1.5*total_length) % total_length - 0.5*total_length;
-        let d_sq_decrease = d2_sq - d1_sq;
-        rcfg.travel_coeff * travel 
-            + rcfg.center_coeff * d_sq_decrease 
-            - rcfg.center_integral_coeff * d2_sq * self.config.dt
-            + rcfg.crash_reward*(is_crashed as i32 as f32)
+    /// Get the random number generator, e.g. to randomize an episode before 'reset'
+    pub(crate) fn rng(&mut self) -> &mut rand_pcg::Pcg64 {
+        &mut self.rng
     }
 
     /// Get the clock of the simulator
@@ -196,6 +1025,184 @@ impl Simulator<SplineMap> {
     pub fn get_i(&self) -> usize {
         self.i
     }
+
+    /// Get the progress along the track of the car at the last step
+    pub fn progress(&self) -> f32 {
+        self.projection.1.progress
+    }
+
+    /// Get the distance driven along the track since the last reset, in the direction of the
+    /// episode, so that driving backwards decreases it
+    pub fn distance(&self) -> f32 {
+        self.laps.distance
+    }
+
+    /// Get the number of laps completed since the last reset
+    pub fn laps(&self) -> usize {
+        self.laps.laps
+    }
+
+    /// Get the time spent in the current lap, i.e. since the last lap was completed or else since
+    /// the reset
+    pub fn lap_time(&self) -> f32 {
+        self.t - self.laps.lap_start
+    }
+
+    /// Get whether the episode is driven in the reverse direction of the track, see
+    /// 'SimConfig::reverse_prob'
+    pub fn is_reversed(&self) -> bool {
+        self.reversed
+    }
+
+    /// Get whether the car is currently on the shoulder of the road
+    pub fn get_on_shoulder(&self) -> bool {
+        self.on_shoulder
+    }
+
+    /// Start recording the steps of all episodes, beginning with the current one from the current
+    /// state. Any previous recording is discarded.
+    pub fn start_recording(&mut self) {
+        self.recorder = Some(Recorder::default());
+        self.begin_recorded_episode();
+    }
+
+    /// Stop recording, and get the recorded episodes if recording
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recorder.take().map(Recorder::into_recording)
+    }
+
+    pub fn recorder(&self) -> Option<&Recorder> {
+        self.recorder.as_ref()
+    }
+
+    /// Log the telemetry of every step from now on to a CSV file at 'path', replacing any current
+    /// logger. A current logger is finished first, and its error returned, if any.
+    pub fn start_telemetry<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
+        self.stop_telemetry()?;
+        self.telemetry.0 = Some(TelemetryLogger::create(path)?);
+        Ok(())
+    }
+
+    /// Stop logging telemetry and flush the file, reporting the first error while logging. Does
+    /// nothing if no telemetry is being logged.
+    pub fn stop_telemetry(&mut self) -> std::io::Result<()> {
+        match self.telemetry.0.take() {
+            Some(logger) => logger.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Stream a frame of every 'steps_per_frame'-th step to WebSocket clients connecting to
+    /// 'address', replacing any current publisher. Returns the address listened on.
+    #[cfg(feature = "stream")]
+    pub fn start_streaming<A: std::net::ToSocketAddrs>(&mut self, address: A, steps_per_frame: usize) -> std::io::Result<std::net::SocketAddr> {
+        self.stop_streaming();
+        let publisher = FramePublisher::bind(address)?;
+        let address = publisher.local_addr();
+        self.stream.0 = Some((publisher, steps_per_frame.max(1)));
+        Ok(address)
+    }
+
+    #[cfg(feature = "stream")]
+    pub fn stop_streaming(&mut self) {
+        self.stream.0 = None;
+    }
+
+    /// Run 'n_steps' steps from the current state with actions from 'policy', resetting whenever
+    /// an episode ends, and collect the observations the actions were chosen from along with the
+    /// actions, rewards and done flags
+    pub fn rollout<P: Policy + ?Sized>(&mut self, policy: &mut P, n_steps: usize) -> Demonstrations {
+        let mut rollout = Demonstrations::default();
+        let mut observation = vec![0.0; self.observation_dim()];
+        for _ in 0 .. n_steps {
+            self.observe_into(&mut observation);
+            let action = policy.act(&observation);
+            let TransitionObservation { reward, done, .. } = self.step(action);
+            rollout.push(observation.clone(), self.config.action_mode.index_of(action), reward, done);
+            if done {
+                self.reset(None);
+            }
+        }
+        rollout
+    }
+
+    /// Get the positions, speeds, actions and rewards of the steps since the last reset
+    pub fn trajectory(&self) -> &Trajectory {
+        &self.trajectory
+    }
+
+    /// Reset with 'seed' and play a sequence of actions, returning the car state after each step
+    pub fn replay(&mut self, actions: &[Action], seed: Option<u64>) -> Vec<CarState> {
+        self.reset(seed);
+        actions.iter()
+            .map(|&action| {
+                self.step(action);
+                self.state.clone()
+            })
+            .collect()
+    }
+
+    /// Check that replaying a recorded episode from its start reproduces the recorded car states
+    /// within 'tolerance', e.g. to catch changes of the physics or nondeterminism. The simulator
+    /// must have the same road and config as the recording one, and is left at the end of the
+    /// replay, or at the first deviating step.
+    pub fn verify_episode(&mut self, episode: &EpisodeRecord, tolerance: f32) -> Result<(), ReplayError> {
+        self.set_state(&episode.start);
+        for (step, record) in episode.steps.iter().enumerate() {
+            self.step_index(record.action)
+                .map_err(|_| ReplayError::InvalidAction { step, action: record.action })?;
+            let deviation = state_deviation(&self.state, &record.state);
+            if deviation.is_nan() || deviation > tolerance {
+                return Err(ReplayError::Mismatch { step, deviation });
+            }
+        }
+        Ok(())
+    }
+
+    fn begin_recorded_episode(&mut self) {
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.begin_episode(self.get_state());
+            self.recorder = Some(recorder);
+        }
+    }
+
+    /// Take a snapshot of the dynamic state of the simulator
+    pub fn get_state(&self) -> SimState {
+        SimState {
+            car: self.state.clone(),
+            on_shoulder: self.on_shoulder,
+            steer_target: self.steer_target,
+            actuation: self.actuation.clone(),
+            observation_history: self.observation_history.clone(),
+            reversed: self.reversed,
+            episode: self.episode,
+            laps: self.laps,
+            tire: self.tire,
+            t: self.t,
+            i: self.i,
+            projection: self.projection,
+            obstacles: self.road.obstacle_state(),
+            rng: self.rng.clone(),
+        }
+    }
+
+    /// Restore a snapshot taken by 'get_state' on a simulator with the same road and config
+    pub fn set_state(&mut self, state: &SimState) {
+        self.state = state.car.clone();
+        self.on_shoulder = state.on_shoulder;
+        self.steer_target = state.steer_target;
+        self.actuation = state.actuation.clone();
+        self.observation_history = state.observation_history.clone();
+        self.reversed = state.reversed;
+        self.episode = state.episode;
+        self.laps = state.laps;
+        self.tire = state.tire;
+        self.t = state.t;
+        self.i = state.i;
+        self.projection = state.projection;
+        self.road.set_obstacle_state(&state.obstacles);
+        self.rng = state.rng.clone();
+    }
 }
 
 
@@ -203,6 +1210,7 @@ impl Simulator<SplineMap> {
 mod tests {
     use super::*;
     use crate::map;
+    use crate::map::{SplineMap, CellMap};
 
     fn make_sim() -> Simulator<SplineMap> {
         let config = SimConfig { dt: 0.25, ..SimConfig::default() };
@@ -233,7 +1241,7 @@ mod tests {
 
         // Accelerate uncontrollably; should crash eventually
         for _ in 1 .. 50 {
-            TransitionObservation { done, reward } = env.step(Action::Accelerate);
+            TransitionObservation { done, reward, .. } = env.step(Action::Accelerate);
             dbg!(reward, done);
             if done {
                 break
@@ -242,5 +1250,698 @@ mod tests {
         assert!(done);
         assert!(reward < 0.0)
     }
-}
 
+    #[test]
+    fn test_cell_map() {
+        let config = SimConfig { dt: 0.25, ..SimConfig::default() };
+        let road = CellMap::new(map::CIRCUIT, 20.0);
+        let mut env = Simulator::new(config, road, Some(0));
+        env.reset(Some(0));
+        assert!(!env.road.is_crashed(&env.state, &env.config.car));
+
+        // Driving straight along the track gives positive reward
+        let TransitionObservation { reward, done, .. } = env.step(Action::Coast);
+        assert!(!done);
+        assert!(reward > 0.0);
+
+        // Accelerating without steering eventually hits a corner
+        let mut done = false;
+        for _ in 1 .. 50 {
+            TransitionObservation { done, .. } = env.step(Action::Accelerate);
+            if done {
+                break
+            }
+        }
+        assert!(done);
+    }
+
+    #[test]
+    fn test_obstacle() {
+        let mut env = make_sim();
+        env.reset(Some(0));
+        let center_ray = env.config.lidar.n_angles() / 2;
+        let free_reading = env.observe().lidar_readings[center_ray];
+        assert!(!env.road.is_crashed(&env.state, &env.config.car));
+
+        // Place a cone on the centerline a short distance ahead of the car
+        let u = env.road.spline.closest_point(env.state.position).parameter;
+        let arc = env.road.spline.arc_length(u);
+        env.road.place_obstacle(map::ObstacleShape::Circle { radius: 1.0 }, arc + 3.0, 0.0);
+
+        let blocked_reading = env.observe().lidar_readings[center_ray];
+        assert!(blocked_reading < free_reading);
+        assert!((blocked_reading - 2.0).abs() < 0.2);
+        assert!(env.road.is_crashed(&env.state, &env.config.car));
+    }
+
+    #[test]
+    fn test_shoulder() {
+        let mut env = make_sim();
+        env.road.set_shoulder_width(4.0);
+        env.reset(Some(0));
+
+        // Move the car sideways, just beyond the edge of the driveable road
+        let lateral = env.state.unit_forward.rotate90() * (0.5*env.road.width + 1.0);
+        env.state.position = env.state.position + lateral;
+        assert!(!env.road.is_crashed(&env.state, &env.config.car));
+        assert!(env.road.is_on_shoulder(&env.state, &env.config.car));
+
+        let speed = env.state.speed;
+        let TransitionObservation { done, on_shoulder, .. } = env.step(Action::Coast);
+        assert!(!done);
+        assert!(on_shoulder);
+        assert!(env.observe().on_shoulder);
+        assert!(env.state.speed < speed);
+    }
+
+    #[test]
+    fn test_moving_obstacle() {
+        let mut env = make_sim();
+        let shape = map::ObstacleShape::Rectangle { length: 4.0, width: 2.0 };
+        env.road.add_moving_obstacle(shape, 10.0, 0.0, 4.0);
+        env.reset(Some(0));
+
+        // The pace car is placed relative to the start pose of the car
+        let u = env.road.spline.closest_point(env.state.position).parameter;
+        let arc = env.road.spline.arc_length(u);
+        let start_arc = env.road.moving_obstacles[0].arc();
+        assert!((start_arc - arc - 10.0).abs() < 0.1);
+
+        let center_ray = env.config.lidar.n_angles() / 2;
+        let reading = env.observe().lidar_readings[center_ray];
+        assert!(reading < 8.5);
+
+        // The pace car moves along the road, and the car catches up with the slower obstacle
+        env.step(Action::Coast);
+        assert!((env.road.moving_obstacles[0].arc() - start_arc - 4.0*env.config.dt).abs() < 1e-3);
+        assert!(env.observe().lidar_readings[center_ray] < reading);
+    }
+
+    #[test]
+    fn test_shared_queries() {
+        let mut env = make_sim();
+        env.road.set_shoulder_width(2.0);
+        env.reset(Some(0));
+        for lateral in [0.0, 0.5*env.road.width + 1.0, 0.5*env.road.width + 3.0] {
+            let mut state = env.state.clone();
+            state.position = state.position + state.unit_forward.rotate90()*lateral;
+            let RoadContact { on_shoulder, crashed } = env.road.contact(&state, &env.config.car);
+            assert_eq!(on_shoulder, env.road.is_on_shoulder(&state, &env.config.car));
+            assert_eq!(crashed, env.road.is_crashed(&state, &env.config.car));
+        }
+
+        // Observing with the projection of the last step reads the same LiDAR
+        for action in [Action::Accelerate, Action::Left, Action::Coast] {
+            env.step(action);
+            assert_eq!(env.observe().lidar_readings, env.road.read_lidar(&env.state, &env.config.lidar));
+        }
+    }
+
+    #[test]
+    fn test_observe_into() {
+        let mut env = make_sim();
+        env.config.observation = ObservationConfig { delta: false, speed: true, shoulder: true, energy: false, grip: false };
+        env.reset(Some(0));
+        env.step(Action::Accelerate);
+
+        let mut out = vec![0.0; env.observation_dim()];
+        env.observe_into(&mut out);
+        let StateObservation { lidar_readings, speed, on_shoulder, .. } = env.observe();
+        let n = lidar_readings.len();
+        assert_eq!(out.len(), n + 2);
+        assert_eq!(out[..n], lidar_readings[..]);
+        assert_eq!(out[n .. ], [speed, on_shoulder as i32 as f32]);
+    }
+
+    #[test]
+    fn test_energy() {
+        let mut env = make_sim();
+        env.config.car.energy_capacity = Some(1.0);
+        env.config.observation.energy = true;
+        env.reset(Some(0));
+        assert_eq!(env.state.energy, Some(1.0));
+        assert_eq!(env.config.observation_components().last().unwrap().label, "energy");
+
+        // Accelerating spends the energy, which is observed last
+        let mut out = vec![0.0; env.observation_dim()];
+        env.step(Action::Accelerate);
+        env.observe_into(&mut out);
+        let energy = env.state.energy.unwrap();
+        assert!(energy < 1.0);
+        assert_eq!(*out.last().unwrap(), energy);
+
+        // Once spent, accelerating no longer gains speed
+        env.step(Action::Accelerate);
+        assert_eq!(env.state.energy, Some(0.0));
+        let speed = env.state.speed;
+        env.step(Action::Accelerate);
+        assert!(env.state.speed <= speed);
+
+        // A new episode refills it
+        env.reset(Some(0));
+        assert_eq!(env.state.energy, Some(1.0));
+    }
+
+    #[test]
+    fn test_seeded_episodes() {
+        // Plays a few episodes without seeding them
+        let run = |env: &mut Simulator<SplineMap>| {
+            (0 .. 3)
+                .map(|_| {
+                    env.reset(None);
+                    env.step(Action::Accelerate);
+                    (env.state.position, env.observe().lidar_readings)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let trace = run(&mut make_sim());
+        assert_eq!(trace, run(&mut make_sim()));
+        assert_ne!(trace[0].0, trace[1].0);
+
+        // Seeding a reset fixes the following episodes, whatever came before
+        let mut unseeded = Simulator::new(SimConfig { dt: 0.25, ..SimConfig::default() }, map::make_oval(), None);
+        unseeded.reset(Some(7));
+        let mut played = make_sim();
+        run(&mut played);
+        played.reset(Some(7));
+        assert_eq!(unseeded.state.position, played.state.position);
+        assert_eq!(run(&mut unseeded), run(&mut played));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut env = make_sim();
+        env.road.add_moving_obstacle(map::ObstacleShape::Circle { radius: 1.0 }, 30.0, 0.0, 4.0);
+        env.reset(Some(0));
+        env.step(Action::Accelerate);
+        let snapshot = env.get_state();
+
+        let play = |env: &mut Simulator<SplineMap>| {
+            let rewards: Vec<f32> = [Action::Left, Action::Accelerate, Action::Right, Action::Coast].into_iter()
+                .map(|action| env.step(action).reward)
+                .collect();
+            env.reset(None);
+            (rewards, env.observe().lidar_readings, env.get_state())
+        };
+        let first = play(&mut env);
+
+        // Restoring replays the same steps and the same next random start, also after a round
+        // trip through JSON
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let restored: SimState = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, snapshot);
+        env.set_state(&restored);
+        assert_eq!(env.get_state(), snapshot);
+        assert_eq!(env.get_i(), 1);
+        assert_eq!(play(&mut env), first);
+        assert!(snapshot.progress() > 0.0);
+    }
+
+    #[test]
+    fn test_clone() {
+        let mut env = make_sim();
+        env.reset(Some(0));
+        env.step(Action::Accelerate);
+        let mut fork = env.clone();
+        for env in [&mut env, &mut fork] {
+            env.step(Action::Left);
+            env.reset(None);
+        }
+        assert_eq!(env.get_state(), fork.get_state());
+    }
+
+    #[test]
+    fn test_config_file() {
+        // Missing fields keep their defaults
+        let config = SimConfig::from_toml("dt = 0.1\n[reward]\ncrash_reward = -10.0\n[lidar]\nangles = [10.0, 45.0]\n").unwrap();
+        assert_eq!(config.dt, 0.1);
+        assert_eq!(config.reward.crash_reward, -10.0);
+        assert_eq!(config.reward.travel_coeff, RewardConfig::default().travel_coeff);
+        assert_eq!(config.car.length, CarConfig::default().length);
+        assert_eq!(config.lidar.get_angles(), LidarArray::new(vec![10.0, 45.0]).get_angles());
+        assert!(config.observation.speed);
+
+        for extension in ["json", "toml"] {
+            let path = std::env::temp_dir().join(format!("car_sim_test_config.{}", extension));
+            config.save(&path).unwrap();
+            let loaded = SimConfig::from_file(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(serde_json::to_string(&loaded).unwrap(), serde_json::to_string(&config).unwrap());
+        }
+
+        assert!(matches!(SimConfig::from_toml("dt = 'fast'"), Err(ConfigLoadError::Parse(_))));
+        assert!(matches!(SimConfig::from_file("config.yaml"), Err(ConfigLoadError::UnsupportedFormat(_))));
+    }
+
+    #[test]
+    fn test_telemetry() {
+        let path = std::env::temp_dir().join("car_sim_test_telemetry.csv");
+        let mut env = make_sim();
+        env.start_telemetry(&path).unwrap();
+        let rewards: Vec<f32> = [Action::Accelerate, Action::Left].into_iter().map(|action| env.step(action).reward).collect();
+        // A clone does not log to the same file
+        env.clone().step(Action::Brake);
+        env.stop_telemetry().unwrap();
+        env.step(Action::Coast);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("t,x,y,speed,steer,progress,"));
+        let accelerations = [env.config.car.acceleration, 0.0];
+        for ((line, reward), acceleration) in lines[1..].iter().zip(rewards).zip(accelerations) {
+            // The grip utilization is empty without a grip limit
+            let columns: Vec<&str> = line.split(',').collect();
+            assert_eq!(columns.last(), Some(&""));
+            let columns: Vec<f32> = columns[.. columns.len() - 1].iter().map(|column| column.parse().unwrap()).collect();
+            let reward_column = 11;
+            assert_eq!(columns[reward_column], reward);
+            assert!((columns[6 .. reward_column].iter().sum::<f32>() - reward).abs() < 1e-4);
+            assert!((columns[reward_column + 2] - acceleration).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_recording() {
+        let config = SimConfig { dt: 0.25, record: true, ..SimConfig::default() };
+        let mut env = Simulator::new(config, map::make_oval(), Some(0));
+        env.reset(Some(1));
+        let start = env.get_state();
+        let actions = [Action::Accelerate, Action::Left, Action::Coast];
+        for action in actions {
+            env.step(action);
+        }
+        env.reset(None);
+        env.step(Action::Brake);
+
+        // The reset right after construction replaces the empty first episode
+        let recording = env.stop_recording().unwrap();
+        assert!(env.recorder().is_none());
+        assert_eq!(recording.episodes.len(), 2);
+        let episode = &recording.episodes[0];
+        assert_eq!(episode.start, start);
+        assert_eq!(episode.steps.iter().map(|step| step.action).collect::<Vec<_>>(), actions.map(|action| action as u8));
+        let last = &recording.episodes[1].steps[0];
+        assert_eq!(last.state, env.state);
+        assert_eq!(last.observation.len(), env.observation_dim());
+
+        let path = std::env::temp_dir().join("car_sim_test_recording.json");
+        recording.save(&path).unwrap();
+        let loaded = Recording::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, recording);
+
+        // Recording started mid-episode begins from the current state
+        env.start_recording();
+        env.step(Action::Coast);
+        let recording = env.stop_recording().unwrap();
+        assert_eq!(recording.episodes.len(), 1);
+        assert_eq!(recording.episodes[0].start.i, 1);
+    }
+
+    #[test]
+    fn test_observation_components() {
+        let config = SimConfig { observation: ObservationConfig { delta: false, speed: true, shoulder: true, energy: false, grip: false }, ..SimConfig::default() };
+        let components = config.observation_components();
+        assert_eq!(components.len(), config.observation_dim());
+        assert!(components[0].label.starts_with("lidar_"));
+        let features: Vec<&str> = components[config.lidar.n_angles() ..].iter().map(|component| component.label.as_str()).collect();
+        assert_eq!(features, ["speed", "on_shoulder"]);
+        assert_eq!(components.last().unwrap().high, 1.0);
+        assert_eq!(Action::ALL.map(Action::name), ["left", "right", "accelerate", "brake", "coast"]);
+    }
+
+    #[test]
+    fn test_rollout() {
+        let mut env = make_sim();
+        let mut policy = crate::policy::RandomPolicy::new(Some(0));
+        let rollout = env.rollout(&mut policy, 200);
+        assert_eq!(rollout.len(), 200);
+        assert!(rollout.observations.iter().all(|observation| observation.len() == env.observation_dim()));
+        assert!(rollout.actions.iter().all(|&action| Action::try_from(action).is_ok()));
+
+        // The environment resets after each episode, so only the steps since the last crash remain
+        let since_done = rollout.dones.iter().rev().take_while(|&&done| !done).count();
+        assert_eq!(env.get_i(), since_done);
+
+        // The same seeds give the same rollout
+        let mut other = make_sim();
+        assert_eq!(other.rollout(&mut crate::policy::RandomPolicy::new(Some(0)), 200), rollout);
+    }
+
+    #[test]
+    fn test_trajectory() {
+        let mut env = make_sim();
+        let actions = [Action::Accelerate, Action::Accelerate, Action::Left];
+        let rewards: Vec<f32> = actions.iter().map(|&action| env.step(action).reward).collect();
+
+        let trajectory = env.trajectory();
+        assert_eq!(trajectory.len(), 3);
+        assert_eq!(trajectory.actions, vec![2, 2, 0]);
+        assert_eq!(trajectory.rewards, rewards);
+        assert_eq!(trajectory.positions.last(), Some(&env.state.position));
+        assert_eq!(trajectory.speeds.last(), Some(&env.state.speed));
+
+        env.reset(None);
+        assert!(env.trajectory().is_empty());
+    }
+
+    #[test]
+    fn test_replay() {
+        let actions = [Action::Accelerate, Action::Left, Action::Left, Action::Coast, Action::Right];
+        let mut env = make_sim();
+        let states = env.replay(&actions, Some(5));
+        assert_eq!(states.len(), actions.len());
+        assert_eq!(states.last(), Some(&env.state));
+        assert_eq!(make_sim().replay(&actions, Some(5)), states);
+
+        let mut recorder = make_sim();
+        recorder.reset(Some(5));
+        recorder.start_recording();
+        for action in actions {
+            recorder.step(action);
+        }
+        let mut episode = recorder.stop_recording().unwrap().episodes.remove(0);
+        assert_eq!(episode.steps.iter().map(|step| step.state.clone()).collect::<Vec<_>>(), states);
+        assert_eq!(make_sim().verify_episode(&episode, 0.0), Ok(()));
+
+        // A changed physics config or a corrupt action log is caught at the first affected step
+        let mut modified = make_sim();
+        modified.config.car.steer_speed *= 2.0;
+        assert!(matches!(modified.verify_episode(&episode, 1e-3), Err(ReplayError::Mismatch { step: 1, .. })));
+        episode.steps[2].action = 9;
+        assert_eq!(make_sim().verify_episode(&episode, 1e-3), Err(ReplayError::InvalidAction { step: 2, action: 9 }));
+    }
+
+    #[test]
+    fn test_steer_target_rate() {
+        let mut env = make_sim();
+        env.config.car.steer_target_rate = Some(0.2);
+        env.reset(Some(1));
+
+        // The target moves by the rate per second, and the wheels follow it
+        let max_change = 0.2*env.config.dt;
+        env.step(Action::Left);
+        assert_eq!(env.get_state().steer_target, max_change);
+        assert!(env.state.steer_delta > 0.0 && env.state.steer_delta <= max_change);
+        env.step(Action::Left);
+        assert_eq!(env.get_state().steer_target, 2.0*max_change);
+        env.step(Action::Right);
+        assert!((env.get_state().steer_target - max_change).abs() < 1e-6);
+
+        // Without a rate, the target jumps to the one of the action at once
+        let mut env = make_sim();
+        env.reset(Some(1));
+        let input = Action::Left.to_input(&env.state, &env.config.car);
+        env.step(Action::Left);
+        assert_eq!(env.get_state().steer_target, input.target_delta);
+    }
+
+    #[test]
+    fn test_perturbations() {
+        let actions = [Action::Accelerate, Action::Left, Action::Left, Action::Right, Action::Brake, Action::Coast];
+
+        // A delayed car coasts first, and then drives as an undelayed one did before
+        let mut env = make_sim();
+        env.config.perturbation.action_delay = 2;
+        let delayed = env.replay(&actions, Some(4));
+        let undelayed = make_sim().replay(&[&[Action::Coast, Action::Coast], &actions[.. 4]].concat(), Some(4));
+        assert_eq!(delayed, undelayed);
+
+        // A car with sticky actions always repeats its first action
+        let mut env = make_sim();
+        env.config.perturbation.sticky_action_prob = 1.0;
+        let sticky = env.replay(&actions, Some(4));
+        assert_eq!(sticky, make_sim().replay(&[Action::Accelerate; 6], Some(4)));
+
+        // Sticky actions are replayed exactly from a snapshot
+        let mut env = make_sim();
+        env.config.perturbation.sticky_action_prob = 0.5;
+        env.config.perturbation.action_delay = 1;
+        env.reset(Some(4));
+        env.step(Action::Left);
+        let snapshot = env.get_state();
+        let states: Vec<CarState> = actions.iter().map(|&action| { env.step(action); env.state.clone() }).collect();
+        env.set_state(&snapshot);
+        let restored: Vec<CarState> = actions.iter().map(|&action| { env.step(action); env.state.clone() }).collect();
+        assert_eq!(states, restored);
+    }
+
+    #[test]
+    fn test_disturbances() {
+        let actions = [Action::Coast; 8];
+
+        // Disturbances push the car off the path it drives without them, reproducibly
+        let mut env = make_sim();
+        env.config.perturbation.heading_noise = 0.1;
+        env.config.perturbation.lateral_noise = 0.5;
+        let disturbed = env.replay(&actions, Some(4));
+        assert_eq!(disturbed, env.replay(&actions, Some(4)));
+        assert_ne!(disturbed, make_sim().replay(&actions, Some(4)));
+
+        // The draws are zero-mean with unit variance
+        let mut rng = rand_pcg::Pcg64::seed_from_u64(0);
+        let draws: Vec<f32> = (0 .. 10000).map(|_| standard_normal(&mut rng)).collect();
+        let mean = draws.iter().sum::<f32>() / draws.len() as f32;
+        let variance = draws.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / draws.len() as f32;
+        assert!(mean.abs() < 0.05);
+        assert!((variance - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_swept_crash() {
+        // A car fast enough to jump from one straight of the oval to the other in a step crashes
+        let mut env = make_sim();
+        env.reset(Some(0));
+        env.state = CarState { position: Vec2(-10.0, 0.0), unit_forward: Vec2(0.0, 1.0), speed: 80.0, ..CarState::default() };
+        let transition = env.step(Action::Coast);
+        assert!(!env.road.is_crashed(&env.state, &env.config.car));
+        assert!(transition.done);
+    }
+
+    #[test]
+    fn test_bounce() {
+        let mut env = make_sim();
+        env.config.crash_mode = CrashMode::Bounce;
+        env.reset(Some(0));
+
+        // Steer into the edge of the road until the car bounces off it
+        let mut bounce = None;
+        for _ in 0 .. 100 {
+            let before = env.state.clone();
+            let transition = env.step(Action::Left);
+            assert!(!transition.done);
+            if transition.reward <= env.config.reward.crash_reward + 1.0 {
+                bounce = Some((before, env.state.clone()));
+                break;
+            }
+        }
+        let (before, after) = bounce.expect("the car to crash");
+
+        // It is moved back inside the road, slowed down and turned away from the edge
+        assert_eq!(after.position, before.position);
+        assert!(!env.road.is_crashed(&after, &env.config.car));
+        assert!(after.speed < before.speed);
+        let projection = env.road.project(after.position);
+        let (center, tangent) = env.road.pose_at(projection.progress);
+        let outwards = (after.position - center).dot(tangent.rotate90()).signum();
+        assert!(after.unit_forward.dot(tangent.rotate90())*outwards < 0.0);
+    }
+
+    #[test]
+    fn test_observation_delay() {
+        let actions = [Action::Accelerate, Action::Left, Action::Left, Action::Right, Action::Brake];
+        let mut env = make_sim();
+        env.config.perturbation.observation_delay = 2;
+        env.reset(Some(6));
+        let mut current = vec![env.observe_current()];
+        let mut out = vec![0.0; env.observation_dim()];
+        for (i, action) in actions.into_iter().enumerate() {
+            env.step(action);
+            current.push(env.observe_current());
+
+            // The observation lags two steps behind, but not past the start of the episode
+            let delayed = &current[(i + 1).saturating_sub(2)];
+            assert_eq!(&env.observe(), delayed);
+            env.observe_into(&mut out);
+            assert_eq!(out[.. env.config.lidar.n_angles()], delayed.lidar_readings);
+            assert_eq!(out[env.config.lidar.n_angles() + 1], delayed.speed);
+        }
+
+        // Without a delay, the observation is the current one
+        env.config.perturbation.observation_delay = 0;
+        env.step(Action::Coast);
+        assert_eq!(env.observe(), env.observe_current());
+    }
+
+    #[test]
+    fn test_reversed_episodes() {
+        let reward = RewardConfig { travel_coeff: 1.0, center_coeff: 0.0, center_integral_coeff: 0.0, crash_reward: 0.0, shoulder_coeff: 0.0, ..RewardConfig::default() };
+        let mut forward = make_sim();
+        forward.config.reward = reward.clone();
+        forward.reset(Some(3));
+        let mut reversed = make_sim();
+        reversed.config.reward = reward;
+        reversed.config.reverse_prob = 1.0;
+        reversed.reset(Some(3));
+        assert!(!forward.is_reversed());
+        assert!(reversed.is_reversed());
+        assert_eq!(reversed.state.unit_forward, -forward.state.unit_forward);
+
+        // Driving ahead is rewarded for travel in either direction, though progress decreases when
+        // reversed
+        let progress = reversed.progress();
+        let (mut forward_return, mut reversed_return) = (0.0, 0.0);
+        for _ in 0 .. 4 {
+            forward_return += forward.step(Action::Accelerate).reward;
+            reversed_return += reversed.step(Action::Accelerate).reward;
+        }
+        assert!(forward_return > 0.0);
+        assert!(reversed_return > 0.0);
+        assert!(reversed.road.progress_delta(progress, reversed.progress()) < 0.0);
+
+        // The direction is part of the state
+        let state = reversed.get_state();
+        forward.set_state(&state);
+        assert!(forward.is_reversed());
+    }
+
+    #[test]
+    fn test_start_distribution() {
+        let mut env = make_sim();
+        let track_length = env.road.track_length();
+
+        // A grid sweeps the track and starts over when seeded
+        env.config.start = StartDistribution::Grid { n: 4 };
+        let mut starts = Vec::new();
+        for i in 0 .. 6 {
+            env.reset(if i == 0 { Some(1) } else { None });
+            starts.push(env.progress());
+        }
+        for (i, &progress) in starts.iter().enumerate() {
+            let expected = track_length * (i % 4) as f32 / 4.0;
+            assert!(env.road.progress_delta(expected, progress).abs() < 1e-2);
+        }
+        env.reset(Some(2));
+        assert_eq!(env.progress(), starts[0]);
+
+        // A fixed start is the same in every episode, whatever the seed
+        env.config.start = StartDistribution::Fixed { arc: 30.0 };
+        env.reset(Some(3));
+        let state = env.state.clone();
+        env.reset(Some(4));
+        assert_eq!(env.state, state);
+
+        let config = SimConfig::from_toml("[start.grid]\nn = 8\n").unwrap();
+        assert_eq!(config.start, StartDistribution::Grid { n: 8 });
+    }
+
+    #[test]
+    fn test_laps() {
+        let mut env = make_sim();
+        env.reset(Some(0));
+        let start = env.progress();
+        for _ in 0 .. 6 {
+            env.step(Action::Accelerate);
+        }
+        assert!((env.distance() - env.road.progress_delta(start, env.progress())).abs() < 1e-3);
+        assert_eq!(env.laps(), 0);
+        assert_eq!(env.lap_time(), env.get_t());
+
+        // Crossing a full track length completes a lap, which restarts the lap clock
+        env.laps.distance = env.road.track_length() - 1e-3;
+        env.step(Action::Accelerate);
+        assert_eq!(env.laps(), 1);
+        assert_eq!(env.lap_time(), 0.0);
+        env.step(Action::Accelerate);
+        assert_eq!(env.lap_time(), env.config.dt);
+
+        env.reset(None);
+        assert_eq!((env.distance(), env.laps(), env.lap_time()), (0.0, 0, 0.0));
+    }
+
+    #[test]
+    fn test_step_input() {
+        let mut env = make_sim();
+        env.reset(Some(2));
+        let mut raw = env.clone();
+        for action in [Action::Accelerate, Action::Accelerate, Action::Left, Action::Brake] {
+            let input = action.to_input(&env.state, &env.config.car);
+            assert_eq!(raw.step_input(input).reward, env.step(action).reward);
+            assert_eq!(raw.state, env.state);
+        }
+
+        // Inputs beyond the limits of the car are clamped
+        let mut clamped = raw.clone();
+        let max_delta = raw.config.car.max_delta;
+        raw.step_input(CarInput { forward_acc: 1e3, target_delta: 10.0, braking: false });
+        clamped.step_input(CarInput { forward_acc: clamped.config.car.acceleration, target_delta: max_delta, braking: false });
+        assert_eq!(raw.state, clamped.state);
+        assert_eq!(raw.trajectory().actions.last(), Some(&RAW_INPUT_ACTION));
+    }
+
+    #[test]
+    fn test_reward_postprocessing() {
+        let terms = RewardTerms { travel: 2.0, crash: -100.0, ..RewardTerms::default() };
+        let mut config = RewardConfig::default();
+        assert_eq!(config.reward(&terms), -98.0);
+        config.reward_scale = 0.5;
+        assert_eq!(config.reward(&terms), -49.0);
+        config.reward_clip = Some(10.0);
+        assert_eq!(config.reward(&terms), -10.0);
+        assert_eq!(config.reward(&RewardTerms { travel: 4.0, ..RewardTerms::default() }), 2.0);
+
+        // The simulator gives the postprocessed reward
+        let mut env = make_sim();
+        env.config.reward.reward_clip = Some(0.01);
+        env.reset(Some(0));
+        assert!(env.step(Action::Coast).reward.abs() <= 0.01);
+    }
+
+    #[test]
+    fn test_steer_throttle() {
+        for index in 0 .. SteerThrottle::N as u8 {
+            assert_eq!(SteerThrottle::from_index(index).unwrap().index(), index);
+        }
+        assert_eq!(SteerThrottle::from_index(SteerThrottle::N as u8), None);
+        assert_eq!(ActionMode::SteerThrottle.labels().len(), ActionMode::SteerThrottle.n_actions());
+
+        // The discrete actions drive the same in either mode
+        let actions = [Action::Accelerate, Action::Left, Action::Brake, Action::Right];
+        let mut env = make_sim();
+        env.config.action_mode = ActionMode::SteerThrottle;
+        env.reset(Some(2));
+        for action in actions {
+            assert!(env.step_index(env.config.action_mode.index_of(action)).is_ok());
+        }
+        assert_eq!(Some(&env.state), make_sim().replay(&actions, Some(2)).last());
+        assert!(env.step_index(SteerThrottle::N as u8).is_err());
+
+        // Only the combined action steers and accelerates in the same step
+        let mut env = make_sim();
+        env.reset(Some(2));
+        let speed = env.state.speed;
+        env.step_steer_throttle(SteerThrottle { steer: 1, throttle: Throttle::Accelerate });
+        assert!(env.state.speed > speed);
+        assert!(env.state.steer_delta > 0.0);
+    }
+
+    #[test]
+    fn test_projection_cache() {
+        let config = SimConfig { dt: 0.25, ..SimConfig::default() };
+        let mut env = Simulator::new(config, map::make_racetrack(), Some(0));
+        env.reset(Some(3));
+
+        // The warm-started projection agrees with a full search along the trajectory
+        for action in [Action::Accelerate, Action::Accelerate, Action::Left, Action::Coast, Action::Right] {
+            env.step(action);
+            let (position, projection) = env.projection;
+            assert_eq!(position, env.state.position);
+            let exact = env.road.project(env.state.position);
+            assert!((projection.progress - exact.progress).abs() < 1e-3);
+            assert!((projection.distance_sq - exact.distance_sq).abs() < 1e-3);
+        }
+    }
+}
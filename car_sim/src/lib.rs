@@ -2,3 +2,14 @@ pub mod physics;
 pub mod map;
 pub mod lidar;
 pub mod gym;
+pub mod recorder;
+pub mod telemetry;
+pub mod render;
+pub mod policy;
+pub mod controller;
+pub mod evaluation;
+pub mod curriculum;
+pub mod random_tracks;
+pub mod multi_car;
+#[cfg(feature = "stream")]
+pub mod stream;
@@ -1,21 +1,46 @@
 use std::cmp::Ordering;
 
+use serde::{Serialize, Deserialize};
 
-// A struct for maintaining the angles of an array of LIDAR sensors
-#[derive(Debug)]
+
+// A struct for maintaining the angles of an array of LIDAR sensors. Serialized as the angles in
+// degrees given to 'new'.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "LidarAngles", into = "LidarAngles")]
 pub struct LidarArray {
-    angles: Vec<f32>
+    angles: Vec<f32>,
+    /// The angles in degrees of the rays on one side, mirrored to the other
+    side_angles: Vec<f32>,
+}
+
+
+/// The serialized form of a 'LidarArray'
+#[derive(Serialize, Deserialize)]
+struct LidarAngles {
+    angles: Vec<f32>,
+}
+
+impl From<LidarAngles> for LidarArray {
+    fn from(LidarAngles { angles }: LidarAngles) -> Self {
+        LidarArray::new(angles)
+    }
+}
+
+impl From<LidarArray> for LidarAngles {
+    fn from(LidarArray { side_angles, .. }: LidarArray) -> Self {
+        LidarAngles { angles: side_angles }
+    }
 }
 
 
 impl LidarArray {
-    pub fn new(angles: Vec<f32>) -> Self {
-        let angles = angles.clone().into_iter().rev()
+    pub fn new(side_angles: Vec<f32>) -> Self {
+        let angles = side_angles.iter().rev().copied()
             .chain(std::iter::once(0.0))
-            .chain(angles.iter().map(|angle| -angle))
-            .map(|angle| angle.to_radians())
+            .chain(side_angles.iter().map(|angle| -angle))
+            .map(|angle: f32| angle.to_radians())
             .collect();
-        Self{ angles }
+        Self{ angles, side_angles }
     }
 
     pub fn n_angles(&self) -> usize {
@@ -25,6 +50,11 @@ impl LidarArray {
     pub fn get_angles(&self) -> &[f32] {
         &self.angles
     }
+
+    /// Get the angles in degrees of the rays on one side, as given to 'new'
+    pub fn side_angles(&self) -> &[f32] {
+        &self.side_angles
+    }
 }
 
 impl Default for LidarArray {
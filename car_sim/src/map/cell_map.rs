@@ -1,40 +1,185 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use itertools::Itertools;
 
 use math_utils::Vec2;
 
 use crate::physics::{CarState, CarConfig};
 use crate::lidar::{LidarDistance};
-use super::traits::Road;
+use super::traits::{Road, TrackProjection};
 
 
 #[derive(Hash, PartialEq, Eq, Debug, Copy, Clone)]
 pub struct Cell(pub i32, pub i32);
 
 
+/// Why a drawing of a cell track could not be read, see 'CellMap::from_ascii'
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsciiTrackError {
+    /// The drawing has no cells
+    Empty,
+    /// A character which is neither a direction nor empty space, at a line and column from 1
+    UnknownCharacter { line: usize, column: usize, character: char },
+    /// The cell points to a position without a cell
+    OffTrack(Cell),
+    /// The cell is not on the loop through the start cell, or is entered twice by it
+    NotOnLoop(Cell),
+}
+
+impl fmt::Display for AsciiTrackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsciiTrackError::Empty => write!(f, "The track has no cells"),
+            AsciiTrackError::UnknownCharacter { line, column, character } => write!(f, "Unknown character '{}' at line {}, column {}. Expected one of '>', '<', '^', 'v', '.' or ' '.", character, line, column),
+            AsciiTrackError::OffTrack(Cell(x, y)) => write!(f, "The cell at ({}, {}) points off the track", x, y),
+            AsciiTrackError::NotOnLoop(Cell(x, y)) => write!(f, "The cell at ({}, {}) is not on a single loop through the start", x, y),
+        }
+    }
+}
+
+impl std::error::Error for AsciiTrackError {}
+
+
+/// Why a loop of cells does not form a track, see 'CellMapBuilder::build'
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CellTrackError {
+    /// The cell is on the loop more than once
+    Repeated(Cell),
+    /// The cell is not next to the one before it on the loop or a branch, counting diagonally
+    Gap(Cell),
+}
+
+impl fmt::Display for CellTrackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CellTrackError::Repeated(Cell(x, y)) => write!(f, "The cell at ({}, {}) is on the track more than once", x, y),
+            CellTrackError::Gap(Cell(x, y)) => write!(f, "The cell at ({}, {}) is not next to the cell before it", x, y),
+        }
+    }
+}
+
+impl std::error::Error for CellTrackError {}
+
+
+#[derive(Clone)]
 pub struct CellMap {
-    pub cells: &'static [Cell],
+    /// The loop of cells along which progress is measured
+    pub cells: Vec<Cell>,
     pub cell_size: f32,
     min_x: i32, 
     max_x: i32,
     min_y: i32, 
     max_y: i32,
-    idx_map: HashMap<Cell, usize>
+    /// The cells which a car may cross into directly from each cell of the track, including the
+    /// cells of branches off the loop
+    links: HashMap<Cell, HashSet<Cell>>,
+    /// The distance along the loop from the center of the first cell to the center of the cell at
+    /// each index, with the total length of the loop last
+    cumulative: Vec<f32>,
 }
 
 
 impl CellMap {
-    pub fn new(cells: &'static [Cell], cell_size: f32) -> Self {
-        let mut idx_map = HashMap::new();
-        for (idx, &cell) in cells.iter().enumerate() {
-            idx_map.insert(cell, idx);
+    /// Create a track from a loop of cells, linking each cell to the next, without checking that
+    /// they are next to each other. See 'CellMap::builder' for a checked track.
+    pub fn new(cells: impl Into<Vec<Cell>>, cell_size: f32) -> Self {
+        let cells = cells.into();
+        let Cell(x, y) = *cells.first().expect("at least one cell");
+        let mut this = Self { cells, cell_size, min_x: x, max_x: x, min_y: y, max_y: y, links: HashMap::new(), cumulative: vec![0.0] };
+        for idx in 0 .. this.cells.len() {
+            let (cell, next) = (this.cells[idx], this.cells[(idx + 1) % this.cells.len()]);
+            this.connect(cell, next);
+            // Diagonal steps are longer than straight ones
+            let length = (this.cell_center(idx + 1) - this.cell_center(idx)).norm();
+            this.cumulative.push(this.cumulative[idx] + length);
+        }
+        this
+    }
+
+    /// Read a track drawn as text, with one character per cell and the top line furthest up.
+    /// Each cell is drawn as the direction to the next one on the loop, one of '>', '<', '^' and
+    /// 'v', and '.' or ' ' leave a position empty. The loop starts at the leftmost cell of the
+    /// bottom line, which is at the origin.
+    ///
+    /// ```text
+    /// v<<
+    /// v^<
+    /// >>^
+    /// ```
+    pub fn from_ascii(text: &str, cell_size: f32) -> Result<Self, AsciiTrackError> {
+        let lines: Vec<&str> = text.lines().collect();
+        let mut next = HashMap::new();
+        for (row, line) in lines.iter().enumerate() {
+            let y = (lines.len() - 1 - row) as i32;
+            for (x, character) in line.chars().enumerate() {
+                let x = x as i32;
+                let step = match character {
+                    '>' => (1, 0),
+                    '<' => (-1, 0),
+                    '^' => (0, 1),
+                    'v' => (0, -1),
+                    '.' | ' ' => continue,
+                    character => return Err(AsciiTrackError::UnknownCharacter { line: row + 1, column: x as usize + 1, character }),
+                };
+                next.insert(Cell(x, y), Cell(x + step.0, y + step.1));
+            }
         }
 
-        let min_x = cells.iter().map(|cell| cell.0).min().expect("at least one cell");
-        let max_x = cells.iter().map(|cell| cell.0).max().expect("at least one cell");
-        let min_y = cells.iter().map(|cell| cell.1).min().expect("at least one cell");
-        let max_y = cells.iter().map(|cell| cell.1).max().expect("at least one cell");
-        Self { cells, cell_size, idx_map, min_x, max_x, min_y, max_y}
+        // Follow the directions from the start until they lead back to it
+        let start = *next.keys().min_by_key(|Cell(x, y)| (*y, *x)).ok_or(AsciiTrackError::Empty)?;
+        let mut cells = vec![start];
+        let mut visited = HashSet::from([start]);
+        let mut cell = start;
+        loop {
+            let previous = cell;
+            cell = next[&previous];
+            if !next.contains_key(&cell) {
+                return Err(AsciiTrackError::OffTrack(previous));
+            }
+            if cell == start {
+                break;
+            }
+            if !visited.insert(cell) {
+                return Err(AsciiTrackError::NotOnLoop(cell));
+            }
+            cells.push(cell);
+        }
+        if let Some(&unreached) = next.keys().find(|cell| !visited.contains(cell)) {
+            return Err(AsciiTrackError::NotOnLoop(unreached));
+        }
+
+        // Shift the track so that the start is at the origin
+        let cells: Vec<Cell> = cells.into_iter().map(|Cell(x, y)| Cell(x - start.0, y - start.1)).collect();
+        Ok(Self::new(cells, cell_size))
+    }
+
+    /// Start building a track from the cell 'start', see 'CellMapBuilder'
+    pub fn builder(start: Cell) -> CellMapBuilder {
+        CellMapBuilder { cells: vec![start], branches: Vec::new() }
+    }
+
+    /// Let cars cross directly between two cells, e.g. diagonally through their shared corner or
+    /// onto a shortcut. Cells which are not yet on the track are added as part of a branch.
+    pub fn connect(&mut self, a: Cell, b: Cell) {
+        for (cell, other) in [(a, b), (b, a)] {
+            self.links.entry(cell).or_default().insert(other);
+            self.min_x = self.min_x.min(cell.0);
+            self.max_x = self.max_x.max(cell.0);
+            self.min_y = self.min_y.min(cell.1);
+            self.max_y = self.max_y.max(cell.1);
+        }
+    }
+
+    /// Get all cells of the track, on the loop or on branches, in no particular order
+    pub fn all_cells(&self) -> impl Iterator<Item = Cell> + '_ {
+        self.links.keys().copied()
+    }
+
+    /// Get every pair of cells which cars may cross between directly, each pair once
+    pub fn links(&self) -> impl Iterator<Item = (Cell, Cell)> + '_ {
+        self.links.iter()
+            .flat_map(|(&cell, others)| others.iter().map(move |&other| (cell, other)))
+            .filter(|&(Cell(x1, y1), Cell(x2, y2))| (x1, y1) < (x2, y2))
     }
 
     pub fn cell(&self, p1: Vec2) -> Cell {
@@ -42,48 +187,158 @@ impl CellMap {
         Cell(cell_float_vec.0.round() as i32, cell_float_vec.1.round() as i32)
     }
 
-    fn cell_idx(&self, p1: Vec2) -> Option<usize> {
-        let cell = self.cell(p1);
-        self.idx_map.get(&cell).copied()
+    /// Get the cell of the track containing a point, if any
+    fn track_cell(&self, point: Vec2) -> Option<Cell> {
+        let cell = self.cell(point);
+        self.links.contains_key(&cell).then_some(cell)
     }
 
-
     fn step_is_along(&self, p1: Vec2, p2: Vec2) -> bool {
-        let c1 = self.cell(p1);
-        let c2 = self.cell(p2);
-        self.contiguous_cells(Some(&c1), Some(&c2))
+        self.contiguous(self.track_cell(p1), self.track_cell(p2))
     }
 
-    fn contiguous_cells(&self, cell1: Option<&Cell>, cell2: Option<&Cell>) -> bool {
-        let Some(c1) = cell1 else {return false;};
-        let Some(c2) = cell2 else {return false;};
-        let idx1 = self.idx_map.get(c1);
-        let idx2 = self.idx_map.get(c2);
-        self.contiguous_idx(idx1.copied(), idx2.copied())
+    /// Get the world coordinates of the center of the cell at index 'idx' in the loop
+    fn cell_center(&self, idx: usize) -> Vec2 {
+        let Cell(x, y) = self.cells[idx % self.cells.len()];
+        Vec2(x as f32, y as f32) * self.cell_size
     }
 
-    fn contiguous_idx(&self, idx1: Option<usize>, idx2: Option<usize>) -> bool {
-        let Some(idx1) = idx1 else {return false;};
-        let Some(idx2) = idx2 else {return false;};
+    fn contiguous(&self, cell1: Option<Cell>, cell2: Option<Cell>) -> bool {
+        let Some(cell1) = cell1 else {return false;};
+        let Some(cell2) = cell2 else {return false;};
 
-        // If player remains in the same square, we have not crashed
-        if idx1 == idx2 {
-            return true;
+        // If player remains in the same square, we have not crashed. Otherwise, the squares must
+        // be linked.
+        cell1 == cell2 || self.links.get(&cell1).is_some_and(|others| others.contains(&cell2))
+    }
+}
+
+/// Builds the loop of a track cell by cell, e.g. the 'CIRCUIT' loop as
+/// 'CellMap::builder(Cell(0, 0)).right(2).up(2).left(2).down(1)'. The loop closes from the last
+/// cell back to the first. Consecutive cells may also be diagonal neighbours, and branches may
+/// add shortcuts between cells of the loop.
+#[derive(Debug, Clone)]
+pub struct CellMapBuilder {
+    cells: Vec<Cell>,
+    branches: Vec<Vec<Cell>>,
+}
+
+impl CellMapBuilder {
+    /// Add the next cell of the loop
+    pub fn cell(mut self, cell: Cell) -> Self {
+        self.cells.push(cell);
+        self
+    }
+
+    /// Add 'n' cells in a straight line from the last one, moving by 'step' each
+    fn straight(mut self, step: (i32, i32), n: usize) -> Self {
+        for _ in 0 .. n {
+            let Cell(x, y) = *self.cells.last().expect("at least the start");
+            self.cells.push(Cell(x + step.0, y + step.1));
         }
+        self
+    }
+
+    pub fn right(self, n: usize) -> Self {
+        self.straight((1, 0), n)
+    }
+
+    pub fn left(self, n: usize) -> Self {
+        self.straight((-1, 0), n)
+    }
 
-        // If the player moved between adjacent squares, we have not crashed
-        idx2 == idx1 + 1 
-            || idx1 == idx2 + 1 
-            || (idx1, idx2) == (0, self.cells.len()-1) 
-            || (idx1, idx2) == (self.cells.len()-1, 0)
+    pub fn up(self, n: usize) -> Self {
+        self.straight((0, 1), n)
+    }
+
+    pub fn down(self, n: usize) -> Self {
+        self.straight((0, -1), n)
+    }
+
+    /// Add a path of cells off the loop, linking each to the next, e.g. a shortcut starting and
+    /// ending on the loop. Progress is still measured along the loop.
+    pub fn branch(mut self, path: impl IntoIterator<Item = Cell>) -> Self {
+        self.branches.push(path.into_iter().collect());
+        self
+    }
+
+    /// Get the track with side length 'cell_size', checking that every cell is on the loop once
+    /// and that every cell of the loop and the branches is next to the one before it
+    pub fn build(self, cell_size: f32) -> Result<CellMap, CellTrackError> {
+        let mut visited = HashSet::new();
+        if let Some(&repeated) = self.cells.iter().find(|&&cell| !visited.insert(cell)) {
+            return Err(CellTrackError::Repeated(repeated));
+        }
+        let adjacent = |Cell(x1, y1): Cell, Cell(x2, y2): Cell| (x1 - x2).abs().max((y1 - y2).abs()) == 1;
+        let closing = (*self.cells.last().expect("at least the start"), self.cells[0]);
+        let loop_steps = self.cells.iter().copied().tuple_windows().chain((self.cells.len() > 1).then_some(closing));
+        let branch_steps = self.branches.iter().flat_map(|path| path.iter().copied().tuple_windows());
+        let mut steps = loop_steps.chain(branch_steps);
+        if let Some((_, cell)) = steps.find(|&(before, cell)| !adjacent(before, cell)) {
+            return Err(CellTrackError::Gap(cell));
+        }
+
+        let mut road = CellMap::new(self.cells, cell_size);
+        for (before, cell) in self.branches.iter().flat_map(|path| path.iter().copied().tuple_windows()) {
+            road.connect(before, cell);
+        }
+        Ok(road)
     }
 }
 
+
 impl Road for CellMap {
+    fn contains(&self, point: Vec2) -> bool {
+        self.track_cell(point).is_some()
+    }
+
+    fn bounds(&self) -> (Vec2, Vec2) {
+        let min = Vec2(self.min_x as f32 - 0.5, self.min_y as f32 - 0.5) * self.cell_size;
+        let max = Vec2(self.max_x as f32 + 0.5, self.max_y as f32 + 0.5) * self.cell_size;
+        (min, max)
+    }
+
+    fn track_length(&self) -> f32 {
+        *self.cumulative.last().expect("at least the start")
+    }
+
+    /// The centerline is the closed polyline connecting the cell centers in order
+    fn project(&self, point: Vec2) -> TrackProjection {
+        (0 .. self.cells.len())
+            .map(|idx| {
+                // Project onto the centerline segment from this cell to the next one
+                let start = self.cell_center(idx);
+                let delta = self.cell_center(idx + 1) - start;
+                let t = ((point - start).dot(delta) / delta.dot(delta)).clamp(0.0, 1.0);
+                let offset = point - (start + delta*t);
+                TrackProjection { progress: self.cumulative[idx] + t*delta.norm(), distance_sq: offset.dot(offset) }
+            })
+            .reduce(|best, projection| if projection.distance_sq < best.distance_sq { projection } else { best })
+            .expect("at least one cell")
+    }
+
+    fn pose_at(&self, progress: f32) -> (Vec2, Vec2) {
+        let progress = progress.rem_euclid(self.track_length());
+        // Find the last cell center at or before the progress
+        let idx = (self.cumulative.partition_point(|&distance| distance <= progress) - 1).min(self.cells.len() - 1);
+        let start = self.cell_center(idx);
+        let delta = self.cell_center(idx + 1) - start;
+        let t = (progress - self.cumulative[idx]) / (self.cumulative[idx + 1] - self.cumulative[idx]);
+        (start + delta*t, delta.normalized())
+    }
+
     fn is_crashed(&self, state: &CarState, config: &CarConfig) -> bool {
-        let back_point = state.position - state.unit_forward*config.back_axle;
-        let front_point = back_point + state.unit_forward*config.length;
-        !self.step_is_along(back_point, front_point)
+        // Each side of the car must run within one cell or between consecutive ones
+        !state.sides(config).into_iter().all(|(start, end)| self.step_is_along(start, end))
+    }
+
+    /// Samples the path at a quarter of the cell size, so that no cell is skipped between samples
+    fn path_leaves_road(&self, from: Vec2, to: Vec2) -> bool {
+        let n = ((to - from).norm() / (0.25*self.cell_size)).ceil() as usize;
+        (0 ..= n)
+            .map(|i| from + (to - from)*(i as f32 / n.max(1) as f32))
+            .tuple_windows()
+            .any(|(start, end)| !self.step_is_along(start, end))
     }
 
     /// Takes in a point and (non-normalized) direction defining a ray,
@@ -149,14 +404,14 @@ impl Road for CellMap {
         // In case of (tN, infinity), we select the cell be None
         // We also report the t value at the end of the window (which the cell is associated with)
 
-        let cell_idx = ts
+        let cells = ts
             .tuple_windows()
-            .map(|(t1d, t2d): (LidarDistance, LidarDistance)| -> (Option<usize>, LidarDistance) {
+            .map(|(t1d, t2d): (LidarDistance, LidarDistance)| -> (Option<Cell>, LidarDistance) {
                 let LidarDistance::Specific(t1) = t1d else {panic!("First t should be a specific distance, never Far");};
-                let cell: Option<usize> = match t2d {
+                let cell: Option<Cell> = match t2d {
                     LidarDistance::Specific(t2) => {
                         let midpoint = point + direction*self.cell_size*0.5*(t1+t2);
-                        self.cell_idx(midpoint)
+                        self.track_cell(midpoint)
                     },
                     LidarDistance::Far => None
                 };
@@ -164,9 +419,9 @@ impl Road for CellMap {
             });
 
         // Find the first comparison that fails
-        let t_max = cell_idx.tuple_windows()
-            .filter_map(|((cell_before, t_before), (cell_after, t_between)): ((Option<usize>, LidarDistance), (Option<usize>, LidarDistance))| {
-                let contiguous = self.contiguous_idx(cell_before, cell_after);
+        let t_max = cells.tuple_windows()
+            .filter_map(|((cell_before, t_before), (cell_after, t_between)): ((Option<Cell>, LidarDistance), (Option<Cell>, LidarDistance))| {
+                let contiguous = self.contiguous(cell_before, cell_after);
                 match (cell_before, contiguous) {
                     (Some(_), false) => Some(t_between),  // Had a valid cell before, so t_between
                                                            // is failure
@@ -190,3 +445,135 @@ impl Road for CellMap {
     }
 }
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{CIRCUIT, FOLD};
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!((a - b).norm() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_crash() {
+        let map = CellMap::new(CIRCUIT, 10.0);
+        let config = CarConfig::default();
+
+        let state = CarState { position: Vec2(0.0, 0.0), unit_forward: Vec2(1.0, 0.0), ..CarState::default() };
+        assert!(!map.is_crashed(&state, &config));
+
+        // Crossing into the hole in the middle of the circuit
+        let state = CarState { position: Vec2(10.0, 3.0), unit_forward: Vec2(0.0, 1.0), ..CarState::default() };
+        assert!(map.is_crashed(&state, &config));
+
+        // Crossing the seam between the last and first cell is fine
+        let state = CarState { position: Vec2(0.0, 8.0), unit_forward: Vec2(0.0, -1.0), ..CarState::default() };
+        assert!(!map.is_crashed(&state, &config));
+
+        // Jumping across the hole leaves the road, while driving along the cells does not
+        assert!(map.path_leaves_road(Vec2(0.0, 0.0), Vec2(20.0, 20.0)));
+        assert!(!map.path_leaves_road(Vec2(0.0, 0.0), Vec2(20.0, 0.0)));
+    }
+
+    #[test]
+    fn test_from_ascii() {
+        let circuit = CellMap::from_ascii("v<<\nv.^\n>>^\n", 10.0).unwrap();
+        assert_eq!(circuit.cells, CIRCUIT);
+        let fold = CellMap::from_ascii("v<.\nv^<\n>>^", 10.0).unwrap();
+        assert_eq!(fold.cells, FOLD);
+
+        // The start is moved to the origin
+        let shifted = CellMap::from_ascii("...\n.v<\n.>^", 10.0).unwrap();
+        assert_eq!(shifted.cells[.. 2], [Cell(0, 0), Cell(1, 0)]);
+
+        assert_eq!(CellMap::from_ascii(" .\n", 1.0).err(), Some(AsciiTrackError::Empty));
+        assert_eq!(
+            CellMap::from_ascii("v<\n>x", 1.0).err(),
+            Some(AsciiTrackError::UnknownCharacter { line: 2, column: 2, character: 'x' })
+        );
+        assert_eq!(CellMap::from_ascii("v<\n>>", 1.0).err(), Some(AsciiTrackError::OffTrack(Cell(1, 0))));
+        assert_eq!(CellMap::from_ascii("v<<\n>^.", 1.0).err(), Some(AsciiTrackError::NotOnLoop(Cell(2, 1))));
+    }
+
+    #[test]
+    fn test_builder() {
+        let circuit = CellMap::builder(Cell(0, 0)).right(2).up(2).left(2).down(1).build(10.0).unwrap();
+        assert_eq!(circuit.cells, CIRCUIT);
+        let fold = CellMap::builder(Cell(0, 0)).right(2).up(1).left(1).up(1).left(1).down(1).build(10.0).unwrap();
+        assert_eq!(fold.cells, FOLD);
+
+        let repeated = CellMap::builder(Cell(0, 0)).right(1).left(1).build(10.0);
+        assert_eq!(repeated.err(), Some(CellTrackError::Repeated(Cell(0, 0))));
+        let jump = CellMap::builder(Cell(0, 0)).cell(Cell(2, 0)).build(10.0);
+        assert_eq!(jump.err(), Some(CellTrackError::Gap(Cell(2, 0))));
+        let open = CellMap::builder(Cell(0, 0)).right(3).build(10.0);
+        assert_eq!(open.err(), Some(CellTrackError::Gap(Cell(0, 0))));
+    }
+
+    #[test]
+    fn test_diagonal_and_branch() {
+        // A loop cutting the bottom right and top left corners of a 3x3 block diagonally
+        let map = CellMap::builder(Cell(0, 0)).right(1).cell(Cell(2, 1)).up(1).left(1).cell(Cell(0, 1)).build(10.0).unwrap();
+        assert!(!map.path_leaves_road(Vec2(10.0, 0.0), Vec2(20.0, 10.0)));
+        assert!(map.path_leaves_road(Vec2(10.0, 0.0), Vec2(20.0, 0.0)));
+        assert!(!map.contains(Vec2(20.0, 0.0)));
+
+        // A shortcut through the middle of the circuit, which is only reachable at its ends
+        let map = CellMap::builder(Cell(0, 0)).right(2).up(2).left(2).down(1)
+            .branch([Cell(1, 0), Cell(1, 1), Cell(1, 2)])
+            .build(10.0)
+            .unwrap();
+        assert_eq!(map.all_cells().count(), 9);
+        assert_eq!(map.links().count(), 10);
+        assert!(!map.path_leaves_road(Vec2(10.0, 0.0), Vec2(10.0, 20.0)));
+        assert!(map.path_leaves_road(Vec2(10.0, 10.0), Vec2(0.0, 10.0)));
+        assert_close(map.ray_collision(Vec2(10.0, 0.0), Vec2(0.0, 1.0)), Vec2(10.0, 25.0));
+        assert_eq!(map.track_length(), 80.0);
+
+        // Progress is measured along the diagonal steps of the loop
+        let map = CellMap::builder(Cell(0, 0)).right(1).cell(Cell(2, 1)).up(1).left(1).cell(Cell(0, 1)).build(10.0).unwrap();
+        let diagonal = 10.0*2.0_f32.sqrt();
+        assert!((map.track_length() - (40.0 + 2.0*diagonal)).abs() < 1e-4);
+        let (position, unit_forward) = map.pose_at(10.0 + 0.5*diagonal);
+        assert_close(position, Vec2(15.0, 5.0));
+        assert_close(unit_forward, Vec2(1.0, 1.0).normalized());
+        for progress in [5.0, 10.0 + 0.5*diagonal, 30.0 + diagonal, 40.0 + 1.5*diagonal] {
+            let TrackProjection { progress: projected, distance_sq } = map.project(map.pose_at(progress).0);
+            assert!((projected - progress).abs() < 1e-3 && distance_sq < 1e-6, "{} != {}", projected, progress);
+        }
+
+        let gap = CellMap::builder(Cell(0, 0)).right(1).up(1).left(1).branch([Cell(0, 0), Cell(2, 2)]).build(10.0);
+        assert_eq!(gap.err(), Some(CellTrackError::Gap(Cell(2, 2))));
+    }
+
+    #[test]
+    fn test_ray_collision() {
+        let map = CellMap::new(CIRCUIT, 10.0);
+        assert_close(map.ray_collision(Vec2(0.0, 0.0), Vec2(1.0, 0.0)), Vec2(25.0, 0.0));
+        assert_close(map.ray_collision(Vec2(0.0, 0.0), Vec2(0.0, 1.0)), Vec2(0.0, 25.0));
+        assert_close(map.ray_collision(Vec2(0.0, 0.0), Vec2(0.0, -1.0)), Vec2(0.0, -5.0));
+        assert_close(map.ray_collision(Vec2(10.0, 0.0), Vec2(0.0, 1.0)), Vec2(10.0, 5.0));
+    }
+
+    #[test]
+    fn test_track_coordinates() {
+        let map = CellMap::new(CIRCUIT, 10.0);
+        assert_eq!(map.track_length(), 80.0);
+        assert_eq!(map.bounds(), (Vec2(-5.0, -5.0), Vec2(25.0, 25.0)));
+
+        let TrackProjection { progress, distance_sq } = map.project(Vec2(15.0, 2.0));
+        assert_eq!(progress, 15.0);
+        assert_eq!(distance_sq, 4.0);
+
+        let (position, unit_forward) = map.pose_at(25.0);
+        assert_close(position, Vec2(20.0, 5.0));
+        assert_close(unit_forward, Vec2(0.0, 1.0));
+
+        // The pose wraps around the seam
+        let (position, unit_forward) = map.pose_at(75.0 + 80.0);
+        assert_close(position, Vec2(0.0, 5.0));
+        assert_close(unit_forward, Vec2(0.0, -1.0));
+    }
+}
@@ -1,10 +1,22 @@
 mod cell_map;
 mod spline_map;
 mod traits;
+mod obstacle;
+mod wall_map;
+mod sdf_map;
+mod track_file;
+mod svg;
+mod raster;
 
-pub use cell_map::{Cell, CellMap};
-pub use spline_map::{SplineMap, make_oval, make_racetrack, make_simple_racetrack};
-pub use traits::{Road};
+pub use cell_map::{Cell, CellMap, CellMapBuilder, AsciiTrackError, CellTrackError};
+pub use spline_map::{SplineMap, RoadSamples, RayCasting, CurvatureStats, make_oval, make_racetrack, make_simple_racetrack, make_random_track};
+pub use wall_map::WallMap;
+pub use sdf_map::SdfMap;
+pub use track_file::{parse_waypoints_csv, TrackDefinition, ObstacleDefinition, TrackLoadError};
+pub use svg::{to_svg, export_svg};
+pub use raster::{RoadImage, rasterize, ROAD_PIXEL, OFF_ROAD_PIXEL};
+pub use traits::{Road, RoadContact, TrackProjection, RayMarch};
+pub use obstacle::{Obstacle, ObstacleShape, MovingObstacle};
 
 pub static CIRCUIT: [Cell; 8] = [Cell(0,0), Cell(1,0), Cell(2,0), Cell(2,1), Cell(2,2), Cell(1,2), Cell(0,2), Cell(0,1)];
 pub static FOLD: [Cell; 8] = [Cell(0,0), Cell(1,0), Cell(2,0), Cell(2,1), Cell(1,1), Cell(1,2), Cell(0,2), Cell(0,1)];
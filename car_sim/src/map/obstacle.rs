@@ -0,0 +1,155 @@
+use serde::{Serialize, Deserialize};
+
+use math_utils::{Vec2, Aabb};
+
+
+/// The geometric shape of a static obstacle, given in the obstacle's local frame
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "lowercase")]
+pub enum ObstacleShape {
+    Circle { radius: f32 },
+    /// A rectangle with 'length' along the obstacle's forward direction and 'width' across it
+    Rectangle { length: f32, width: f32 },
+}
+
+
+impl ObstacleShape {
+    /// Get the same shape with all dimensions scaled by 'factor'
+    pub fn scaled(self, factor: f32) -> Self {
+        match self {
+            ObstacleShape::Circle { radius } => ObstacleShape::Circle { radius: radius*factor },
+            ObstacleShape::Rectangle { length, width } => ObstacleShape::Rectangle { length: length*factor, width: width*factor },
+        }
+    }
+}
+
+
+/// A static obstacle placed on the road, e.g. a cone or a box
+#[derive(Debug, Clone, PartialEq)]
+pub struct Obstacle {
+    pub shape: ObstacleShape,
+    pub center: Vec2,
+    pub unit_forward: Vec2,
+}
+
+
+impl Obstacle {
+    pub fn new(shape: ObstacleShape, center: Vec2, unit_forward: Vec2) -> Self {
+        Self { shape, center, unit_forward: unit_forward.normalized() }
+    }
+
+    /// Express a world coordinate in the local (forward, left) frame of the obstacle
+    fn to_local(&self, world: Vec2) -> Vec2 {
+        Vec2(world.dot(self.unit_forward), world.dot(self.unit_forward.rotate90()))
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        let delta = point - self.center;
+        match self.shape {
+            ObstacleShape::Circle { radius } => delta.dot(delta) <= radius*radius,
+            ObstacleShape::Rectangle { length, width } => {
+                let Vec2(x, y) = self.to_local(delta);
+                x.abs() <= 0.5*length && y.abs() <= 0.5*width
+            }
+        }
+    }
+
+    /// Takes in a point and (non-normalized) direction defining a ray and returns the distance
+    /// along the ray to the first intersection with the obstacle, if any.
+    /// If the point is inside the obstacle, the distance is zero.
+    pub fn ray_intersection(&self, point: Vec2, direction: Vec2) -> Option<f32> {
+        if self.contains(point) {
+            return Some(0.0);
+        }
+        let direction = direction.normalized();
+        let delta = point - self.center;
+
+        match self.shape {
+            ObstacleShape::Circle { radius } => {
+                // |delta + t*d|^2 = r^2
+                // t^2 + 2*t*(delta.d) + |delta|^2 - r^2 = 0
+                let b = delta.dot(direction);
+                let c = delta.dot(delta) - radius*radius;
+                let discriminant = b*b - c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let t = -b - discriminant.sqrt();
+                if t >= 0.0 { Some(t) } else { None }
+            },
+            ObstacleShape::Rectangle { length, width } => {
+                // Slab test in the local frame of the rectangle
+                let half_extents = Vec2(0.5*length, 0.5*width);
+                Aabb::new(-half_extents, half_extents)
+                    .ray_intersection(self.to_local(delta), self.to_local(direction))
+                    .map(|(t_enter, _)| t_enter)
+            }
+        }
+    }
+
+    /// Checks whether the line segment between 'start' and 'end' touches the obstacle
+    pub fn intersects_segment(&self, start: Vec2, end: Vec2) -> bool {
+        let delta = end - start;
+        let length = delta.norm();
+        if length == 0.0 {
+            return self.contains(start);
+        }
+        self.ray_intersection(start, delta).is_some_and(|t| t <= length)
+    }
+}
+
+
+/// An obstacle that travels along the centerline of the road at a fixed speed, e.g. a pace car
+#[derive(Debug, Clone, PartialEq)]
+pub struct MovingObstacle {
+    pub shape: ObstacleShape,
+    pub speed: f32,
+    pub lateral: f32,
+    /// The distance ahead of the car along the centerline at which the obstacle is placed on reset
+    pub start_offset: f32,
+    pub(crate) arc: f32,
+    pub(crate) obstacle: Obstacle,
+}
+
+impl MovingObstacle {
+    /// Get the current distance of the obstacle along the centerline
+    pub fn arc(&self) -> f32 {
+        self.arc
+    }
+
+    /// Get the current world-space pose of the obstacle
+    pub fn obstacle(&self) -> &Obstacle {
+        &self.obstacle
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circle() {
+        let cone = Obstacle::new(ObstacleShape::Circle { radius: 1.0 }, Vec2(5.0, 0.0), Vec2(1.0, 0.0));
+        assert!(cone.contains(Vec2(5.5, 0.5)));
+        assert!(!cone.contains(Vec2(4.0, 1.0)));
+        assert_eq!(cone.ray_intersection(Vec2(0.0, 0.0), Vec2(2.0, 0.0)), Some(4.0));
+        assert_eq!(cone.ray_intersection(Vec2(0.0, 0.0), Vec2(-1.0, 0.0)), None);
+        assert_eq!(cone.ray_intersection(Vec2(0.0, 2.0), Vec2(1.0, 0.0)), None);
+        assert!(cone.intersects_segment(Vec2(0.0, 0.0), Vec2(10.0, 0.0)));
+        assert!(!cone.intersects_segment(Vec2(0.0, 0.0), Vec2(3.0, 0.0)));
+    }
+
+    #[test]
+    fn test_rectangle() {
+        // A 2x4 box rotated to point along the y axis
+        let shape = ObstacleShape::Rectangle { length: 2.0, width: 4.0 };
+        let block = Obstacle::new(shape, Vec2(0.0, 5.0), Vec2(0.0, 1.0));
+        assert!(block.contains(Vec2(1.5, 5.5)));
+        assert!(!block.contains(Vec2(0.0, 6.5)));
+        assert_eq!(block.ray_intersection(Vec2(0.0, 0.0), Vec2(0.0, 1.0)), Some(4.0));
+        assert_eq!(block.ray_intersection(Vec2(-5.0, 5.0), Vec2(1.0, 0.0)), Some(3.0));
+        assert_eq!(block.ray_intersection(Vec2(3.0, 0.0), Vec2(0.0, 1.0)), None);
+        assert_eq!(block.ray_intersection(Vec2(0.0, 5.0), Vec2(1.0, 1.0)), Some(0.0));
+    }
+}
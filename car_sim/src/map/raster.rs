@@ -0,0 +1,80 @@
+use std::path::Path;
+
+use math_utils::Vec2;
+
+use super::traits::Road;
+
+
+/// Pixel value of points on the road
+pub const ROAD_PIXEL: u8 = 255;
+/// Pixel value of points off the road, including static obstacles
+pub const OFF_ROAD_PIXEL: u8 = 0;
+
+
+/// A grayscale image of a road, stored row by row starting at the top (largest y) row
+pub struct RoadImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+    /// World coordinates of the bottom left corner of the image
+    pub origin: Vec2,
+    pub px_per_m: f32,
+}
+
+
+/// Rasterize a road to a grayscale image with 'px_per_m' pixels per meter, covering the bounds of
+/// the road. Each pixel is sampled at its center with 'Road::contains_points'.
+pub fn rasterize<R: Road>(road: &R, px_per_m: f32) -> RoadImage {
+    assert!(px_per_m > 0.0, "Pixels per meter must be positive, got {}", px_per_m);
+    let (min, max) = road.bounds();
+    let size = (max - min)*px_per_m;
+    let width = (size.0.ceil() as usize).max(1);
+    let height = (size.1.ceil() as usize).max(1);
+
+    let points: Vec<Vec2> = (0 .. width*height)
+        .map(|idx| {
+            let (column, row) = (idx % width, idx / width);
+            min + Vec2(column as f32 + 0.5, (height - row) as f32 - 0.5) / px_per_m
+        })
+        .collect();
+    let pixels = road.contains_points(&points)
+        .into_iter()
+        .map(|inside| if inside { ROAD_PIXEL } else { OFF_ROAD_PIXEL })
+        .collect();
+
+    RoadImage { width, height, pixels, origin: min, px_per_m }
+}
+
+
+impl RoadImage {
+    /// Encode the image as a grayscale PNG file
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(std::io::Error::other)?;
+        writer.write_image_data(&self.pixels).map_err(std::io::Error::other)?;
+        writer.finish().map_err(std::io::Error::other)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{CellMap, CIRCUIT};
+
+    #[test]
+    fn test_rasterize() {
+        // The circuit spans [-5, 25] in both axes, with a hole in the middle
+        let image = rasterize(&CellMap::new(CIRCUIT, 10.0), 1.0);
+        assert_eq!((image.width, image.height), (30, 30));
+        assert_eq!(image.origin, Vec2(-5.0, -5.0));
+
+        let pixel = |column: usize, row: usize| image.pixels[row*image.width + column];
+        assert_eq!(pixel(0, 0), ROAD_PIXEL);
+        assert_eq!(pixel(15, 15), OFF_ROAD_PIXEL);
+        assert_eq!(pixel(15, 2), ROAD_PIXEL);
+    }
+}
@@ -0,0 +1,251 @@
+use math_utils::Vec2;
+
+use crate::physics::{CarState, CarConfig};
+use super::traits::{Road, TrackProjection, RayMarch};
+use super::spline_map::SplineMap;
+
+
+/// Large value standing in for an infinite squared distance in the distance transform
+const FAR: f64 = 1e20;
+
+
+/// A road backed by a signed distance field precomputed on a grid from another road.
+///
+/// The field holds the distance to the edge of the road, positive inside and negative outside,
+/// giving O(1) containment checks and sphere-traced LiDAR. Track coordinates are delegated to the
+/// wrapped road. Only static geometry is baked into the field; moving obstacles are ignored.
+pub struct SdfMap<R> {
+    pub road: R,
+    pub resolution: f32,
+    origin: Vec2,
+    nx: usize,
+    ny: usize,
+    field: Vec<f32>,
+    ray_march: RayMarch,
+}
+
+
+/// One-dimensional squared Euclidean distance transform of a sampled function (Felzenszwalb &
+/// Huttenlocher), i.e. d(q) = min_p (q-p)^2 + f(p)
+fn distance_transform_1d(f: &[f64]) -> Vec<f64> {
+    let n = f.len();
+    let mut v = vec![0usize; n];  // Locations of the parabolas in the lower envelope
+    let mut z = vec![0.0; n+1];  // Boundaries between the parabolas
+    let mut k = 0;
+    z[0] = f64::NEG_INFINITY;
+    z[1] = f64::INFINITY;
+
+    let intersection = |q: usize, p: usize| {
+        let (q_f, p_f) = (q as f64, p as f64);
+        ((f[q] + q_f*q_f) - (f[p] + p_f*p_f)) / (2.0*(q_f - p_f))
+    };
+    for q in 1 .. n {
+        let mut s = intersection(q, v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = intersection(q, v[k]);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k+1] = f64::INFINITY;
+    }
+
+    k = 0;
+    (0 .. n).map(|q| {
+            while z[k+1] < q as f64 {
+                k += 1;
+            }
+            let delta = q as f64 - v[k] as f64;
+            delta*delta + f[v[k]]
+        })
+        .collect()
+}
+
+
+/// Two-dimensional squared distance transform, giving the squared distance (in grid units) from each
+/// sample to the closest sample where 'is_site' is true
+fn distance_transform_2d(is_site: &[bool], nx: usize, ny: usize) -> Vec<f64> {
+    let mut grid: Vec<f64> = is_site.iter().map(|&site| if site { 0.0 } else { FAR }).collect();
+
+    // Transform along the columns, then along the rows
+    for i in 0 .. nx {
+        let column: Vec<f64> = (0 .. ny).map(|j| grid[j*nx + i]).collect();
+        for (j, value) in distance_transform_1d(&column).into_iter().enumerate() {
+            grid[j*nx + i] = value;
+        }
+    }
+    for j in 0 .. ny {
+        let row = distance_transform_1d(&grid[j*nx .. (j+1)*nx]);
+        grid[j*nx .. (j+1)*nx].copy_from_slice(&row);
+    }
+    grid
+}
+
+
+impl<R: Road> SdfMap<R> {
+    /// Precompute the signed distance field of 'road' on a grid with spacing 'resolution'
+    pub fn new(road: R, resolution: f32) -> Self {
+        assert!(resolution > 0.0, "SDF resolution must be positive, got {}", resolution);
+
+        // Pad the grid so that its border is always outside the road
+        let (min, max) = road.bounds();
+        let padding = Vec2(2.0*resolution, 2.0*resolution);
+        let origin = min - padding;
+        let extent = max + padding - origin;
+        let nx = (extent.0 / resolution).ceil() as usize + 1;
+        let ny = (extent.1 / resolution).ceil() as usize + 1;
+
+        let inside: Vec<bool> = (0 .. nx*ny)
+            .map(|idx| road.contains(origin + Vec2((idx % nx) as f32, (idx / nx) as f32)*resolution))
+            .collect();
+        let outside: Vec<bool> = inside.iter().map(|&is_inside| !is_inside).collect();
+
+        // Inside samples measure the distance to the closest outside sample and vice versa.
+        // The edge lies roughly halfway between the samples on either side of it.
+        let distance_to_outside = distance_transform_2d(&outside, nx, ny);
+        let distance_to_inside = distance_transform_2d(&inside, nx, ny);
+        let field = inside.iter().enumerate()
+            .map(|(idx, &is_inside)| {
+                if is_inside {
+                    (distance_to_outside[idx].sqrt() as f32 - 0.5) * resolution
+                } else {
+                    -(distance_to_inside[idx].sqrt() as f32 - 0.5) * resolution
+                }
+            })
+            .collect();
+
+        Self { road, resolution, origin, nx, ny, field, ray_march: RayMarch::default() }
+    }
+
+    /// Get the signed distance from a point to the edge of the road, positive inside the road.
+    /// Bilinearly interpolates between the grid samples.
+    pub fn signed_distance(&self, point: Vec2) -> f32 {
+        let grid = (point - self.origin) / self.resolution;
+        let x = grid.0.clamp(0.0, (self.nx - 1) as f32);
+        let y = grid.1.clamp(0.0, (self.ny - 1) as f32);
+        let i = (x as usize).min(self.nx - 2);
+        let j = (y as usize).min(self.ny - 2);
+        let (fx, fy) = (x - i as f32, y - j as f32);
+
+        let at = |i: usize, j: usize| self.field[j*self.nx + i];
+        let bottom = at(i, j)*(1.0 - fx) + at(i+1, j)*fx;
+        let top = at(i, j+1)*(1.0 - fx) + at(i+1, j+1)*fx;
+        bottom*(1.0 - fy) + top*fy
+    }
+}
+
+
+impl<R: Road> Road for SdfMap<R> {
+    fn is_crashed(&self, state: &CarState, config: &CarConfig) -> bool {
+        !state.corners(config).into_iter().all(|corner| self.contains(corner))
+    }
+
+    /// Marches along the path by the distance to the edge, but at least a fraction of the grid
+    /// resolution
+    fn path_leaves_road(&self, from: Vec2, to: Vec2) -> bool {
+        let length = (to - from).norm();
+        let mut t = 0.0;
+        while t < length {
+            let distance = self.signed_distance(from + (to - from)*(t/length));
+            if distance <= 0.0 {
+                return true;
+            }
+            t += distance.max(0.25*self.resolution);
+        }
+        false
+    }
+
+    /// Takes in a point and (non-normalized) direction defining a ray, and finds the first
+    /// intersection with the edge of the track by sphere tracing the distance field. The field is
+    /// no more accurate than its resolution, so the tolerance is at least a fraction of it.
+    fn ray_collision(&self, point: Vec2, direction: Vec2) -> Vec2 {
+        if !self.contains(point) {
+            return point;
+        }
+        let direction = direction.normalized();
+        let threshold = self.ray_march.tolerance.max(0.01*self.resolution);
+        let range = self.ray_march.range();
+        let mut t = 0.0;
+        for _ in 0 .. self.ray_march.max_steps {
+            let distance = self.signed_distance(point + direction*t);
+            if distance < threshold || t >= range {
+                break;
+            }
+            t += distance;
+        }
+        point + direction*t.min(range)
+    }
+
+    fn set_ray_march(&mut self, ray_march: RayMarch) {
+        self.ray_march = ray_march;
+    }
+
+    fn contains(&self, point: Vec2) -> bool {
+        self.signed_distance(point) > 0.0
+    }
+
+    fn bounds(&self) -> (Vec2, Vec2) {
+        self.road.bounds()
+    }
+
+    fn track_length(&self) -> f32 {
+        self.road.track_length()
+    }
+
+    fn project(&self, point: Vec2) -> TrackProjection {
+        self.road.project(point)
+    }
+
+    fn pose_at(&self, progress: f32) -> (Vec2, Vec2) {
+        self.road.pose_at(progress)
+    }
+}
+
+
+impl SplineMap {
+    /// Convert the road into a signed-distance-field backed road with grid spacing 'resolution'
+    pub fn to_sdf(self, resolution: f32) -> SdfMap<SplineMap> {
+        SdfMap::new(self, resolution)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{CellMap, CIRCUIT, make_oval};
+    use crate::lidar::LidarArray;
+
+    #[test]
+    fn test_distance_transform() {
+        let transformed = distance_transform_1d(&[FAR, 0.0, FAR, FAR, FAR, 0.0]);
+        assert_eq!(transformed, vec![1.0, 0.0, 1.0, 4.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_cell_sdf() {
+        let sdf = SdfMap::new(CellMap::new(CIRCUIT, 10.0), 0.5);
+
+        // Center of the first cell is 5 units from the edge below it
+        assert!((sdf.signed_distance(Vec2(0.0, 0.0)) - 5.0).abs() < 0.3);
+        assert!((sdf.signed_distance(Vec2(10.0, 10.0)) + 5.0).abs() < 0.3);
+        assert!(sdf.contains(Vec2(20.0, 20.0)));
+        assert!(!sdf.contains(Vec2(30.0, 0.0)));
+
+        let hit = sdf.ray_collision(Vec2(0.0, 0.0), Vec2(1.0, 0.0));
+        assert!((hit - Vec2(25.0, 0.0)).norm() < 0.3);
+    }
+
+    #[test]
+    fn test_spline_sdf() {
+        let lidar = LidarArray::default();
+        let state = CarState::default();
+        let exact = make_oval().read_lidar(&state, &lidar);
+        let sdf = make_oval().to_sdf(0.25);
+        let approximate = sdf.read_lidar(&state, &lidar);
+        for (exact, approximate) in exact.iter().zip(approximate) {
+            assert!((exact - approximate).abs() < 0.3, "{} != {}", exact, approximate);
+        }
+    }
+}
@@ -1,75 +1,507 @@
 use math_utils::{
-    Vec2, 
-    spline::{SmoothBezierSpline, ClosestPointOutput, BezierControl},
-    root::find_root,
+    Vec2, Aabb,
+    spline::{SmoothBezierSpline, CubicBezier, ClosestPointOutput, BezierControl},
 };
 
+use rand::Rng;
+
 use crate::physics::{CarState, CarConfig};
-use super::traits::Road;
+use crate::lidar::LidarArray;
+use super::traits::{Road, RoadContact, TrackProjection, RayMarch};
+use super::obstacle::{Obstacle, ObstacleShape, MovingObstacle};
+
+/// Window in spline parameter around the hint which is searched by 'project_near'
+const PROJECTION_WINDOW: f32 = 1.0;
+
+/// Maximum number of cells along each side of the spatial index over the spline
+const MAX_INDEX_CELLS: f32 = 256.0;
+
+/// Maximum deviation of the Bezier curves approximating the road edges from the exact edges
+const EDGE_TOLERANCE: f32 = 0.01;
+
+/// Smallest step when marching along the path of a car, which bounds the number of closest point
+/// queries near the edge. Gaps between parts of the road narrower than this may be missed.
+const PATH_MIN_STEP: f32 = 0.25;
+
 
+#[derive(Clone)]
 pub struct SplineMap {
     pub spline: SmoothBezierSpline,
     pub width: f32,
+    pub obstacles: Vec<Obstacle>,
+    pub moving_obstacles: Vec<MovingObstacle>,
+    /// Distances along the centerline of the checkpoints of the track
+    pub checkpoints: Vec<f32>,
+    shoulder_width: f32,
     max_d2: f32,
+    crash_d2: f32,
+    /// Bezier curves approximating both road edges, and the end caps of an open road
+    edges: Vec<CubicBezier>,
+    ray_casting: RayCasting,
+    ray_march: RayMarch,
+}
+
+
+/// The method used to find where LiDAR rays leave the road
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RayCasting {
+    /// Intersect rays exactly with Bezier curves approximating the road edges
+    #[default]
+    Analytic,
+    /// March rays by the distance to the road edge from each point, which is a safe step since
+    /// all points closer than that are on the road. Needs few closest point queries on straights.
+    SphereTracing,
+}
+
+
+/// Points sampled with roughly even spacing along the centerline and edges of a spline road
+pub struct RoadSamples {
+    pub center: Vec<Vec2>,
+    pub left: Vec<Vec2>,
+    pub right: Vec<Vec2>,
+}
+
+
+/// How sharply the centerline of a spline road turns, see 'SplineMap::curvature_stats'
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CurvatureStats {
+    /// The largest absolute curvature, in 1/m
+    pub max_abs: f32,
+    /// The mean absolute curvature along the track, in 1/m
+    pub mean_abs: f32,
+}
+
+impl CurvatureStats {
+    /// Get the radius of the sharpest turn, which is infinite on a straight road
+    pub fn min_radius(&self) -> f32 {
+        1.0 / self.max_abs
+    }
 }
 
 
 impl SplineMap {
-    fn new(spline: SmoothBezierSpline, width: f32) -> Self {
+    pub fn new(mut spline: SmoothBezierSpline, width: f32) -> Self {
+        // Index the spline with cells about the size of the road width, covering the road and
+        // its surroundings where LiDAR rays and cars may query it
+        let size = spline.aabb().size();
+        let extent = size.0.max(size.1);
+        spline.build_index(width.max(extent / MAX_INDEX_CELLS), 2.0*width);
+
+        let mut edges = spline.offset_curves(0.5*width, EDGE_TOLERANCE);
+        edges.extend(spline.offset_curves(-0.5*width, EDGE_TOLERANCE));
+        if !spline.is_closed() {
+            for u in [0.0, spline.max_u] {
+                let (left, right) = (spline.offset_point(u, 0.5*width), spline.offset_point(u, -0.5*width));
+                edges.push(CubicBezier::new(left, left + (right - left)/3.0, right - (right - left)/3.0, right));
+            }
+        }
+
         let max_d2 = 0.25*width*width;
-        SplineMap { spline, width, max_d2 }
+        SplineMap {
+            spline, width, obstacles: Vec::new(), moving_obstacles: Vec::new(), checkpoints: Vec::new(),
+            shoulder_width: 0.0, max_d2, crash_d2: max_d2, edges, ray_casting: RayCasting::default(),
+            ray_march: RayMarch::default(),
+        }
     }
 
     fn point_inside(&self, point: Vec2) -> bool {
         let ClosestPointOutput { distance_sq, ..} = self.spline.closest_point(point);
         distance_sq < self.max_d2
     }
+
+    fn point_inside_shoulder(&self, point: Vec2) -> bool {
+        let ClosestPointOutput { distance_sq, ..} = self.spline.closest_point(point);
+        distance_sq < self.crash_d2
+    }
+
+    /// Set the width of the shoulder band on either side of the road, in which the car is off the
+    /// road but not yet crashed
+    pub fn set_shoulder_width(&mut self, shoulder_width: f32) {
+        assert!(shoulder_width >= 0.0, "Shoulder width must be non-negative, got {}", shoulder_width);
+        self.shoulder_width = shoulder_width;
+        let crash_distance = 0.5*self.width + shoulder_width;
+        self.crash_d2 = crash_distance*crash_distance;
+    }
+
+    pub fn shoulder_width(&self) -> f32 {
+        self.shoulder_width
+    }
+
+    pub fn set_ray_casting(&mut self, ray_casting: RayCasting) {
+        self.ray_casting = ray_casting;
+    }
+
+    pub fn ray_casting(&self) -> RayCasting {
+        self.ray_casting
+    }
+
+    pub fn ray_march(&self) -> RayMarch {
+        self.ray_march
+    }
+
+    /// Get the distance along a ray from a point on the road to the first crossing of the road
+    /// edge, given by the intersections with the Bezier edge curves
+    fn analytic_edge_distance(&self, point: Vec2, direction: Vec2) -> f32 {
+        // Where a corner is tighter than half the road width, the inner edge curve loops back
+        // into the road, so skip crossings which are closer to the centerline than the edge
+        let mut crossings: Vec<f32> = self.edges.iter()
+            .flat_map(|edge| edge.ray_intersections(point, direction))
+            .collect();
+        crossings.sort_by(f32::total_cmp);
+        let unit_direction = direction.normalized();
+        let min_edge_distance = 0.5*self.width - 2.0*EDGE_TOLERANCE;
+        crossings.iter()
+            .copied()
+            .find(|&distance| self.spline.closest_point(point + unit_direction*distance).distance_sq >= min_edge_distance*min_edge_distance)
+            .or(crossings.first().copied())
+            .expect("a ray from inside the road to cross its edge")
+    }
+
+    /// Get the signed distance from a point to the nearest road edge, positive on the road and
+    /// negative outside it. Obstacles are not taken into account.
+    pub fn distance_to_edge(&self, point: Vec2) -> f32 {
+        0.5*self.width - self.spline.closest_point(point).distance_sq.sqrt()
+    }
+
+    /// Get the distance along a ray from a point on the road to the road edge by sphere tracing
+    fn sphere_traced_edge_distance(&self, point: Vec2, direction: Vec2) -> f32 {
+        let RayMarch { tolerance, max_steps, .. } = self.ray_march;
+        let range = self.ray_march.range();
+        let unit_direction = direction.normalized();
+        let mut distance = 0.0;
+        for _ in 0 .. max_steps {
+            let safe_distance = self.distance_to_edge(point + unit_direction*distance);
+            if safe_distance < tolerance || distance >= range {
+                break;
+            }
+            distance += safe_distance;
+        }
+        distance
+    }
+
+    /// Get the distance along a ray from a point off the road to where it enters the road, by
+    /// sphere tracing the distance to the road. None if the ray misses the road.
+    fn entry_distance(&self, point: Vec2, direction: Vec2) -> Option<f32> {
+        let RayMarch { tolerance, max_steps, .. } = self.ray_march;
+        let unit_direction = direction.normalized();
+        let (min, max) = self.bounds();
+        let mut distance = 0.0;
+        for _ in 0 .. max_steps {
+            let ray_point = point + unit_direction*distance;
+            let gap = -self.distance_to_edge(ray_point);
+            if gap < tolerance {
+                return (distance <= self.ray_march.range()).then_some(distance);
+            }
+            // A ray which has left the box around the road never comes back to it
+            let Vec2(x, y) = ray_point;
+            if x < min.0 || x > max.0 || y < min.1 || y > max.1 {
+                return None;
+            }
+            distance += gap;
+        }
+        None
+    }
+
+    /// Find the first intersection of a ray from a point on the road with the edge of the track
+    /// or an obstacle
+    fn ray_collision_inside(&self, point: Vec2, direction: Vec2) -> Vec2 {
+        let edge_distance = match self.ray_casting {
+            RayCasting::Analytic => self.analytic_edge_distance(point, direction),
+            RayCasting::SphereTracing => self.sphere_traced_edge_distance(point, direction),
+        };
+        let edge_distance = edge_distance.min(self.ray_march.range());
+
+        // Obstacles may block the ray before it reaches the edge
+        let distance = self.all_obstacles()
+            .filter_map(|obstacle| obstacle.ray_intersection(point, direction))
+            .fold(edge_distance, f32::min);
+        point + direction.normalized()*distance
+    }
+
+    /// Find the spline parameter at a given arc length along the centerline
+    pub fn parameter_at_arc_length(&self, arc: f32) -> f32 {
+        self.spline.u_at_arc_length(arc.rem_euclid(self.spline.total_length()))
+    }
+
+    /// Get the road coordinates (arc, lateral) of a point, i.e. the distance along the centerline to
+    /// the closest centerline point and the signed displacement to the left of it
+    pub fn road_coordinates(&self, point: Vec2) -> (f32, f32) {
+        let TrackProjection { progress, distance_sq } = self.project(point);
+        let u = self.parameter_at_arc_length(progress);
+        let side = (point - self.spline.get(u)).dot(self.spline.tangent(u).rotate90()).signum();
+        (progress, side*distance_sq.sqrt())
+    }
+
+    /// Get an obstacle of a given shape in world coordinates, given its road coordinates
+    fn obstacle_at(&self, shape: ObstacleShape, arc: f32, lateral: f32) -> Obstacle {
+        let u = self.parameter_at_arc_length(arc);
+        let unit_forward = self.spline.tangent(u);
+        let center = self.spline.get(u) + unit_forward.rotate90()*lateral;
+        Obstacle::new(shape, center, unit_forward)
+    }
+
+    /// Place an obstacle in road coordinates, i.e. at a distance 'arc' along the centerline and
+    /// displaced 'lateral' to the left of it. The obstacle is aligned with the road direction.
+    pub fn place_obstacle(&mut self, shape: ObstacleShape, arc: f32, lateral: f32) {
+        let obstacle = self.obstacle_at(shape, arc, lateral);
+        self.obstacles.push(obstacle);
+    }
+
+    /// Add an obstacle which travels along the road at a fixed 'speed'. On reset, it is placed at a
+    /// distance 'start_offset' ahead of the car.
+    pub fn add_moving_obstacle(&mut self, shape: ObstacleShape, start_offset: f32, lateral: f32, speed: f32) {
+        let obstacle = self.obstacle_at(shape, start_offset, lateral);
+        self.moving_obstacles.push(MovingObstacle { shape, speed, lateral, start_offset, arc: start_offset, obstacle });
+    }
+
+    pub fn clear_obstacles(&mut self) {
+        self.obstacles.clear();
+        self.moving_obstacles.clear();
+    }
+
+    fn set_moving_obstacles<F: FnMut(&MovingObstacle) -> f32>(&mut self, mut new_arc: F) {
+        let total_length = self.spline.total_length();
+        let mut moving_obstacles = std::mem::take(&mut self.moving_obstacles);
+        for moving in moving_obstacles.iter_mut() {
+            moving.arc = new_arc(moving).rem_euclid(total_length);
+            moving.obstacle = self.obstacle_at(moving.shape, moving.arc, moving.lateral);
+        }
+        self.moving_obstacles = moving_obstacles;
+    }
+
+    /// Iterate over all static and moving obstacles at their current positions
+    pub fn all_obstacles(&self) -> impl Iterator<Item=&Obstacle> {
+        self.obstacles.iter().chain(self.moving_obstacles.iter().map(|moving| &moving.obstacle))
+    }
+
+    /// Check whether any obstacle touches the sides of the footprint of the car
+    fn hits_obstacle(&self, state: &CarState, config: &CarConfig) -> bool {
+        let sides = state.sides(config);
+        self.all_obstacles().any(|obstacle| sides.iter().any(|&(start, end)| obstacle.intersects_segment(start, end)))
+    }
+
+    /// Build a copy of the road on a transformed spline, moving the obstacles and checkpoints with
+    /// it. A road length scaled by 'scale' is assumed, and 'mirror' and 'reverse' flip the sides
+    /// of the road and direction of travel respectively.
+    fn transformed(&self, spline: SmoothBezierSpline, scale: f32, mirror: bool, reverse: bool) -> SplineMap {
+        let total_length = spline.total_length();
+        let arc_map = |arc: f32| if reverse { (total_length - arc*scale).rem_euclid(total_length) } else { arc*scale };
+        let lateral_sign = if mirror != reverse { -1.0 } else { 1.0 };
+
+        let mut road = SplineMap::new(spline, self.width*scale);
+        road.set_shoulder_width(self.shoulder_width*scale);
+        road.set_ray_casting(self.ray_casting);
+        road.set_ray_march(self.ray_march);
+        for obstacle in &self.obstacles {
+            let (arc, lateral) = self.road_coordinates(obstacle.center);
+            road.place_obstacle(obstacle.shape.scaled(scale), arc_map(arc), lateral_sign*lateral*scale);
+        }
+        for moving in &self.moving_obstacles {
+            // Moving obstacles are placed relative to the car, so keep driving ahead of it in the
+            // same lane when the track is reversed
+            let lateral_sign = if mirror { -1.0 } else { 1.0 };
+            road.add_moving_obstacle(moving.shape.scaled(scale), moving.start_offset*scale, lateral_sign*moving.lateral*scale, moving.speed);
+        }
+        road.checkpoints = self.checkpoints.iter().map(|&arc| arc_map(arc)).collect();
+        road
+    }
+
+    /// Get a copy of the road scaled by 'factor' about the origin, including its width
+    pub fn scaled(&self, factor: f32) -> SplineMap {
+        assert!(factor > 0.0, "Scale factor must be positive, got {}", factor);
+        self.transformed(self.spline.scaled(factor), factor, false, false)
+    }
+
+    /// Get a copy of the road rotated counterclockwise by 'angle' radians about the origin
+    pub fn rotated(&self, angle: f32) -> SplineMap {
+        self.transformed(self.spline.rotated(angle), 1.0, false, false)
+    }
+
+    /// Get a copy of the road mirrored in the x axis, turning left corners into right corners
+    pub fn mirrored(&self) -> SplineMap {
+        self.transformed(self.spline.mirrored(), 1.0, true, false)
+    }
+
+    /// Get a copy of the road driven in the opposite direction
+    pub fn reversed(&self) -> SplineMap {
+        self.transformed(self.spline.reversed(), 1.0, false, true)
+    }
+
+    /// Get the curvature statistics of the centerline from 'n_samples' points evenly spaced along it
+    pub fn curvature_stats(&self, n_samples: usize) -> CurvatureStats {
+        let ds = self.spline.total_length() / n_samples as f32;
+        let (max_abs, sum_abs) = (0 .. n_samples)
+            .map(|i| self.spline.curvature(self.parameter_at_arc_length((i as f32 + 0.5)*ds)).abs())
+            .fold((0.0f32, 0.0), |(max, sum), curvature| (max.max(curvature), sum + curvature));
+        CurvatureStats { max_abs, mean_abs: sum_abs / n_samples as f32 }
+    }
+
+    /// Sample the centerline and both edges of the road at roughly 'n_segments' evenly spaced points
+    pub fn sample_road(&self, n_segments: usize) -> RoadSamples {
+        let spline = &self.spline;
+        let mut samples = RoadSamples { center: Vec::new(), left: Vec::new(), right: Vec::new() };
+
+        let max_u = spline.max_u;
+        let ds = spline.total_length() / n_segments as f32;
+
+        let mut u = 0.0;
+
+        while u < max_u {
+            // Step by the average of the speeds at the start and an estimated end of the step
+            let v0 = spline.velocity(u);
+            let du = ds / v0.norm();
+            let v = v0*0.5 + spline.velocity(u + du)*0.5;
+            let du = ds / v.norm();
+
+            let center = spline.get(u);
+            let lateral = spline.tangent(u).rotate90()*0.5*self.width;
+            samples.center.push(center);
+            samples.left.push(center + lateral);
+            samples.right.push(center - lateral);
+
+            u += du;
+        }
+
+        samples
+    }
 }
 
 
 impl Road for SplineMap {
     fn is_crashed(&self, state: &CarState, config: &CarConfig) -> bool {
-        // Check if both the back and front points are inside the road;
-        let back_point = state.position - state.unit_forward*config.back_axle;
-        let front_point = back_point + state.unit_forward*config.length;
-        !self.point_inside(back_point) || !self.point_inside(front_point)
+        // Check if all corners of the car are inside the road, and no obstacle touches its sides
+        !state.corners(config).into_iter().all(|corner| self.point_inside_shoulder(corner))
+            || self.hits_obstacle(state, config)
+    }
+
+    fn is_on_shoulder(&self, state: &CarState, config: &CarConfig) -> bool {
+        !state.corners(config).into_iter().all(|corner| self.point_inside(corner))
+    }
+
+    fn contact(&self, state: &CarState, config: &CarConfig) -> RoadContact {
+        // Query the closest points of the corners once for both checks
+        let max_d2 = state.corners(config).into_iter()
+            .map(|corner| self.spline.closest_point(corner).distance_sq)
+            .fold(0.0, f32::max);
+        let on_shoulder = max_d2 >= self.max_d2;
+        let crashed = max_d2 >= self.crash_d2 || self.hits_obstacle(state, config);
+        RoadContact { on_shoulder, crashed }
+    }
+
+    /// Marches along the path by the distance to the crash boundary, which is a safe step since
+    /// all points closer than that are within it
+    fn path_leaves_road(&self, from: Vec2, to: Vec2) -> bool {
+        let crash_distance = self.crash_d2.sqrt();
+        let length = (to - from).norm();
+        let mut t = 0.0;
+        while t < length {
+            let distance = self.spline.closest_point(from + (to - from)*(t/length)).distance_sq.sqrt();
+            if distance >= crash_distance {
+                return true;
+            }
+            t += (crash_distance - distance).max(PATH_MIN_STEP);
+        }
+        self.all_obstacles().any(|obstacle| obstacle.intersects_segment(from, to))
+    }
+
+    fn contains(&self, point: Vec2) -> bool {
+        self.point_inside(point) && !self.obstacles.iter().any(|obstacle| obstacle.contains(point))
+    }
+
+    fn contains_points(&self, points: &[Vec2]) -> Vec<bool> {
+        self.spline.closest_points(points)
+            .into_iter()
+            .zip(points)
+            .map(|(closest, &point)| closest.distance_sq < self.max_d2 && !self.obstacles.iter().any(|obstacle| obstacle.contains(point)))
+            .collect()
+    }
+
+    fn bounds(&self) -> (Vec2, Vec2) {
+        let Aabb { min, max } = self.spline.aabb().padded(0.5*self.width + self.shoulder_width);
+        (min, max)
+    }
+
+    fn track_length(&self) -> f32 {
+        self.spline.total_length()
+    }
+
+    fn project(&self, point: Vec2) -> TrackProjection {
+        let ClosestPointOutput { parameter, distance_sq } = self.spline.closest_point(point);
+        TrackProjection { progress: self.spline.arc_length(parameter), distance_sq }
+    }
+
+    fn project_near(&self, point: Vec2, progress_hint: f32) -> TrackProjection {
+        let u_hint = self.spline.u_at_arc_length(progress_hint);
+        let ClosestPointOutput { parameter, distance_sq } = self.spline.closest_point_near(point, u_hint, PROJECTION_WINDOW);
+        TrackProjection { progress: self.spline.arc_length(parameter), distance_sq }
+    }
+
+    fn progress_delta(&self, from: f32, to: f32) -> f32 {
+        self.spline.arc_delta(from, to)
+    }
+
+    fn pose_at(&self, progress: f32) -> (Vec2, Vec2) {
+        let u = self.parameter_at_arc_length(progress);
+        (self.spline.get(u), self.spline.tangent(u))
+    }
+
+    /// Move all moving obstacles back to their starting offset relative to the car
+    fn reset_obstacles(&mut self, start_progress: f32) {
+        self.set_moving_obstacles(|moving| start_progress + moving.start_offset);
+    }
+
+    /// Advance all moving obstacles along the road by a time step 'dt'
+    fn advance_obstacles(&mut self, dt: f32) {
+        self.set_moving_obstacles(|moving| moving.arc + moving.speed*dt);
+    }
+
+    /// The state of the moving obstacles is their distances along the centerline
+    fn obstacle_state(&self) -> Vec<f32> {
+        self.moving_obstacles.iter().map(|moving| moving.arc).collect()
+    }
+
+    fn set_obstacle_state(&mut self, state: &[f32]) {
+        assert_eq!(state.len(), self.moving_obstacles.len(), "Obstacle state does not match the moving obstacles of the road");
+        let mut arcs = state.iter();
+        self.set_moving_obstacles(|_| *arcs.next().expect("one arc per obstacle"));
     }
 
     /// Takes in a point and (non-normalized) direction defining a ray,
     /// and finds the first intersection with the edge of the track.
-    fn ray_collision(&self, point: Vec2, direction: Vec2) -> Vec2 {  
-        let step_length = self.width * 0.1;
-        let step = direction.normalized() * step_length;
-        let mut p = point;
-
+    fn ray_collision(&self, point: Vec2, direction: Vec2) -> Vec2 {
         // Early return if we have already crashed
         if !self.point_inside(point) {
             return point;
         }
+        self.ray_collision_inside(point, direction)
+    }
 
-        // Find the a point 'inside_point' such that 'inside_point' is inside the road
-        // and inside_point + step is outside
-        let inside_point = loop {
-            let next_p = p + step;
-            if !self.point_inside(next_p) {
-                break p
-            }
-            p = next_p;
-        };
+    fn set_ray_march(&mut self, ray_march: RayMarch) {
+        self.ray_march = ray_march;
+    }
 
-        // Do bisection to find the exact position of the edge
-        //
-        // Define a function f(t) such that f(t) is zero at t such that inside_point * t*step is on
-        // the edge
-        let edge_deviation = |t| self.spline.closest_point(inside_point + step*t).distance_sq - self.max_d2;
-        let t = find_root(edge_deviation, 0.0, 1.0, 1e-2).expect("the prior code to ensure a root exists");
-        inside_point + step*t
+    /// Off the road, e.g. on the shoulder, the readings are signed: minus the distance along each
+    /// ray to where it enters the road, or zero for rays which miss it
+    fn read_lidar_into(&self, state: &CarState, lidar: &LidarArray, projection: Option<&TrackProjection>, out: &mut [f32]) {
+        // Check once for all rays whether the car is on the road, using the projection if given
+        let inside = match projection {
+            Some(projection) => projection.distance_sq < self.max_d2,
+            None => self.point_inside(state.position),
+        };
+        for (reading, &angle) in out.iter_mut().zip(lidar.get_angles()) {
+            let direction = state.unit_forward.rotate(angle);
+            *reading = if inside {
+                direction.dot(self.ray_collision_inside(state.position, direction) - state.position)
+            } else {
+                self.entry_distance(state.position, direction).map_or(0.0, |distance| -distance)
+            };
+        }
     }
 }
 
 
 pub fn make_oval() -> SplineMap {
-    let spline = SmoothBezierSpline::new(
+    let spline = SmoothBezierSpline::new_closed(
         vec![BezierControl{ point: Vec2(0.0, 0.0), velocity: Vec2(6.0, 0.0)},
              BezierControl{ point: Vec2(10.0, 10.0), velocity: Vec2(0.0, 6.0)},
              BezierControl{ point: Vec2(0.0, 20.0), velocity: Vec2(-6.0, 0.0)},
@@ -83,7 +515,7 @@ pub fn make_oval() -> SplineMap {
 }
 
 pub fn make_simple_racetrack() -> SplineMap {
-    let spline = SmoothBezierSpline::new(
+    let spline = SmoothBezierSpline::new_closed(
         vec![
             BezierControl { point: Vec2(0.0, 0.0), velocity: Vec2(50.0, -10.0) },
             BezierControl { point: Vec2(87.5, 50.0), velocity: Vec2(0.0, 25.0) },
@@ -99,7 +531,7 @@ pub fn make_simple_racetrack() -> SplineMap {
 }
 
 pub fn make_racetrack() -> SplineMap {
-    let spline = SmoothBezierSpline::new(
+    let spline = SmoothBezierSpline::new_closed(
         vec![
             BezierControl { point: Vec2(0.0, 0.0), velocity: Vec2(-30.0, 4.0) },
             BezierControl { point: Vec2(-168.4, 24.5), velocity: Vec2(-30.0, 4.0) },
@@ -135,3 +567,231 @@ pub fn make_racetrack() -> SplineMap {
     let width = 10.0;
     SplineMap::new(spline, width)
 }
+
+
+/// Generate a random closed track, through waypoints at random distances from the origin in
+/// increasing directions, so that the track winds around the origin once without crossing itself
+pub fn make_random_track<G: Rng + ?Sized>(rng: &mut G) -> SplineMap {
+    let n_points = rng.random_range(8 ..= 12);
+    let radius = rng.random_range(60.0 .. 100.0);
+    let waypoints: Vec<Vec2> = (0 .. n_points)
+        .map(|i| {
+            let angle = (i as f32 + rng.random_range(-0.3 .. 0.3)) * std::f32::consts::TAU / n_points as f32;
+            Vec2(angle.cos(), angle.sin()) * radius * rng.random_range(0.7 .. 1.3)
+        })
+        .collect();
+    let spline = SmoothBezierSpline::through_points(&waypoints, 0.5);
+    let width = 10.0;
+    SplineMap::new(spline, width)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains_points() {
+        let mut road = make_oval();
+        road.place_obstacle(ObstacleShape::Circle { radius: 0.5 }, 10.0, 1.0);
+        let points: Vec<Vec2> = (0 .. 200).map(|i| Vec2((i % 20) as f32 * 2.0 - 30.0, (i / 20) as f32 * 3.0 - 5.0)).collect();
+        let contained = road.contains_points(&points);
+        assert!(contained.iter().any(|&inside| inside) && contained.iter().any(|&inside| !inside));
+        assert_eq!(contained, points.iter().map(|&point| road.contains(point)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_footprint() {
+        let road = make_oval();
+        let config = CarConfig::default();
+        let (position, unit_forward) = road.pose_at(10.0);
+
+        // A car driving along the edge crashes once its side leaves the road
+        let lateral = 0.5*road.width - 0.25*config.width;
+        let state = CarState { position: position + unit_forward.rotate90()*lateral, unit_forward, ..CarState::default() };
+        assert!(road.is_crashed(&state, &config));
+        assert!(road.contact(&state, &config).crashed);
+        let narrow = CarConfig { width: 0.0, ..config.clone() };
+        assert!(!road.is_crashed(&state, &narrow));
+
+        // and so does one whose side touches an obstacle
+        let mut road = road;
+        road.place_obstacle(ObstacleShape::Circle { radius: 0.5 }, 11.0, 0.5*config.width + 0.3);
+        let state = CarState { position, unit_forward, ..CarState::default() };
+        assert!(road.is_crashed(&state, &config));
+        assert!(!road.is_crashed(&state, &narrow));
+    }
+
+    #[test]
+    fn test_path_leaves_road() {
+        let mut road = make_oval();
+
+        // Crossing the infield between the straights leaves the road, driving along one does not
+        assert!(road.path_leaves_road(Vec2(-10.0, 0.0), Vec2(-10.0, 20.0)));
+        assert!(!road.path_leaves_road(Vec2(-15.0, 1.0), Vec2(-5.0, -1.0)));
+        assert!(!road.path_leaves_road(Vec2(-10.0, 0.0), Vec2(-10.0, 0.0)));
+
+        // and so does passing through an obstacle
+        road.place_obstacle(ObstacleShape::Circle { radius: 0.5 }, 10.0, 0.0);
+        let (center, unit_forward) = road.pose_at(10.0);
+        assert!(road.path_leaves_road(center - unit_forward*3.0, center + unit_forward*3.0));
+    }
+
+    #[test]
+    fn test_lidar_off_road() {
+        let mut road = make_oval();
+        road.set_shoulder_width(4.0);
+        let lidar = LidarArray::default();
+
+        // On the shoulder outside the bottom straight, rays towards the road read minus the
+        // distance to it, and rays away from it read zero
+        let state = CarState { position: Vec2(-10.0, -6.0), unit_forward: Vec2(0.0, 1.0), ..CarState::default() };
+        let readings = road.read_lidar(&state, &lidar);
+        let ahead = lidar.get_angles().iter().position(|&angle| angle == 0.0).unwrap();
+        assert!((readings[ahead] + 2.0).abs() < 0.01, "{}", readings[ahead]);
+        assert!(readings.iter().all(|&reading| reading <= 0.0));
+
+        let state = CarState { unit_forward: Vec2(0.0, -1.0), ..state };
+        let readings = road.read_lidar(&state, &lidar);
+        assert!(readings.iter().zip(lidar.get_angles()).all(|(&reading, angle)| angle.abs() > 1.5 || reading == 0.0));
+    }
+
+    #[test]
+    fn test_ray_collision() {
+        let road = make_racetrack();
+        let (position, unit_forward) = road.pose_at(100.0);
+        for i in 0 .. 36 {
+            // Every hit is on the edge of the road
+            let direction = unit_forward.rotate(i as f32 * 10.0_f32.to_radians());
+            let hit = road.ray_collision(position, direction);
+            let edge_distance = road.spline.closest_point(hit).distance_sq.sqrt();
+            assert!((edge_distance - 0.5*road.width).abs() < 0.05, "{} != {}", edge_distance, 0.5*road.width);
+
+            // and the road is not left before it
+            for s in [0.2, 0.5, 0.9] {
+                assert!(road.contains(position + (hit - position)*s));
+            }
+        }
+
+        // Obstacles block rays
+        let mut blocked = make_oval();
+        blocked.place_obstacle(ObstacleShape::Circle { radius: 0.5 }, 10.0, 0.0);
+        let (position, _) = blocked.pose_at(5.0);
+        let center = blocked.obstacles[0].center;
+        let hit = blocked.ray_collision(position, center - position);
+        assert!(((hit - center).norm() - 0.5).abs() < 1e-3);
+
+        // Rays from outside of the road end where they start
+        let outside = Vec2(0.0, -100.0);
+        assert_eq!(blocked.ray_collision(outside, Vec2(1.0, 0.0)), outside);
+    }
+
+    #[test]
+    fn test_sphere_tracing() {
+        let analytic = make_racetrack();
+        let mut traced = make_racetrack();
+        traced.set_ray_casting(RayCasting::SphereTracing);
+        for progress in [0.0, 100.0, 500.0] {
+            let (position, unit_forward) = analytic.pose_at(progress);
+            for i in 0 .. 36 {
+                let direction = unit_forward.rotate(i as f32 * 10.0_f32.to_radians());
+                let expected = analytic.ray_collision(position, direction);
+                let hit = traced.ray_collision(position, direction);
+                assert!((hit - expected).norm() < 0.1, "{:?} != {:?}", hit, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ray_march() {
+        let mut road = make_racetrack();
+        let (position, unit_forward) = road.pose_at(0.0);
+
+        // Rays stop at the maximum distance with either method of ray casting
+        road.set_ray_march(RayMarch { max_distance: Some(2.0), ..RayMarch::default() });
+        for ray_casting in [RayCasting::Analytic, RayCasting::SphereTracing] {
+            road.set_ray_casting(ray_casting);
+            let hit = road.ray_collision(position, unit_forward);
+            assert!(((hit - position).norm() - 2.0).abs() < 1e-3, "{:?}", hit);
+        }
+
+        // Sphere tracing stops after the maximum number of steps, short of the edge
+        road.set_ray_march(RayMarch { max_steps: 1, ..RayMarch::default() });
+        let hit = road.ray_collision(position, unit_forward);
+        assert!(((hit - position).norm() - road.distance_to_edge(position)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_transformations() {
+        let mut road = make_oval();
+        road.place_obstacle(ObstacleShape::Circle { radius: 0.5 }, 10.0, 2.0);
+        road.checkpoints = vec![20.0];
+        let length = road.track_length();
+        let close = |a: f32, b: f32| (a - b).abs() < 0.05;
+
+        let scaled = road.scaled(2.0);
+        assert_eq!(scaled.width, 2.0*road.width);
+        assert!(close(scaled.track_length(), 2.0*length));
+        assert_eq!(scaled.checkpoints, vec![40.0]);
+        assert_eq!(scaled.obstacles[0].shape, ObstacleShape::Circle { radius: 1.0 });
+        let (arc, lateral) = scaled.road_coordinates(scaled.obstacles[0].center);
+        assert!(close(arc, 20.0) && close(lateral, 4.0));
+
+        // Mirroring moves the obstacle to the other side of the road
+        let mirrored = road.mirrored();
+        let Vec2(x, y) = road.obstacles[0].center;
+        assert!((mirrored.obstacles[0].center - Vec2(x, -y)).norm() < 0.05);
+        let (arc, lateral) = mirrored.road_coordinates(mirrored.obstacles[0].center);
+        assert!(close(arc, 10.0) && close(lateral, -2.0));
+
+        // Reversing keeps the obstacle in place, but measures it from the other direction
+        let reversed = road.reversed();
+        assert!((reversed.obstacles[0].center - road.obstacles[0].center).norm() < 0.05);
+        let (arc, lateral) = reversed.road_coordinates(reversed.obstacles[0].center);
+        assert!(close(arc, length - 10.0) && close(lateral, -2.0));
+        assert!(close(reversed.checkpoints[0], length - 20.0));
+
+        let rotated = road.rotated(1.0);
+        assert!((rotated.obstacles[0].center - road.obstacles[0].center.rotate(1.0)).norm() < 0.05);
+    }
+
+    #[test]
+    fn test_distance_to_edge() {
+        let road = make_oval();
+        let (position, unit_forward) = road.pose_at(5.0);
+        let left = unit_forward.rotate90();
+        assert!((road.distance_to_edge(position) - 4.0).abs() < 1e-3);
+        assert!((road.distance_to_edge(position + left*3.0) - 1.0).abs() < 1e-2);
+        assert!((road.distance_to_edge(position - left*6.0) + 2.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_curvature_stats() {
+        let road = make_oval();
+        let stats = road.curvature_stats(500);
+        assert!(stats.min_radius() > 5.0 && stats.min_radius() < 15.0);
+        assert!(stats.mean_abs > 0.0 && stats.mean_abs < stats.max_abs);
+
+        // Scaling the road scales the radii, and mirroring does not change them
+        let scaled = road.scaled(2.0).curvature_stats(500);
+        assert!((scaled.min_radius() - 2.0*stats.min_radius()).abs() < 0.1);
+        assert!((road.mirrored().curvature_stats(500).mean_abs - stats.mean_abs).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_random_track() {
+        use rand::SeedableRng;
+        let make = |seed| make_random_track(&mut rand_pcg::Pcg64::seed_from_u64(seed));
+        let road = make(3);
+        assert!(road.spline.is_closed());
+        assert_eq!(road.spline.get(1.5), make(3).spline.get(1.5));
+        assert_ne!(road.spline.get(1.5), make(4).spline.get(1.5));
+
+        // The centerline stays on the road all the way around
+        let length = road.track_length();
+        for i in 0 .. 50 {
+            let (position, _) = road.pose_at(i as f32 * length / 50.0);
+            assert!(road.contains(position));
+        }
+    }
+}
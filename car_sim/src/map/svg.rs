@@ -0,0 +1,101 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use math_utils::Vec2;
+
+use super::obstacle::{Obstacle, ObstacleShape};
+use super::spline_map::SplineMap;
+use super::traits::Road;
+
+
+/// Format a closed polyline as SVG path data. SVG has its y axis pointing down, so y is flipped.
+fn path_data(points: &[Vec2]) -> String {
+    let mut data = String::new();
+    for (i, point) in points.iter().enumerate() {
+        let command = if i == 0 { 'M' } else { 'L' };
+        let _ = write!(data, "{}{:.3},{:.3} ", command, point.0, 0.0 - point.1);
+    }
+    data.push('Z');
+    data
+}
+
+fn obstacle_element(obstacle: &Obstacle) -> String {
+    match obstacle.shape {
+        ObstacleShape::Circle { radius } => {
+            format!(r#"<circle cx="{:.3}" cy="{:.3}" r="{:.3}" fill="orange"/>"#, obstacle.center.0, -obstacle.center.1, radius)
+        },
+        ObstacleShape::Rectangle { length, width } => {
+            let half_forward = obstacle.unit_forward*0.5*length;
+            let half_lateral = obstacle.unit_forward.rotate90()*0.5*width;
+            let corners = [
+                obstacle.center - half_forward + half_lateral,
+                obstacle.center + half_forward + half_lateral,
+                obstacle.center + half_forward - half_lateral,
+                obstacle.center - half_forward - half_lateral,
+            ];
+            format!(r#"<path d="{}" fill="orange"/>"#, path_data(&corners))
+        }
+    }
+}
+
+
+/// Render the centerline, road edges and static obstacles of a spline road as an SVG document,
+/// in world units
+pub fn to_svg(road: &SplineMap) -> String {
+    let center = road.spline.offset(0.0);
+    let left = road.spline.offset(0.5*road.width);
+    let right = road.spline.offset(-0.5*road.width);
+
+    let (min, max) = road.bounds();
+    let size = max - min;
+    let line_width = 0.01*size.0.max(size.1);
+
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{:.3} {:.3} {:.3} {:.3}">"#, min.0, -max.1, size.0, size.1);
+
+    // The road surface is the area between the edges, filled with the even-odd rule
+    let _ = writeln!(svg, r#"<path d="{} {}" fill="gray" fill-rule="evenodd"/>"#, path_data(&left), path_data(&right));
+    for edge in [&left, &right] {
+        let _ = writeln!(svg, r#"<path d="{}" fill="none" stroke="black" stroke-width="{:.3}"/>"#, path_data(edge), line_width);
+    }
+    let _ = writeln!(svg, r#"<path d="{}" fill="none" stroke="white" stroke-width="{:.3}" stroke-dasharray="{:.3}"/>"#,
+                     path_data(&center), 0.5*line_width, 4.0*line_width);
+    for obstacle in &road.obstacles {
+        let _ = writeln!(svg, "{}", obstacle_element(obstacle));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+
+/// Write an SVG image of the centerline, road edges and static obstacles of a spline road to 'path'
+pub fn export_svg<P: AsRef<Path>>(road: &SplineMap, path: P) -> std::io::Result<()> {
+    std::fs::write(path, to_svg(road))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::make_oval;
+
+    #[test]
+    fn test_svg() {
+        let mut road = make_oval();
+        road.place_obstacle(ObstacleShape::Circle { radius: 0.5 }, 10.0, 1.0);
+        road.place_obstacle(ObstacleShape::Rectangle { length: 1.0, width: 2.0 }, 40.0, -1.0);
+        let svg = to_svg(&road);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        // Road surface, two edges, centerline and the rectangle
+        assert_eq!(svg.matches("<path").count(), 5);
+        assert_eq!(svg.matches("<circle").count(), 1);
+    }
+
+    #[test]
+    fn test_path_data() {
+        assert_eq!(path_data(&[Vec2(0.0, 0.0), Vec2(1.0, 2.0)]), "M0.000,0.000 L1.000,-2.000 Z");
+    }
+}
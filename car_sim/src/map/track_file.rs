@@ -0,0 +1,321 @@
+use std::fmt;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use math_utils::Vec2;
+use math_utils::spline::{SmoothBezierSpline, BezierControl};
+
+use super::obstacle::ObstacleShape;
+use super::spline_map::SplineMap;
+
+
+/// A static obstacle placed in road coordinates
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObstacleDefinition {
+    #[serde(flatten)]
+    pub shape: ObstacleShape,
+    /// Distance along the centerline
+    pub distance: f32,
+    /// Displacement to the left of the centerline
+    #[serde(default)]
+    pub lateral: f32,
+}
+
+
+/// A serializable definition of a spline-based track
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackDefinition {
+    pub controls: Vec<BezierControl>,
+    pub width: f32,
+    #[serde(default)]
+    pub shoulder_width: f32,
+    #[serde(default)]
+    pub obstacles: Vec<ObstacleDefinition>,
+    /// Distances along the centerline of the checkpoints
+    #[serde(default)]
+    pub checkpoints: Vec<f32>,
+    /// Whether the track is a closed loop, joining the last control point to the first
+    #[serde(default = "default_closed")]
+    pub closed: bool,
+}
+
+fn default_closed() -> bool {
+    true
+}
+
+
+#[derive(Debug)]
+pub enum TrackLoadError {
+    Io(std::io::Error),
+    Parse(String),
+    UnsupportedFormat(String),
+    Invalid(String),
+}
+
+impl fmt::Display for TrackLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrackLoadError::Io(err) => write!(f, "Could not access track file: {}", err),
+            TrackLoadError::Parse(msg) => write!(f, "Could not parse track file: {}", msg),
+            TrackLoadError::UnsupportedFormat(extension) => write!(f, "Unsupported track file format '{}'. Expected 'json' or 'toml'.", extension),
+            TrackLoadError::Invalid(msg) => write!(f, "Invalid track definition: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for TrackLoadError {}
+
+impl From<std::io::Error> for TrackLoadError {
+    fn from(err: std::io::Error) -> Self {
+        TrackLoadError::Io(err)
+    }
+}
+
+
+enum TrackFormat {
+    Json,
+    Toml,
+}
+
+impl TrackFormat {
+    fn from_path(path: &Path) -> Result<Self, TrackLoadError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(TrackFormat::Json),
+            Some("toml") => Ok(TrackFormat::Toml),
+            extension => Err(TrackLoadError::UnsupportedFormat(extension.unwrap_or("").to_string())),
+        }
+    }
+}
+
+
+impl TrackDefinition {
+    pub fn from_json(text: &str) -> Result<Self, TrackLoadError> {
+        serde_json::from_str(text).map_err(|err| TrackLoadError::Parse(err.to_string()))
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, TrackLoadError> {
+        toml::from_str(text).map_err(|err| TrackLoadError::Parse(err.to_string()))
+    }
+
+    /// Load a track definition from a '.json' or '.toml' file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, TrackLoadError> {
+        let path = path.as_ref();
+        let format = TrackFormat::from_path(path)?;
+        let text = std::fs::read_to_string(path)?;
+        match format {
+            TrackFormat::Json => Self::from_json(&text),
+            TrackFormat::Toml => Self::from_toml(&text),
+        }
+    }
+
+    /// Save the track definition to a '.json' or '.toml' file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), TrackLoadError> {
+        let path = path.as_ref();
+        let text = match TrackFormat::from_path(path)? {
+            TrackFormat::Json => serde_json::to_string_pretty(self).map_err(|err| TrackLoadError::Parse(err.to_string()))?,
+            TrackFormat::Toml => toml::to_string(self).map_err(|err| TrackLoadError::Parse(err.to_string()))?,
+        };
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), TrackLoadError> {
+        if self.controls.len() < 2 {
+            return Err(TrackLoadError::Invalid(format!("expected at least two control points, got {}", self.controls.len())));
+        }
+        if self.width <= 0.0 || self.width.is_nan() {
+            return Err(TrackLoadError::Invalid(format!("width must be positive, got {}", self.width)));
+        }
+        if self.shoulder_width < 0.0 {
+            return Err(TrackLoadError::Invalid(format!("shoulder width must be non-negative, got {}", self.shoulder_width)));
+        }
+        Ok(())
+    }
+}
+
+
+/// Parse centerline waypoints from CSV text, e.g. a published track centerline. The first two
+/// columns of each row are the x and y coordinates; any further columns are ignored, as are empty
+/// lines, lines starting with '#' and a non-numeric header line.
+pub fn parse_waypoints_csv(text: &str) -> Result<Vec<Vec2>, TrackLoadError> {
+    let mut waypoints = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut columns = line.split(',').map(|column| column.trim().parse::<f32>());
+        match (columns.next(), columns.next()) {
+            (Some(Ok(x)), Some(Ok(y))) => waypoints.push(Vec2(x, y)),
+            _ if waypoints.is_empty() && i == 0 => continue,  // Header
+            _ => return Err(TrackLoadError::Parse(format!("expected numeric x and y columns on line {}: '{}'", i+1, line))),
+        }
+    }
+    Ok(waypoints)
+}
+
+
+/// Tension of the spline fitted through waypoints, giving a standard Catmull-Rom spline
+const CATMULL_ROM_TENSION: f32 = 0.5;
+
+
+impl TrackDefinition {
+    /// Create a closed track of a given width passing through a sequence of centerline waypoints.
+    /// The control velocities are estimated Catmull-Rom style from the neighboring waypoints.
+    pub fn from_waypoints(waypoints: &[Vec2], width: f32) -> Result<Self, TrackLoadError> {
+        let distinct = match waypoints {
+            [first, .., last] if first == last => waypoints.len() - 1,
+            _ => waypoints.len(),
+        };
+        if distinct < 3 {
+            return Err(TrackLoadError::Invalid(format!("expected at least three distinct waypoints, got {}", distinct)));
+        }
+
+        let spline = SmoothBezierSpline::through_points(waypoints, CATMULL_ROM_TENSION);
+        let segments = &spline.segments;
+        let controls = segments.iter()
+            .map(|segment| BezierControl { point: segment.start, velocity: segment.p1 - segment.start })
+            .chain(std::iter::once(BezierControl { point: segments[0].start, velocity: segments[0].p1 - segments[0].start }))
+            .collect();
+
+        Ok(TrackDefinition { controls, width, shoulder_width: 0.0, obstacles: Vec::new(), checkpoints: Vec::new(), closed: true })
+    }
+
+    /// Create a track of a given width from CSV centerline waypoints, see 'parse_waypoints_csv'
+    pub fn from_csv(text: &str, width: f32) -> Result<Self, TrackLoadError> {
+        Self::from_waypoints(&parse_waypoints_csv(text)?, width)
+    }
+}
+
+
+impl SplineMap {
+    pub fn from_definition(definition: &TrackDefinition) -> Result<Self, TrackLoadError> {
+        definition.validate()?;
+        let controls = definition.controls.clone();
+        let spline = if definition.closed { SmoothBezierSpline::new_closed(controls) } else { SmoothBezierSpline::new(controls) };
+        let mut road = SplineMap::new(spline, definition.width);
+        road.set_shoulder_width(definition.shoulder_width);
+        for obstacle in &definition.obstacles {
+            road.place_obstacle(obstacle.shape, obstacle.distance, obstacle.lateral);
+        }
+        road.checkpoints = definition.checkpoints.clone();
+        Ok(road)
+    }
+
+    /// Load a track from a '.json' or '.toml' track definition file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, TrackLoadError> {
+        Self::from_definition(&TrackDefinition::from_file(path)?)
+    }
+
+    /// Load a track of a given width from a CSV file of centerline waypoints
+    pub fn from_csv<P: AsRef<Path>>(path: P, width: f32) -> Result<Self, TrackLoadError> {
+        let text = std::fs::read_to_string(path)?;
+        Self::from_definition(&TrackDefinition::from_csv(&text, width)?)
+    }
+
+    /// Get a serializable definition of the track. Static obstacles are converted back to road
+    /// coordinates; moving obstacles are not included.
+    pub fn to_definition(&self) -> TrackDefinition {
+        let segments = &self.spline.segments;
+        let last = segments.last().expect("at least one segment");
+        let controls = segments.iter()
+            .map(|segment| (segment.start, segment.p1 - segment.start))
+            .chain(std::iter::once((last.end, last.end - last.p2)))
+            .map(|(point, velocity)| BezierControl { point, velocity })
+            .collect();
+
+        let obstacles = self.obstacles.iter()
+            .map(|obstacle| {
+                let (distance, lateral) = self.road_coordinates(obstacle.center);
+                ObstacleDefinition { shape: obstacle.shape, distance, lateral }
+            })
+            .collect();
+
+        TrackDefinition {
+            controls,
+            width: self.width,
+            shoulder_width: self.shoulder_width(),
+            obstacles,
+            checkpoints: self.checkpoints.clone(),
+            closed: self.spline.is_closed(),
+        }
+    }
+
+    /// Save the track to a '.json' or '.toml' track definition file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), TrackLoadError> {
+        self.to_definition().save(path)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Road, make_oval};
+
+    const OVAL_TOML: &str = include_str!("../../../tracks/oval.toml");
+
+    #[test]
+    fn test_load_toml() {
+        let definition = TrackDefinition::from_toml(OVAL_TOML).expect("valid track file");
+        let road = SplineMap::from_definition(&definition).expect("valid track");
+        let reference = make_oval();
+        assert_eq!(road.width, reference.width);
+        assert_eq!(road.spline.total_length(), reference.spline.total_length());
+        assert_eq!(road.obstacles.len(), 2);
+        assert_eq!(road.checkpoints, vec![0.0, 30.0]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut road = make_oval();
+        road.place_obstacle(ObstacleShape::Circle { radius: 0.5 }, 10.0, 1.5);
+        road.place_obstacle(ObstacleShape::Rectangle { length: 1.0, width: 2.0 }, 40.0, -1.0);
+        road.checkpoints = vec![5.0];
+
+        let definition = road.to_definition();
+        let json = serde_json::to_string(&definition).expect("serializable definition");
+        let loaded = TrackDefinition::from_json(&json).expect("valid json");
+        assert_eq!(loaded.controls, definition.controls);
+        assert_eq!(loaded.checkpoints, vec![5.0]);
+        for (loaded, obstacle) in loaded.obstacles.iter().zip([(10.0, 1.5), (40.0, -1.0)]) {
+            assert!((loaded.distance - obstacle.0).abs() < 0.1);
+            assert!((loaded.lateral - obstacle.1).abs() < 0.1);
+        }
+
+        let toml_text = toml::to_string(&definition).expect("serializable definition");
+        assert_eq!(TrackDefinition::from_toml(&toml_text).expect("valid toml").controls, definition.controls);
+    }
+
+    #[test]
+    fn test_csv_waypoints() {
+        // Waypoints on a circle of radius 20, with a header, a comment and a repeated closing point
+        let mut csv = String::from("x_m,y_m,w_tr_right_m,w_tr_left_m\n# Circle\n");
+        for i in 0 ..= 16 {
+            let angle = std::f32::consts::TAU * (i % 16) as f32 / 16.0;
+            csv.push_str(&format!("{},{},4.0,4.0\n", 20.0*angle.cos(), 20.0*angle.sin()));
+        }
+        let definition = TrackDefinition::from_csv(&csv, 8.0).expect("valid csv");
+        assert_eq!(definition.controls.len(), 17);
+        assert_eq!(definition.controls.first(), definition.controls.last());
+
+        let road = SplineMap::from_definition(&definition).expect("valid track");
+        let circumference = std::f32::consts::TAU*20.0;
+        assert!((road.track_length() - circumference).abs() < 0.01*circumference);
+        for u in [0.5, 3.25, 15.75] {
+            assert!(((road.spline.get(u)).norm() - 20.0).abs() < 0.1);
+        }
+
+        assert!(matches!(TrackDefinition::from_csv("0,0\n1,1\n", 8.0), Err(TrackLoadError::Invalid(_))));
+        assert!(matches!(parse_waypoints_csv("0,0\n1,a\n"), Err(TrackLoadError::Parse(_))));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let result = TrackDefinition::from_json(r#"{"controls": [{"point": [0, 0], "velocity": [1, 0]}], "width": 5}"#)
+            .and_then(|definition| SplineMap::from_definition(&definition));
+        assert!(matches!(result, Err(TrackLoadError::Invalid(_))));
+        assert!(matches!(TrackDefinition::from_file("track.yaml"), Err(TrackLoadError::UnsupportedFormat(_))));
+    }
+}
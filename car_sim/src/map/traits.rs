@@ -1,24 +1,140 @@
+use serde::{Serialize, Deserialize};
+
 use math_utils::Vec2;
 use crate::physics::{CarState, CarConfig};
 use crate::lidar::LidarArray;
 
 
+/// A point expressed in road coordinates, i.e. relative to the centerline of the road
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrackProjection {
+    /// Distance travelled along the centerline to the closest centerline point
+    pub progress: f32,
+    /// Squared distance to the closest centerline point
+    pub distance_sq: f32,
+}
+
+
+/// Limits on marching LiDAR rays step by step towards the road edge, for roads which do so
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RayMarch {
+    /// Distance from the road edge at which a ray is considered to have hit it
+    pub tolerance: f32,
+    /// Maximum number of steps along a ray, reached only by rays grazing the edge
+    pub max_steps: usize,
+    /// Distance at which a ray stops as if it had hit the edge, if any, which caps the readings
+    pub max_distance: Option<f32>,
+}
+
+impl Default for RayMarch {
+    fn default() -> Self {
+        Self { tolerance: 1e-3, max_steps: 256, max_distance: None }
+    }
+}
+
+impl RayMarch {
+    /// Get the distance at which rays stop, which is infinite without a limit
+    pub fn range(&self) -> f32 {
+        self.max_distance.unwrap_or(f32::INFINITY)
+    }
+}
+
+
+/// How a car touches the road, see 'Road::is_on_shoulder' and 'Road::is_crashed'
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoadContact {
+    pub on_shoulder: bool,
+    pub crashed: bool,
+}
+
+
 /// A trait representing a representation of a road in the game
 /// Should support a method of determining whether a car is crashed, and methods for determining
 /// lidar stats
 pub trait Road {
     fn is_crashed(&self, state: &CarState, config: &CarConfig) -> bool;
+    /// Whether the car has left the driveable road without crashing
+    fn is_on_shoulder(&self, _state: &CarState, _config: &CarConfig) -> bool {
+        false
+    }
+    /// Check both 'is_on_shoulder' and 'is_crashed', which roads may answer with shared queries
+    fn contact(&self, state: &CarState, config: &CarConfig) -> RoadContact {
+        RoadContact { on_shoulder: self.is_on_shoulder(state, config), crashed: self.is_crashed(state, config) }
+    }
+    /// Whether a point moving straight from 'from' to 'to' leaves the road on the way, e.g. a fast
+    /// car passing through a thin wall within a step. Roads without such a check only look at
+    /// where the car ends up.
+    fn path_leaves_road(&self, _from: Vec2, _to: Vec2) -> bool {
+        false
+    }
     fn ray_collision(&self, point: Vec2, direction: Vec2) -> Vec2;
+    /// Set the limits on marching LiDAR rays, which roads finding the edge in closed form ignore
+    fn set_ray_march(&mut self, _ray_march: RayMarch) {}
+    /// Whether a point is on the driveable part of the road, and not blocked by a static obstacle
+    fn contains(&self, point: Vec2) -> bool;
+    /// Check 'contains' for a batch of points, which roads may answer faster than one at a time
+    fn contains_points(&self, points: &[Vec2]) -> Vec<bool> {
+        points.iter().map(|&point| self.contains(point)).collect()
+    }
+    /// Get the (min, max) corners of an axis-aligned box containing the whole road
+    fn bounds(&self) -> (Vec2, Vec2);
     fn read_lidar(&self, state: &CarState, lidar: &LidarArray) -> Vec<f32> {
-        lidar.get_angles()
-            .iter()
-            .map(|&angle| {
-                let direction = state.unit_forward.rotate(angle);
-                let intersection = self.ray_collision(state.position, direction);
-                // Get distance = projection along 'direction'
-                direction.dot(intersection-state.position)
-            })
-            .collect()
+        let mut readings = vec![0.0; lidar.n_angles()];
+        self.read_lidar_into(state, lidar, None, &mut readings);
+        readings
     }
-}
 
+    /// Write the LiDAR readings into 'out', which holds one value per angle, without allocating.
+    /// Roads may reuse the projection of the car position onto the centerline, if it is known.
+    fn read_lidar_into(&self, state: &CarState, lidar: &LidarArray, _projection: Option<&TrackProjection>, out: &mut [f32]) {
+        for (reading, &angle) in out.iter_mut().zip(lidar.get_angles()) {
+            let direction = state.unit_forward.rotate(angle);
+            let intersection = self.ray_collision(state.position, direction);
+            // Get distance = projection along 'direction'
+            *reading = direction.dot(intersection-state.position);
+        }
+    }
+
+    /// The total length of the (closed) centerline of the road
+    fn track_length(&self) -> f32;
+
+    /// Project a point onto the centerline of the road
+    fn project(&self, point: Vec2) -> TrackProjection;
+
+    /// Project a point onto the centerline, given the progress of a nearby point, e.g. the
+    /// position of the car at the previous time step. Roads may use the hint to search locally.
+    fn project_near(&self, point: Vec2, _progress_hint: f32) -> TrackProjection {
+        self.project(point)
+    }
+
+    /// Get the distance travelled along the centerline to the point closest to 'point'
+    fn progress(&self, point: Vec2) -> f32 {
+        self.project(point).progress
+    }
+
+    /// Get the signed distance travelled along the centerline going from progress 'from' to
+    /// progress 'to'. Roads are closed loops by default, so this takes the shortest way around.
+    fn progress_delta(&self, from: f32, to: f32) -> f32 {
+        let total_length = self.track_length();
+        (to - from + 1.5*total_length).rem_euclid(total_length) - 0.5*total_length
+    }
+
+    /// Get the (position, unit_forward) pose on the centerline at a distance 'progress' along it
+    fn pose_at(&self, progress: f32) -> (Vec2, Vec2);
+
+    /// Move any dynamic obstacles back to their initial positions relative to a car starting at
+    /// 'start_progress'
+    fn reset_obstacles(&mut self, _start_progress: f32) {}
+
+    /// Advance any dynamic obstacles by a time step 'dt'
+    fn advance_obstacles(&mut self, _dt: f32) {}
+
+    /// Get the state of any dynamic obstacles, e.g. for a snapshot of the simulator
+    fn obstacle_state(&self) -> Vec<f32> {
+        Vec::new()
+    }
+
+    /// Restore the dynamic obstacles to a state given by 'obstacle_state'
+    fn set_obstacle_state(&mut self, _state: &[f32]) {}
+}
@@ -0,0 +1,159 @@
+use math_utils::{Vec2, Aabb, polyline::{Polyline, PolylineProjection}};
+
+use crate::physics::{CarState, CarConfig};
+use super::traits::{Road, TrackProjection};
+use super::spline_map::SplineMap;
+
+
+/// A road defined by two closed polyline walls, e.g. imported from real-world boundary data.
+/// The driveable region is the area between the walls.
+#[derive(Clone)]
+pub struct WallMap {
+    pub inner: Polyline,
+    pub outer: Polyline,
+    /// Closed polyline running between the walls, used for measuring progress
+    pub centerline: Polyline,
+}
+
+
+impl WallMap {
+    pub fn new(inner: Vec<Vec2>, outer: Vec<Vec2>) -> Self {
+        assert!(inner.len() >= 3 && outer.len() >= 3, "Tried to construct WallMap with walls of fewer than three points.");
+        let (inner, outer) = (Polyline::new_closed(inner), Polyline::new_closed(outer));
+
+        // Place the centerline halfway between each outer point and the inner wall
+        let centerline = Polyline::new_closed(outer.points().iter()
+            .map(|&point| (point + inner.closest_point(point).point) * 0.5)
+            .collect());
+
+        Self { inner, outer, centerline }
+    }
+
+    /// Build a wall map following the edges of a spline road, approximating each edge by a
+    /// polyline within 'tolerance' of the edge
+    pub fn from_spline_map(road: &SplineMap, tolerance: f32) -> Self {
+        let left = road.spline.offset_with_tolerance(0.5*road.width, tolerance);
+        let right = road.spline.offset_with_tolerance(-0.5*road.width, tolerance);
+        Self::new(left, right)
+    }
+
+    /// Checks whether a point is between the walls, using the even-odd rule
+    pub fn point_inside(&self, point: Vec2) -> bool {
+        self.inner.contains(point) != self.outer.contains(point)
+    }
+}
+
+
+impl Road for WallMap {
+    fn is_crashed(&self, state: &CarState, config: &CarConfig) -> bool {
+        !state.corners(config).into_iter().all(|corner| self.point_inside(corner))
+            || state.sides(config).into_iter().any(|(start, end)| {
+                self.inner.intersects_segment(start, end) || self.outer.intersects_segment(start, end)
+            })
+    }
+
+    fn path_leaves_road(&self, from: Vec2, to: Vec2) -> bool {
+        self.inner.intersects_segment(from, to) || self.outer.intersects_segment(from, to)
+    }
+
+    /// Takes in a point and (non-normalized) direction defining a ray,
+    /// and finds the first intersection with a wall.
+    fn ray_collision(&self, point: Vec2, direction: Vec2) -> Vec2 {
+        if !self.point_inside(point) {
+            return point;
+        }
+        let t = [&self.inner, &self.outer].into_iter()
+            .filter_map(|wall| wall.ray_intersection(point, direction))
+            .fold(f32::INFINITY, f32::min);
+        point + direction.normalized()*t
+    }
+
+    fn contains(&self, point: Vec2) -> bool {
+        self.point_inside(point)
+    }
+
+    fn bounds(&self) -> (Vec2, Vec2) {
+        let Aabb { min, max } = self.inner.aabb().union(&self.outer.aabb());
+        (min, max)
+    }
+
+    fn track_length(&self) -> f32 {
+        self.centerline.length()
+    }
+
+    fn project(&self, point: Vec2) -> TrackProjection {
+        let PolylineProjection { arc_length, distance_sq, .. } = self.centerline.closest_point(point);
+        TrackProjection { progress: arc_length, distance_sq }
+    }
+
+    fn pose_at(&self, progress: f32) -> (Vec2, Vec2) {
+        self.centerline.point_at(progress)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Square annulus between |x|,|y| = 10 and |x|,|y| = 20
+    fn setup_map() -> WallMap {
+        let square = |size: f32| vec![Vec2(size, -size), Vec2(size, size), Vec2(-size, size), Vec2(-size, -size)];
+        WallMap::new(square(10.0), square(20.0))
+    }
+
+    #[test]
+    fn test_inside() {
+        let map = setup_map();
+        assert!(map.point_inside(Vec2(15.0, 0.0)));
+        assert!(map.point_inside(Vec2(-12.0, 18.0)));
+        assert!(!map.point_inside(Vec2(0.0, 0.0)));
+        assert!(!map.point_inside(Vec2(25.0, 0.0)));
+    }
+
+    #[test]
+    fn test_ray_collision() {
+        let map = setup_map();
+        assert_eq!(map.ray_collision(Vec2(15.0, 0.0), Vec2(0.0, 1.0)), Vec2(15.0, 20.0));
+        assert_eq!(map.ray_collision(Vec2(15.0, 0.0), Vec2(-2.0, 0.0)), Vec2(10.0, 0.0));
+        assert_eq!(map.ray_collision(Vec2(15.0, 0.0), Vec2(1.0, 0.0)), Vec2(20.0, 0.0));
+    }
+
+    #[test]
+    fn test_crash() {
+        let map = setup_map();
+        let config = CarConfig::default();
+        let state = CarState { position: Vec2(15.0, 0.0), unit_forward: Vec2(0.0, 1.0), ..CarState::default() };
+        assert!(!map.is_crashed(&state, &config));
+        let state = CarState { position: Vec2(17.0, 0.0), unit_forward: Vec2(1.0, 0.0), ..CarState::default() };
+        assert!(map.is_crashed(&state, &config));
+
+        // Along the wall, the side of the car crashes although its centerline is inside
+        let state = CarState { position: Vec2(19.5, 0.0), unit_forward: Vec2(0.0, 1.0), ..CarState::default() };
+        assert!(map.is_crashed(&state, &config));
+        assert!(!map.is_crashed(&state, &CarConfig { width: 0.0, ..config }));
+    }
+
+    #[test]
+    fn test_path_leaves_road() {
+        let map = setup_map();
+        assert!(map.path_leaves_road(Vec2(15.0, 0.0), Vec2(-15.0, 0.0)));
+        assert!(!map.path_leaves_road(Vec2(15.0, 0.0), Vec2(15.0, 15.0)));
+    }
+
+    #[test]
+    fn test_track_coordinates() {
+        let map = setup_map();
+        assert_eq!(map.centerline.points()[0], Vec2(15.0, -15.0));
+        assert_eq!(map.track_length(), 120.0);
+        assert_eq!(map.bounds(), (Vec2(-20.0, -20.0), Vec2(20.0, 20.0)));
+
+        let TrackProjection { progress, distance_sq } = map.project(Vec2(16.0, 0.0));
+        assert_eq!(progress, 15.0);
+        assert_eq!(distance_sq, 1.0);
+
+        let (position, unit_forward) = map.pose_at(135.0);
+        assert_eq!(position, Vec2(15.0, 0.0));
+        assert_eq!(unit_forward, Vec2(0.0, 1.0));
+    }
+}
@@ -0,0 +1,170 @@
+use rand::{Rng, SeedableRng};
+
+use crate::gym::{InvalidActionError, SimConfig, Simulator, StartDistribution, TransitionObservation};
+use crate::map::{Obstacle, ObstacleShape, Road, SplineMap};
+
+
+/// Several cars racing on the same track, acting simultaneously. Each car runs in a simulator of
+/// its own, on a copy of the road where the other cars are placed as obstacles, so that its
+/// LiDAR sees them and driving into one crashes it. A crashed car leaves the race until the next
+/// reset.
+pub struct MultiCarSimulator {
+    sims: Vec<Simulator<SplineMap>>,
+    /// Whether each car is still racing
+    active: Vec<bool>,
+    /// The number of static obstacles of the road, after which the other cars are placed
+    n_static: usize,
+    /// The distance along the track between consecutive cars at the start
+    pub grid_spacing: f32,
+    /// Source of the start points and of the seeds of the simulators
+    rng: rand_pcg::Pcg64,
+}
+
+
+impl MultiCarSimulator {
+    pub fn new(config: SimConfig, road: SplineMap, n_cars: usize, seed: Option<u64>) -> Self {
+        assert!(n_cars > 0, "A race needs at least one car");
+        let mut rng = match seed {
+            Some(seed) => rand_pcg::Pcg64::seed_from_u64(seed),
+            None => rand_pcg::Pcg64::from_rng(&mut rand::rng()),
+        };
+        let n_static = road.obstacles.len();
+        let sims = (0 .. n_cars)
+            .map(|_| Simulator::new(config.clone(), road.clone(), Some(rng.random())))
+            .collect();
+        let mut this = Self { sims, active: vec![true; n_cars], n_static, grid_spacing: 8.0, rng };
+        this.reset(None);
+        this
+    }
+
+    pub fn n_cars(&self) -> usize {
+        self.sims.len()
+    }
+
+    /// Get the simulator of car 'i'
+    pub fn sim(&self, i: usize) -> &Simulator<SplineMap> {
+        &self.sims[i]
+    }
+
+    /// Get whether car 'i' is still racing
+    pub fn is_active(&self, i: usize) -> bool {
+        self.active[i]
+    }
+
+    /// Start a new race, with the cars in single file behind a leader placed uniformly along the
+    /// track. Given a seed, all simulators are reseeded, which fixes this and all following races.
+    pub fn reset(&mut self, seed: Option<u64>) {
+        if let Some(seed) = seed {
+            self.rng = rand_pcg::Pcg64::seed_from_u64(seed);
+        }
+        let arc = self.sims[0].road.track_length() * self.rng.random::<f32>();
+        for (i, sim) in self.sims.iter_mut().enumerate() {
+            let sim_seed = seed.map(|_| self.rng.random());
+            // Start on the grid for this reset only, keeping the start distribution of the config
+            let grid = StartDistribution::Fixed { arc: arc - i as f32 * self.grid_spacing };
+            let start = std::mem::replace(&mut sim.config.start, grid);
+            sim.reset(sim_seed);
+            sim.config.start = start;
+        }
+        self.active.fill(true);
+        self.place_cars();
+    }
+
+    /// Step all cars still racing at once, with the integer value of an action for every car in
+    /// the action mode of the config. The actions of cars out of the race are ignored, and they
+    /// get no transition. No car moves if any action is invalid.
+    pub fn step(&mut self, actions: &[u8]) -> Result<Vec<Option<TransitionObservation>>, InvalidActionError> {
+        assert_eq!(actions.len(), self.n_cars(), "Expected one action per car");
+        for (sim, &action) in self.sims.iter().zip(actions) {
+            sim.config.action_mode.to_steer_throttle(action)?;
+        }
+
+        let transitions = self.sims.iter_mut()
+            .zip(&mut self.active)
+            .zip(actions)
+            .map(|((sim, active), &action)| {
+                if !*active {
+                    return None;
+                }
+                let transition = sim.step_index(action).expect("action to be validated");
+                *active = !transition.done;
+                Some(transition)
+            })
+            .collect();
+        self.place_cars();
+        Ok(transitions)
+    }
+
+    /// Get the observation of car 'i', see 'Simulator::observe_into'
+    pub fn observe_into(&self, i: usize, out: &mut [f32]) {
+        self.sims[i].observe_into(out);
+    }
+
+    /// Place the cars still racing as obstacles on the roads of the others
+    fn place_cars(&mut self) {
+        let cars: Vec<Option<Obstacle>> = self.sims.iter()
+            .zip(&self.active)
+            .map(|(sim, &active)| active.then(|| {
+                let state = &sim.state;
+                let car = &sim.config.car;
+                let center = state.position + state.unit_forward*(0.5*car.length - car.back_axle);
+                Obstacle::new(ObstacleShape::Rectangle { length: car.length, width: car.width }, center, state.unit_forward)
+            }))
+            .collect();
+
+        for (i, sim) in self.sims.iter_mut().enumerate() {
+            let obstacles = &mut sim.road.obstacles;
+            obstacles.truncate(self.n_static);
+            obstacles.extend(cars.iter().enumerate().filter(|&(j, _)| j != i).filter_map(|(_, car)| car.clone()));
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gym::Action;
+    use crate::map::make_oval;
+
+    #[test]
+    fn test_multi_car() {
+        let mut race = MultiCarSimulator::new(SimConfig::default(), make_oval(), 3, Some(0));
+
+        // The cars start in single file, and each sees the others as obstacles
+        for i in 1 .. 3 {
+            let gap = race.sim(0).road.progress_delta(race.sim(i).progress(), race.sim(0).progress());
+            assert!((gap - i as f32 * race.grid_spacing).abs() < 0.1);
+        }
+        assert!((0 .. 3).all(|i| race.sim(i).road.obstacles.len() == 2));
+        assert!((0 .. 3).all(|i| race.sim(i).config.start == StartDistribution::default()));
+
+        // Invalid actions move no car
+        let position = race.sim(0).state.position;
+        assert!(race.step(&[0, 1, 9]).is_err());
+        assert_eq!(race.sim(0).state.position, position);
+
+        // The second car drives into the leader, which crashes it out of the race
+        let accelerate = Action::Accelerate as u8;
+        let brake = Action::Brake as u8;
+        let mut crashed = false;
+        for _ in 0 .. 50 {
+            let transitions = race.step(&[brake, accelerate, brake]).unwrap();
+            if transitions[1].as_ref().is_some_and(|transition| transition.done) {
+                crashed = true;
+                break;
+            }
+        }
+        assert!(crashed);
+        assert!(!race.is_active(1));
+        assert!(race.step(&[brake, accelerate, brake]).unwrap()[1].is_none());
+        assert_eq!(race.sim(0).road.obstacles.len(), 1);
+
+        // The same seed gives the same race
+        race.reset(Some(4));
+        let start = race.sim(2).state.clone();
+        race.reset(Some(4));
+        assert_eq!(race.sim(2).state, start);
+        assert!(race.is_active(1));
+    }
+}
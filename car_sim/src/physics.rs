@@ -1,40 +1,103 @@
+use serde::{Serialize, Deserialize};
+
 use math_utils::Vec2;
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CarConfig {
     pub length: f32,
+    /// The width of the footprint of the car, which crashes when any part of it leaves the road
+    pub width: f32,
     pub front_axle: f32,
     pub back_axle: f32,
     pub max_delta: f32,
     pub acceleration: f32,
     pub brake_acceleration: f32,
-    pub steer_speed: f32
+    pub steer_speed: f32,
+    pub shoulder_deceleration: f32,
+    /// Rate in radians per second at which the simulator moves the steering target of the gym
+    /// actions towards the one they ask for. Without a rate, the target jumps at once.
+    pub steer_target_rate: Option<f32>,
+    /// Budget of speed in m/s which the car can gain by accelerating over an episode. Once spent,
+    /// the car can no longer accelerate. Without a capacity, the budget is unlimited.
+    pub energy_capacity: Option<f32>,
+    /// Engine power per unit mass in W/kg, which limits the acceleration to 'engine_power/speed'
+    /// at high speed. Without a power, the acceleration is the same at every speed.
+    pub engine_power: Option<f32>,
+    /// Coefficient of the air drag, a deceleration of 'drag_coeff*speed^2'
+    pub drag_coeff: f32,
+    /// The largest combined braking and lateral acceleration that the tyres hold while braking.
+    /// Above it, the wheels lock. Without a grip, they never do.
+    pub brake_grip: Option<f32>,
+    /// The time in seconds that the wheels stay locked
+    pub lock_duration: f32,
+    /// The largest lateral acceleration while the wheels are locked, which widens the turns
+    pub locked_lateral_grip: f32,
+    /// Acceleration of the reverse gear, which braking engages once the car has stopped. Without
+    /// it, the car cannot drive backwards.
+    pub reverse_acceleration: Option<f32>,
+    /// The largest speed when driving backwards
+    pub max_reverse_speed: f32,
 }
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CarState {
     pub position: Vec2,
     pub unit_forward: Vec2,
     pub speed: f32,
     pub steer_delta: f32,
+    /// The energy left to accelerate with, or none if it is unlimited
+    #[serde(default)]
+    pub energy: Option<f32>,
+    /// The time left until locked wheels grip again, zero if they are not locked
+    #[serde(default)]
+    pub lock_time: f32,
 }
 
 
 impl Default for CarConfig {
     fn default() -> Self {
-        Self { length: 4.0, front_axle: 3.5, back_axle: 0.5, max_delta: 0.5, 
-            acceleration: 6.0, brake_acceleration: 8.0, steer_speed: 0.7 }
+        Self { length: 4.0, width: 1.6, front_axle: 3.5, back_axle: 0.5, max_delta: 0.5, 
+            acceleration: 6.0, brake_acceleration: 8.0, steer_speed: 0.7, shoulder_deceleration: 4.0,
+            steer_target_rate: None, energy_capacity: None, engine_power: None, drag_coeff: 0.0,
+            brake_grip: None, lock_duration: 0.5, locked_lateral_grip: 2.0,
+            reverse_acceleration: None, max_reverse_speed: 5.0 }
     }
 }
 
 impl Default for CarState {
     fn default() -> Self { 
-        CarState {position: Vec2(0.0, 0.0), speed: 8.0, unit_forward: Vec2(1.0, 0.0), steer_delta: 0.0 }
+        CarState {position: Vec2(0.0, 0.0), speed: 8.0, unit_forward: Vec2(1.0, 0.0), steer_delta: 0.0, energy: None, lock_time: 0.0 }
+    }
+}
+
+/// The accelerations that the tyres give the car over a step, along and across its heading
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TireUsage {
+    pub longitudinal_acc: f32,
+    /// Positive when turning to the left
+    pub lateral_acc: f32,
+}
+
+impl TireUsage {
+    /// Get the accelerations of a step from 'old' to 'new' of length 'dt'
+    pub fn between(old: &CarState, new: &CarState, dt: f32, config: &CarConfig) -> Self {
+        let longitudinal_acc = (new.speed - old.speed) / dt;
+        let lateral_acc = new.speed * new.speed * inv_turn_radius(config, new.steer_delta);
+        Self { longitudinal_acc, lateral_acc }
+    }
+
+    /// Get the fraction of the grip that the combined acceleration uses, see
+    /// 'CarConfig::brake_grip'. Above 1 when braking, the wheels lock. None if the tyres have no
+    /// grip limit.
+    pub fn grip_utilization(&self, config: &CarConfig) -> Option<f32> {
+        config.brake_grip.map(|grip| self.longitudinal_acc.hypot(self.lateral_acc) / grip)
     }
 }
 
+
 #[derive(Debug)]
 pub struct CarInput {
     pub forward_acc: f32,
@@ -49,6 +112,30 @@ impl Default for CarInput {
 }
 
 
+impl CarConfig {
+    /// Get the largest forward acceleration that the engine can give at 'speed'. Unlimited
+    /// without an engine power.
+    pub fn engine_acceleration(&self, speed: f32) -> f32 {
+        match self.engine_power {
+            Some(power) => (power / speed.abs()).min(self.acceleration),
+            None => f32::INFINITY,
+        }
+    }
+
+    /// Get the speed at which the drag balances full acceleration, if there is drag
+    pub fn top_speed(&self) -> Option<f32> {
+        if self.drag_coeff <= 0.0 {
+            return None;
+        }
+        // At the top speed v, P/v = c*v^2 above the speed where the power limit kicks in, and
+        // a = c*v^2 below it
+        let drag_limited = (self.acceleration / self.drag_coeff).sqrt();
+        let power_limited = self.engine_power.map_or(f32::INFINITY, |power| (power / self.drag_coeff).cbrt());
+        Some(drag_limited.min(power_limited))
+    }
+}
+
+
 /// Computes the reciprocal turn radius (positive when turning to the left) when having a wheel deflection
 /// 'delta'
 fn inv_turn_radius(config: &CarConfig, delta: f32) -> f32 {
@@ -59,6 +146,28 @@ fn inv_turn_radius(config: &CarConfig, delta: f32) -> f32 {
 
 
 impl CarState {
+    /// Get whether the wheels are locked from braking too hard
+    pub fn is_locked(&self) -> bool {
+        self.lock_time > 0.0
+    }
+
+    /// Get the corners of the footprint of the car, in the order back left, front left, front
+    /// right and back right
+    pub fn corners(&self, config: &CarConfig) -> [Vec2; 4] {
+        let back_center = self.position - self.unit_forward*config.back_axle;
+        let half_lateral = self.unit_forward.rotate90()*(0.5*config.width);
+        let forward = self.unit_forward*config.length;
+        let back_left = back_center + half_lateral;
+        let back_right = back_center - half_lateral;
+        [back_left, back_left + forward, back_right + forward, back_right]
+    }
+
+    /// Get the sides of the footprint of the car, as segments between consecutive corners
+    pub fn sides(&self, config: &CarConfig) -> [(Vec2, Vec2); 4] {
+        let corners = self.corners(config);
+        std::array::from_fn(|i| (corners[i], corners[(i + 1) % 4]))
+    }
+
     pub fn update(&self, input: &CarInput, dt: f32, config: &CarConfig) -> Self {
         // Update the steering wheel
         let steer_delta = self.steer_update(input.target_delta, dt, config);
@@ -66,24 +175,51 @@ impl CarState {
         // Current speed
         let speed = self.speed;
 
+        // Braking when stopped or driving backwards puts the car in reverse, if it has the gear
+        let reverse_acc = config.reverse_acceleration.filter(|_| input.braking && speed <= 0.0);
+
+        // Accelerate only as much as the engine and the energy left allow
+        let drive_acc = match reverse_acc {
+            Some(reverse_acc) => -reverse_acc,
+            None => input.forward_acc.min(config.engine_acceleration(speed)),
+        };
+        let drive_acc = match self.energy {
+            Some(energy) => drive_acc.signum() * drive_acc.abs().min(energy / dt),
+            None => drive_acc,
+        };
+        let drag_acc = -config.drag_coeff * speed * speed.abs();
+        let energy = self.energy.map(|energy| (energy - dt*drive_acc.abs()).max(0.0));
+
         // Get average speed over the time step
-        let dv = if input.braking { 
+        let dv = if input.braking && reverse_acc.is_none() {
             let brake_acc = -speed.signum() * config.brake_acceleration;
-            dt*(brake_acc + input.forward_acc)
+            dt*(brake_acc + drive_acc + drag_acc)
         } else {
-            dt*input.forward_acc
+            dt*(drive_acc + drag_acc)
         };
+        let min_speed = if reverse_acc.is_some() || speed < 0.0 { -config.max_reverse_speed } else { 0.0 };
         let avg_speed = {
             let avg_speed = speed + 0.5*dv;
-            if avg_speed * speed > 0.0 { avg_speed } else { 0.0 }
-        };
-        let new_speed = {
-            let new_speed = speed + dv;
-            if new_speed > 0.0 { new_speed } else {0.0}
+            if avg_speed * speed > 0.0 || reverse_acc.is_some() { avg_speed.max(min_speed) } else { 0.0 }
         };
+        let new_speed = (speed + dv).max(min_speed);
 
-        // Determine the turning circle
+        // Braking harder than the tyres hold, counting the lateral acceleration of the turn,
+        // locks the wheels
         let signed_inv_radius = inv_turn_radius(config, steer_delta);
+        let lateral_acc = speed * speed * signed_inv_radius.abs();
+        let locks = input.braking && config.brake_grip
+            .is_some_and(|grip| config.brake_acceleration.hypot(lateral_acc) > grip);
+        let lock_time = if locks { config.lock_duration } else { (self.lock_time - dt).max(0.0) };
+
+        // Determine the turning circle, which locked wheels can only follow up to their lateral
+        // grip
+        let signed_inv_radius = if self.is_locked() || locks {
+            let max_inv_radius = config.locked_lateral_grip / (avg_speed * avg_speed);
+            signed_inv_radius.signum() * signed_inv_radius.abs().min(max_inv_radius)
+        } else {
+            signed_inv_radius
+        };
         let arc = avg_speed * dt;
         let signed_radians_traversed = arc * signed_inv_radius;
         let phi = signed_radians_traversed.abs();  // positive angle
@@ -104,9 +240,12 @@ impl CarState {
                 //
                 // Lateral displacement (absolute value) is
                 // R - R*cos(r) = R*[1-cos(r)]
+                //
+                // The car moves backwards along the circle when reversing, and the center of the
+                // circle is to the left when turning left either way
                 let radius = 1.0 / signed_inv_radius.abs();
 
-                (radius * phi.sin(), radius * (1.0-phi.cos()))
+                (arc.signum() * radius * phi.sin(), signed_inv_radius.signum() * radius * (1.0-phi.cos()))
             } else {
                 // Forward displacement is 
                 // R*sin(phi) = R*sin(arc / R) = R * [arc/R - 1/6(arc/R)^3 + O(arc/R)^5]
@@ -135,7 +274,7 @@ impl CarState {
         // Rotate the velocity vector according to the swept arc
         let new_unit_forward = self.unit_forward.rotate(signed_radians_traversed);
 
-        Self { position: new_position, speed: new_speed, unit_forward: new_unit_forward, steer_delta }
+        Self { position: new_position, speed: new_speed, unit_forward: new_unit_forward, steer_delta, energy, lock_time }
     }
 
     fn steer_update(&self, target_delta: f32, dt: f32, config: &CarConfig) -> f32 {
@@ -161,7 +300,7 @@ mod tests {
     #[test]
     fn test_inertial() {
         let config = CarConfig { length: 1.0, back_axle: 0.0, front_axle: 1.0, ..CarConfig::default() };
-        let initial_state = CarState { position: Vec2(0.0, 0.0), speed: 1.0, unit_forward: Vec2(1.0, 0.0), steer_delta: 0.0 };
+        let initial_state = CarState { position: Vec2(0.0, 0.0), speed: 1.0, unit_forward: Vec2(1.0, 0.0), steer_delta: 0.0, energy: None, lock_time: 0.0 };
         let input = CarInput { forward_acc: 0.0, target_delta: 0.0, braking: false };
 
         let mut state = initial_state.clone();
@@ -175,7 +314,7 @@ mod tests {
     #[test]
     fn test_circle() {
         let config = CarConfig { length: 1.0, back_axle: 0.0, front_axle: 1.0, ..CarConfig::default() };
-        let initial_state = CarState { position: Vec2(0.0, 0.0), speed: 1.0, unit_forward: Vec2(1.0, 0.0), steer_delta: 45.0_f32.to_radians() };
+        let initial_state = CarState { position: Vec2(0.0, 0.0), speed: 1.0, unit_forward: Vec2(1.0, 0.0), steer_delta: 45.0_f32.to_radians(), energy: None, lock_time: 0.0 };
 
         // Deflect wheel 45 degrees
         // Turning radius is same as length = 1
@@ -215,5 +354,127 @@ mod tests {
         assert!((state.speed - 1.0).abs() < 0.001);
         assert!((state.position + Vec2(-0.5, 0.0)).norm() < 0.001);
     }
-}
 
+    #[test]
+    fn test_energy() {
+        let config = CarConfig::default();
+        let initial_state = CarState { speed: 0.0, energy: Some(3.0), ..CarState::default() };
+        let input = CarInput { forward_acc: 2.0, target_delta: 0.0, braking: false };
+
+        // The car gains speed until the energy is spent, after which it coasts
+        let mut state = initial_state.clone();
+        let dt = 1.0 / 64.0;
+        for _ in 1 ..= 128 {
+            state = state.update(&input, dt, &config);
+        }
+        assert!((state.speed - 3.0).abs() < 0.001);
+        assert!(state.energy.is_some_and(|energy| energy < 1e-4));
+
+        // Braking does not use energy
+        let brake = CarInput { forward_acc: 0.0, target_delta: 0.0, braking: true };
+        let braked = initial_state.update(&brake, dt, &config);
+        assert_eq!(braked.energy, Some(3.0));
+    }
+
+    #[test]
+    fn test_top_speed() {
+        let config = CarConfig { engine_power: Some(100.0), drag_coeff: 0.01, ..CarConfig::default() };
+        assert!((config.engine_acceleration(10.0) - config.acceleration).abs() < 1e-6);
+        assert!((config.engine_acceleration(100.0) - 1.0).abs() < 1e-6);
+        let top_speed = config.top_speed().unwrap();
+        assert!((top_speed - 10000.0_f32.cbrt()).abs() < 1e-3);
+
+        // Under full acceleration, the speed settles at the top speed
+        let input = CarInput { forward_acc: config.acceleration, target_delta: 0.0, braking: false };
+        let mut state = CarState::default();
+        let dt = 1.0 / 16.0;
+        for _ in 1 ..= 16*120 {
+            state = state.update(&input, dt, &config);
+        }
+        assert!((state.speed - top_speed).abs() < 0.01);
+
+        // Without drag, there is no top speed
+        assert!(CarConfig::default().top_speed().is_none());
+    }
+
+    #[test]
+    fn test_wheel_lock() {
+        let config = CarConfig { brake_grip: Some(10.0), ..CarConfig::default() };
+        let dt = 1.0 / 16.0;
+        let brake = |target_delta| CarInput { forward_acc: 0.0, target_delta, braking: true };
+
+        // Braking straight stays within the grip
+        let straight = CarState { speed: 20.0, ..CarState::default() };
+        assert!(!straight.update(&brake(0.0), dt, &config).is_locked());
+
+        // Braking in a turn locks the wheels, which then turn less
+        let turning = CarState { steer_delta: 0.2, ..straight.clone() };
+        let locked = turning.update(&brake(0.2), dt, &config);
+        assert!(locked.is_locked());
+        let gripping = turning.update(&brake(0.2), dt, &CarConfig::default());
+        assert!(locked.unit_forward.1 < gripping.unit_forward.1);
+
+        // The wheels grip again some time after letting go of the brake
+        let coast = CarInput { forward_acc: 0.0, target_delta: 0.2, braking: false };
+        let mut state = locked;
+        for _ in 0 .. 8 {
+            state = state.update(&coast, dt, &config);
+        }
+        assert!(!state.is_locked());
+    }
+
+    #[test]
+    fn test_reverse() {
+        let dt = 1.0 / 16.0;
+        let brake = CarInput { forward_acc: 0.0, target_delta: 0.0, braking: true };
+        let moving = CarState { speed: 2.0, ..CarState::default() };
+
+        // Without a reverse gear, braking stops the car
+        let mut state = moving.clone();
+        for _ in 0 .. 32 {
+            state = state.update(&brake, dt, &CarConfig::default());
+        }
+        assert_eq!(state.speed, 0.0);
+
+        // With one, the car stops and then backs up to the largest reverse speed
+        let config = CarConfig { reverse_acceleration: Some(4.0), ..CarConfig::default() };
+        let mut state = moving.clone();
+        state = state.update(&brake, dt, &config);
+        while state.speed > 0.0 {
+            state = state.update(&brake, dt, &config);
+        }
+        let stop = state.position;
+        for _ in 0 .. 64 {
+            state = state.update(&brake, dt, &config);
+        }
+        assert_eq!(state.speed, -config.max_reverse_speed);
+        assert!(state.position.0 < stop.0);
+        assert_eq!(state.unit_forward, Vec2(1.0, 0.0));
+
+        // Reversing with the wheels turned left turns the rear to the left, i.e. the nose right
+        let turning = CarState { steer_delta: 0.3, ..state.clone() };
+        let turned = turning.update(&CarInput { target_delta: 0.3, ..brake }, dt, &config);
+        assert!(turned.unit_forward.1 < 0.0);
+        assert!(turned.position.1 > turning.position.1);
+
+        // Accelerating drives forwards again
+        let accelerate = CarInput { forward_acc: config.acceleration, target_delta: 0.0, braking: false };
+        for _ in 0 .. 64 {
+            state = state.update(&accelerate, dt, &config);
+        }
+        assert!(state.speed > 0.0);
+    }
+
+    #[test]
+    fn test_tire_usage() {
+        let config = CarConfig { brake_grip: Some(10.0), ..CarConfig::default() };
+        let old = CarState { speed: 10.0, steer_delta: 0.1, ..CarState::default() };
+        let new = old.update(&CarInput { forward_acc: 0.0, target_delta: 0.1, braking: true }, 0.1, &config);
+        let usage = TireUsage::between(&old, &new, 0.1, &config);
+        assert!((usage.longitudinal_acc + config.brake_acceleration).abs() < 1e-3);
+        assert!((usage.lateral_acc - new.speed.powi(2) * 0.1_f32.tan() / config.length).abs() < 1e-3);
+        let utilization = usage.grip_utilization(&config).unwrap();
+        assert!((utilization - usage.longitudinal_acc.hypot(usage.lateral_acc) / 10.0).abs() < 1e-6);
+        assert!(usage.grip_utilization(&CarConfig::default()).is_none());
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use rand::{Rng, SeedableRng};
+use serde::{Serialize, Deserialize};
+
+use crate::gym::Action;
+
+
+/// Chooses an action from the observation vector of a simulator, see 'Simulator::observe_into'
+pub trait Policy {
+    fn act(&mut self, observation: &[f32]) -> Action;
+}
+
+
+/// Chooses uniformly random actions, e.g. to collect warm-up data or to benchmark the simulator
+#[derive(Debug, Clone)]
+pub struct RandomPolicy {
+    rng: rand_pcg::Pcg64,
+}
+
+
+impl RandomPolicy {
+    /// Create a policy drawing from a generator seeded with 'seed', or else seeded randomly
+    pub fn new(seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => rand_pcg::Pcg64::seed_from_u64(seed),
+            None => rand_pcg::Pcg64::from_rng(&mut rand::rng()),
+        };
+        Self { rng }
+    }
+}
+
+
+impl Policy for RandomPolicy {
+    fn act(&mut self, _observation: &[f32]) -> Action {
+        Action::ALL[self.rng.random_range(0 .. Action::ALL.len())]
+    }
+}
+
+
+/// Discretizes observation vectors for tabular methods. Each component of an observation is
+/// replaced by the index of its bin, which is the number of bin edges at or below its value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chunking {
+    /// Increasing bin edges for each component of the observation vector
+    pub edges: Vec<Vec<f32>>,
+}
+
+
+impl Chunking {
+    pub fn observation_dim(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn chunk(&self, observation: &[f32]) -> Vec<u32> {
+        assert_eq!(observation.len(), self.observation_dim(), "Observation has the wrong length for the chunking");
+        observation.iter().zip(&self.edges)
+            .map(|(&value, edges)| edges.partition_point(|&edge| edge <= value) as u32)
+            .collect()
+    }
+}
+
+
+/// The Q values of the actions in a chunked state, as stored in a Q-table file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct QTableEntry {
+    state: Vec<u32>,
+    /// One value per action, in the order of 'Action::ALL'
+    q_values: [f32; Action::ALL.len()],
+}
+
+#[derive(Serialize, Deserialize)]
+struct QTableFile {
+    chunking: Chunking,
+    entries: Vec<QTableEntry>,
+}
+
+
+/// The greedy policy of a Q-table over chunked observations. States missing from the table have
+/// all Q values zero, and ties go to the first action.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "QTableFile", into = "QTableFile")]
+pub struct QTablePolicy {
+    pub chunking: Chunking,
+    q_values: HashMap<Vec<u32>, [f32; Action::ALL.len()]>,
+}
+
+
+impl From<QTableFile> for QTablePolicy {
+    fn from(QTableFile { chunking, entries }: QTableFile) -> Self {
+        let q_values = entries.into_iter().map(|QTableEntry { state, q_values }| (state, q_values)).collect();
+        Self { chunking, q_values }
+    }
+}
+
+impl From<QTablePolicy> for QTableFile {
+    fn from(QTablePolicy { chunking, q_values }: QTablePolicy) -> Self {
+        let entries = q_values.into_iter().map(|(state, q_values)| QTableEntry { state, q_values }).collect();
+        Self { chunking, entries }
+    }
+}
+
+
+impl QTablePolicy {
+    pub fn new(chunking: Chunking) -> Self {
+        Self { chunking, q_values: HashMap::new() }
+    }
+
+    /// Load a Q-table from a JSON file with the chunking and a list of entries of the form
+    /// '{"state": [...], "q_values": [...]}'
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        Ok(serde_json::to_writer(BufWriter::new(File::create(path)?), self)?)
+    }
+
+    pub fn get(&self, state: &[u32], action: Action) -> f32 {
+        self.q_values.get(state).map_or(0.0, |q_values| q_values[action as usize])
+    }
+
+    pub fn set(&mut self, state: Vec<u32>, action: Action, value: f32) {
+        self.q_values.entry(state).or_insert([0.0; Action::ALL.len()])[action as usize] = value;
+    }
+
+    pub fn greedy_action(&self, observation: &[f32]) -> Action {
+        let state = self.chunking.chunk(observation);
+        Action::ALL.into_iter()
+            .reduce(|best, action| if self.get(&state, action) > self.get(&state, best) { action } else { best })
+            .expect("at least one action")
+    }
+}
+
+
+impl Policy for QTablePolicy {
+    fn act(&mut self, observation: &[f32]) -> Action {
+        self.greedy_action(observation)
+    }
+}
+
+
+impl<P: Policy + ?Sized> Policy for Box<P> {
+    fn act(&mut self, observation: &[f32]) -> Action {
+        (**self).act(observation)
+    }
+}
+
+
+/// The greedy policy of a neural network exported to ONNX, e.g. from a torch or SB3 policy. The
+/// network maps a (1, observation_dim) float32 observation to (1, 5) scores of the actions in
+/// the order of 'Action::ALL', e.g. Q values or logits.
+#[cfg(feature = "onnx")]
+pub struct OnnxPolicy {
+    model: std::sync::Arc<tract_onnx::prelude::TypedRunnableModel>,
+    observation_dim: usize,
+}
+
+
+#[cfg(feature = "onnx")]
+impl OnnxPolicy {
+    pub fn load<P: AsRef<Path>>(path: P, observation_dim: usize) -> tract_onnx::prelude::TractResult<Self> {
+        use tract_onnx::prelude::*;
+        let model = tract_onnx::onnx()
+            .model_for_path(path)?
+            .with_input_fact(0, f32::fact([1, observation_dim]).into())?
+            .into_optimized()?
+            .into_runnable()?;
+        Ok(Self { model, observation_dim })
+    }
+
+    /// Get the scores of the actions for an observation
+    pub fn scores(&self, observation: &[f32]) -> tract_onnx::prelude::TractResult<Vec<f32>> {
+        use tract_onnx::prelude::*;
+        let input = Tensor::from_shape(&[1, self.observation_dim], observation)?;
+        let outputs = self.model.run(tvec!(input.into()))?;
+        Ok(outputs[0].to_plain_array_view::<f32>()?.iter().copied().collect())
+    }
+}
+
+
+#[cfg(feature = "onnx")]
+impl Policy for OnnxPolicy {
+    fn act(&mut self, observation: &[f32]) -> Action {
+        let scores = self.scores(observation).expect("the policy network to run on the observation");
+        assert_eq!(scores.len(), Action::ALL.len(), "Policy network must output one score per action");
+        Action::ALL.into_iter()
+            .zip(scores)
+            .reduce(|best, candidate| if candidate.1 > best.1 { candidate } else { best })
+            .expect("at least one action")
+            .0
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_q_table() {
+        let chunking = Chunking { edges: vec![vec![1.0, 2.0], vec![0.0]] };
+        assert_eq!(chunking.chunk(&[0.5, -1.0]), vec![0, 0]);
+        assert_eq!(chunking.chunk(&[2.0, 3.0]), vec![2, 1]);
+
+        let mut policy = QTablePolicy::new(chunking);
+        // Unknown states tie at zero and take the first action
+        assert_eq!(policy.act(&[1.5, 1.0]), Action::Left);
+        policy.set(vec![1, 1], Action::Brake, 2.0);
+        policy.set(vec![1, 1], Action::Coast, 1.0);
+        assert_eq!(policy.act(&[1.5, 1.0]), Action::Brake);
+
+        let path = std::env::temp_dir().join("car_sim_test_q_table.json");
+        policy.save(&path).unwrap();
+        let loaded = QTablePolicy::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.chunking, policy.chunking);
+        assert_eq!(loaded.get(&[1, 1], Action::Coast), 1.0);
+        assert_eq!(loaded.greedy_action(&[1.5, 1.0]), Action::Brake);
+    }
+}
@@ -0,0 +1,80 @@
+use rand::{Rng, SeedableRng};
+
+use crate::gym::Simulator;
+use crate::map::{self, SplineMap};
+
+
+/// Where the random track of each episode comes from, see 'Simulator::reset_random_track'
+#[derive(Clone)]
+pub enum RandomTracks {
+    /// Generate a new track for every episode
+    Generate,
+    /// Draw from a fixed set of tracks, e.g. to hold out other tracks for evaluation
+    Pool(Vec<SplineMap>),
+}
+
+
+impl RandomTracks {
+    /// Build a pool of 'size' random tracks, which is the same for the same 'seed'
+    pub fn pool(size: usize, seed: u64) -> Self {
+        assert!(size > 0, "A pool of random tracks needs at least one track");
+        let mut rng = rand_pcg::Pcg64::seed_from_u64(seed);
+        Self::Pool((0 .. size).map(|_| map::make_random_track(&mut rng)).collect())
+    }
+
+    pub fn sample<G: Rng + ?Sized>(&self, rng: &mut G) -> SplineMap {
+        match self {
+            RandomTracks::Generate => map::make_random_track(rng),
+            RandomTracks::Pool(tracks) => tracks[rng.random_range(0 .. tracks.len())].clone(),
+        }
+    }
+}
+
+
+impl Simulator<SplineMap> {
+    /// Start a new episode on a random track from 'tracks', keeping the shoulder width and ray
+    /// casting of the current road. The track is drawn from the random number generator of the
+    /// simulator, so that a seed fixes the tracks of all following episodes as well.
+    pub fn reset_random_track(&mut self, tracks: &RandomTracks, seed: Option<u64>) {
+        if let Some(seed) = seed {
+            self.reseed(seed);
+        }
+        let mut road = tracks.sample(self.rng());
+        road.set_shoulder_width(self.road.shoulder_width());
+        road.set_ray_casting(self.road.ray_casting());
+        self.road = road;
+        self.reset(None);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gym::SimConfig;
+    use crate::map::Road;
+
+    #[test]
+    fn test_random_tracks() {
+        let mut sim = Simulator::new(SimConfig::default(), map::make_oval(), None);
+        let length = |sim: &Simulator<SplineMap>| sim.road.track_length();
+
+        // A seed fixes the tracks of the following episodes
+        sim.reset_random_track(&RandomTracks::Generate, Some(7));
+        let lengths: Vec<f32> = (0 .. 3).map(|_| { sim.reset_random_track(&RandomTracks::Generate, None); length(&sim) }).collect();
+        sim.reset_random_track(&RandomTracks::Generate, Some(7));
+        let repeated: Vec<f32> = (0 .. 3).map(|_| { sim.reset_random_track(&RandomTracks::Generate, None); length(&sim) }).collect();
+        assert_eq!(lengths, repeated);
+        assert_ne!(lengths[0], lengths[1]);
+
+        // Pooled tracks are drawn from the pool only
+        let pool = RandomTracks::pool(2, 0);
+        let RandomTracks::Pool(tracks) = &pool else { unreachable!() };
+        let pool_lengths: Vec<f32> = tracks.iter().map(|track| track.track_length()).collect();
+        for _ in 0 .. 5 {
+            sim.reset_random_track(&pool, None);
+            assert!(pool_lengths.contains(&length(&sim)));
+            assert!(sim.road.contains(sim.state.position));
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use math_utils::Vec2;
+
+use crate::physics::CarState;
+use crate::gym::SimState;
+
+
+/// What happened in a single step of a recorded episode
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepRecord {
+    /// The action taken, as its integer value
+    pub action: u8,
+    pub reward: f32,
+    pub done: bool,
+    /// The car state after the step
+    pub state: CarState,
+    /// The observation vector after the step, see 'Simulator::observe_into'
+    pub observation: Vec<f32>,
+}
+
+
+/// A recorded episode, starting from a snapshot of the simulator from which it can be replayed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EpisodeRecord {
+    pub start: SimState,
+    pub steps: Vec<StepRecord>,
+}
+
+
+/// A sequence of recorded episodes, stored as compact JSON
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub episodes: Vec<EpisodeRecord>,
+}
+
+
+impl Recording {
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        Ok(serde_json::to_writer(BufWriter::new(File::create(path)?), self)?)
+    }
+}
+
+
+/// The path of the car through the current episode, for plotting and analysis. Entry 'i' of each
+/// field belongs to step 'i', with positions and speeds taken after the step.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trajectory {
+    pub positions: Vec<Vec2>,
+    pub speeds: Vec<f32>,
+    /// The actions taken, as their integer values
+    pub actions: Vec<u8>,
+    pub rewards: Vec<f32>,
+}
+
+
+impl Trajectory {
+    pub fn push(&mut self, state: &CarState, action: u8, reward: f32) {
+        self.positions.push(state.position);
+        self.speeds.push(state.speed);
+        self.actions.push(action);
+        self.rewards.push(reward);
+    }
+
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.speeds.clear();
+        self.actions.clear();
+        self.rewards.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+
+/// (observation, action) pairs for imitation learning and offline RL, e.g. from a human driving
+/// in the game, stored as compact JSON with one list per field. Entry 'i' of each field belongs to
+/// step 'i', with the observation taken before the step, i.e. the one the action was chosen from.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Demonstrations {
+    pub observations: Vec<Vec<f32>>,
+    /// The actions taken, as their integer values
+    pub actions: Vec<u8>,
+    pub rewards: Vec<f32>,
+    /// Whether the step ended the episode
+    pub dones: Vec<bool>,
+}
+
+
+impl Demonstrations {
+    pub fn push(&mut self, observation: Vec<f32>, action: u8, reward: f32, done: bool) {
+        self.observations.push(observation);
+        self.actions.push(action);
+        self.rewards.push(reward);
+        self.dones.push(done);
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        Ok(serde_json::to_writer(BufWriter::new(File::create(path)?), self)?)
+    }
+}
+
+
+/// Collects the episodes of a simulator into a 'Recording', see 'Simulator::start_recording'
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    recording: Recording,
+}
+
+
+impl Recorder {
+    /// Start a new episode from a snapshot of the simulator. A current episode without steps, e.g.
+    /// from a reset right after starting to record, is replaced.
+    pub fn begin_episode(&mut self, start: SimState) {
+        if self.recording.episodes.last().is_some_and(|episode| episode.steps.is_empty()) {
+            self.recording.episodes.pop();
+        }
+        self.recording.episodes.push(EpisodeRecord { start, steps: Vec::new() });
+    }
+
+    /// Record a step of the current episode. Panics if no episode has begun.
+    pub fn record_step(&mut self, step: StepRecord) {
+        self.recording.episodes.last_mut()
+            .expect("an episode to have begun before recording steps")
+            .steps.push(step);
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn into_recording(self) -> Recording {
+        self.recording
+    }
+}
@@ -0,0 +1,153 @@
+use math_utils::{Vec2, Aabb};
+
+use crate::gym::Simulator;
+use crate::map::Road;
+
+
+pub const ROAD_COLOR: [u8; 3] = [96, 96, 96];
+/// Color of points off the road, including static obstacles
+pub const OFF_ROAD_COLOR: [u8; 3] = [40, 110, 40];
+pub const CAR_COLOR: [u8; 3] = [220, 40, 40];
+pub const LIDAR_COLOR: [u8; 3] = [250, 210, 40];
+
+
+/// An RGB image, stored row by row starting at the top row, with three bytes per pixel
+pub struct RgbImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+
+impl RgbImage {
+    /// Set a pixel, ignoring pixels outside the image
+    fn set(&mut self, column: isize, row: isize, color: [u8; 3]) {
+        if (0 .. self.width as isize).contains(&column) && (0 .. self.height as isize).contains(&row) {
+            let idx = 3*(row as usize*self.width + column as usize);
+            self.pixels[idx .. idx+3].copy_from_slice(&color);
+        }
+    }
+
+    pub fn get(&self, column: usize, row: usize) -> [u8; 3] {
+        let idx = 3*(row*self.width + column);
+        [self.pixels[idx], self.pixels[idx+1], self.pixels[idx+2]]
+    }
+}
+
+
+/// Maps between world coordinates and pixels, with the same scale along both axes
+struct View {
+    /// World coordinates of the bottom left corner of the image
+    origin: Vec2,
+    px_per_m: f32,
+    height: usize,
+}
+
+
+impl View {
+    /// Fit 'region' into an image of the given size, centered along the axis with room to spare
+    fn fit(region: &Aabb, width: usize, height: usize) -> Self {
+        let size = region.size();
+        let px_per_m = (width as f32 / size.0).min(height as f32 / size.1);
+        let origin = region.center() - Vec2(width as f32, height as f32) / (2.0*px_per_m);
+        Self { origin, px_per_m, height }
+    }
+
+    /// Get the continuous (column, row) image coordinates of a world point
+    fn to_pixel(&self, point: Vec2) -> Vec2 {
+        let Vec2(x, y) = (point - self.origin)*self.px_per_m;
+        Vec2(x, self.height as f32 - y)
+    }
+
+    /// Get the world coordinates of the center of a pixel
+    fn to_world(&self, column: usize, row: usize) -> Vec2 {
+        self.origin + Vec2(column as f32 + 0.5, (self.height - row) as f32 - 0.5) / self.px_per_m
+    }
+}
+
+
+fn draw_line(image: &mut RgbImage, start: Vec2, end: Vec2, color: [u8; 3]) {
+    let delta = end - start;
+    let n_steps = delta.0.abs().max(delta.1.abs()).ceil().max(1.0) as usize;
+    for step in 0 ..= n_steps {
+        let Vec2(column, row) = start + delta*(step as f32 / n_steps as f32);
+        image.set(column.floor() as isize, row.floor() as isize, color);
+    }
+}
+
+
+/// Render the road, the LiDAR rays and the car of a simulator to a 'width' x 'height' image in
+/// software, without a window. The whole road is shown, scaled to fit the image.
+pub fn render_rgb<R: Road>(sim: &Simulator<R>, width: usize, height: usize) -> RgbImage {
+    assert!(width > 0 && height > 0, "Image size must be positive, got {}x{}", width, height);
+    let (min, max) = sim.road.bounds();
+    let view = View::fit(&Aabb::new(min, max), width, height);
+
+    let points: Vec<Vec2> = (0 .. width*height)
+        .map(|idx| view.to_world(idx % width, idx / width))
+        .collect();
+    let pixels = sim.road.contains_points(&points)
+        .into_iter()
+        .flat_map(|inside| if inside { ROAD_COLOR } else { OFF_ROAD_COLOR })
+        .collect();
+    let mut image = RgbImage { width, height, pixels };
+
+    let state = &sim.state;
+    let readings = sim.observe_current().lidar_readings;
+    let lidar_center = view.to_pixel(state.position);
+    for (&angle, &reading) in sim.config.lidar.get_angles().iter().zip(&readings) {
+        let hit = state.position + state.unit_forward.rotate(angle)*reading.abs();
+        draw_line(&mut image, lidar_center, view.to_pixel(hit), LIDAR_COLOR);
+    }
+
+    // Fill the pixels whose centers lie in the car, and at least the one under its position
+    let car = &sim.config.car;
+    let back_center = state.position - state.unit_forward*car.back_axle;
+    let corners = state.corners(car);
+    let pixel_box = Aabb::from_points(corners.map(|corner| view.to_pixel(corner))).expect("four corners");
+    let columns = pixel_box.min.0.floor().max(0.0) as usize .. (pixel_box.max.0.ceil().max(0.0) as usize).min(width);
+    let rows = pixel_box.min.1.floor().max(0.0) as usize .. (pixel_box.max.1.ceil().max(0.0) as usize).min(height);
+    for row in rows {
+        for column in columns.clone() {
+            let delta = view.to_world(column, row) - back_center;
+            let along = delta.dot(state.unit_forward);
+            let across = delta.dot(state.unit_forward.rotate90());
+            if (0.0 ..= car.length).contains(&along) && across.abs() <= 0.5*car.width {
+                image.set(column as isize, row as isize, CAR_COLOR);
+            }
+        }
+    }
+    image.set(lidar_center.0.floor() as isize, lidar_center.1.floor() as isize, CAR_COLOR);
+
+    image
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gym::SimConfig;
+    use crate::map::make_oval;
+
+    #[test]
+    fn test_render() {
+        let mut sim = Simulator::new(SimConfig::default(), make_oval(), Some(0));
+        sim.reset(None);
+        let image = render_rgb(&sim, 160, 90);
+        assert_eq!((image.width, image.height), (160, 90));
+        assert_eq!(image.pixels.len(), 160*90*3);
+
+        let count = |color: [u8; 3]| (0 .. 90).flat_map(|row| (0 .. 160).map(move |column| (column, row)))
+            .filter(|&(column, row)| image.get(column, row) == color)
+            .count();
+        assert!(count(ROAD_COLOR) > 0);
+        assert!(count(OFF_ROAD_COLOR) > 0);
+        assert!(count(CAR_COLOR) > 0);
+        assert!(count(LIDAR_COLOR) > 0);
+
+        // The car is drawn over the pixel at its position
+        let view = View::fit(&Aabb::new(sim.road.bounds().0, sim.road.bounds().1), 160, 90);
+        let Vec2(column, row) = view.to_pixel(sim.state.position);
+        assert_eq!(image.get(column as usize, row as usize), CAR_COLOR);
+    }
+}
@@ -0,0 +1,151 @@
+use std::io;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tungstenite::{Message, WebSocket};
+
+use crate::gym::Simulator;
+use crate::map::Road;
+
+
+/// Time after which a client that does not keep up with the frames is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+
+/// The state of a simulator for drawing it, sent to clients as a JSON object
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Frame {
+    pub t: f32,
+    pub i: usize,
+    /// The (x, y) corners of the car: back left, front left, front right and back right
+    pub car: [[f32; 2]; 4],
+    pub lidar_center: [f32; 2],
+    /// Where each LiDAR ray hits
+    pub lidar_points: Vec<[f32; 2]>,
+    pub speed: f32,
+    pub steer_delta: f32,
+}
+
+
+impl Frame {
+    pub fn from_sim<R: Road>(sim: &Simulator<R>) -> Self {
+        let state = &sim.state;
+        let config = &sim.config.car;
+        let car = state.corners(config).map(|corner| [corner.0, corner.1]);
+
+        let readings = sim.observe_current().lidar_readings;
+        let lidar_points = sim.config.lidar.get_angles().iter().zip(readings)
+            .map(|(&angle, reading)| {
+                let hit = state.position + state.unit_forward.rotate(angle)*reading.abs();
+                [hit.0, hit.1]
+            })
+            .collect();
+
+        Self {
+            t: sim.get_t(), i: sim.get_i(), car,
+            lidar_center: [state.position.0, state.position.1], lidar_points,
+            speed: state.speed, steer_delta: state.steer_delta,
+        }
+    }
+}
+
+
+/// Serves frames to any number of WebSocket clients, e.g. a browser dashboard watching a training
+/// run. Clients connect in the background, and each published frame goes to all of them.
+pub struct FramePublisher {
+    address: SocketAddr,
+    clients: Arc<Mutex<Vec<WebSocket<TcpStream>>>>,
+}
+
+
+impl FramePublisher {
+    /// Listen for clients on 'address', e.g. "127.0.0.1:8765", or on a free port with port 0
+    pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let clients = Arc::new(Mutex::new(Vec::new()));
+
+        // The thread ends with the process, or when the publisher is dropped and the next client
+        // connects
+        let weak_clients = Arc::downgrade(&clients);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Some(clients) = weak_clients.upgrade() else { break };
+                let Ok(stream) = stream else { continue };
+                if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+                    continue;
+                }
+                if let Ok(socket) = tungstenite::accept(stream) {
+                    clients.lock().expect("the clients not to be poisoned").push(socket);
+                }
+            }
+        });
+
+        Ok(Self { address, clients })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn n_clients(&self) -> usize {
+        self.clients.lock().expect("the clients not to be poisoned").len()
+    }
+
+    /// Send a frame to all clients, dropping those that have disconnected or fall behind
+    pub fn publish(&self, frame: &Frame) {
+        let mut clients = self.clients.lock().expect("the clients not to be poisoned");
+        if clients.is_empty() {
+            return;
+        }
+        let text = serde_json::to_string(frame).expect("frames to serialize");
+        clients.retain_mut(|client| client.send(Message::text(text.clone())).is_ok());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gym::{Action, SimConfig};
+    use crate::map::make_oval;
+
+    /// Poll 'condition' until it holds, failing the test if that takes more than a few seconds
+    fn wait_until(description: &str, mut condition: impl FnMut() -> bool) {
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(std::time::Instant::now() < deadline, "Timed out waiting until {}", description);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_stream() {
+        let mut sim = Simulator::new(SimConfig::default(), make_oval(), Some(0));
+        sim.reset(None);
+        sim.step(Action::Coast);
+
+        let publisher = FramePublisher::bind("127.0.0.1:0").unwrap();
+        let (mut client, _) = tungstenite::connect(format!("ws://{}", publisher.local_addr())).unwrap();
+        // The client is added after the handshake completes on the server side
+        wait_until("the client is added", || publisher.n_clients() == 1);
+
+        let frame = Frame::from_sim(&sim);
+        assert_eq!(frame.i, 1);
+        assert_eq!(frame.lidar_points.len(), sim.config.lidar.n_angles());
+        publisher.publish(&frame);
+        let text = client.read().unwrap().into_text().unwrap();
+        let received: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(received["i"], 1);
+        assert_eq!(received["car"].as_array().unwrap().len(), 4);
+
+        // Disconnected clients are dropped, once publishing to them fails
+        drop(client);
+        wait_until("the client is dropped", || {
+            publisher.publish(&frame);
+            publisher.n_clients() == 0
+        });
+    }
+}
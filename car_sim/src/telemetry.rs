@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::gym::RewardTerms;
+use crate::physics::TireUsage;
+
+
+const HEADER: &str = "t,x,y,speed,steer,progress,travel_reward,center_reward,center_integral_reward,crash_reward,shoulder_reward,reward,done,longitudinal_acc,lateral_acc,grip_utilization";
+
+
+/// The telemetry of a single step, taken after the step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TelemetryRow {
+    pub t: f32,
+    pub x: f32,
+    pub y: f32,
+    pub speed: f32,
+    pub steer: f32,
+    pub progress: f32,
+    pub reward: RewardTerms,
+    pub done: bool,
+    pub tire: TireUsage,
+    /// See 'TireUsage::grip_utilization', an empty column if none
+    pub grip_utilization: Option<f32>,
+}
+
+
+/// Writes one CSV row per simulator step, with a header naming the columns
+pub struct TelemetryLogger {
+    writer: Box<dyn Write + Send + Sync>,
+    /// The first error while writing a row. Logging stops there, and the error is reported by
+    /// 'finish'.
+    error: Option<std::io::Error>,
+}
+
+
+impl TelemetryLogger {
+    pub fn new<W: Write + Send + Sync + 'static>(writer: W) -> std::io::Result<Self> {
+        let mut writer: Box<dyn Write + Send + Sync> = Box::new(writer);
+        writeln!(writer, "{}", HEADER)?;
+        Ok(Self { writer, error: None })
+    }
+
+    /// Create a logger writing to a new file at 'path'
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn log(&mut self, row: &TelemetryRow) {
+        if self.error.is_some() {
+            return;
+        }
+        let TelemetryRow { t, x, y, speed, steer, progress, reward, done, tire, grip_utilization } = row;
+        let RewardTerms { travel, center, center_integral, crash, shoulder } = reward;
+        let grip_utilization = grip_utilization.map(|utilization| utilization.to_string()).unwrap_or_default();
+        if let Err(err) = writeln!(
+            self.writer, "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            t, x, y, speed, steer, progress, travel, center, center_integral, crash, shoulder, reward.total(), *done as u8,
+            tire.longitudinal_acc, tire.lateral_acc, grip_utilization
+        ) {
+            self.error = Some(err);
+        }
+    }
+
+    /// Flush the written rows, reporting the first error while logging, if any
+    pub fn finish(mut self) -> std::io::Result<()> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// A writer whose contents stay readable after it is moved into a logger
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_rows() {
+        let buffer = SharedBuffer::default();
+        let mut logger = TelemetryLogger::new(buffer.clone()).unwrap();
+        let reward = RewardTerms { travel: 2.0, center: -0.5, crash: -10.0, ..RewardTerms::default() };
+        let row = TelemetryRow { t: 0.5, x: 1.0, y: -2.0, speed: 3.0, steer: 0.25, progress: 4.0, reward, done: true, tire: TireUsage::default(), grip_utilization: None };
+        logger.log(&row);
+        let tire = TireUsage { longitudinal_acc: -8.0, lateral_acc: 6.0 };
+        logger.log(&TelemetryRow { tire, grip_utilization: Some(0.5), done: false, ..row });
+        logger.finish().unwrap();
+
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines, vec![HEADER, "0.5,1,-2,3,0.25,4,2,-0.5,0,-10,0,-8.5,1,0,0,", "0.5,1,-2,3,0.25,4,2,-0.5,0,-10,0,-8.5,0,-8,6,0.5"]);
+    }
+}
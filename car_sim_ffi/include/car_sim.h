@@ -0,0 +1,48 @@
+/* C API of the car_sim racing environment, see car_sim_ffi/src/lib.rs */
+#ifndef CAR_SIM_H
+#define CAR_SIM_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Actions of car_sim_step in the default "discrete" action mode */
+enum {
+    CAR_SIM_LEFT = 0,
+    CAR_SIM_RIGHT = 1,
+    CAR_SIM_ACCELERATE = 2,
+    CAR_SIM_BRAKE = 3,
+    CAR_SIM_COAST = 4,
+};
+
+typedef struct CarSim CarSim;
+
+/* Create an environment with the default settings. Free it with car_sim_destroy. */
+CarSim *car_sim_create(uint64_t seed);
+
+/* Create an environment from a JSON config. Returns NULL if the config is NULL or invalid. */
+CarSim *car_sim_create_from_json(const char *config_json, uint64_t seed);
+
+void car_sim_destroy(CarSim *sim);
+
+void car_sim_reset(CarSim *sim, uint64_t seed);
+
+/* Steps with an action of the action mode of the config: 0 to 4 as below in the default
+ * "discrete" mode, or 0 to 14 in the "steer_throttle" mode. Returns 0 on success and -1 for an
+ * invalid action. */
+int car_sim_step(CarSim *sim, uint8_t action, float *reward, bool *done);
+
+size_t car_sim_observation_dim(const CarSim *sim);
+
+/* Returns 0 on success and -1 if len is not the observation dimension */
+int car_sim_observe(const CarSim *sim, float *out, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,160 @@
+use std::ffi::{CStr, c_char, c_int};
+
+use car_sim::gym;
+use car_sim::map;
+
+
+/// An environment on the default racetrack, opaque to C
+pub struct CarSim {
+    sim: gym::Simulator<map::SplineMap>,
+}
+
+
+fn create(config: gym::SimConfig, seed: u64) -> *mut CarSim {
+    let mut sim = gym::Simulator::new(config, map::make_simple_racetrack(), Some(seed));
+    sim.reset(None);
+    Box::into_raw(Box::new(CarSim { sim }))
+}
+
+
+/// Create an environment with the default settings, seeded with 'seed'. Free it with
+/// 'car_sim_destroy'.
+#[unsafe(no_mangle)]
+pub extern "C" fn car_sim_create(seed: u64) -> *mut CarSim {
+    create(gym::SimConfig::default(), seed)
+}
+
+
+/// Create an environment with the settings of a JSON config, as in a config file. Returns null if
+/// the config is null or invalid.
+///
+/// # Safety
+/// 'config_json' must be null or a valid null-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn car_sim_create_from_json(config_json: *const c_char, seed: u64) -> *mut CarSim {
+    if config_json.is_null() {
+        return std::ptr::null_mut();
+    }
+    let config = unsafe { CStr::from_ptr(config_json) }.to_str().ok()
+        .and_then(|text| gym::SimConfig::from_json(text).ok());
+    match config {
+        Some(config) => create(config, seed),
+        None => std::ptr::null_mut(),
+    }
+}
+
+
+/// # Safety
+/// 'sim' must be null or created by 'car_sim_create', and not used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn car_sim_destroy(sim: *mut CarSim) {
+    if !sim.is_null() {
+        drop(unsafe { Box::from_raw(sim) });
+    }
+}
+
+
+/// Start a new episode, with the random initialization seeded by 'seed'
+///
+/// # Safety
+/// 'sim' must be a valid environment.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn car_sim_reset(sim: *mut CarSim, seed: u64) {
+    unsafe { &mut *sim }.sim.reset(Some(seed));
+}
+
+
+/// Step the environment with the integer value of an action in the action mode of the config,
+/// writing the reward and whether the episode is over. Returns 0 on success and -1 for an invalid
+/// action.
+///
+/// # Safety
+/// 'sim' must be a valid environment, and 'reward' and 'done' valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn car_sim_step(sim: *mut CarSim, action: u8, reward: *mut f32, done: *mut bool) -> c_int {
+    let Ok(transition) = unsafe { &mut *sim }.sim.step_index(action) else {
+        return -1;
+    };
+    unsafe {
+        *reward = transition.reward;
+        *done = transition.done;
+    }
+    0
+}
+
+
+/// # Safety
+/// 'sim' must be a valid environment.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn car_sim_observation_dim(sim: *const CarSim) -> usize {
+    unsafe { &*sim }.sim.observation_dim()
+}
+
+
+/// Write the observation vector of the current state into 'out', which holds 'len' floats.
+/// Returns 0 on success and -1 if 'len' is not the observation dimension.
+///
+/// # Safety
+/// 'sim' must be a valid environment, and 'out' valid for writing 'len' floats.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn car_sim_observe(sim: *const CarSim, out: *mut f32, len: usize) -> c_int {
+    let sim = &unsafe { &*sim }.sim;
+    if len != sim.observation_dim() {
+        return -1;
+    }
+    sim.observe_into(unsafe { std::slice::from_raw_parts_mut(out, len) });
+    0
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        unsafe {
+            let sim = car_sim_create(0);
+            assert!(!sim.is_null());
+            car_sim_reset(sim, 1);
+
+            // The observation fills exactly the reported dimension
+            let dim = car_sim_observation_dim(sim);
+            let mut observation = vec![f32::NAN; dim + 1];
+            assert_eq!(car_sim_observe(sim, observation.as_mut_ptr(), dim), 0);
+            assert!(observation[.. dim].iter().all(|value| value.is_finite()));
+            assert!(observation[dim].is_nan());
+            assert_eq!(car_sim_observe(sim, observation.as_mut_ptr(), dim + 1), -1);
+
+            let (mut reward, mut done) = (f32::NAN, true);
+            assert_eq!(car_sim_step(sim, 2, &mut reward, &mut done), 0);
+            assert!(reward.is_finite());
+            assert!(!done);
+
+            // An invalid action writes nothing
+            let (mut reward, mut done) = (f32::NAN, true);
+            assert_eq!(car_sim_step(sim, 5, &mut reward, &mut done), -1);
+            assert!(reward.is_nan());
+            assert!(done);
+
+            car_sim_destroy(sim);
+        }
+    }
+
+    #[test]
+    fn test_create_from_json() {
+        unsafe {
+            // The actions follow the action mode of the config
+            let sim = car_sim_create_from_json(c"{\"action_mode\": \"steer_throttle\"}".as_ptr(), 0);
+            assert!(!sim.is_null());
+            let (mut reward, mut done) = (0.0, false);
+            assert_eq!(car_sim_step(sim, 14, &mut reward, &mut done), 0);
+            assert_eq!(car_sim_step(sim, 15, &mut reward, &mut done), -1);
+            car_sim_destroy(sim);
+
+            assert!(car_sim_create_from_json(c"{\"dt\": ".as_ptr(), 0).is_null());
+            assert!(car_sim_create_from_json(std::ptr::null(), 0).is_null());
+            car_sim_destroy(std::ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,7 @@
+// Compile the protocol with a pure Rust protobuf compiler, so that building needs no 'protoc'
+fn main() -> std::io::Result<()> {
+    let file_descriptors = protox::compile(["proto/car_gym.proto"], ["proto"]).map_err(std::io::Error::other)?;
+    tonic_prost_build::configure()
+        .build_client(true)
+        .compile_fds(file_descriptors)
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tonic::{Request, Response, Status};
+
+use car_sim::gym;
+use car_sim::map;
+use car_sim::render;
+
+pub mod proto {
+    tonic::include_proto!("car_gym");
+}
+
+use proto::env_server::Env;
+pub use proto::env_server::EnvServer;
+pub use proto::env_client::EnvClient;
+
+
+type Sim = gym::Simulator<map::SplineMap>;
+
+
+/// Serves independent environments to any number of clients, which refer to them by id
+#[derive(Default)]
+pub struct EnvService {
+    envs: Mutex<HashMap<u64, Arc<Mutex<Sim>>>>,
+    next_id: AtomicU64,
+}
+
+
+impl EnvService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn get(&self, env_id: u64) -> Result<Arc<Mutex<Sim>>, Status> {
+        self.envs.lock().expect("the environments not to be poisoned")
+            .get(&env_id)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("No environment with id {}", env_id)))
+    }
+}
+
+
+fn observation(sim: &Sim) -> proto::Observation {
+    let mut values = vec![0.0; sim.observation_dim()];
+    sim.observe_into(&mut values);
+    proto::Observation { values }
+}
+
+
+#[tonic::async_trait]
+impl Env for EnvService {
+    async fn make(&self, request: Request<proto::MakeRequest>) -> Result<Response<proto::MakeResponse>, Status> {
+        let proto::MakeRequest { config_json, track_json, seed } = request.into_inner();
+        let config = if config_json.is_empty() {
+            gym::SimConfig::default()
+        } else {
+            gym::SimConfig::from_json(&config_json).map_err(|err| Status::invalid_argument(err.to_string()))?
+        };
+        let road = if track_json.is_empty() {
+            map::make_simple_racetrack()
+        } else {
+            map::TrackDefinition::from_json(&track_json)
+                .and_then(|definition| map::SplineMap::from_definition(&definition))
+                .map_err(|err| Status::invalid_argument(err.to_string()))?
+        };
+
+        let mut sim = gym::Simulator::new(config, road, seed);
+        sim.reset(None);
+        let env_id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.envs.lock().expect("the environments not to be poisoned").insert(env_id, Arc::new(Mutex::new(sim)));
+        Ok(Response::new(proto::MakeResponse { env_id }))
+    }
+
+    async fn close(&self, request: Request<proto::EnvId>) -> Result<Response<proto::Empty>, Status> {
+        let env_id = request.into_inner().env_id;
+        match self.envs.lock().expect("the environments not to be poisoned").remove(&env_id) {
+            Some(_) => Ok(Response::new(proto::Empty {})),
+            None => Err(Status::not_found(format!("No environment with id {}", env_id))),
+        }
+    }
+
+    async fn reset(&self, request: Request<proto::ResetRequest>) -> Result<Response<proto::Observation>, Status> {
+        let proto::ResetRequest { env_id, seed } = request.into_inner();
+        let env = self.get(env_id)?;
+        let mut sim = env.lock().expect("the environment not to be poisoned");
+        sim.reset(seed);
+        Ok(Response::new(observation(&sim)))
+    }
+
+    async fn step(&self, request: Request<proto::StepRequest>) -> Result<Response<proto::StepResponse>, Status> {
+        let proto::StepRequest { env_id, action } = request.into_inner();
+        let env = self.get(env_id)?;
+        let mut sim = env.lock().expect("the environment not to be poisoned");
+        let n_actions = sim.config.action_mode.n_actions();
+        let gym::TransitionObservation { reward, done, .. } = u8::try_from(action).ok()
+            .and_then(|action| sim.step_index(action).ok())
+            .ok_or_else(|| Status::invalid_argument(format!("Invalid action '{}'. Actions are integers from 0 to {}.", action, n_actions - 1)))?;
+        Ok(Response::new(proto::StepResponse { observation: Some(observation(&sim)), reward, done }))
+    }
+
+    async fn spaces(&self, request: Request<proto::EnvId>) -> Result<Response<proto::SpacesResponse>, Status> {
+        let env = self.get(request.into_inner().env_id)?;
+        let config = env.lock().expect("the environment not to be poisoned").config.clone();
+        let components = config.observation_components();
+        let observation_space = proto::BoxSpace {
+            shape: vec![components.len() as u32],
+            low: components.iter().map(|component| component.low).collect(),
+            high: components.iter().map(|component| component.high).collect(),
+        };
+        let action_space = proto::DiscreteSpace { n: config.action_mode.n_actions() as u32 };
+        Ok(Response::new(proto::SpacesResponse { observation_space: Some(observation_space), action_space: Some(action_space) }))
+    }
+
+    async fn render(&self, request: Request<proto::RenderRequest>) -> Result<Response<proto::Image>, Status> {
+        let proto::RenderRequest { env_id, width, height } = request.into_inner();
+        if width == 0 || height == 0 {
+            return Err(Status::invalid_argument(format!("Invalid image size {}x{}", width, height)));
+        }
+        let env = self.get(env_id)?;
+        let sim = env.lock().expect("the environment not to be poisoned");
+        let image = render::render_rgb(&sim, width as usize, height as usize);
+        Ok(Response::new(proto::Image { width, height, rgb: image.pixels }))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    #[tokio::test]
+    async fn test_env_service() {
+        let service = EnvService::new();
+        let make = proto::MakeRequest { config_json: String::new(), track_json: String::new(), seed: Some(0) };
+        let env_id = service.make(Request::new(make)).await.unwrap().into_inner().env_id;
+
+        let spaces = service.spaces(Request::new(proto::EnvId { env_id })).await.unwrap().into_inner();
+        let observation_space = spaces.observation_space.unwrap();
+        assert_eq!(spaces.action_space.unwrap().n, 5);
+
+        let observation = service.reset(Request::new(proto::ResetRequest { env_id, seed: Some(1) })).await.unwrap().into_inner();
+        assert_eq!(observation.values.len(), observation_space.shape[0] as usize);
+
+        let step = service.step(Request::new(proto::StepRequest { env_id, action: 2 })).await.unwrap().into_inner();
+        assert_eq!(step.observation.unwrap().values.len(), observation_space.shape[0] as usize);
+        assert!(!step.done);
+
+        // Invalid actions and unknown environments are rejected
+        let invalid = service.step(Request::new(proto::StepRequest { env_id, action: 5 })).await.unwrap_err();
+        assert_eq!(invalid.code(), Code::InvalidArgument);
+        let unknown = service.step(Request::new(proto::StepRequest { env_id: env_id + 1, action: 2 })).await.unwrap_err();
+        assert_eq!(unknown.code(), Code::NotFound);
+        let unknown = service.reset(Request::new(proto::ResetRequest { env_id: env_id + 1, seed: None })).await.unwrap_err();
+        assert_eq!(unknown.code(), Code::NotFound);
+
+        // A closed environment is gone
+        service.close(Request::new(proto::EnvId { env_id })).await.unwrap();
+        let closed = service.spaces(Request::new(proto::EnvId { env_id })).await.unwrap_err();
+        assert_eq!(closed.code(), Code::NotFound);
+        let closed = service.close(Request::new(proto::EnvId { env_id })).await.unwrap_err();
+        assert_eq!(closed.code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_steer_throttle_spaces() {
+        let service = EnvService::new();
+        let make = proto::MakeRequest { config_json: r#"{"action_mode": "steer_throttle"}"#.to_string(), track_json: String::new(), seed: Some(0) };
+        let env_id = service.make(Request::new(make)).await.unwrap().into_inner().env_id;
+        let spaces = service.spaces(Request::new(proto::EnvId { env_id })).await.unwrap().into_inner();
+        assert_eq!(spaces.action_space.unwrap().n, 15);
+        assert!(service.step(Request::new(proto::StepRequest { env_id, action: 14 })).await.is_ok());
+
+        let invalid = proto::MakeRequest { config_json: "{".to_string(), track_json: String::new(), seed: None };
+        assert_eq!(service.make(Request::new(invalid)).await.unwrap_err().code(), Code::InvalidArgument);
+    }
+}
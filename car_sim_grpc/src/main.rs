@@ -0,0 +1,14 @@
+use car_sim_grpc::{EnvService, EnvServer};
+
+
+/// Serve environments on the address given as the first argument, by default on localhost
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let address = std::env::args().nth(1).unwrap_or_else(|| "127.0.0.1:50051".to_string()).parse()?;
+    println!("Serving environments on {}", address);
+    tonic::transport::Server::builder()
+        .add_service(EnvServer::new(EnvService::new()))
+        .serve(address)
+        .await?;
+    Ok(())
+}
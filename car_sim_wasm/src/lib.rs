@@ -0,0 +1,161 @@
+use wasm_bindgen::prelude::*;
+
+use car_sim::gym;
+use car_sim::map::{self, Road, RoadSamples};
+
+
+/// The outcome of a call to 'RacingEnv.step'
+#[wasm_bindgen]
+pub struct StepResult {
+    pub reward: f32,
+    pub done: bool,
+}
+
+
+/// The left and right edges of a road as flat [x0, y0, x1, y1, ...] arrays, for drawing it
+#[wasm_bindgen(getter_with_clone)]
+pub struct RoadExport {
+    pub left: Vec<f32>,
+    pub right: Vec<f32>,
+}
+
+
+/// The racing environment for JavaScript, with the same actions, rewards and observations as the
+/// Python 'gym_car.RacingEnv'
+#[wasm_bindgen]
+pub struct RacingEnv {
+    sim: gym::Simulator<map::SplineMap>,
+    /// Buffer for the latest observation, reused between calls to 'observe'
+    observation: Vec<f32>,
+}
+
+
+impl RacingEnv {
+    fn from_parts(config: gym::SimConfig, road: map::SplineMap, seed: Option<u64>) -> Self {
+        let sim = gym::Simulator::new(config, road, seed);
+        let observation = vec![0.0; sim.observation_dim()];
+        let mut this = Self { sim, observation };
+        this.reset(None);
+        this
+    }
+
+    /// Step with the integer value of an action in the action mode of the config, with the error
+    /// message for an invalid action. Kept apart from 'step' since a 'JsError' can only be created in wasm.
+    fn try_step(&mut self, action: u8) -> Result<StepResult, String> {
+        let n_actions = self.sim.config.action_mode.n_actions();
+        let gym::TransitionObservation { reward, done, .. } = self.sim.step_index(action)
+            .map_err(|_| format!("Invalid action '{}'. Actions are integers from 0 to {}.", action, n_actions - 1))?;
+        Ok(StepResult { reward, done })
+    }
+}
+
+
+#[wasm_bindgen]
+impl RacingEnv {
+    /// Create an environment on the default racetrack with the default settings
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: Option<u64>) -> Self {
+        Self::from_parts(gym::SimConfig::default(), map::make_simple_racetrack(), seed)
+    }
+
+    /// Create an environment from the contents of a JSON config file and, if given, a JSON track
+    /// file, since the browser has no file system to load them from
+    #[wasm_bindgen(js_name = fromJson)]
+    pub fn from_json(config: &str, track: Option<String>, seed: Option<u64>) -> Result<Self, JsError> {
+        let config = gym::SimConfig::from_json(config)?;
+        let road = match track {
+            Some(track) => map::SplineMap::from_definition(&map::TrackDefinition::from_json(&track)?)?,
+            None => map::make_simple_racetrack(),
+        };
+        Ok(Self::from_parts(config, road, seed))
+    }
+
+    /// Get the simulator settings as JSON, in the layout of a config file
+    #[wasm_bindgen(js_name = configJson)]
+    pub fn config_json(&self) -> Result<String, JsError> {
+        Ok(serde_json::to_string(&self.sim.config)?)
+    }
+
+    pub fn reset(&mut self, seed: Option<u64>) {
+        self.sim.reset(seed)
+    }
+
+    pub fn step(&mut self, action: u8) -> Result<StepResult, JsError> {
+        self.try_step(action).map_err(|message| JsError::new(&message))
+    }
+
+    /// Get the observation vector of the current state, as a Float32Array
+    pub fn observe(&mut self) -> Vec<f32> {
+        self.sim.observe_into(&mut self.observation);
+        self.observation.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = observationDim)]
+    pub fn observation_dim(&self) -> usize {
+        self.sim.observation_dim()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn t(&self) -> f32 {
+        self.sim.get_t()
+    }
+
+    /// The car state as [x, y, forward_x, forward_y, speed, steer_delta]
+    #[wasm_bindgen(js_name = carState)]
+    pub fn car_state(&self) -> Vec<f32> {
+        let state = &self.sim.state;
+        vec![state.position.0, state.position.1, state.unit_forward.0, state.unit_forward.1, state.speed, state.steer_delta]
+    }
+
+    #[wasm_bindgen(getter, js_name = trackLength)]
+    pub fn track_length(&self) -> f32 {
+        self.sim.road.track_length()
+    }
+
+    /// Sample the edges of the road at 'n_segments' points along it
+    #[wasm_bindgen(js_name = exportRoad)]
+    pub fn export_road(&self, n_segments: usize) -> RoadExport {
+        let RoadSamples { left, right, .. } = self.sim.road.sample_road(n_segments);
+        let flatten = |points: Vec<math_utils::Vec2>| points.into_iter().flat_map(|point| [point.0, point.1]).collect();
+        RoadExport { left: flatten(left), right: flatten(right) }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_racing_env() {
+        let mut env = RacingEnv::new(Some(0));
+        env.reset(Some(1));
+        assert_eq!(env.observe().len(), env.observation_dim());
+        let result = env.try_step(2).unwrap();
+        assert!(!result.done);
+        assert_eq!(env.observe().len(), env.observation_dim());
+        assert!(env.t() > 0.0);
+
+        let error = env.try_step(5).err().unwrap();
+        assert_eq!(error, "Invalid action '5'. Actions are integers from 0 to 4.");
+    }
+
+    #[test]
+    fn test_config_json() {
+        let env = RacingEnv::new(Some(0));
+        let config = env.config_json().unwrap();
+
+        // A config read back from JSON gives the same JSON and environment
+        let mut copy = RacingEnv::from_json(&config, None, Some(0)).unwrap();
+        assert_eq!(copy.config_json().unwrap(), config);
+        assert_eq!(copy.observation_dim(), env.observation_dim());
+        assert_eq!(copy.track_length(), env.track_length());
+
+        // The actions follow the action mode of the config
+        let mut steer_throttle = RacingEnv::from_json(r#"{"action_mode": "steer_throttle", "dt": 0.1}"#, None, Some(0)).unwrap();
+        assert!(steer_throttle.config_json().unwrap().contains(r#""dt":0.1"#));
+        assert!(steer_throttle.try_step(14).is_ok());
+        assert_eq!(steer_throttle.try_step(15).err().unwrap(), "Invalid action '15'. Actions are integers from 0 to 14.");
+        assert!(copy.try_step(4).is_ok());
+    }
+}
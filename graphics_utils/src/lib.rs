@@ -1,29 +1,67 @@
 use macroquad::prelude as mq;
 
-use math_utils::{Vec2, spline};
+use math_utils::{Vec2, Aabb, spline};
+
+
+/// Largest distance in pixels between the drawn edges of a spline and the true curves
+const TESSELLATION_TOLERANCE_PX: f32 = 0.5;
+const MAX_SUBDIVISIONS: usize = 256;
+/// Number of steps along a Bezier curve for estimating how much it turns
+const TURNING_SAMPLES: usize = 8;
 
 
 pub struct ScreenTransform {
     px_per_m: f32,
     center_m: Vec2,
+    /// Angle of the world direction shown pointing right on screen, counterclockwise from the x axis
+    rotation: f32,
 }
 
 
 impl ScreenTransform {
     pub fn new(px_per_m: f32) -> Self {
-        Self { px_per_m, center_m: Vec2(0.0, 0.0) }
+        Self { px_per_m, center_m: Vec2(0.0, 0.0), rotation: 0.0 }
     }
 
     pub fn to_screen(&self, world: Vec2) -> mq::Vec2 {
         let center_px = mq::Vec2 { x: mq::screen_width()*0.5, y: mq::screen_height()*0.5 };
-        let world_center_dev = world - self.center_m;
+        let world_center_dev = (world - self.center_m).rotate(-self.rotation);
 
         mq::Vec2 { x: world_center_dev.0 * self.px_per_m, y: -world_center_dev.1*self.px_per_m } + center_px
     }
 
+    /// Get the world coordinates shown at a point on screen, the inverse of 'to_screen'
+    pub fn to_world(&self, screen: mq::Vec2) -> Vec2 {
+        let center_px = mq::Vec2 { x: mq::screen_width()*0.5, y: mq::screen_height()*0.5 };
+        let screen_center_dev = screen - center_px;
+
+        Vec2(screen_center_dev.x / self.px_per_m, -screen_center_dev.y / self.px_per_m).rotate(self.rotation) + self.center_m
+    }
+
+    pub fn center(&self) -> Vec2 {
+        self.center_m
+    }
+
     pub fn set_center(&mut self, center: Vec2) {
         self.center_m = center;
     }
+
+    pub fn px_per_m(&self) -> f32 {
+        self.px_per_m
+    }
+
+    pub fn set_px_per_m(&mut self, px_per_m: f32) {
+        self.px_per_m = px_per_m;
+    }
+
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    /// Rotate the view such that the world direction at angle 'rotation' points right on screen
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+    }
 }
 
 
@@ -78,9 +116,96 @@ pub fn draw_bezier(curve: &spline::CubicBezier, transform: &ScreenTransform, wor
 }
 
 
-pub fn draw_spline(spline: &spline::SmoothBezierSpline, transform: &ScreenTransform, world_width: f32, segments: usize, color: mq::Color) {
-    let sub_segments = segments / spline.segments.len() + 1;
+/// Choose the number of pieces to draw a Bezier curve of width 'world_width' with, such that its
+/// edges deviate from the true curves by at most 'TESSELLATION_TOLERANCE_PX' on screen
+pub fn bezier_subdivisions(curve: &spline::CubicBezier, transform: &ScreenTransform, world_width: f32) -> usize {
+    // Estimate the total turning angle and the length of the curve
+    let mut turning = 0.0;
+    let mut length = 0.0;
+    let (mut last_point, mut last_tangent) = (curve.get(0.0), curve.tangent(0.0));
+    for i in 1 ..= TURNING_SAMPLES {
+        let t = i as f32 / TURNING_SAMPLES as f32;
+        let (point, tangent) = (curve.get(t), curve.tangent(t));
+        turning += last_tangent.angle_between(tangent).abs();
+        length += last_point.distance(point);
+        (last_point, last_tangent) = (point, tangent);
+    }
+    if turning <= 0.0 {
+        return 1;
+    }
+
+    // Splitting an arc of radius r and angle a into n chords deviates from it by about
+    // r*a^2/(8n^2), where the outer edge has the largest radius
+    let radius_px = (length / turning + 0.5*world_width) * transform.px_per_m();
+    let subdivisions = turning * (radius_px / (8.0*TESSELLATION_TOLERANCE_PX)).sqrt();
+    (subdivisions.ceil() as usize).clamp(1, MAX_SUBDIVISIONS)
+}
+
+
+/// Check whether a world-space box may be visible on screen
+fn is_on_screen(aabb: &Aabb, transform: &ScreenTransform) -> bool {
+    let corners = aabb.corners().map(|corner| {
+        let screen = transform.to_screen(corner);
+        Vec2(screen.x, screen.y)
+    });
+    let screen = Aabb::new(Vec2(0.0, 0.0), Vec2(mq::screen_width(), mq::screen_height()));
+    Aabb::from_points(corners).is_some_and(|bounds| bounds.intersects(&screen))
+}
+
+
+/// Draw a spline of width 'world_width', subdividing each curve according to its curvature and
+/// size on screen, and skipping curves outside the screen
+pub fn draw_spline(spline: &spline::SmoothBezierSpline, transform: &ScreenTransform, world_width: f32, color: mq::Color) {
     for segment in &spline.segments {
-        draw_bezier(segment, transform, world_width, sub_segments, color);
+        if is_on_screen(&segment.aabb().padded(0.5*world_width), transform) {
+            draw_bezier(segment, transform, world_width, bezier_subdivisions(segment, transform, world_width), color);
+        }
     };
 }
+
+
+/// Draw text centered on a world point. The size is given in pixels, so labels stay readable at
+/// any zoom.
+pub fn draw_world_text(text: &str, position: Vec2, transform: &ScreenTransform, font_size: f32, color: mq::Color) {
+    let center = transform.to_screen(position);
+    let dimensions = mq::measure_text(text, None, font_size as u16, 1.0);
+    let x = center.x - 0.5*dimensions.width;
+    let y = center.y - 0.5*dimensions.height + dimensions.offset_y;
+    mq::draw_text(text, x, y, font_size, color);
+}
+
+
+/// Draw a filled circle with a radius in meters around a world point
+pub fn draw_world_circle(center: Vec2, radius: f32, transform: &ScreenTransform, color: mq::Color) {
+    let screen = transform.to_screen(center);
+    mq::draw_circle(screen.x, screen.y, radius*transform.px_per_m(), color);
+}
+
+
+/// Draw the outline of a circle with a radius in meters around a world point, with a line
+/// thickness in pixels
+pub fn draw_world_circle_lines(center: Vec2, radius: f32, thickness: f32, transform: &ScreenTransform, color: mq::Color) {
+    let screen = transform.to_screen(center);
+    mq::draw_circle_lines(screen.x, screen.y, radius*transform.px_per_m(), thickness, color);
+}
+
+
+/// Draw an arrow between two world points, with a line thickness in pixels and a head scaled
+/// with the thickness
+pub fn draw_world_arrow(start: Vec2, end: Vec2, thickness: f32, transform: &ScreenTransform, color: mq::Color) {
+    let start = transform.to_screen(start);
+    let end = transform.to_screen(end);
+    let delta = end - start;
+    let length = delta.length();
+    if length == 0.0 {
+        return;
+    }
+
+    // Shorten the line to end at the base of the head, which is never longer than the arrow
+    let head_length = (4.0*thickness + 4.0).min(length);
+    let direction = delta / length;
+    let lateral = mq::Vec2 { x: -direction.y, y: direction.x } * 0.5*head_length;
+    let base = end - direction*head_length;
+    mq::draw_line(start.x, start.y, base.x, base.y, thickness, color);
+    mq::draw_triangle(end, base + lateral, base - lateral, color);
+}
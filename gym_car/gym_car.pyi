@@ -1,18 +1,70 @@
 import numpy as np
 import numpy.typing as npt
 
-from typing import Tuple, List, Optional
+from typing import Any, Callable, Dict, Tuple, List, Optional
+
+
+# The discrete actions of the environments, by integer value
+LEFT: int = 0
+RIGHT: int = 1
+ACCELERATE: int = 2
+BRAKE: int = 3
+COAST: int = 4
+N_ACTIONS: int = 5
+# Lowercase names of the actions, indexed by their integer values
+ACTION_NAMES: List[str] = ["left", "right", "accelerate", "brake", "coast"]
+
+
+def load_demonstrations(path: str) -> Dict[str, npt.NDArray]:
+    """
+    Load (observation, action) pairs recorded by a person driving in car_game with '--demo <file>',
+    e.g. for imitation learning or offline RL.
+
+    Parameters
+    ----------
+    path : str
+        The dataset file written by the game.
+
+    Returns
+    -------
+    dict
+        Arrays with one entry per step: 'observation' of shape (n, observation_dim) as float32,
+        taken before the step, 'action' of shape (n,) as uint8, 'reward' of shape (n,) as
+        float32, and 'done' of shape (n,) as bool.
+    """
 
 class RacingEnv:
     def __init__(
             self,
+            track_file: Optional[str] = None,
+            track_width: Optional[float] = None,
             dt: Optional[float] = None,
             crash_reward: Optional[float] = None,
             travel_coeff: Optional[float] = None,
             center_coeff: Optional[float] = None,
             center_integral_coeff: Optional[float] = None,
+            reward_scale: Optional[float] = None,
+            reward_clip: Optional[float] = None,
+            shoulder_width: Optional[float] = None,
+            shoulder_coeff: Optional[float] = None,
             observe_delta: Optional[bool] = True,
             observe_speed: Optional[bool] = True,
+            observe_shoulder: Optional[bool] = False,
+            observe_energy: Optional[bool] = False,
+            observe_grip: Optional[bool] = False,
+            action_mode: str = "discrete",
+            steer_target_rate: Optional[float] = None,
+            energy_capacity: Optional[float] = None,
+            sticky_action_prob: float = 0.0,
+            action_delay: int = 0,
+            observation_delay: int = 0,
+            heading_noise: float = 0.0,
+            lateral_noise: float = 0.0,
+            reverse_prob: float = 0.0,
+            crash_mode: str = "terminate",
+            restitution: float = 0.5,
+            config: Optional["SimConfig"] = None,
+            seed: Optional[int] = None,
         ):
         """
         Create a new racing environment.
@@ -21,6 +73,11 @@ class RacingEnv:
 
         Parameters
         ----------
+        track_file : str, optional
+            Path to a '.json' or '.toml' track definition file to race on, or a '.csv' file of
+            centerline waypoints (x and y in the first two columns). Defaults to a built-in track.
+        track_width : float, optional
+            The road width of a track loaded from a '.csv' waypoint file. Required for such files.
         dt : float, optional
             The time step used in simulation.
         crash_reward : float, optional
@@ -31,47 +88,517 @@ class RacingEnv:
             The strength of the potential-based penalty for deviating from the center-line. Given as positive [penalty/meter^2].
         center_coeff : float, optional
             The strength of the time-integral-based penalty for deviating from the center-line. Given as positive [penalty/(second*meter^2)].
+        reward_scale : float, optional
+            The factor that the sum of the reward terms is multiplied by. Default is 1.
+        reward_clip : float, optional
+            The bound on the absolute value of the scaled reward. Not clipped if not given.
+        shoulder_width : float, optional
+            The width of the shoulder band on either side of the road, where the car is slowed down and penalized but not crashed. Default is 0.
+        shoulder_coeff : float, optional
+            The penalty for driving on the shoulder. Given as positive [penalty/second].
         observe_delta : bool,
             Whether to include the wheel steering angle as part of the state observation. Default is True.
+            This is the actual angle of the wheels, which follows the steering target with a lag.
         observe_speed : bool
             Whether to include the speed as part of the state observation. Default is True.
+        observe_shoulder : bool
+            Whether to include a flag (1.0 or 0.0) telling if the car is on the shoulder as part of the state observation. Default is False.
+        observe_energy : bool
+            Whether to include the energy left as part of the state observation, which is infinite
+            without an energy capacity. Default is False.
+        observe_grip : bool
+            Whether to include the fraction of the tyre grip used at the last step as part of the
+            state observation, which is 0 without a grip limit. See CarConfig.brake_grip. Default
+            is False.
+        action_mode : str
+            "discrete" for the five actions which either steer or change the speed, or
+            "steer_throttle" for 15 actions which combine one of five steering levels, from -2
+            (right) to 2 (left), with braking, coasting or accelerating. The latter are numbered
+            3*(steer + 2) + throttle, with throttle 0, 1 and 2 for brake, coast and accelerate.
+            Default is "discrete".
+        steer_target_rate : float, optional
+            The rate, in radians per second, at which the steering target moves towards the one
+            asked for by an action, as when turning a steering wheel. Without a rate, the target
+            jumps at once.
+        energy_capacity : float, optional
+            The speed in meters per second that the car can gain by accelerating during an
+            episode. Once it is spent, the car can no longer accelerate but still brakes and
+            steers. Unlimited if not given.
+        sticky_action_prob : float
+            The probability that the car repeats its previous action instead of the one taken, to
+            evaluate the robustness of a policy. Default is 0.
+        action_delay : int
+            The number of steps before an action reaches the car, which coasts until the first
+            one does. Default is 0.
+        observation_delay : int
+            The number of steps that observations lag behind, as for sensors with latency. Early
+            in an episode, the first observation of the episode is repeated. Default is 0.
+        heading_noise : float
+            The standard deviation of random turns of the heading, in radians per square root of a
+            second, as from bumps in the road. Drawn with the seeded random number generator of
+            the environment. Default is 0.
+        lateral_noise : float
+            The standard deviation of random sideways displacements, in meters per square root of
+            a second, as from gusts of wind. Default is 0.
+        reverse_prob : float
+            The probability that an episode is driven in the reverse direction of the track,
+            starting turned around and rewarded for travel against the direction of progress.
+            Default is 0.
+        crash_mode : str
+            "terminate" to end the episode when the car crashes, or "bounce" to bounce the car off
+            the edge of the road and drive on. A bouncing car is moved back to where it was before
+            the step, and its heading is mirrored about the direction of the road. The crash reward
+            is given either way. Default is "terminate".
+        restitution : float
+            The fraction of the speed that the car keeps when it bounces. Default is 0.5.
+        config : SimConfig, optional
+            All simulator settings at once, instead of the arguments above from dt to
+            restitution, which must then be left at their defaults. The track arguments apply
+            either way.
+        seed : int, optional
+            The seed of the random number generator of the environment, which makes all episodes reproducible.
+            Drawn from the operating system if not given.
         """
 
-    def step(self, action: int) -> Tuple[float, bool]:
+    def step(self, action: int) -> Tuple[npt.NDArray[np.float32], float, bool]:
         """
         Perform a single MDP update step and return transition information.
 
-        Mutates the environment state and returns reward and truncation information.
+        Mutates the environment state and returns the next observation, the reward and
+        truncation information. The simulation and the ray casting of the observation run in a
+        single call without holding the GIL, so environments stepped from several Python
+        threads run in parallel.
 
         Parameters
         ----------
         action : int
-            Index of the action to perform in the environment.
+            Index of the action to perform in the environment, in its action mode.
 
         Returns
         -------
+        observation : ndarray
+            The observation after the transition, as returned by observe. The same array is
+            overwritten by each call to step and observe.
         reward : float
             The collected reward for this transition.
         done : bool
             Whether the transition caused the episode to terminate.
         """
 
-    def reset(self):
+    def step_raw(self, forward_acc: float, target_delta: float, braking: bool = False) -> Tuple[npt.NDArray[np.float32], float, bool]:
+        """
+        Perform a single update step with a raw car input instead of an action, e.g. for
+        continuous controllers such as pure pursuit.
+
+        The input bypasses the action mode, sticky actions and action delays, but follows the
+        steering rate limit like actions do. The step is recorded with the action 255.
+
+        Parameters
+        ----------
+        forward_acc : float
+            The forward acceleration, clamped to between 0 and the acceleration of the car.
+        target_delta : float
+            The steering target in radians, positive to the left, clamped to the maximum
+            steering angle of the car.
+        braking : bool
+            Whether to brake. Default is False.
+
+        Returns
+        -------
+        (observation, reward, done)
+            As returned by step.
+        """
+
+    def reset(self, seed: Optional[int] = None):
         """
         Reset the environment.
+
+        Parameters
+        ----------
+        seed : int, optional
+            Reseeds the random number generator of the environment. Episodes following a seeded reset
+            are reproducible bit for bit until the next seed is given.
         """
 
     def observe(self) -> npt.NDArray[np.float32]:
         """
         Observe the current state of the environment.
 
-        Returns an array representation of the observation of the current state.
+        Returns an array representation of the observation of the current state. To avoid
+        allocating at every step, the same array is returned and overwritten by each call, so copy
+        it to keep an observation, e.g. in a replay buffer.
 
         Returns
         -------
         observation : ndarray
             An array of shape (observation_dim,) holding the observations
         """
+
+    def __copy__(self) -> "RacingEnv":
+        """
+        Fork the environment, including its track, obstacles, settings and random number
+        generator. The copy continues independently of the original, so copy.copy and
+        copy.deepcopy are the same.
+        """
+
+    def __deepcopy__(self, memo: dict) -> "RacingEnv": ...
+
+    def get_state(self) -> bytes:
+        """
+        Take a snapshot of the dynamic state of the environment.
+
+        The snapshot holds the car state, the clock, the moving obstacles and the random number
+        generator, so that restoring it with set_state replays the following steps and resets
+        exactly. Useful for planning, e.g. tree search, or for resuming rollouts.
+
+        Returns
+        -------
+        bytes
+            The serialized snapshot, which can be stored or pickled as is.
+        """
+
+    def set_state(self, state: bytes):
+        """
+        Restore a snapshot taken by get_state.
+
+        Parameters
+        ----------
+        state : bytes
+            A snapshot from an environment with the same track, obstacles and settings.
+        """
+
+    def get_trajectory(self) -> Dict[str, npt.NDArray]:
+        """
+        Get the path of the car through the current episode, for plotting and analysis.
+
+        Returns
+        -------
+        dict
+            Arrays with one entry per step since the last reset:
+            'position' of shape (n, 2) and 'speed' of shape (n,), both float32 and taken after
+            the step, 'action' of shape (n,) as uint8, and 'reward' of shape (n,) as float32.
+        """
+
+    def observation_space(self) -> Dict[str, Any]:
+        """
+        Describe the observation vector, e.g. to build a Gymnasium space with
+        `Box(low=space["low"], high=space["high"], dtype=np.float32)`.
+
+        Returns
+        -------
+        dict
+            The 'shape' (observation_dim,), the 'dtype' "float32", the bounds 'low' and 'high'
+            as float32 arrays, where unbounded components have infinite bounds, and the 'labels'
+            of the components, e.g. "lidar_-30" for the LiDAR ray at -30 degrees, "steer_delta",
+            "speed" and "on_shoulder".
+        """
+
+    def rollout(self, n_steps: int, policy: str = "random", seed: Optional[int] = None) -> Dict[str, npt.NDArray]:
+        """
+        Run a rollout entirely in Rust, without the GIL, e.g. to collect warm-up data or to
+        benchmark the simulator.
+
+        The rollout continues from the current state and resets the environment whenever an
+        episode ends.
+
+        Parameters
+        ----------
+        n_steps : int
+            The number of steps to run.
+        policy : str
+            "random" for uniformly random actions, or the path of a Q-table JSON file whose
+            greedy policy chooses the actions.
+        seed : int, optional
+            Seed of the random policy.
+
+        Returns
+        -------
+        dict
+            Arrays with one entry per step, as returned by load_demonstrations: 'observation' of
+            shape (n_steps, observation_dim), taken before the step, 'action', 'reward' and 'done'.
+        """
+
+    def evaluate(self, policy_fn: Callable[[npt.NDArray[np.float32]], int], episodes: int, max_steps: int = 1000) -> Dict[str, Any]:
+        """
+        Evaluate a Python policy. Only the policy is called in Python; the resets, the episode
+        bookkeeping and the statistics are handled in Rust.
+
+        Each episode starts from a reset and ends with a crash or after max_steps steps.
+
+        Parameters
+        ----------
+        policy_fn : callable
+            Maps an observation to an integer action. The observation array is overwritten by
+            the next step, so it must be copied to be kept.
+        episodes : int
+            The number of episodes to run.
+        max_steps : int
+            The maximum length of an episode.
+
+        Returns
+        -------
+        dict
+            The 'mean_return', 'mean_length', 'lap_completion_rate' and 'crash_rate' over the
+            episodes, and the 'returns' and 'lengths' of the individual episodes as arrays.
+        """
+
+    def set_reward(
+            self,
+            *,
+            travel_coeff: Optional[float] = None,
+            center_coeff: Optional[float] = None,
+            center_integral_coeff: Optional[float] = None,
+            crash_reward: Optional[float] = None,
+            shoulder_coeff: Optional[float] = None,
+            reward_scale: Optional[float] = None,
+            reward_clip: Optional[float] = None,
+        ) -> None:
+        """
+        Change reward coefficients from the next step on, e.g. to anneal them between episodes
+        without recreating the environment.
+
+        The coefficients are as for __init__. Coefficients which are not given keep their
+        values, and a reward_clip of float('inf') removes the clipping.
+        """
+
+    def get_reward(self) -> Dict[str, Optional[float]]:
+        """
+        Get the current reward coefficients, as accepted by set_reward. The 'reward_clip' is
+        None when the reward is not clipped.
+        """
+
+    def set_start_distribution(self, distribution: str, arc: float = 0.0, n: int = 1) -> None:
+        """
+        Set where the following episodes start along the track, facing along the road.
+
+        Parameters
+        ----------
+        distribution : str
+            "uniform" for a point drawn uniformly along the track, "fixed" for the same point in
+            every episode, or "grid" for n evenly spaced points taken in turn at each reset, e.g.
+            to sweep the track in evaluation. A grid starts from its first point at the next
+            seeded reset.
+        arc : float
+            The arc length along the centerline of a fixed start.
+        n : int
+            The number of points of a grid.
+        """
+
+    def action_space(self) -> Dict[str, Any]:
+        """
+        Describe the actions of the action mode, e.g. to build a Gymnasium space with
+        `Discrete(space["n"])`.
+
+        Returns
+        -------
+        dict
+            The number of actions 'n', and the 'labels' of the actions in the order of their
+            integer values. In the "steer_throttle" mode also 'nvec', the numbers of steering
+            and throttle levels, for a `MultiDiscrete(space["nvec"])` space.
+        """
+
+    def enable_curriculum(
+            self,
+            stages: Optional[List[Tuple[str, float]]] = None,
+            window: int = 20,
+            promote_rate: float = 0.8,
+            demote_rate: float = 0.2,
+            seed: Optional[int] = None,
+        ):
+        """
+        Choose the track of each episode by a curriculum, which moves between stages of
+        increasing difficulty by the results given to report_result.
+
+        Starts a new episode on the first stage, replacing the current track and its obstacles,
+        and ends random tracks. The shoulder width of the current track is kept.
+
+        Parameters
+        ----------
+        stages : list of (str, float), optional
+            The stages as (track, width factor) pairs, where the track is one of "oval",
+            "simple_racetrack", "racetrack" or "random", for a new random track in every
+            episode, and the width factor scales the road width of the track. Defaults to all
+            four tracks in that order, at their own widths.
+        window : int
+            The number of latest results of a stage that its success rate is taken over. The
+            stage only changes once that many results are reported.
+        promote_rate : float
+            The success rate at or above which the curriculum moves on to the next stage.
+        demote_rate : float
+            The success rate at or below which the curriculum moves back to the previous stage.
+        seed : int, optional
+            Seed of the random tracks.
+        """
+
+    def enable_random_tracks(self, pool_size: Optional[int] = None, pool_seed: int = 0):
+        """
+        Drive each episode on a random track, for domain randomization.
+
+        The tracks are drawn with the random number generator of the environment, so that
+        resetting with a seed fixes the tracks of all following episodes as well. Starts a new
+        episode, replacing the current track and its obstacles, and ends any curriculum. The
+        shoulder width of the current track is kept.
+
+        Parameters
+        ----------
+        pool_size : int, optional
+            The number of tracks in a pool that the tracks are drawn from, e.g. to hold out
+            other tracks for evaluation. Without a pool, every episode has a new track.
+        pool_seed : int
+            The seed that the pool is built with, which fixes its tracks.
+        """
+
+    def disable_random_tracks(self):
+        """
+        Stay on the current track from now on.
+        """
+
+    def disable_curriculum(self):
+        """
+        Stop following the curriculum, staying on the current track.
+        """
+
+    def report_result(self, success: bool) -> bool:
+        """
+        Report whether the last episode succeeded, e.g. completed a lap, to the curriculum.
+
+        Returns
+        -------
+        bool
+            Whether the curriculum changed stage, which takes effect at the next reset.
+        """
+
+    @property
+    def curriculum_stage(self) -> Optional[int]:
+        """
+        The index of the current curriculum stage, or None without a curriculum.
+        """
+
+    @property
+    def curriculum_success_rate(self) -> Optional[float]:
+        """
+        The success rate of the results reported in the current curriculum stage, or None if
+        there are none.
+        """
+
+    def start_recording(self, path: str):
+        """
+        Record every step from now on, starting with the current episode from the current state.
+
+        Each step records the action, reward, done flag, car state and observation, and each
+        episode starts from a snapshot as returned by get_state, so that it can be replayed.
+        Copies of the environment do not record.
+
+        Parameters
+        ----------
+        path : str
+            Path to the '.json' file the recording is saved to by stop_recording.
+        """
+
+    def stop_recording(self, path: Optional[str] = None):
+        """
+        Stop recording and save the recorded episodes.
+
+        Parameters
+        ----------
+        path : str, optional
+            Path to save the recording to instead of the one given to start_recording. Needed when
+            recording was enabled by the 'record' entry of a config file.
+        """
+
+    def start_telemetry(self, path: str):
+        """
+        Log the telemetry of every step from now on to a CSV file, e.g. for analysis in pandas.
+
+        Each row is taken after a step, with the columns t, x, y, speed, steer, progress, the
+        reward terms travel_reward, center_reward, center_integral_reward, crash_reward and
+        shoulder_reward, their sum reward, done as 0 or 1, and the tyre accelerations
+        longitudinal_acc and lateral_acc with their grip_utilization, which is empty unless the
+        car config sets brake_grip. Copies of the environment do not log. A current log is
+        finished first.
+
+        Parameters
+        ----------
+        path : str
+            Path to the '.csv' file to create.
+        """
+
+    def stop_telemetry(self):
+        """
+        Stop logging telemetry and flush the CSV file. Raises an IOError if writing the file
+        failed while logging, and does nothing if no telemetry is logged.
+        """
+
+    def start_streaming(self, address: str = "127.0.0.1:8765", steps_per_frame: int = 1) -> str:
+        """
+        Stream the state of the environment live to WebSocket clients, e.g. a browser dashboard
+        watching training, replacing any current stream. Copies of the environment do not stream.
+
+        Each frame is a JSON object with the time 't', the step 'i', the (x, y) corners of the
+        'car', the 'lidar_center' and 'lidar_points' where the rays hit, the 'speed' and the
+        'steer_delta'. Clients that disconnect or fall behind are dropped.
+
+        Parameters
+        ----------
+        address : str
+            The address to listen on, with port 0 for any free port.
+        steps_per_frame : int
+            Send a frame every this many steps.
+
+        Returns
+        -------
+        str
+            The address listened on.
+        """
+
+    def stop_streaming(self):
+        """
+        Stop streaming and disconnect all clients.
+        """
+
+    @staticmethod
+    def from_config(
+            config_file: str,
+            track_file: Optional[str] = None,
+            track_width: Optional[float] = None,
+            shoulder_width: Optional[float] = None,
+            seed: Optional[int] = None,
+        ) -> "RacingEnv":
+        """
+        Create a racing environment with the simulator settings of a config file.
+
+        The config file holds the time step 'dt' and the tables 'car', 'reward', 'lidar' and
+        'observation', in the layout returned by config_dict. Missing entries take their default
+        values, so a file only needs to list what it changes, e.g. in TOML:
+
+            dt = 0.1
+
+            [reward]
+            crash_reward = -50.0
+
+            [lidar]
+            angles = [5.0, 15.0, 45.0, 90.0]
+
+        Parameters
+        ----------
+        config_file : str
+            Path to a '.json' or '.toml' config file.
+        track_file, track_width, shoulder_width, seed
+            See RacingEnv.
+        """
+
+    def config_dict(self) -> Dict[str, Any]:
+        """
+        Get the simulator settings of the environment as a dict, in the layout of a config file.
+        """
+
+    @property
+    def config(self) -> "SimConfig":
+        """
+        A copy of the simulator settings of the environment. Changing it does not change the
+        environment, but it can be given to create another one.
+        """
+
     @property
     def dt(self) -> float:
         """
@@ -91,7 +618,119 @@ class RacingEnv:
         """
 
     @property
-    def observation_dim(self) -> int
+    def on_shoulder(self) -> bool:
+        """
+        Whether the car is currently on the shoulder of the road.
+        """
+
+    @property
+    def reversed(self) -> bool:
+        """
+        Whether the current episode is driven in the reverse direction of the track.
+        """
+
+    @property
+    def edge_distance(self) -> float:
+        """
+        The signed distance from the car to the nearest road edge. See distance_to_edge.
+        """
+
+    @property
+    def track_length(self) -> float:
+        """
+        The length of the centerline of the track, in meters.
+        """
+
+    @property
+    def track_width(self) -> float:
+        """
+        The width of the road, in meters.
+        """
+
+    @property
+    def n_segments(self) -> int:
+        """
+        The number of Bezier segments of the centerline.
+        """
+
+    @property
+    def min_radius(self) -> float:
+        """
+        The radius of the sharpest turn of the centerline, in meters. Infinite for a straight road.
+        """
+
+    @property
+    def mean_abs_curvature(self) -> float:
+        """
+        The mean absolute curvature along the centerline, in 1/meters.
+        """
+
+    @property
+    def track_bounds(self) -> Tuple[Tuple[float, float], Tuple[float, float]]:
+        """
+        The ((x_min, y_min), (x_max, y_max)) corners of an axis-aligned box containing the road,
+        e.g. to set the limits of a plot.
+        """
+
+    @property
+    def progress_m(self) -> float:
+        """
+        The arc length along the centerline of the point closest to the car, in meters.
+        """
+
+    @property
+    def progress_fraction(self) -> float:
+        """
+        The progress along the centerline as a fraction of the track length, between 0 and 1.
+        """
+
+    @property
+    def lap(self) -> int:
+        """
+        The number of laps completed since the last reset, in the direction of the episode.
+        """
+
+    @property
+    def lap_time(self) -> float:
+        """
+        The time spent in the current lap, i.e. since the last completed lap or the reset.
+        """
+
+    @property
+    def energy(self) -> Optional[float]:
+        """
+        The energy left to accelerate with, or None if it is unlimited.
+        """
+
+    @property
+    def wheels_locked(self) -> bool:
+        """
+        Whether the wheels are locked from braking too hard in a turn, which reduces the grip
+        for steering for a short time. See CarConfig.brake_grip.
+        """
+
+    @property
+    def longitudinal_acc(self) -> float:
+        """
+        The acceleration along the heading of the car at the last step, negative when slowing down.
+        """
+
+    @property
+    def lateral_acc(self) -> float:
+        """
+        The acceleration across the heading of the car at the last step, positive when turning
+        to the left.
+        """
+
+    @property
+    def grip_utilization(self) -> Optional[float]:
+        """
+        The fraction of the tyre grip that the combined longitudinal and lateral acceleration of
+        the last step used, or None if the car has no grip limit. See CarConfig.brake_grip.
+        """
+
+    @property
+    def observation_dim(self) -> int:
         """
         The number of features in the observation space.
         """
@@ -106,6 +745,122 @@ class RacingEnv:
             A struct holding information about relevant coordinates of points in the simulator environment.
         """
 
+    def add_circle_obstacle(self, distance: float, lateral: float = 0.0, radius: float = 0.5):
+        """
+        Place a circular obstacle (e.g. a cone) on the road.
+
+        Obstacles are visible to the LiDAR, and hitting one counts as a crash.
+
+        Parameters
+        ----------
+        distance : float
+            The distance along the centerline at which to place the obstacle.
+        lateral : float
+            The displacement of the obstacle to the left of the centerline. Default is 0.
+        radius : float
+            The radius of the obstacle. Default is 0.5.
+        """
+
+    def add_rectangle_obstacle(self, distance: float, lateral: float = 0.0, length: float = 1.0, width: float = 1.0):
+        """
+        Place a rectangular obstacle on the road, aligned with the road direction.
+
+        Obstacles are visible to the LiDAR, and hitting one counts as a crash.
+
+        Parameters
+        ----------
+        distance : float
+            The distance along the centerline at which to place the obstacle.
+        lateral : float
+            The displacement of the obstacle to the left of the centerline. Default is 0.
+        length : float
+            The extent of the obstacle along the road. Default is 1.
+        width : float
+            The extent of the obstacle across the road. Default is 1.
+        """
+
+    def add_pace_car(self, start_offset: float = 20.0, speed: float = 8.0, lateral: float = 0.0, length: float = 4.0, width: float = 1.6):
+        """
+        Add a pace car which drives along the road at a fixed speed.
+
+        The pace car is a moving rectangular obstacle; it is visible to the LiDAR, and hitting it counts as a crash.
+        Takes effect from the next reset.
+
+        Parameters
+        ----------
+        start_offset : float
+            The distance along the centerline ahead of the car at which the pace car starts each episode. Default is 20.
+        speed : float
+            The speed of the pace car. Default is 8.
+        lateral : float
+            The displacement of the pace car to the left of the centerline. Default is 0.
+        length : float
+            The length of the pace car. Default is 4.
+        width : float
+            The width of the pace car. Default is 1.6.
+        """
+
+    def clear_obstacles(self):
+        """
+        Remove all static obstacles and pace cars from the road.
+        """
+
+    def save_track(self, path: str):
+        """
+        Save the current track, including static obstacles, to a '.json' or '.toml' track definition file.
+
+        Parameters
+        ----------
+        path : str
+            The path of the file to write. The format is given by the file extension.
+        """
+
+    def distance_to_edge(self, x: float, y: float) -> float:
+        """
+        Get the signed distance from a point to the nearest road edge, e.g. as a safety margin.
+
+        Obstacles are not taken into account.
+
+        Parameters
+        ----------
+        x, y : float
+            The coordinates of the point.
+
+        Returns
+        -------
+        float
+            The distance in meters, positive on the road and negative outside it.
+        """
+
+    def closest_point(self, x: float, y: float) -> Tuple[float, float, float]:
+        """
+        Project a point onto the centerline of the track, e.g. to put recorded trajectories in
+        track coordinates.
+
+        Parameters
+        ----------
+        x, y : float
+            The coordinates of the point.
+
+        Returns
+        -------
+        (u, s, lateral) : (float, float, float)
+            The spline parameter and the arc length of the closest point of the centerline, and
+            the signed distance of the point to the left of the centerline.
+        """
+
+    def tangent_at(self, u: float) -> Tuple[float, float]:
+        """
+        Get the unit tangent (x, y) of the centerline at the spline parameter u, which runs
+        from 0 to n_segments. Parameters outside of that range wrap around a closed track.
+        """
+
+    def point_at_arclength(self, s: float) -> Tuple[float, float]:
+        """
+        Get the point (x, y) of the centerline at the arc length s, which wraps around the
+        track.
+        """
+
     def export_road(self, n_segments: int) -> 'SplineRoadExport':
         """
         Returns a representation of the coordinates of the road segments useful for rendering.
@@ -121,7 +876,422 @@ class RacingEnv:
             A struct holding the static coordinates of the road
         """
 
+    def rasterize(self, px_per_m: float = 2.0) -> npt.NDArray[np.uint8]:
+        """
+        Render the road as a grayscale image covering its bounding box, without launching the game.
+
+        Parameters
+        ----------
+        px_per_m : float
+            The resolution of the image in pixels per meter. Default is 2.
+
+        Returns
+        -------
+        image : np.ndarray
+            A (height, width) uint8 array, 255 on the road and 0 off the road or on a static obstacle.
+            The first row is the top (largest y) edge of the track.
+        """
+
+    def render_rgb(self, width: int, height: int) -> npt.NDArray[np.uint8]:
+        """
+        Render the track, the LiDAR rays and the car as a color image, without a window, e.g. to
+        make videos on a headless server.
+
+        Parameters
+        ----------
+        width : int
+            Width of the image in pixels.
+        height : int
+            Height of the image in pixels.
 
+        Returns
+        -------
+        image : np.ndarray
+            A (height, width, 3) uint8 RGB array showing the whole track, scaled to fit. The first
+            row is the top (largest y) edge of the image.
+        """
+
+
+
+class CellRacingEnv:
+    def __init__(
+            self,
+            track: str = "circuit",
+            track_file: Optional[str] = None,
+            cell_size: float = 20.0,
+            dt: Optional[float] = None,
+            crash_reward: Optional[float] = None,
+            travel_coeff: Optional[float] = None,
+            center_coeff: Optional[float] = None,
+            center_integral_coeff: Optional[float] = None,
+            reward_scale: Optional[float] = None,
+            reward_clip: Optional[float] = None,
+            observe_delta: Optional[bool] = True,
+            observe_speed: Optional[bool] = True,
+            seed: Optional[int] = None,
+        ):
+        """
+        Create a new racing environment on a grid-based track.
+
+        The track is a loop of square cells, and the car crashes when leaving the loop.
+        Otherwise shares the step/observe API and reward structure of RacingEnv.
+
+        Parameters
+        ----------
+        track : str
+            The name of the track layout, one of 'circuit' or 'fold'. Default is 'circuit'.
+        track_file : str, optional
+            A text file with the track drawn as one character per cell, which replaces 'track'.
+            Each cell is the direction to the next cell of the loop, one of '>', '<', '^' or 'v',
+            with '.' or ' ' for empty positions and the top line furthest up, e.g.
+
+                v<<
+                v.^
+                >>^
+
+            The loop starts at the leftmost cell of the bottom line.
+        cell_size : float
+            The side length of each cell, and thereby the width of the road. Default is 20.
+        dt, crash_reward, travel_coeff, center_coeff, center_integral_coeff, reward_scale, reward_clip, observe_delta, observe_speed, seed
+            See RacingEnv.
+        """
+
+    def step(self, action: int) -> Tuple[npt.NDArray[np.float32], float, bool]:
+        """
+        Perform a single MDP update step and return (observation, reward, done). See
+        RacingEnv.step.
+        """
+
+    def reset(self, seed: Optional[int] = None):
+        """
+        Reset the environment. See RacingEnv.reset.
+        """
+
+    def observe(self) -> npt.NDArray[np.float32]:
+        """
+        Observe the current state of the environment. See RacingEnv.observe.
+        """
+
+    def __copy__(self) -> "CellRacingEnv":
+        """
+        Fork the environment. See RacingEnv.__copy__.
+        """
+
+    def __deepcopy__(self, memo: dict) -> "CellRacingEnv": ...
+
+    def get_state(self) -> bytes:
+        """
+        Take a snapshot of the dynamic state of the environment. See RacingEnv.get_state.
+        """
+
+    def set_state(self, state: bytes):
+        """
+        Restore a snapshot taken by get_state. See RacingEnv.set_state.
+        """
+
+    def get_trajectory(self) -> Dict[str, npt.NDArray]:
+        """
+        Get the path of the car through the current episode. See RacingEnv.get_trajectory.
+        """
+
+    def observation_space(self) -> Dict[str, Any]:
+        """
+        Describe the observation vector. See RacingEnv.observation_space.
+        """
+
+    def rollout(self, n_steps: int, policy: str = "random", seed: Optional[int] = None) -> Dict[str, npt.NDArray]:
+        """
+        Run a rollout entirely in Rust. See RacingEnv.rollout.
+        """
+
+    def evaluate(self, policy_fn: Callable[[npt.NDArray[np.float32]], int], episodes: int, max_steps: int = 1000) -> Dict[str, Any]:
+        """
+        Evaluate a Python policy. See RacingEnv.evaluate.
+        """
+
+    def set_reward(
+            self,
+            *,
+            travel_coeff: Optional[float] = None,
+            center_coeff: Optional[float] = None,
+            center_integral_coeff: Optional[float] = None,
+            crash_reward: Optional[float] = None,
+            shoulder_coeff: Optional[float] = None,
+            reward_scale: Optional[float] = None,
+            reward_clip: Optional[float] = None,
+        ) -> None:
+        """
+        Change reward coefficients from the next step on. See RacingEnv.set_reward.
+        """
+
+    def get_reward(self) -> Dict[str, Optional[float]]:
+        """
+        Get the current reward coefficients. See RacingEnv.get_reward.
+        """
+
+    def set_start_distribution(self, distribution: str, arc: float = 0.0, n: int = 1) -> None:
+        """
+        Set where the following episodes start. See RacingEnv.set_start_distribution.
+        """
+
+    def action_space(self) -> Dict[str, Any]:
+        """
+        Describe the discrete actions. See RacingEnv.action_space.
+        """
+
+    def rasterize(self, px_per_m: float = 1.0) -> npt.NDArray[np.uint8]:
+        """
+        Render the road as a grayscale image. See RacingEnv.rasterize.
+        """
+
+    def render_rgb(self, width: int, height: int) -> npt.NDArray[np.uint8]:
+        """
+        Render the track, the LiDAR rays and the car as a color image. See RacingEnv.render_rgb.
+        """
+
+    @property
+    def dt(self) -> float: ...
+    @property
+    def t(self) -> float: ...
+    @property
+    def i(self) -> int: ...
+    @property
+    def observation_dim(self) -> int: ...
+
+
+class MultiCarRacingEnv:
+    """
+    Several cars racing head-to-head on the same track, following the PettingZoo parallel API.
+
+    Each car sees the others with its LiDAR, and crashes if it drives into one. A crashed car
+    leaves the episode, and with it the track. The agents are named 'car_0', 'car_1' and so on,
+    and start in single file behind 'car_0'.
+    """
+
+    def __init__(
+            self,
+            n_agents: int = 2,
+            track_file: Optional[str] = None,
+            track_width: Optional[float] = None,
+            shoulder_width: Optional[float] = None,
+            dt: Optional[float] = None,
+            action_mode: str = "discrete",
+            grid_spacing: float = 8.0,
+            max_steps: Optional[int] = None,
+            seed: Optional[int] = None,
+        ):
+        """
+        Create a new multi-car racing environment.
+
+        Parameters
+        ----------
+        n_agents : int
+            The number of cars.
+        track_file, track_width, shoulder_width, dt, action_mode
+            As for RacingEnv.
+        grid_spacing : float
+            The distance along the track between consecutive cars at the start, in meters.
+        max_steps : int, optional
+            The number of steps after which all agents are truncated. Never truncated if not given.
+        seed : int, optional
+            The seed of the random number generator of the environment. Drawn from the operating
+            system if not given.
+        """
+
+    def reset(self, seed: Optional[int] = None, options: Optional[Dict[str, Any]] = None) -> Tuple[Dict[str, npt.NDArray[np.float32]], Dict[str, Dict[str, Any]]]:
+        """
+        Start a new episode with all agents, returning their observations and infos.
+        """
+
+    def step(self, actions: Dict[str, int]) -> Tuple[Dict[str, npt.NDArray[np.float32]], Dict[str, float], Dict[str, bool], Dict[str, bool], Dict[str, Dict[str, Any]]]:
+        """
+        Step all agents in the episode at once.
+
+        Parameters
+        ----------
+        actions : dict
+            The action of every agent in the episode, in the action mode.
+
+        Returns
+        -------
+        (observations, rewards, terminations, truncations, infos)
+            Dicts keyed by the agents that were stepped. Agents that terminate, by crashing, or
+            are truncated leave the episode.
+        """
+
+    def observation_space(self, agent: str) -> Dict[str, Any]:
+        """
+        Describe the observation vector of an agent. See RacingEnv.observation_space.
+        """
+
+    def action_space(self, agent: str) -> Dict[str, Any]:
+        """
+        Describe the actions of an agent. See RacingEnv.action_space.
+        """
+
+    @property
+    def possible_agents(self) -> List[str]: ...
+    @property
+    def agents(self) -> List[str]:
+        """
+        The agents still in the episode.
+        """
+    @property
+    def num_agents(self) -> int: ...
+    @property
+    def max_num_agents(self) -> int: ...
+
+
+class CarConfig:
+    """
+    The physical parameters of the car. All fields can be read and assigned.
+    """
+
+    length: float
+    width: float
+    """The width of the footprint of the car, which crashes when any of its corners leaves the road."""
+    front_axle: float
+    back_axle: float
+    max_delta: float
+    acceleration: float
+    brake_acceleration: float
+    steer_speed: float
+    shoulder_deceleration: float
+    steer_target_rate: Optional[float]
+    energy_capacity: Optional[float]
+    engine_power: Optional[float]
+    """The engine power per unit mass in W/kg, which limits the acceleration to engine_power/speed at high speed."""
+    drag_coeff: float
+    """The coefficient of the air drag, which decelerates the car by drag_coeff*speed**2."""
+    brake_grip: Optional[float]
+    """The largest combined braking and lateral acceleration before braking locks the wheels. They never lock if None."""
+    lock_duration: float
+    """The time in seconds that the wheels stay locked."""
+    locked_lateral_grip: float
+    """The largest lateral acceleration while the wheels are locked, which widens the turns."""
+    reverse_acceleration: Optional[float]
+    """The acceleration of the reverse gear, which braking engages once the car has stopped. The car cannot drive backwards if None."""
+    max_reverse_speed: float
+    """The largest speed when driving backwards. The speed is negative while reversing."""
+
+    @property
+    def top_speed(self) -> Optional[float]:
+        """
+        The speed at which the drag balances full acceleration, or None without drag.
+        """
+
+    def __init__(self, **kwargs: Any):
+        """
+        Create a car config with the default values, changed by any fields given as keyword
+        arguments. Unknown fields raise a TypeError.
+        """
+
+    def to_dict(self) -> Dict[str, Any]:
+        """
+        Get the config as a dict, in the layout of the 'car' table of a config file.
+        """
+
+    @staticmethod
+    def from_dict(dict: Dict[str, Any]) -> "CarConfig":
+        """
+        Create a config from a dict as returned by to_dict. Missing fields take their defaults.
+        """
+
+
+class RewardConfig:
+    """
+    The coefficients of the reward terms, as for the arguments of RacingEnv. All fields can be
+    read and assigned, with a reward_clip of None for no clipping.
+    """
+
+    travel_coeff: float
+    center_coeff: float
+    center_integral_coeff: float
+    crash_reward: float
+    shoulder_coeff: float
+    reward_scale: float
+    reward_clip: Optional[float]
+
+    def __init__(self, **kwargs: Any):
+        """
+        Create a reward config with the default values, changed by any fields given as keyword
+        arguments. Unknown fields raise a TypeError.
+        """
+
+    def to_dict(self) -> Dict[str, Any]:
+        """
+        Get the config as a dict, in the layout of the 'reward' table of a config file.
+        """
+
+    @staticmethod
+    def from_dict(dict: Dict[str, Any]) -> "RewardConfig":
+        """
+        Create a config from a dict as returned by to_dict. Missing fields take their defaults.
+        """
+
+
+class SimConfig:
+    """
+    The full configuration of a simulator, e.g. to give to RacingEnv. All fields can be read and
+    assigned. The car and reward configs are shared rather than copied, so that
+    `config.car.length = 5.0` changes the simulator config.
+    """
+
+    car: CarConfig
+    reward: RewardConfig
+    dt: float
+    lidar_angles: List[float]
+    """The angles in degrees of the LiDAR rays on one side, mirrored to the other, besides the one straight ahead."""
+    observe_delta: bool
+    observe_speed: bool
+    observe_shoulder: bool
+    observe_energy: bool
+    observe_grip: bool
+    action_mode: str
+    sticky_action_prob: float
+    action_delay: int
+    observation_delay: int
+    heading_noise: float
+    lateral_noise: float
+    reverse_prob: float
+    crash_mode: str
+    restitution: float
+    ray_tolerance: float
+    """The distance from the road edge at which a marched LiDAR ray is considered to have hit it."""
+    ray_max_steps: int
+    """The maximum number of steps along a marched LiDAR ray, reached only by rays grazing the edge."""
+    ray_max_distance: Optional[float]
+    """The distance at which LiDAR rays stop as if they had hit the edge, or None for no limit."""
+    record: bool
+
+    def __init__(self, **kwargs: Any):
+        """
+        Create a simulator config with the default values, changed by any fields given as
+        keyword arguments. Unknown fields raise a TypeError.
+        """
+
+    def to_dict(self) -> Dict[str, Any]:
+        """
+        Get the config as a dict, in the layout of a config file. See RacingEnv.from_config.
+        """
+
+    @staticmethod
+    def from_dict(dict: Dict[str, Any]) -> "SimConfig":
+        """
+        Create a config from a dict in the layout of a config file. Missing fields take their
+        defaults.
+        """
+
+    def save(self, path: str) -> None:
+        """
+        Save the config, with all fields, to a '.json' or '.toml' config file.
+        """
+
+    @staticmethod
+    def load(path: str) -> "SimConfig":
+        """
+        Load a config from a '.json' or '.toml' config file.
+        """
 
 
 class SplineRoadExport:
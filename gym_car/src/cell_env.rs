@@ -0,0 +1,198 @@
+use pyo3::prelude::*;
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::types::{PyBytes, PyDict};
+use numpy::{PyArray1, PyArray2, PyArray3};
+
+use car_sim::map;
+use car_sim::gym;
+
+use super::{rasterize_road, observation_buffer, observe_into_buffer, step_detached, state_bytes, restore_state, trajectory_dict, render_rgb_array, observation_space_dict, action_space_dict, rollout_dict, evaluate_dict, parse_start_distribution, update_reward, reward_dict};
+
+
+/// A racing environment on a grid-based track
+#[pyclass(module="gym_car")]
+pub struct CellRacingEnv {
+    sim: gym::Simulator<map::CellMap>,
+    /// Array holding the latest observation, reused between calls to 'observe'
+    observation: Py<PyArray1<f32>>,
+}
+
+
+#[pymethods]
+impl CellRacingEnv {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(
+        signature = (track="circuit", track_file=None, cell_size=20.0, dt=None, crash_reward=None, travel_coeff=None, center_coeff=None, center_integral_coeff=None, reward_scale=None, reward_clip=None, observe_delta=true, observe_speed=true, seed=None)
+    )]
+    fn new(
+        track: &str,
+        track_file: Option<std::path::PathBuf>,
+        cell_size: f32,
+        dt: Option<f32>,
+        crash_reward: Option<f32>,
+        travel_coeff: Option<f32>,
+        center_coeff: Option<f32>,
+        center_integral_coeff: Option<f32>,
+        reward_scale: Option<f32>,
+        reward_clip: Option<f32>,
+        observe_delta: bool,
+        observe_speed: bool,
+        seed: Option<u64>,
+        py: Python<'_>,
+    ) -> PyResult<Self> {
+        let road = match track_file {
+            Some(path) => {
+                let text = std::fs::read_to_string(path).map_err(|err| PyIOError::new_err(format!("Could not access track file: {}", err)))?;
+                map::CellMap::from_ascii(&text, cell_size).map_err(|err| PyValueError::new_err(format!("Could not parse track file: {}", err)))?
+            },
+            None => {
+                let cells: &[map::Cell] = match track {
+                    "circuit" => &map::CIRCUIT,
+                    "fold" => &map::FOLD,
+                    _ => return Err(PyValueError::new_err(
+                        format!("Unknown track '{}'. Track must be one of 'circuit' or 'fold'.", track)
+                    )),
+                };
+                map::CellMap::new(cells, cell_size)
+            },
+        };
+
+        let observation = gym::ObservationConfig { delta: observe_delta, speed: observe_speed, shoulder: false, energy: false, grip: false };
+        let mut config = gym::SimConfig { observation, ..gym::SimConfig::default() };
+        if let Some(dt) = dt {
+            config.dt = dt;
+        }
+        if let Some(crash_reward) = crash_reward {
+            config.reward.crash_reward = crash_reward;
+        }
+        if let Some(travel_coeff) = travel_coeff {
+            config.reward.travel_coeff = travel_coeff;
+        }
+        if let Some(center_coeff) = center_coeff {
+            config.reward.center_coeff = center_coeff;
+        }
+        if let Some(center_integral_coeff) = center_integral_coeff {
+            config.reward.center_integral_coeff = center_integral_coeff;
+        }
+        if let Some(reward_scale) = reward_scale {
+            config.reward.reward_scale = reward_scale;
+        }
+        config.reward.reward_clip = reward_clip;
+
+        let sim = gym::Simulator::new(config, road, seed);
+        let observation = observation_buffer(py, &sim);
+        let mut this = Self { sim, observation };
+        this.reset(None);
+        Ok(this)
+    }
+
+    #[pyo3( signature = (seed=None) )]
+    fn reset(&mut self, seed: Option<u64>) {
+        self.sim.reset(seed)
+    }
+
+    fn step(&mut self, py: Python<'_>, action: u8) -> PyResult<(Py<PyArray1<f32>>, f32, bool)> {
+        step_detached(py, &mut self.sim, &self.observation, action)
+    }
+
+    fn observe<'py>(&self, py: Python<'py>) -> PyResult<Py<PyArray1<f32>>> {
+        observe_into_buffer(py, &self.sim, &self.observation)
+    }
+
+    /// Fork the environment, e.g. for planning. The copy shares no state with the original, so
+    /// shallow and deep copies are the same.
+    fn __copy__(&self, py: Python<'_>) -> Self {
+        let sim = self.sim.clone();
+        let observation = observation_buffer(py, &sim);
+        Self { sim, observation }
+    }
+
+    fn __deepcopy__(&self, py: Python<'_>, _memo: &Bound<'_, PyAny>) -> Self {
+        self.__copy__(py)
+    }
+
+    fn get_state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        state_bytes(py, &self.sim)
+    }
+
+    fn set_state(&mut self, state: &[u8]) -> PyResult<()> {
+        restore_state(&mut self.sim, state)
+    }
+
+    fn get_trajectory<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        trajectory_dict(py, &self.sim)
+    }
+
+    fn observation_space<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        observation_space_dict(py, &self.sim.config)
+    }
+
+    #[pyo3( signature = (n_steps, policy="random", seed=None) )]
+    fn rollout<'py>(&mut self, py: Python<'py>, n_steps: usize, policy: &str, seed: Option<u64>) -> PyResult<Bound<'py, PyDict>> {
+        rollout_dict(py, &mut self.sim, policy, n_steps, seed)
+    }
+
+    #[pyo3( signature = (policy_fn, episodes, max_steps=1000) )]
+    fn evaluate<'py>(&mut self, py: Python<'py>, policy_fn: &Bound<'py, PyAny>, episodes: usize, max_steps: usize) -> PyResult<Bound<'py, PyDict>> {
+        evaluate_dict(py, &mut self.sim, &self.observation, policy_fn, episodes, max_steps)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3( signature = (*, travel_coeff=None, center_coeff=None, center_integral_coeff=None, crash_reward=None, shoulder_coeff=None, reward_scale=None, reward_clip=None) )]
+    fn set_reward(
+        &mut self,
+        travel_coeff: Option<f32>,
+        center_coeff: Option<f32>,
+        center_integral_coeff: Option<f32>,
+        crash_reward: Option<f32>,
+        shoulder_coeff: Option<f32>,
+        reward_scale: Option<f32>,
+        reward_clip: Option<f32>,
+    ) {
+        update_reward(&mut self.sim.config.reward, travel_coeff, center_coeff, center_integral_coeff, crash_reward, shoulder_coeff, reward_scale, reward_clip);
+    }
+
+    fn get_reward<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        reward_dict(py, &self.sim.config.reward)
+    }
+
+    #[pyo3( signature = (distribution, arc=0.0, n=1) )]
+    fn set_start_distribution(&mut self, distribution: &str, arc: f32, n: usize) -> PyResult<()> {
+        self.sim.config.start = parse_start_distribution(distribution, arc, n)?;
+        Ok(())
+    }
+
+    fn action_space<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        action_space_dict(py, self.sim.config.action_mode)
+    }
+
+    #[pyo3( signature = (px_per_m=1.0) )]
+    fn rasterize<'py>(&self, py: Python<'py>, px_per_m: f32) -> PyResult<Py<PyArray2<u8>>> {
+        rasterize_road(py, &self.sim.road, px_per_m)
+    }
+
+    fn render_rgb<'py>(&self, py: Python<'py>, width: usize, height: usize) -> PyResult<Py<PyArray3<u8>>> {
+        render_rgb_array(py, &self.sim, width, height)
+    }
+
+    #[getter]
+    fn dt(&self) -> f32 {
+        self.sim.config.dt
+    }
+
+    #[getter]
+    fn t(&self) -> f32 {
+        self.sim.get_t()
+    }
+
+    #[getter]
+    fn i(&self) -> usize {
+        self.sim.get_i()
+    }
+
+    #[getter]
+    fn observation_dim(&self) -> usize {
+        self.sim.observation_dim()
+    }
+}
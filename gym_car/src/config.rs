@@ -0,0 +1,671 @@
+use pyo3::prelude::*;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::types::PyDict;
+
+use car_sim::gym;
+use car_sim::lidar::LidarArray;
+use car_sim::physics;
+
+use super::{parse_action_mode, parse_crash_mode, config_error};
+
+
+/// Set the attributes of a config object from keyword arguments, rejecting unknown names
+fn set_attributes(object: &Bound<'_, PyAny>, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+    for (name, value) in kwargs.into_iter().flatten() {
+        let name: String = name.extract()?;
+        if name.starts_with('_') || !object.hasattr(&name)? {
+            return Err(PyTypeError::new_err(format!("Unknown config field '{}'.", name)));
+        }
+        object.setattr(name, value)?;
+    }
+    Ok(())
+}
+
+
+/// Convert a serializable config to a dict, in the layout of a config file
+fn to_dict<'py, T: serde::Serialize>(py: Python<'py>, config: &T) -> PyResult<Bound<'py, PyAny>> {
+    let json = serde_json::to_string(config)
+        .map_err(|err| PyValueError::new_err(format!("Could not serialize config: {}", err)))?;
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+
+/// Convert a dict in the layout of a config file to a config, with missing fields at their defaults
+fn from_dict<T: serde::de::DeserializeOwned>(dict: &Bound<'_, PyAny>) -> PyResult<T> {
+    let json: String = dict.py().import("json")?.call_method1("dumps", (dict,))?.extract()?;
+    serde_json::from_str(&json).map_err(|err| PyValueError::new_err(format!("Could not parse config: {}", err)))
+}
+
+
+/// The physical parameters of the car
+#[pyclass(name = "CarConfig", module = "gym_car")]
+#[derive(Clone)]
+pub struct PyCarConfig {
+    pub config: physics::CarConfig,
+}
+
+#[pymethods]
+impl PyCarConfig {
+    /// Create a car config with the defaults, changed by any keyword arguments
+    #[new]
+    #[pyo3( signature = (**kwargs) )]
+    fn new(py: Python<'_>, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let this = Bound::new(py, Self { config: physics::CarConfig::default() })?;
+        set_attributes(this.as_any(), kwargs)?;
+        Ok(this.borrow().clone())
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_dict(py, &self.config)
+    }
+
+    #[staticmethod]
+    fn from_dict(dict: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(Self { config: from_dict(dict)? })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("CarConfig({:?})", self.config)
+    }
+
+    #[getter]
+    fn length(&self) -> f32 {
+        self.config.length
+    }
+
+    #[setter]
+    fn set_length(&mut self, length: f32) {
+        self.config.length = length;
+    }
+
+    #[getter]
+    fn width(&self) -> f32 {
+        self.config.width
+    }
+
+    #[setter]
+    fn set_width(&mut self, width: f32) {
+        self.config.width = width;
+    }
+
+    #[getter]
+    fn front_axle(&self) -> f32 {
+        self.config.front_axle
+    }
+
+    #[setter]
+    fn set_front_axle(&mut self, front_axle: f32) {
+        self.config.front_axle = front_axle;
+    }
+
+    #[getter]
+    fn back_axle(&self) -> f32 {
+        self.config.back_axle
+    }
+
+    #[setter]
+    fn set_back_axle(&mut self, back_axle: f32) {
+        self.config.back_axle = back_axle;
+    }
+
+    #[getter]
+    fn max_delta(&self) -> f32 {
+        self.config.max_delta
+    }
+
+    #[setter]
+    fn set_max_delta(&mut self, max_delta: f32) {
+        self.config.max_delta = max_delta;
+    }
+
+    #[getter]
+    fn acceleration(&self) -> f32 {
+        self.config.acceleration
+    }
+
+    #[setter]
+    fn set_acceleration(&mut self, acceleration: f32) {
+        self.config.acceleration = acceleration;
+    }
+
+    #[getter]
+    fn brake_acceleration(&self) -> f32 {
+        self.config.brake_acceleration
+    }
+
+    #[setter]
+    fn set_brake_acceleration(&mut self, brake_acceleration: f32) {
+        self.config.brake_acceleration = brake_acceleration;
+    }
+
+    #[getter]
+    fn steer_speed(&self) -> f32 {
+        self.config.steer_speed
+    }
+
+    #[setter]
+    fn set_steer_speed(&mut self, steer_speed: f32) {
+        self.config.steer_speed = steer_speed;
+    }
+
+    #[getter]
+    fn shoulder_deceleration(&self) -> f32 {
+        self.config.shoulder_deceleration
+    }
+
+    #[setter]
+    fn set_shoulder_deceleration(&mut self, shoulder_deceleration: f32) {
+        self.config.shoulder_deceleration = shoulder_deceleration;
+    }
+
+    #[getter]
+    fn steer_target_rate(&self) -> Option<f32> {
+        self.config.steer_target_rate
+    }
+
+    #[setter]
+    fn set_steer_target_rate(&mut self, steer_target_rate: Option<f32>) {
+        self.config.steer_target_rate = steer_target_rate;
+    }
+
+    #[getter]
+    fn energy_capacity(&self) -> Option<f32> {
+        self.config.energy_capacity
+    }
+
+    #[setter]
+    fn set_energy_capacity(&mut self, energy_capacity: Option<f32>) {
+        self.config.energy_capacity = energy_capacity;
+    }
+
+    #[getter]
+    fn engine_power(&self) -> Option<f32> {
+        self.config.engine_power
+    }
+
+    #[setter]
+    fn set_engine_power(&mut self, engine_power: Option<f32>) {
+        self.config.engine_power = engine_power;
+    }
+
+    #[getter]
+    fn drag_coeff(&self) -> f32 {
+        self.config.drag_coeff
+    }
+
+    #[setter]
+    fn set_drag_coeff(&mut self, drag_coeff: f32) {
+        self.config.drag_coeff = drag_coeff;
+    }
+
+    #[getter]
+    fn brake_grip(&self) -> Option<f32> {
+        self.config.brake_grip
+    }
+
+    #[setter]
+    fn set_brake_grip(&mut self, brake_grip: Option<f32>) {
+        self.config.brake_grip = brake_grip;
+    }
+
+    #[getter]
+    fn lock_duration(&self) -> f32 {
+        self.config.lock_duration
+    }
+
+    #[setter]
+    fn set_lock_duration(&mut self, lock_duration: f32) {
+        self.config.lock_duration = lock_duration;
+    }
+
+    #[getter]
+    fn locked_lateral_grip(&self) -> f32 {
+        self.config.locked_lateral_grip
+    }
+
+    #[setter]
+    fn set_locked_lateral_grip(&mut self, locked_lateral_grip: f32) {
+        self.config.locked_lateral_grip = locked_lateral_grip;
+    }
+
+    #[getter]
+    fn reverse_acceleration(&self) -> Option<f32> {
+        self.config.reverse_acceleration
+    }
+
+    #[setter]
+    fn set_reverse_acceleration(&mut self, reverse_acceleration: Option<f32>) {
+        self.config.reverse_acceleration = reverse_acceleration;
+    }
+
+    #[getter]
+    fn max_reverse_speed(&self) -> f32 {
+        self.config.max_reverse_speed
+    }
+
+    #[setter]
+    fn set_max_reverse_speed(&mut self, max_reverse_speed: f32) {
+        self.config.max_reverse_speed = max_reverse_speed;
+    }
+
+    /// Get the speed at which the drag balances full acceleration, if there is drag
+    #[getter]
+    fn top_speed(&self) -> Option<f32> {
+        self.config.top_speed()
+    }
+}
+
+
+/// The coefficients of the reward terms
+#[pyclass(name = "RewardConfig", module = "gym_car")]
+#[derive(Clone)]
+pub struct PyRewardConfig {
+    pub config: gym::RewardConfig,
+}
+
+#[pymethods]
+impl PyRewardConfig {
+    /// Create a reward config with the defaults, changed by any keyword arguments
+    #[new]
+    #[pyo3( signature = (**kwargs) )]
+    fn new(py: Python<'_>, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let this = Bound::new(py, Self { config: gym::RewardConfig::default() })?;
+        set_attributes(this.as_any(), kwargs)?;
+        Ok(this.borrow().clone())
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_dict(py, &self.config)
+    }
+
+    #[staticmethod]
+    fn from_dict(dict: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Ok(Self { config: from_dict(dict)? })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("RewardConfig({:?})", self.config)
+    }
+
+    #[getter]
+    fn travel_coeff(&self) -> f32 {
+        self.config.travel_coeff
+    }
+
+    #[setter]
+    fn set_travel_coeff(&mut self, travel_coeff: f32) {
+        self.config.travel_coeff = travel_coeff;
+    }
+
+    #[getter]
+    fn center_coeff(&self) -> f32 {
+        self.config.center_coeff
+    }
+
+    #[setter]
+    fn set_center_coeff(&mut self, center_coeff: f32) {
+        self.config.center_coeff = center_coeff;
+    }
+
+    #[getter]
+    fn center_integral_coeff(&self) -> f32 {
+        self.config.center_integral_coeff
+    }
+
+    #[setter]
+    fn set_center_integral_coeff(&mut self, center_integral_coeff: f32) {
+        self.config.center_integral_coeff = center_integral_coeff;
+    }
+
+    #[getter]
+    fn crash_reward(&self) -> f32 {
+        self.config.crash_reward
+    }
+
+    #[setter]
+    fn set_crash_reward(&mut self, crash_reward: f32) {
+        self.config.crash_reward = crash_reward;
+    }
+
+    #[getter]
+    fn shoulder_coeff(&self) -> f32 {
+        self.config.shoulder_coeff
+    }
+
+    #[setter]
+    fn set_shoulder_coeff(&mut self, shoulder_coeff: f32) {
+        self.config.shoulder_coeff = shoulder_coeff;
+    }
+
+    #[getter]
+    fn reward_scale(&self) -> f32 {
+        self.config.reward_scale
+    }
+
+    #[setter]
+    fn set_reward_scale(&mut self, reward_scale: f32) {
+        self.config.reward_scale = reward_scale;
+    }
+
+    #[getter]
+    fn reward_clip(&self) -> Option<f32> {
+        self.config.reward_clip
+    }
+
+    #[setter]
+    fn set_reward_clip(&mut self, reward_clip: Option<f32>) {
+        self.config.reward_clip = reward_clip;
+    }
+}
+
+
+/// The full configuration of a simulator. The car and reward configs are shared with the
+/// simulator config, so that changing e.g. 'config.car.length' changes the simulator config.
+#[pyclass(name = "SimConfig", module = "gym_car")]
+pub struct PySimConfig {
+    car: Py<PyCarConfig>,
+    reward: Py<PyRewardConfig>,
+    /// The rest of the config. Its car and reward configs are replaced by the shared ones.
+    rest: gym::SimConfig,
+}
+
+impl PySimConfig {
+    pub fn from_config(py: Python<'_>, config: gym::SimConfig) -> PyResult<Self> {
+        let car = Py::new(py, PyCarConfig { config: config.car.clone() })?;
+        let reward = Py::new(py, PyRewardConfig { config: config.reward.clone() })?;
+        Ok(Self { car, reward, rest: config })
+    }
+
+    /// Get the config of the simulator, with the current car and reward configs
+    pub fn to_config(&self, py: Python<'_>) -> gym::SimConfig {
+        gym::SimConfig {
+            car: self.car.borrow(py).config.clone(),
+            reward: self.reward.borrow(py).config.clone(),
+            ..self.rest.clone()
+        }
+    }
+}
+
+#[pymethods]
+impl PySimConfig {
+    /// Create a simulator config with the defaults, changed by any keyword arguments
+    #[new]
+    #[pyo3( signature = (**kwargs) )]
+    fn new(py: Python<'_>, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let this = Bound::new(py, Self::from_config(py, gym::SimConfig::default())?)?;
+        set_attributes(this.as_any(), kwargs)?;
+        let this = this.borrow();
+        Self::from_config(py, this.to_config(py))
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_dict(py, &self.to_config(py))
+    }
+
+    #[staticmethod]
+    fn from_dict(py: Python<'_>, dict: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Self::from_config(py, from_dict(dict)?)
+    }
+
+    /// Save the config, with all fields, to a '.json' or '.toml' file
+    fn save(&self, py: Python<'_>, path: std::path::PathBuf) -> PyResult<()> {
+        self.to_config(py).save(path).map_err(config_error)
+    }
+
+    #[staticmethod]
+    fn load(py: Python<'_>, path: std::path::PathBuf) -> PyResult<Self> {
+        Self::from_config(py, gym::SimConfig::from_file(path).map_err(config_error)?)
+    }
+
+    fn __copy__(&self, py: Python<'_>) -> PyResult<Self> {
+        Self::from_config(py, self.to_config(py))
+    }
+
+    fn __deepcopy__(&self, py: Python<'_>, _memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+        self.__copy__(py)
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!("SimConfig({:?})", self.to_config(py))
+    }
+
+    #[getter]
+    fn car(&self, py: Python<'_>) -> Py<PyCarConfig> {
+        self.car.clone_ref(py)
+    }
+
+    #[setter]
+    fn set_car(&mut self, car: Py<PyCarConfig>) {
+        self.car = car;
+    }
+
+    #[getter]
+    fn reward(&self, py: Python<'_>) -> Py<PyRewardConfig> {
+        self.reward.clone_ref(py)
+    }
+
+    #[setter]
+    fn set_reward(&mut self, reward: Py<PyRewardConfig>) {
+        self.reward = reward;
+    }
+
+    #[getter]
+    fn dt(&self) -> f32 {
+        self.rest.dt
+    }
+
+    #[setter]
+    fn set_dt(&mut self, dt: f32) {
+        self.rest.dt = dt;
+    }
+
+    #[getter]
+    fn lidar_angles(&self) -> Vec<f32> {
+        self.rest.lidar.side_angles().to_vec()
+    }
+
+    #[setter]
+    fn set_lidar_angles(&mut self, lidar_angles: Vec<f32>) {
+        self.rest.lidar = LidarArray::new(lidar_angles);
+    }
+
+    #[getter]
+    fn observe_delta(&self) -> bool {
+        self.rest.observation.delta
+    }
+
+    #[setter]
+    fn set_observe_delta(&mut self, observe_delta: bool) {
+        self.rest.observation.delta = observe_delta;
+    }
+
+    #[getter]
+    fn observe_speed(&self) -> bool {
+        self.rest.observation.speed
+    }
+
+    #[setter]
+    fn set_observe_speed(&mut self, observe_speed: bool) {
+        self.rest.observation.speed = observe_speed;
+    }
+
+    #[getter]
+    fn observe_shoulder(&self) -> bool {
+        self.rest.observation.shoulder
+    }
+
+    #[setter]
+    fn set_observe_shoulder(&mut self, observe_shoulder: bool) {
+        self.rest.observation.shoulder = observe_shoulder;
+    }
+
+    #[getter]
+    fn observe_energy(&self) -> bool {
+        self.rest.observation.energy
+    }
+
+    #[setter]
+    fn set_observe_energy(&mut self, observe_energy: bool) {
+        self.rest.observation.energy = observe_energy;
+    }
+
+    #[getter]
+    fn observe_grip(&self) -> bool {
+        self.rest.observation.grip
+    }
+
+    #[setter]
+    fn set_observe_grip(&mut self, observe_grip: bool) {
+        self.rest.observation.grip = observe_grip;
+    }
+
+    #[getter]
+    fn action_mode(&self) -> &'static str {
+        match self.rest.action_mode {
+            gym::ActionMode::Discrete => "discrete",
+            gym::ActionMode::SteerThrottle => "steer_throttle",
+        }
+    }
+
+    #[setter]
+    fn set_action_mode(&mut self, action_mode: &str) -> PyResult<()> {
+        self.rest.action_mode = parse_action_mode(action_mode)?;
+        Ok(())
+    }
+
+    #[getter]
+    fn sticky_action_prob(&self) -> f32 {
+        self.rest.perturbation.sticky_action_prob
+    }
+
+    #[setter]
+    fn set_sticky_action_prob(&mut self, sticky_action_prob: f32) -> PyResult<()> {
+        if !(0.0 ..= 1.0).contains(&sticky_action_prob) {
+            return Err(PyValueError::new_err(format!("Invalid sticky action probability '{}'. It must be between 0 and 1.", sticky_action_prob)));
+        }
+        self.rest.perturbation.sticky_action_prob = sticky_action_prob;
+        Ok(())
+    }
+
+    #[getter]
+    fn action_delay(&self) -> usize {
+        self.rest.perturbation.action_delay
+    }
+
+    #[setter]
+    fn set_action_delay(&mut self, action_delay: usize) {
+        self.rest.perturbation.action_delay = action_delay;
+    }
+
+    #[getter]
+    fn observation_delay(&self) -> usize {
+        self.rest.perturbation.observation_delay
+    }
+
+    #[setter]
+    fn set_observation_delay(&mut self, observation_delay: usize) {
+        self.rest.perturbation.observation_delay = observation_delay;
+    }
+
+    #[getter]
+    fn heading_noise(&self) -> f32 {
+        self.rest.perturbation.heading_noise
+    }
+
+    #[setter]
+    fn set_heading_noise(&mut self, heading_noise: f32) {
+        self.rest.perturbation.heading_noise = heading_noise;
+    }
+
+    #[getter]
+    fn lateral_noise(&self) -> f32 {
+        self.rest.perturbation.lateral_noise
+    }
+
+    #[setter]
+    fn set_lateral_noise(&mut self, lateral_noise: f32) {
+        self.rest.perturbation.lateral_noise = lateral_noise;
+    }
+
+    #[getter]
+    fn crash_mode(&self) -> &'static str {
+        match self.rest.crash_mode {
+            gym::CrashMode::Terminate => "terminate",
+            gym::CrashMode::Bounce => "bounce",
+        }
+    }
+
+    #[setter]
+    fn set_crash_mode(&mut self, crash_mode: &str) -> PyResult<()> {
+        self.rest.crash_mode = parse_crash_mode(crash_mode)?;
+        Ok(())
+    }
+
+    #[getter]
+    fn restitution(&self) -> f32 {
+        self.rest.restitution
+    }
+
+    #[setter]
+    fn set_restitution(&mut self, restitution: f32) {
+        self.rest.restitution = restitution;
+    }
+
+    #[getter]
+    fn ray_tolerance(&self) -> f32 {
+        self.rest.ray_march.tolerance
+    }
+
+    #[setter]
+    fn set_ray_tolerance(&mut self, ray_tolerance: f32) -> PyResult<()> {
+        if ray_tolerance <= 0.0 {
+            return Err(PyValueError::new_err(format!("Invalid ray tolerance '{}'. It must be positive.", ray_tolerance)));
+        }
+        self.rest.ray_march.tolerance = ray_tolerance;
+        Ok(())
+    }
+
+    #[getter]
+    fn ray_max_steps(&self) -> usize {
+        self.rest.ray_march.max_steps
+    }
+
+    #[setter]
+    fn set_ray_max_steps(&mut self, ray_max_steps: usize) {
+        self.rest.ray_march.max_steps = ray_max_steps;
+    }
+
+    #[getter]
+    fn ray_max_distance(&self) -> Option<f32> {
+        self.rest.ray_march.max_distance
+    }
+
+    #[setter]
+    fn set_ray_max_distance(&mut self, ray_max_distance: Option<f32>) {
+        self.rest.ray_march.max_distance = ray_max_distance;
+    }
+
+    #[getter]
+    fn reverse_prob(&self) -> f32 {
+        self.rest.reverse_prob
+    }
+
+    #[setter]
+    fn set_reverse_prob(&mut self, reverse_prob: f32) -> PyResult<()> {
+        if !(0.0 ..= 1.0).contains(&reverse_prob) {
+            return Err(PyValueError::new_err(format!("Invalid reverse probability '{}'. It must be between 0 and 1.", reverse_prob)));
+        }
+        self.rest.reverse_prob = reverse_prob;
+        Ok(())
+    }
+
+    #[getter]
+    fn record(&self) -> bool {
+        self.rest.record
+    }
+
+    #[setter]
+    fn set_record(&mut self, record: bool) {
+        self.rest.record = record;
+    }
+}
@@ -1,40 +1,417 @@
 use pyo3::prelude::*;
-use pyo3::exceptions::PyValueError;
-use numpy::PyArray1;
+use pyo3::exceptions::{PyValueError, PyIOError, PyRuntimeError};
+use pyo3::types::{PyBytes, PyDict};
+use numpy::{PyArray1, PyArray2, PyArray3, PyArrayMethods};
 
 
-use car_sim::map;
+use car_sim::map::{self, Road};
 use car_sim::gym;
+use car_sim::physics::CarInput;
+use car_sim::recorder::Demonstrations;
+use car_sim::policy::{Policy, RandomPolicy, QTablePolicy};
+use car_sim::evaluation::{Evaluation, EpisodeTracker};
+use car_sim::curriculum::{Curriculum, CurriculumTrack, Stage};
+use car_sim::random_tracks::RandomTracks;
+use math_utils::Vec2;
 
 mod graphics;
 use graphics::{SplineRoadExport, CarGraphicsExport};
 
+mod cell_env;
+use cell_env::CellRacingEnv;
+
+mod multi_env;
+use multi_env::MultiCarRacingEnv;
+
+mod config;
+use config::{PySimConfig, PyCarConfig, PyRewardConfig};
+
+
+/// Allocate the numpy array which 'observe' overwrites with each observation
+fn observation_buffer<'py, R: map::Road>(py: Python<'py>, sim: &gym::Simulator<R>) -> Py<PyArray1<f32>> {
+    PyArray1::zeros(py, sim.observation_dim(), false).unbind()
+}
+
+
+/// Write the current observation of a simulator into its preallocated numpy array. The LiDAR
+/// work runs with the GIL released, so that other Python threads can run meanwhile.
+fn observe_into_buffer<'py, R: map::Road + Sync>(py: Python<'py>, sim: &gym::Simulator<R>, buffer: &Py<PyArray1<f32>>) -> PyResult<Py<PyArray1<f32>>> {
+    let array = buffer.bind(py);
+    {
+        let mut view = array.try_readwrite()
+            .map_err(|err| PyValueError::new_err(format!("Observation array is not writeable: {}", err)))?;
+        let out = view.as_slice_mut()?;
+        py.detach(|| sim.observe_into(out));
+    }
+    Ok(array.clone().unbind())
+}
+
+
+/// Step a simulator with the integer value of an action in its action mode, and write the next
+/// observation into its preallocated numpy array, both in a single call with the GIL released
+fn step_into_buffer<R: map::Road + Send + Sync>(py: Python<'_>, sim: &mut gym::Simulator<R>, buffer: &Py<PyArray1<f32>>, action: u8) -> PyResult<gym::TransitionObservation> {
+    let action_mode = sim.config.action_mode;
+    let array = buffer.bind(py);
+    let mut view = array.try_readwrite()
+        .map_err(|err| PyValueError::new_err(format!("Observation array is not writeable: {}", err)))?;
+    let out = view.as_slice_mut()?;
+    py.detach(|| {
+        let transition = sim.step_index(action)?;
+        sim.observe_into(out);
+        Ok(transition)
+    }).map_err(|_: gym::InvalidActionError| invalid_action(action_mode, action))
+}
+
+
+fn step_detached<R: map::Road + Send + Sync>(py: Python<'_>, sim: &mut gym::Simulator<R>, buffer: &Py<PyArray1<f32>>, action: u8) -> PyResult<(Py<PyArray1<f32>>, f32, bool)> {
+    let gym::TransitionObservation { reward, done, .. } = step_into_buffer(py, sim, buffer, action)?;
+    Ok((buffer.clone_ref(py), reward, done))
+}
+
+
+/// Step a simulator with a raw car input and write the next observation into its preallocated
+/// numpy array, with the GIL released
+fn step_input_detached<R: map::Road + Send + Sync>(py: Python<'_>, sim: &mut gym::Simulator<R>, buffer: &Py<PyArray1<f32>>, input: CarInput) -> PyResult<(Py<PyArray1<f32>>, f32, bool)> {
+    let gym::TransitionObservation { reward, done, .. } = {
+        let array = buffer.bind(py);
+        let mut view = array.try_readwrite()
+            .map_err(|err| PyValueError::new_err(format!("Observation array is not writeable: {}", err)))?;
+        let out = view.as_slice_mut()?;
+        py.detach(|| {
+            let transition = sim.step_input(input);
+            sim.observe_into(out);
+            transition
+        })
+    };
+    Ok((buffer.clone_ref(py), reward, done))
+}
+
+
+/// Serialize a snapshot of a simulator as JSON bytes, which Python can store or pickle as is
+fn state_bytes<'py, R: map::Road>(py: Python<'py>, sim: &gym::Simulator<R>) -> PyResult<Bound<'py, PyBytes>> {
+    let json = serde_json::to_vec(&sim.get_state())
+        .map_err(|err| PyValueError::new_err(format!("Could not serialize simulator state: {}", err)))?;
+    Ok(PyBytes::new(py, &json))
+}
+
+
+/// Convert the trajectory of the current episode to a dict of numpy arrays
+fn trajectory_dict<'py, R: map::Road>(py: Python<'py>, sim: &gym::Simulator<R>) -> PyResult<Bound<'py, PyDict>> {
+    let trajectory = sim.trajectory();
+    let positions: Vec<f32> = trajectory.positions.iter().flat_map(|position| [position.0, position.1]).collect();
+    let dict = PyDict::new(py);
+    dict.set_item("position", PyArray1::from_vec(py, positions).reshape([trajectory.len(), 2])?)?;
+    dict.set_item("speed", PyArray1::from_slice(py, &trajectory.speeds))?;
+    dict.set_item("action", PyArray1::from_slice(py, &trajectory.actions))?;
+    dict.set_item("reward", PyArray1::from_slice(py, &trajectory.rewards))?;
+    Ok(dict)
+}
+
+
+/// Restore a snapshot of a simulator from the bytes written by 'state_bytes'
+fn restore_state<R: map::Road>(sim: &mut gym::Simulator<R>, state: &[u8]) -> PyResult<()> {
+    let state: gym::SimState = serde_json::from_slice(state)
+        .map_err(|err| PyValueError::new_err(format!("Invalid simulator state: {}", err)))?;
+    let (expected, found) = (sim.road.obstacle_state().len(), state.obstacle_state().len());
+    if expected != found {
+        return Err(PyValueError::new_err(format!("Simulator state has {} moving obstacles, but the environment has {}.", found, expected)));
+    }
+    sim.set_state(&state);
+    Ok(())
+}
+
+
+fn invalid_action(action_mode: gym::ActionMode, action: u8) -> PyErr {
+    PyValueError::new_err(
+        format!("Invalid action value '{}'. Action must be integer between 0 and {}.", action, action_mode.n_actions() - 1)
+    )
+}
+
+
+fn track_error(err: map::TrackLoadError) -> PyErr {
+    match err {
+        map::TrackLoadError::Io(_) => PyIOError::new_err(err.to_string()),
+        _ => PyValueError::new_err(err.to_string()),
+    }
+}
+
+
+fn config_error(err: gym::ConfigLoadError) -> PyErr {
+    match err {
+        gym::ConfigLoadError::Io(_) => PyIOError::new_err(err.to_string()),
+        _ => PyValueError::new_err(err.to_string()),
+    }
+}
+
+
+/// Load a spline road from a track file, or the default track if none is given
+fn load_road(track_file: Option<std::path::PathBuf>, track_width: Option<f32>, shoulder_width: Option<f32>) -> PyResult<map::SplineMap> {
+    let mut road = match track_file {
+        // CSV waypoint files describe only the centerline, so the width is given separately
+        Some(path) if path.extension().is_some_and(|extension| extension == "csv") => {
+            let width = track_width.ok_or_else(|| PyValueError::new_err("'track_width' must be given for '.csv' track files."))?;
+            map::SplineMap::from_csv(path, width).map_err(track_error)?
+        },
+        Some(path) => map::SplineMap::from_file(path).map_err(track_error)?,
+        None => map::make_simple_racetrack(),
+    };
+    if let Some(shoulder_width) = shoulder_width {
+        road.set_shoulder_width(shoulder_width);
+    }
+    Ok(road)
+}
+
+
+/// Rasterize a road into a (height, width) numpy array, with the top row at the largest y
+fn rasterize_road<'py, R: map::Road>(py: Python<'py>, road: &R, px_per_m: f32) -> PyResult<Py<PyArray2<u8>>> {
+    if !(px_per_m.is_finite() && px_per_m > 0.0) {
+        return Err(PyValueError::new_err(format!("Invalid resolution '{}'. Pixels per meter must be positive.", px_per_m)));
+    }
+    let map::RoadImage { width, height, pixels, .. } = map::rasterize(road, px_per_m);
+    Ok(PyArray1::from_vec(py, pixels).reshape([height, width])?.unbind())
+}
+
+
+/// Describe the observation vector of a simulator config, for building a Box space
+fn observation_space_dict<'py>(py: Python<'py>, config: &gym::SimConfig) -> PyResult<Bound<'py, PyDict>> {
+    let components = config.observation_components();
+    let low: Vec<f32> = components.iter().map(|component| component.low).collect();
+    let high: Vec<f32> = components.iter().map(|component| component.high).collect();
+    let labels: Vec<&str> = components.iter().map(|component| component.label.as_str()).collect();
+    let dict = PyDict::new(py);
+    dict.set_item("shape", (components.len(),))?;
+    dict.set_item("dtype", "float32")?;
+    dict.set_item("low", PyArray1::from_vec(py, low))?;
+    dict.set_item("high", PyArray1::from_vec(py, high))?;
+    dict.set_item("labels", labels)?;
+    Ok(dict)
+}
+
+
+/// Describe the actions of an action mode, for building a Discrete space, or a MultiDiscrete
+/// space of the steering and throttle levels in the 'steer_throttle' mode
+fn action_space_dict(py: Python<'_>, action_mode: gym::ActionMode) -> PyResult<Bound<'_, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("n", action_mode.n_actions())?;
+    dict.set_item("labels", action_mode.labels())?;
+    if action_mode == gym::ActionMode::SteerThrottle {
+        dict.set_item("nvec", [gym::SteerThrottle::N_STEER, gym::Throttle::ALL.len()])?;
+    }
+    Ok(dict)
+}
+
+
+/// Parse the name of an action mode, as in config files
+fn parse_action_mode(action_mode: &str) -> PyResult<gym::ActionMode> {
+    match action_mode {
+        "discrete" => Ok(gym::ActionMode::Discrete),
+        "steer_throttle" => Ok(gym::ActionMode::SteerThrottle),
+        _ => Err(PyValueError::new_err(
+            format!("Unknown action mode '{}'. Action mode must be one of 'discrete' or 'steer_throttle'.", action_mode)
+        )),
+    }
+}
+
+
+fn parse_crash_mode(crash_mode: &str) -> PyResult<gym::CrashMode> {
+    match crash_mode {
+        "terminate" => Ok(gym::CrashMode::Terminate),
+        "bounce" => Ok(gym::CrashMode::Bounce),
+        _ => Err(PyValueError::new_err(
+            format!("Unknown crash mode '{}'. Crash mode must be one of 'terminate' or 'bounce'.", crash_mode)
+        )),
+    }
+}
+
+
+/// Change the given coefficients of a reward config, leaving the others as they are. A
+/// 'reward_clip' of infinity removes the clipping.
+#[allow(clippy::too_many_arguments)]
+fn update_reward(
+    reward: &mut gym::RewardConfig,
+    travel_coeff: Option<f32>,
+    center_coeff: Option<f32>,
+    center_integral_coeff: Option<f32>,
+    crash_reward: Option<f32>,
+    shoulder_coeff: Option<f32>,
+    reward_scale: Option<f32>,
+    reward_clip: Option<f32>,
+) {
+    let fields = [
+        (&mut reward.travel_coeff, travel_coeff),
+        (&mut reward.center_coeff, center_coeff),
+        (&mut reward.center_integral_coeff, center_integral_coeff),
+        (&mut reward.crash_reward, crash_reward),
+        (&mut reward.shoulder_coeff, shoulder_coeff),
+        (&mut reward.reward_scale, reward_scale),
+    ];
+    for (field, value) in fields {
+        if let Some(value) = value {
+            *field = value;
+        }
+    }
+    if let Some(reward_clip) = reward_clip {
+        reward.reward_clip = reward_clip.is_finite().then_some(reward_clip);
+    }
+}
+
+
+/// Convert a reward config to a dict of its coefficients, with a 'reward_clip' of None when the
+/// reward is not clipped
+fn reward_dict<'py>(py: Python<'py>, reward: &gym::RewardConfig) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("travel_coeff", reward.travel_coeff)?;
+    dict.set_item("center_coeff", reward.center_coeff)?;
+    dict.set_item("center_integral_coeff", reward.center_integral_coeff)?;
+    dict.set_item("crash_reward", reward.crash_reward)?;
+    dict.set_item("shoulder_coeff", reward.shoulder_coeff)?;
+    dict.set_item("reward_scale", reward.reward_scale)?;
+    dict.set_item("reward_clip", reward.reward_clip)?;
+    Ok(dict)
+}
+
+
+/// The number of points along the centerline sampled for its curvature statistics
+const CURVATURE_SAMPLES: usize = 1000;
+
+
+/// Parse a start distribution by name, with the arc length of a fixed start and the number of
+/// points of a grid
+fn parse_start_distribution(distribution: &str, arc: f32, n: usize) -> PyResult<gym::StartDistribution> {
+    match distribution {
+        "uniform" => Ok(gym::StartDistribution::Uniform),
+        "fixed" => Ok(gym::StartDistribution::Fixed { arc }),
+        "grid" if n == 0 => Err(PyValueError::new_err("A grid of starts needs at least one point.")),
+        "grid" => Ok(gym::StartDistribution::Grid { n }),
+        _ => Err(PyValueError::new_err(
+            format!("Unknown start distribution '{}'. Start distribution must be one of 'uniform', 'fixed' or 'grid'.", distribution)
+        )),
+    }
+}
+
+
+/// Render a simulator into a (height, width, 3) numpy array, with the GIL released
+fn render_rgb_array<R: map::Road + Sync>(py: Python<'_>, sim: &gym::Simulator<R>, width: usize, height: usize) -> PyResult<Py<PyArray3<u8>>> {
+    if width == 0 || height == 0 {
+        return Err(PyValueError::new_err(format!("Invalid image size {}x{}. Width and height must be positive.", width, height)));
+    }
+    let car_sim::render::RgbImage { pixels, .. } = py.detach(|| car_sim::render::render_rgb(sim, width, height));
+    Ok(PyArray1::from_vec(py, pixels).reshape([height, width, 3])?.unbind())
+}
+
 
 #[pyclass(module="gym_car")]
 struct RacingEnv {
     sim: gym::Simulator<map::SplineMap>,
-    observe_delta: bool,
-    observe_speed: bool,
+    /// Array holding the latest observation, reused between calls to 'observe'
+    observation: Py<PyArray1<f32>>,
+    /// File to save the current recording to when it is stopped
+    recording_path: Option<std::path::PathBuf>,
+    /// Chooses the track of each episode, if enabled
+    curriculum: Option<Curriculum>,
+    /// Gives a random track for each episode, if enabled
+    random_tracks: Option<RandomTracks>,
+}
+
+
+impl RacingEnv {
+    fn from_parts(py: Python<'_>, config: gym::SimConfig, road: map::SplineMap, seed: Option<u64>) -> Self {
+        let sim = gym::Simulator::new(config, road, seed);
+        let observation = observation_buffer(py, &sim);
+        let mut this = Self { sim, observation, recording_path: None, curriculum: None, random_tracks: None };
+        this.reset(None);
+        this
+    }
+
+    /// Switch to the road given by the curriculum for the next episode, if any, keeping the
+    /// shoulder and ray casting settings of the current road
+    fn apply_curriculum(&mut self) {
+        let Some(mut road) = self.curriculum.as_mut().and_then(Curriculum::next_road) else {
+            return;
+        };
+        road.set_shoulder_width(self.sim.road.shoulder_width());
+        road.set_ray_casting(self.sim.road.ray_casting());
+        self.sim.road = road;
+    }
+}
+
+
+/// Parse curriculum stages given as (track name, width factor) pairs
+fn parse_stages(stages: Vec<(String, f32)>) -> PyResult<Vec<Stage>> {
+    if stages.is_empty() {
+        return Err(PyValueError::new_err("A curriculum needs at least one stage."));
+    }
+    stages.into_iter()
+        .map(|(name, width_factor)| {
+            let track = CurriculumTrack::from_name(&name).ok_or_else(|| PyValueError::new_err(format!(
+                "Unknown track '{}'. Track must be one of 'oval', 'simple_racetrack', 'racetrack' or 'random'.", name
+            )))?;
+            if !(width_factor.is_finite() && width_factor > 0.0) {
+                return Err(PyValueError::new_err(format!("Invalid width factor '{}'. Width factors must be positive.", width_factor)));
+            }
+            Ok(Stage { track, width_factor })
+        })
+        .collect()
 }
 
 
 #[pymethods]
 impl RacingEnv {
     #[new]
+    #[allow(clippy::too_many_arguments)]
     #[pyo3(
-        signature = (dt=None, crash_reward=None, travel_coeff=None, center_coeff=None, center_integral_coeff=None, observe_delta=true, observe_speed=true, seed=None)
+        signature = (track_file=None, track_width=None, dt=None, crash_reward=None, travel_coeff=None, center_coeff=None, center_integral_coeff=None, reward_scale=None, reward_clip=None, shoulder_width=None, shoulder_coeff=None, observe_delta=true, observe_speed=true, observe_shoulder=false, observe_energy=false, observe_grip=false, action_mode="discrete", steer_target_rate=None, energy_capacity=None, sticky_action_prob=0.0, action_delay=0, observation_delay=0, heading_noise=0.0, lateral_noise=0.0, reverse_prob=0.0, crash_mode="terminate", restitution=0.5, config=None, seed=None)
     )]
     fn new(
+        track_file: Option<std::path::PathBuf>,
+        track_width: Option<f32>,
         dt: Option<f32>,
         crash_reward: Option<f32>,
         travel_coeff: Option<f32>,
         center_coeff: Option<f32>,
         center_integral_coeff: Option<f32>,
+        reward_scale: Option<f32>,
+        reward_clip: Option<f32>,
+        shoulder_width: Option<f32>,
+        shoulder_coeff: Option<f32>,
         observe_delta: bool,
         observe_speed: bool,
+        observe_shoulder: bool,
+        observe_energy: bool,
+        observe_grip: bool,
+        action_mode: &str,
+        steer_target_rate: Option<f32>,
+        energy_capacity: Option<f32>,
+        sticky_action_prob: f32,
+        action_delay: usize,
+        observation_delay: usize,
+        heading_noise: f32,
+        lateral_noise: f32,
+        reverse_prob: f32,
+        crash_mode: &str,
+        restitution: f32,
+        config: Option<PyRef<'_, PySimConfig>>,
         seed: Option<u64>,
-    ) -> Self {
-        let mut config = gym::SimConfig::default();
+        py: Python<'_>,
+    ) -> PyResult<Self> {
+        // A config object replaces all simulator settings, so none may be given besides it
+        if let Some(config) = config {
+            let overridden = [dt, crash_reward, travel_coeff, center_coeff, center_integral_coeff, reward_scale, reward_clip, shoulder_coeff, steer_target_rate, energy_capacity]
+                .iter().any(Option::is_some)
+                || !observe_delta || !observe_speed || observe_shoulder || observe_energy || observe_grip || action_mode != "discrete"
+                || sticky_action_prob != 0.0 || action_delay != 0 || observation_delay != 0
+                || heading_noise != 0.0 || lateral_noise != 0.0 || reverse_prob != 0.0
+                || crash_mode != "terminate" || restitution != 0.5;
+            if overridden {
+                return Err(PyValueError::new_err("Simulator settings must be given either by 'config' or as arguments, not both."));
+            }
+            let road = load_road(track_file, track_width, shoulder_width)?;
+            return Ok(Self::from_parts(py, config.to_config(py), road, seed));
+        }
+
+        let observation = gym::ObservationConfig { delta: observe_delta, speed: observe_speed, shoulder: observe_shoulder, energy: observe_energy, grip: observe_grip };
+        let action_mode = parse_action_mode(action_mode)?;
+        let mut config = gym::SimConfig { observation, action_mode, ..gym::SimConfig::default() };
         if let Some(dt) = dt {
             config.dt = dt;
         }
@@ -50,49 +427,334 @@ impl RacingEnv {
         if let Some(center_integral_coeff) = center_integral_coeff {
             config.reward.center_integral_coeff = center_integral_coeff;
         }
+        if let Some(reward_scale) = reward_scale {
+            config.reward.reward_scale = reward_scale;
+        }
+        config.reward.reward_clip = reward_clip;
+        if let Some(shoulder_coeff) = shoulder_coeff {
+            config.reward.shoulder_coeff = shoulder_coeff;
+        }
+        config.car.steer_target_rate = steer_target_rate;
+        if let Some(capacity) = energy_capacity.filter(|&capacity| capacity < 0.0) {
+            return Err(PyValueError::new_err(format!("Invalid energy capacity '{}'. It must not be negative.", capacity)));
+        }
+        config.car.energy_capacity = energy_capacity;
+        if !(0.0 ..= 1.0).contains(&sticky_action_prob) {
+            return Err(PyValueError::new_err(format!("Invalid sticky action probability '{}'. It must be between 0 and 1.", sticky_action_prob)));
+        }
+        if heading_noise < 0.0 || lateral_noise < 0.0 {
+            return Err(PyValueError::new_err("Disturbances must not be negative."));
+        }
+        config.perturbation = gym::PerturbationConfig { sticky_action_prob, action_delay, observation_delay, heading_noise, lateral_noise };
+        if !(0.0 ..= 1.0).contains(&reverse_prob) {
+            return Err(PyValueError::new_err(format!("Invalid reverse probability '{}'. It must be between 0 and 1.", reverse_prob)));
+        }
+        config.reverse_prob = reverse_prob;
+        config.crash_mode = parse_crash_mode(crash_mode)?;
+        config.restitution = restitution;
 
-        let road = map::make_simple_racetrack();
-        let mut this = Self { sim: gym::Simulator::new(config, road, seed), observe_delta, observe_speed };
-        this.reset(None);
-        this
+        let road = load_road(track_file, track_width, shoulder_width)?;
+        Ok(Self::from_parts(py, config, road, seed))
+    }
+
+    /// Create an environment with the simulator settings of a '.json' or '.toml' config file
+    #[staticmethod]
+    #[pyo3( signature = (config_file, track_file=None, track_width=None, shoulder_width=None, seed=None) )]
+    fn from_config(
+        py: Python<'_>,
+        config_file: std::path::PathBuf,
+        track_file: Option<std::path::PathBuf>,
+        track_width: Option<f32>,
+        shoulder_width: Option<f32>,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        let config = gym::SimConfig::from_file(config_file).map_err(config_error)?;
+        let road = load_road(track_file, track_width, shoulder_width)?;
+        Ok(Self::from_parts(py, config, road, seed))
+    }
+
+    /// Get a copy of the simulator settings, which can be changed to create another environment
+    #[getter]
+    fn config(&self, py: Python<'_>) -> PyResult<PySimConfig> {
+        PySimConfig::from_config(py, self.sim.config.clone())
+    }
+
+    /// Get the simulator settings as a dict, in the layout of a config file
+    fn config_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let json = serde_json::to_string(&self.sim.config)
+            .map_err(|err| PyValueError::new_err(format!("Could not serialize config: {}", err)))?;
+        py.import("json")?.call_method1("loads", (json,))
     }
 
     #[pyo3( signature = (seed=None) )]
     fn reset(&mut self, seed: Option<u64>) {
-        self.sim.reset(seed)
+        match &self.random_tracks {
+            Some(tracks) => self.sim.reset_random_track(tracks, seed),
+            None => {
+                self.apply_curriculum();
+                self.sim.reset(seed)
+            },
+        }
     }
 
-    fn step(&mut self, action: u8) -> PyResult<(f32, bool)> {
-        let action = gym::Action::try_from(action)
-            .map_err(|_| PyValueError::new_err(
-                    format!("Invalid action value '{}'. Action must be integer between 0 and 4.", action)
-                )
-            )?;
+    fn step(&mut self, py: Python<'_>, action: u8) -> PyResult<(Py<PyArray1<f32>>, f32, bool)> {
+        step_detached(py, &mut self.sim, &self.observation, action)
+    }
 
-        let gym::TransitionObservation { reward, done } = self.sim.step(action);
-        
-        Ok((reward, done))
+    /// Step with a raw car input instead of an action, e.g. for continuous controllers
+    #[pyo3( signature = (forward_acc, target_delta, braking=false) )]
+    fn step_raw(&mut self, py: Python<'_>, forward_acc: f32, target_delta: f32, braking: bool) -> PyResult<(Py<PyArray1<f32>>, f32, bool)> {
+        step_input_detached(py, &mut self.sim, &self.observation, CarInput { forward_acc, target_delta, braking })
     }
 
-    fn observe<'py>(&self, py: Python<'py>) -> Py<PyArray1<f32>> {
-        let gym::StateObservation { lidar_readings, steer_delta, speed } = self.sim.observe();
-        let mut data = lidar_readings;
-        if self.observe_delta {
-            data.push(steer_delta);
-        }
-        if self.observe_speed {
-            data.push(speed);
+    fn observe<'py>(&self, py: Python<'py>) -> PyResult<Py<PyArray1<f32>>> {
+        observe_into_buffer(py, &self.sim, &self.observation)
+    }
+
+    /// Fork the environment, e.g. for planning. The copy shares no state with the original, so
+    /// shallow and deep copies are the same.
+    fn __copy__(&self, py: Python<'_>) -> Self {
+        // A copy does not record, so that it does not overwrite the recording of the original.
+        // Neither does it follow the curriculum, which is driven by the results of the original.
+        let mut sim = self.sim.clone();
+        sim.stop_recording();
+        let observation = observation_buffer(py, &sim);
+        Self { sim, observation, recording_path: None, curriculum: None, random_tracks: self.random_tracks.clone() }
+    }
+
+    fn __deepcopy__(&self, py: Python<'_>, _memo: &Bound<'_, PyAny>) -> Self {
+        self.__copy__(py)
+    }
+
+    fn get_state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        state_bytes(py, &self.sim)
+    }
+
+    fn set_state(&mut self, state: &[u8]) -> PyResult<()> {
+        restore_state(&mut self.sim, state)
+    }
+
+    fn get_trajectory<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        trajectory_dict(py, &self.sim)
+    }
+
+    fn observation_space<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        observation_space_dict(py, &self.sim.config)
+    }
+
+    /// Run 'n_steps' steps in Rust with a built-in policy, resetting after each episode
+    #[pyo3( signature = (n_steps, policy="random", seed=None) )]
+    fn rollout<'py>(&mut self, py: Python<'py>, n_steps: usize, policy: &str, seed: Option<u64>) -> PyResult<Bound<'py, PyDict>> {
+        rollout_dict(py, &mut self.sim, policy, n_steps, seed)
+    }
+
+    /// Run 'episodes' episodes with actions from the Python callable 'policy_fn', each ending with a
+    /// crash or after 'max_steps' steps, and get the statistics of their outcomes
+    #[pyo3( signature = (policy_fn, episodes, max_steps=1000) )]
+    fn evaluate<'py>(&mut self, py: Python<'py>, policy_fn: &Bound<'py, PyAny>, episodes: usize, max_steps: usize) -> PyResult<Bound<'py, PyDict>> {
+        evaluate_dict(py, &mut self.sim, &self.observation, policy_fn, episodes, max_steps)
+    }
+
+    /// Change the given reward coefficients from the next step on, e.g. for annealing schedules
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3( signature = (*, travel_coeff=None, center_coeff=None, center_integral_coeff=None, crash_reward=None, shoulder_coeff=None, reward_scale=None, reward_clip=None) )]
+    fn set_reward(
+        &mut self,
+        travel_coeff: Option<f32>,
+        center_coeff: Option<f32>,
+        center_integral_coeff: Option<f32>,
+        crash_reward: Option<f32>,
+        shoulder_coeff: Option<f32>,
+        reward_scale: Option<f32>,
+        reward_clip: Option<f32>,
+    ) {
+        update_reward(&mut self.sim.config.reward, travel_coeff, center_coeff, center_integral_coeff, crash_reward, shoulder_coeff, reward_scale, reward_clip);
+    }
+
+    fn get_reward<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        reward_dict(py, &self.sim.config.reward)
+    }
+
+    /// Set where the following episodes start along the track. A grid starts from its first
+    /// point at the next seeded reset.
+    #[pyo3( signature = (distribution, arc=0.0, n=1) )]
+    fn set_start_distribution(&mut self, distribution: &str, arc: f32, n: usize) -> PyResult<()> {
+        self.sim.config.start = parse_start_distribution(distribution, arc, n)?;
+        Ok(())
+    }
+
+    fn action_space<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        action_space_dict(py, self.sim.config.action_mode)
+    }
+
+    /// Choose the track of each episode by a curriculum of (track, width factor) stages, moving
+    /// through them by the results given to 'report_result'. Starts a new episode on the first
+    /// stage, replacing the current track and its obstacles, and ends random tracks.
+    #[pyo3( signature = (stages=None, window=20, promote_rate=0.8, demote_rate=0.2, seed=None) )]
+    fn enable_curriculum(&mut self, stages: Option<Vec<(String, f32)>>, window: usize, promote_rate: f32, demote_rate: f32, seed: Option<u64>) -> PyResult<()> {
+        let stages = match stages {
+            Some(stages) => parse_stages(stages)?,
+            None => Curriculum::default_stages(),
+        };
+        let mut curriculum = Curriculum::new(stages, seed);
+        curriculum.window = window.max(1);
+        curriculum.promote_rate = promote_rate;
+        curriculum.demote_rate = demote_rate;
+        self.curriculum = Some(curriculum);
+        self.random_tracks = None;
+        self.reset(None);
+        Ok(())
+    }
+
+    /// Drive each episode on a random track, which is new for every episode, or else drawn from
+    /// a pool of 'pool_size' tracks built with 'pool_seed'. The tracks follow the seed of the
+    /// environment. Starts a new episode, replacing the current track and its obstacles.
+    #[pyo3( signature = (pool_size=None, pool_seed=0) )]
+    fn enable_random_tracks(&mut self, pool_size: Option<usize>, pool_seed: u64) -> PyResult<()> {
+        let tracks = match pool_size {
+            Some(0) => return Err(PyValueError::new_err("'pool_size' must be positive.")),
+            Some(size) => RandomTracks::pool(size, pool_seed),
+            None => RandomTracks::Generate,
+        };
+        self.random_tracks = Some(tracks);
+        self.curriculum = None;
+        self.reset(None);
+        Ok(())
+    }
+
+    /// Stay on the current track from now on
+    fn disable_random_tracks(&mut self) {
+        self.random_tracks = None;
+    }
+
+    fn disable_curriculum(&mut self) {
+        self.curriculum = None;
+    }
+
+    /// Report whether the last episode succeeded to the curriculum, returning whether its stage
+    /// changed. A new stage takes effect at the next reset.
+    fn report_result(&mut self, success: bool) -> PyResult<bool> {
+        let curriculum = self.curriculum.as_mut()
+            .ok_or_else(|| PyRuntimeError::new_err("The environment has no curriculum. Enable it with 'enable_curriculum'."))?;
+        Ok(curriculum.report(success))
+    }
+
+    #[getter]
+    fn curriculum_stage(&self) -> Option<usize> {
+        self.curriculum.as_ref().map(Curriculum::stage_index)
+    }
+
+    #[getter]
+    fn curriculum_success_rate(&self) -> Option<f32> {
+        self.curriculum.as_ref().and_then(Curriculum::success_rate)
+    }
+
+    /// Record all steps from now on, to be saved to the '.json' file at 'path' when stopped
+    fn start_recording(&mut self, path: std::path::PathBuf) {
+        self.sim.start_recording();
+        self.recording_path = Some(path);
+    }
+
+    /// Stop recording and save the recorded episodes to 'path', or else to the path given when
+    /// starting, which is needed when recording was enabled by the config
+    #[pyo3( signature = (path=None) )]
+    fn stop_recording(&mut self, path: Option<std::path::PathBuf>) -> PyResult<()> {
+        if self.sim.recorder().is_none() {
+            return Err(PyRuntimeError::new_err("The environment is not recording."));
         }
+        let path = path.or_else(|| self.recording_path.clone())
+            .ok_or_else(|| PyValueError::new_err("'path' must be given when recording was not started by 'start_recording'."))?;
+        self.recording_path = None;
+        let recording = self.sim.stop_recording().expect("to be recording");
+        recording.save(path).map_err(|err| PyIOError::new_err(format!("Could not save recording: {}", err)))
+    }
+
+    /// Log the telemetry of every step from now on to the CSV file at 'path'
+    fn start_telemetry(&mut self, path: std::path::PathBuf) -> PyResult<()> {
+        self.sim.start_telemetry(path)
+            .map_err(|err| PyIOError::new_err(format!("Could not log telemetry: {}", err)))
+    }
+
+    fn stop_telemetry(&mut self) -> PyResult<()> {
+        self.sim.stop_telemetry()
+            .map_err(|err| PyIOError::new_err(format!("Could not log telemetry: {}", err)))
+    }
+
+    /// Stream frames to WebSocket clients connecting to 'address', returning the address listened on
+    #[pyo3( signature = (address="127.0.0.1:8765", steps_per_frame=1) )]
+    fn start_streaming(&mut self, address: &str, steps_per_frame: usize) -> PyResult<String> {
+        self.sim.start_streaming(address, steps_per_frame)
+            .map(|address| address.to_string())
+            .map_err(|err| PyIOError::new_err(format!("Could not stream on '{}': {}", address, err)))
+    }
+
+    fn stop_streaming(&mut self) {
+        self.sim.stop_streaming()
+    }
+
+    #[pyo3( signature = (distance, lateral=0.0, radius=0.5) )]
+    fn add_circle_obstacle(&mut self, distance: f32, lateral: f32, radius: f32) {
+        self.sim.road.place_obstacle(map::ObstacleShape::Circle { radius }, distance, lateral)
+    }
 
-        PyArray1::from_vec(py, data).unbind()
+    #[pyo3( signature = (distance, lateral=0.0, length=1.0, width=1.0) )]
+    fn add_rectangle_obstacle(&mut self, distance: f32, lateral: f32, length: f32, width: f32) {
+        self.sim.road.place_obstacle(map::ObstacleShape::Rectangle { length, width }, distance, lateral)
+    }
+
+    #[pyo3( signature = (start_offset=20.0, speed=8.0, lateral=0.0, length=4.0, width=1.6) )]
+    fn add_pace_car(&mut self, start_offset: f32, speed: f32, lateral: f32, length: f32, width: f32) {
+        self.sim.road.add_moving_obstacle(map::ObstacleShape::Rectangle { length, width }, start_offset, lateral, speed)
+    }
+
+    fn clear_obstacles(&mut self) {
+        self.sim.road.clear_obstacles()
+    }
+
+    /// Save the current track, including static obstacles, to a '.json' or '.toml' file
+    fn save_track(&self, path: std::path::PathBuf) -> PyResult<()> {
+        self.sim.road.save(path).map_err(track_error)
+    }
+
+    /// Get the signed distance from the point (x, y) to the nearest road edge, negative off the road
+    fn distance_to_edge(&self, x: f32, y: f32) -> f32 {
+        self.sim.road.distance_to_edge(Vec2(x, y))
+    }
+
+    /// Project the point (x, y) onto the centerline, giving the spline parameter and the arc
+    /// length of the closest centerline point, and the signed lateral offset to the left of it
+    fn closest_point(&self, x: f32, y: f32) -> (f32, f32, f32) {
+        let (arc, lateral) = self.sim.road.road_coordinates(Vec2(x, y));
+        (self.sim.road.parameter_at_arc_length(arc), arc, lateral)
+    }
+
+    /// Get the unit tangent of the centerline at the spline parameter 'u'
+    fn tangent_at(&self, u: f32) -> (f32, f32) {
+        let Vec2(x, y) = self.sim.road.spline.tangent(u);
+        (x, y)
+    }
+
+    /// Get the point of the centerline at the arc length 's'
+    fn point_at_arclength(&self, s: f32) -> (f32, f32) {
+        let Vec2(x, y) = self.sim.road.spline.get(self.sim.road.parameter_at_arc_length(s));
+        (x, y)
     }
 
     fn export_road(&self, n_segments: usize) -> SplineRoadExport {
         graphics::export_spline_road(&self.sim.road, n_segments)
     }
 
+    #[pyo3( signature = (px_per_m=2.0) )]
+    fn rasterize<'py>(&self, py: Python<'py>, px_per_m: f32) -> PyResult<Py<PyArray2<u8>>> {
+        rasterize_road(py, &self.sim.road, px_per_m)
+    }
+
+    fn render_rgb<'py>(&self, py: Python<'py>, width: usize, height: usize) -> PyResult<Py<PyArray3<u8>>> {
+        render_rgb_array(py, &self.sim, width, height)
+    }
+
     fn graphics_state(&self) -> CarGraphicsExport {
-        graphics::export_car_graphics(&self.sim.state, &self.sim.config.car, &self.sim.config.lidar, &self.sim.observe().lidar_readings)
+        graphics::export_car_graphics(&self.sim.state, &self.sim.config.car, &self.sim.config.lidar, &self.sim.observe_current().lidar_readings)
     }
 
     #[getter]
@@ -110,22 +772,234 @@ impl RacingEnv {
         self.sim.get_i()
     }
 
+    #[getter]
+    fn on_shoulder(&self) -> bool {
+        self.sim.get_on_shoulder()
+    }
+
+    #[getter]
+    fn reversed(&self) -> bool {
+        self.sim.is_reversed()
+    }
+
+    #[getter]
+    fn edge_distance(&self) -> f32 {
+        self.sim.road.distance_to_edge(self.sim.state.position)
+    }
+
+    #[getter]
+    fn track_length(&self) -> f32 {
+        self.sim.road.track_length()
+    }
+
+    #[getter]
+    fn track_width(&self) -> f32 {
+        self.sim.road.width
+    }
+
+    #[getter]
+    fn n_segments(&self) -> usize {
+        self.sim.road.spline.segments.len()
+    }
+
+    #[getter]
+    fn min_radius(&self) -> f32 {
+        self.sim.road.curvature_stats(CURVATURE_SAMPLES).min_radius()
+    }
+
+    #[getter]
+    fn mean_abs_curvature(&self) -> f32 {
+        self.sim.road.curvature_stats(CURVATURE_SAMPLES).mean_abs
+    }
+
+    #[getter]
+    fn track_bounds(&self) -> ((f32, f32), (f32, f32)) {
+        let (min, max) = self.sim.road.spline.bounds();
+        let margin = 0.5*self.sim.road.width;
+        ((min.0 - margin, min.1 - margin), (max.0 + margin, max.1 + margin))
+    }
+
+    #[getter]
+    fn progress_m(&self) -> f32 {
+        self.sim.progress()
+    }
+
+    #[getter]
+    fn progress_fraction(&self) -> f32 {
+        self.sim.progress() / self.sim.road.track_length()
+    }
+
+    #[getter]
+    fn lap(&self) -> usize {
+        self.sim.laps()
+    }
+
+    #[getter]
+    fn lap_time(&self) -> f32 {
+        self.sim.lap_time()
+    }
+
+    #[getter]
+    fn energy(&self) -> Option<f32> {
+        self.sim.state.energy
+    }
+
+    #[getter]
+    fn wheels_locked(&self) -> bool {
+        self.sim.state.is_locked()
+    }
+
+    #[getter]
+    fn longitudinal_acc(&self) -> f32 {
+        self.sim.tire_usage().longitudinal_acc
+    }
+
+    #[getter]
+    fn lateral_acc(&self) -> f32 {
+        self.sim.tire_usage().lateral_acc
+    }
+
+    #[getter]
+    fn grip_utilization(&self) -> Option<f32> {
+        self.sim.grip_utilization()
+    }
+
     #[getter]
     fn observation_dim(&self) -> usize {
-        self.sim.config.lidar.n_angles() + self.observe_delta as usize + self.observe_speed as usize
+        self.sim.observation_dim()
+    }
+}
+
+
+/// Convert (observation, action) pairs to a dict of numpy arrays
+fn demonstrations_dict<'py>(py: Python<'py>, demonstrations: &Demonstrations) -> PyResult<Bound<'py, PyDict>> {
+    let dim = demonstrations.observations.first().map_or(0, Vec::len);
+    if demonstrations.observations.iter().any(|observation| observation.len() != dim) {
+        return Err(PyValueError::new_err("Demonstrations have observations of different lengths"));
+    }
+
+    let observations: Vec<f32> = demonstrations.observations.concat();
+    let dict = PyDict::new(py);
+    dict.set_item("observation", PyArray1::from_vec(py, observations).reshape([demonstrations.len(), dim])?)?;
+    dict.set_item("action", PyArray1::from_slice(py, &demonstrations.actions))?;
+    dict.set_item("reward", PyArray1::from_slice(py, &demonstrations.rewards))?;
+    dict.set_item("done", PyArray1::from_slice(py, &demonstrations.dones))?;
+    Ok(dict)
+}
+
+
+/// Run a rollout of a simulator with the GIL released, with the policy "random" or the greedy
+/// policy of a Q-table file
+fn rollout_dict<'py, R: map::Road + Send>(py: Python<'py>, sim: &mut gym::Simulator<R>, policy: &str, n_steps: usize, seed: Option<u64>) -> PyResult<Bound<'py, PyDict>> {
+    let mut policy: Box<dyn Policy + Send> = match policy {
+        "random" => Box::new(RandomPolicy::new(seed)),
+        path => {
+            let q_table = QTablePolicy::load(path)
+                .map_err(|err| PyIOError::new_err(format!("Could not load Q-table '{}': {}", path, err)))?;
+            if q_table.chunking.observation_dim() != sim.observation_dim() {
+                return Err(PyValueError::new_err(format!("The Q-table expects observations of length {}, but the environment gives {}",
+                    q_table.chunking.observation_dim(), sim.observation_dim())));
+            }
+            Box::new(q_table)
+        },
+    };
+    let rollout = py.detach(|| sim.rollout(&mut policy, n_steps));
+    demonstrations_dict(py, &rollout)
+}
+
+
+/// Evaluate a Python callable, mapping an observation array to an integer action, over 'episodes'
+/// episodes from resets. Only the policy runs in Python; the bookkeeping and statistics are kept
+/// in Rust.
+fn evaluate_dict<'py, R: map::Road + Send + Sync>(
+    py: Python<'py>,
+    sim: &mut gym::Simulator<R>,
+    buffer: &Py<PyArray1<f32>>,
+    policy_fn: &Bound<'py, PyAny>,
+    episodes: usize,
+    max_steps: usize,
+) -> PyResult<Bound<'py, PyDict>> {
+    let mut evaluation = Evaluation::default();
+    for _ in 0 .. episodes {
+        sim.reset(None);
+        let mut tracker = EpisodeTracker::new(sim);
+        let mut observation = observe_into_buffer(py, sim, buffer)?;
+        let mut crashed = false;
+        for _ in 0 .. max_steps {
+            let action = policy_fn.call1((observation,))?.extract()?;
+            let transition = step_into_buffer(py, sim, buffer, action)?;
+            tracker.step(sim, &transition);
+            if transition.done {
+                crashed = true;
+                break;
+            }
+            observation = buffer.clone_ref(py);
+        }
+        tracker.finish(sim, crashed, &mut evaluation);
     }
+
+    let dict = PyDict::new(py);
+    dict.set_item("mean_return", evaluation.mean_return())?;
+    dict.set_item("mean_length", evaluation.mean_length())?;
+    dict.set_item("lap_completion_rate", evaluation.lap_completion_rate())?;
+    dict.set_item("crash_rate", evaluation.crash_rate())?;
+    dict.set_item("returns", PyArray1::from_slice(py, &evaluation.returns))?;
+    dict.set_item("lengths", PyArray1::from_slice(py, &evaluation.lengths))?;
+    Ok(dict)
+}
+
+
+/// Load the demonstrations recorded in the game with '--demo <file>' as a dict of numpy arrays
+#[pyfunction]
+fn load_demonstrations<'py>(py: Python<'py>, path: std::path::PathBuf) -> PyResult<Bound<'py, PyDict>> {
+    let demonstrations = py.detach(|| Demonstrations::load(&path))
+        .map_err(|err| PyIOError::new_err(format!("Could not load demonstrations from '{}': {}", path.display(), err)))?;
+    demonstrations_dict(py, &demonstrations)
 }
 
 
 /// A Python module implemented in Rust.
 #[pymodule]
 mod gym_car {
+    use pyo3::prelude::*;
+
+    #[pymodule_export]
+    use super::load_demonstrations;
+
     #[pymodule_export]
     use super::RacingEnv;
 
+    #[pymodule_export]
+    use super::CellRacingEnv;
+
+    #[pymodule_export]
+    use super::MultiCarRacingEnv;
+
+    #[pymodule_export]
+    use super::PySimConfig;
+
+    #[pymodule_export]
+    use super::PyCarConfig;
+
+    #[pymodule_export]
+    use super::PyRewardConfig;
+
     #[pymodule_export]
     use super::SplineRoadExport;
 
     #[pymodule_export]
     use super::CarGraphicsExport;
+
+    /// Add the actions as constants, e.g. 'gym_car.ACCELERATE', with 'N_ACTIONS' and their
+    /// 'ACTION_NAMES' by integer value
+    #[pymodule_init]
+    fn init(m: &Bound<'_, PyModule>) -> PyResult<()> {
+        use super::gym::Action;
+        for action in Action::ALL {
+            m.add(action.name().to_uppercase(), action as u8)?;
+        }
+        m.add("N_ACTIONS", Action::ALL.len())?;
+        m.add("ACTION_NAMES", Action::ALL.map(Action::name).to_vec())?;
+        Ok(())
+    }
 }
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyDict;
+use numpy::PyArray1;
+
+use car_sim::gym;
+use car_sim::multi_car::MultiCarSimulator;
+
+use super::{load_road, parse_action_mode, invalid_action, observation_space_dict, action_space_dict};
+
+
+/// Several cars racing head-to-head on the same track, following the PettingZoo parallel API
+#[pyclass(module="gym_car")]
+pub struct MultiCarRacingEnv {
+    race: MultiCarSimulator,
+    /// The number of steps after which all agents are truncated, if any
+    max_steps: Option<usize>,
+    steps: usize,
+}
+
+
+impl MultiCarRacingEnv {
+    fn agent_name(i: usize) -> String {
+        format!("car_{}", i)
+    }
+
+    /// Get whether agent 'i' is still in the episode, i.e. neither crashed nor truncated
+    fn is_live(&self, i: usize) -> bool {
+        self.race.is_active(i) && self.max_steps.is_none_or(|max_steps| self.steps < max_steps)
+    }
+
+    /// Get the observations of the agents in 'agents' as a dict of new numpy arrays
+    fn observations<'py>(&self, py: Python<'py>, agents: &[usize]) -> PyResult<Bound<'py, PyDict>> {
+        let dim = self.race.sim(0).observation_dim();
+        let dict = PyDict::new(py);
+        for &i in agents {
+            let mut observation = vec![0.0; dim];
+            py.detach(|| self.race.observe_into(i, &mut observation));
+            dict.set_item(Self::agent_name(i), PyArray1::from_vec(py, observation))?;
+        }
+        Ok(dict)
+    }
+
+    /// Get an empty info dict for every agent in 'agents'
+    fn infos<'py>(py: Python<'py>, agents: &[usize]) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        for &i in agents {
+            dict.set_item(Self::agent_name(i), PyDict::new(py))?;
+        }
+        Ok(dict)
+    }
+
+    fn live_agents(&self) -> Vec<usize> {
+        (0 .. self.race.n_cars()).filter(|&i| self.is_live(i)).collect()
+    }
+
+    fn agent_index(&self, agent: &str) -> PyResult<usize> {
+        (0 .. self.race.n_cars())
+            .find(|&i| Self::agent_name(i) == agent)
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown agent '{}'.", agent)))
+    }
+}
+
+
+#[pymethods]
+impl MultiCarRacingEnv {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(
+        signature = (n_agents=2, track_file=None, track_width=None, shoulder_width=None, dt=None, action_mode="discrete", grid_spacing=8.0, max_steps=None, seed=None)
+    )]
+    fn new(
+        n_agents: usize,
+        track_file: Option<std::path::PathBuf>,
+        track_width: Option<f32>,
+        shoulder_width: Option<f32>,
+        dt: Option<f32>,
+        action_mode: &str,
+        grid_spacing: f32,
+        max_steps: Option<usize>,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        if n_agents == 0 {
+            return Err(PyValueError::new_err("'n_agents' must be positive."));
+        }
+        let action_mode = parse_action_mode(action_mode)?;
+        let mut config = gym::SimConfig { action_mode, ..gym::SimConfig::default() };
+        if let Some(dt) = dt {
+            config.dt = dt;
+        }
+        let road = load_road(track_file, track_width, shoulder_width)?;
+        let mut race = MultiCarSimulator::new(config, road, n_agents, seed);
+        race.grid_spacing = grid_spacing;
+        race.reset(None);
+        Ok(Self { race, max_steps, steps: 0 })
+    }
+
+    #[pyo3( signature = (seed=None, options=None) )]
+    fn reset<'py>(&mut self, py: Python<'py>, seed: Option<u64>, options: Option<&Bound<'py, PyAny>>) -> PyResult<(Bound<'py, PyDict>, Bound<'py, PyDict>)> {
+        // Options are accepted as in the PettingZoo API, but there are none
+        let _ = options;
+        self.race.reset(seed);
+        self.steps = 0;
+        let agents = self.live_agents();
+        Ok((self.observations(py, &agents)?, Self::infos(py, &agents)?))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn step<'py>(&mut self, py: Python<'py>, actions: HashMap<String, u8>) -> PyResult<(Bound<'py, PyDict>, Bound<'py, PyDict>, Bound<'py, PyDict>, Bound<'py, PyDict>, Bound<'py, PyDict>)> {
+        let agents = self.live_agents();
+        let action_mode = self.race.sim(0).config.action_mode;
+        let mut car_actions = vec![0; self.race.n_cars()];
+        for (agent, &action) in &actions {
+            let i = self.agent_index(agent)?;
+            action_mode.to_steer_throttle(action).map_err(|_| invalid_action(action_mode, action))?;
+            if !agents.contains(&i) {
+                return Err(PyValueError::new_err(format!("Agent '{}' is not in the episode.", agent)));
+            }
+            car_actions[i] = action;
+        }
+        if let Some(&i) = agents.iter().find(|&&i| !actions.contains_key(&Self::agent_name(i))) {
+            return Err(PyValueError::new_err(format!("No action given for agent '{}'.", Self::agent_name(i))));
+        }
+
+        let race = &mut self.race;
+        let transitions = py.detach(|| race.step(&car_actions)).expect("actions to be validated");
+        self.steps += 1;
+
+        let rewards = PyDict::new(py);
+        let terminations = PyDict::new(py);
+        let truncations = PyDict::new(py);
+        for &i in &agents {
+            let transition = transitions[i].as_ref().expect("live agents to be stepped");
+            let name = Self::agent_name(i);
+            rewards.set_item(&name, transition.reward)?;
+            terminations.set_item(&name, transition.done)?;
+            truncations.set_item(&name, !transition.done && !self.is_live(i))?;
+        }
+        Ok((self.observations(py, &agents)?, rewards, terminations, truncations, Self::infos(py, &agents)?))
+    }
+
+    fn observation_space<'py>(&self, py: Python<'py>, agent: &str) -> PyResult<Bound<'py, PyDict>> {
+        self.agent_index(agent)?;
+        observation_space_dict(py, &self.race.sim(0).config)
+    }
+
+    fn action_space<'py>(&self, py: Python<'py>, agent: &str) -> PyResult<Bound<'py, PyDict>> {
+        self.agent_index(agent)?;
+        action_space_dict(py, self.race.sim(0).config.action_mode)
+    }
+
+    #[getter]
+    fn possible_agents(&self) -> Vec<String> {
+        (0 .. self.race.n_cars()).map(Self::agent_name).collect()
+    }
+
+    #[getter]
+    fn agents(&self) -> Vec<String> {
+        self.live_agents().into_iter().map(Self::agent_name).collect()
+    }
+
+    #[getter]
+    fn num_agents(&self) -> usize {
+        self.live_agents().len()
+    }
+
+    #[getter]
+    fn max_num_agents(&self) -> usize {
+        self.race.n_cars()
+    }
+}
@@ -0,0 +1,168 @@
+use serde::{Serialize, Deserialize};
+
+use super::vec::Vec2;
+
+
+/// An axis-aligned bounding box given by its minimum and maximum corners
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Aabb {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+
+impl Aabb {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self { min, max }
+    }
+
+    /// Get the smallest box containing all 'points', or None if there are no points
+    pub fn from_points<I: IntoIterator<Item=Vec2>>(points: I) -> Option<Self> {
+        points.into_iter()
+            .map(|point| Self::new(point, point))
+            .reduce(|a, b| a.union(&b))
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    pub fn center(&self) -> Vec2 {
+        (self.min + self.max)*0.5
+    }
+
+    /// Get the corners in the order (min x, min y), (min x, max y), (max x, min y), (max x, max y)
+    pub fn corners(&self) -> [Vec2; 4] {
+        let Aabb { min, max } = *self;
+        [min, Vec2(min.0, max.1), Vec2(max.0, min.1), max]
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        (self.min.0 ..= self.max.0).contains(&point.0) && (self.min.1 ..= self.max.1).contains(&point.1)
+    }
+
+    /// Check whether the boxes overlap, including touching boundaries
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.0 <= other.max.0 && other.min.0 <= self.max.0 && self.min.1 <= other.max.1 && other.min.1 <= self.max.1
+    }
+
+    /// Get the smallest box containing both boxes
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            Vec2(self.min.0.min(other.min.0), self.min.1.min(other.min.1)),
+            Vec2(self.max.0.max(other.max.0), self.max.1.max(other.max.1)),
+        )
+    }
+
+    /// Get the overlap of the boxes, or None if they do not overlap
+    pub fn intersection(&self, other: &Aabb) -> Option<Aabb> {
+        let overlap = Aabb::new(
+            Vec2(self.min.0.max(other.min.0), self.min.1.max(other.min.1)),
+            Vec2(self.max.0.min(other.max.0), self.max.1.min(other.max.1)),
+        );
+        self.intersects(other).then_some(overlap)
+    }
+
+    /// Get the box grown by 'padding' on every side, or shrunk for negative padding
+    pub fn padded(&self, padding: f32) -> Aabb {
+        Aabb::new(self.min - Vec2(padding, padding), self.max + Vec2(padding, padding))
+    }
+
+    /// Get the point in the box closest to 'point', which is 'point' itself if it is inside
+    pub fn closest_point(&self, point: Vec2) -> Vec2 {
+        Vec2(point.0.clamp(self.min.0, self.max.0), point.1.clamp(self.min.1, self.max.1))
+    }
+
+    /// Get the corner of the box farthest from 'point'
+    pub fn farthest_point(&self, point: Vec2) -> Vec2 {
+        let center = self.center();
+        Vec2(
+            if point.0 < center.0 { self.max.0 } else { self.min.0 },
+            if point.1 < center.1 { self.max.1 } else { self.min.1 },
+        )
+    }
+
+    /// Squared distance between the closest points of the boxes, zero if they overlap
+    pub fn min_distance_sq(&self, other: &Aabb) -> f32 {
+        let dx = (other.min.0 - self.max.0).max(self.min.0 - other.max.0).max(0.0);
+        let dy = (other.min.1 - self.max.1).max(self.min.1 - other.max.1).max(0.0);
+        dx*dx + dy*dy
+    }
+
+    /// Squared distance between the farthest points of the boxes
+    pub fn max_distance_sq(&self, other: &Aabb) -> f32 {
+        let dx = (other.max.0 - self.min.0).max(self.max.0 - other.min.0);
+        let dy = (other.max.1 - self.min.1).max(self.max.1 - other.min.1);
+        dx*dx + dy*dy
+    }
+
+    /// Slab test of the ray 'origin + t*direction' for t >= 0 against the box. Returns the
+    /// parameters (t_enter, t_exit) of the part of the ray inside the box, in units of the
+    /// (non-normalized) 'direction', or None if the ray misses. 't_enter' is zero if the origin is
+    /// inside the box.
+    pub fn ray_intersection(&self, origin: Vec2, direction: Vec2) -> Option<(f32, f32)> {
+        let mut t_enter = 0.0_f32;
+        let mut t_exit = f32::INFINITY;
+        for (origin, direction, min, max) in [(origin.0, direction.0, self.min.0, self.max.0), (origin.1, direction.1, self.min.1, self.max.1)] {
+            if direction == 0.0 {
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+            let t1 = (min - origin) / direction;
+            let t2 = (max - origin) / direction;
+            t_enter = t_enter.max(t1.min(t2));
+            t_exit = t_exit.min(t1.max(t2));
+        }
+        (t_enter <= t_exit).then_some((t_enter, t_exit))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_operations() {
+        let a = Aabb::new(Vec2(0.0, 0.0), Vec2(2.0, 2.0));
+        let b = Aabb::new(Vec2(1.0, -1.0), Vec2(3.0, 1.0));
+        assert_eq!(a.union(&b), Aabb::new(Vec2(0.0, -1.0), Vec2(3.0, 2.0)));
+        assert_eq!(a.intersection(&b), Some(Aabb::new(Vec2(1.0, 0.0), Vec2(2.0, 1.0))));
+        assert_eq!(a.intersection(&Aabb::new(Vec2(5.0, 5.0), Vec2(6.0, 6.0))), None);
+        assert_eq!(a.padded(1.0), Aabb::new(Vec2(-1.0, -1.0), Vec2(3.0, 3.0)));
+        assert_eq!(Aabb::from_points([Vec2(1.0, 3.0), Vec2(-1.0, 0.5), Vec2(0.0, -2.0)]), Some(Aabb::new(Vec2(-1.0, -2.0), Vec2(1.0, 3.0))));
+        assert_eq!(Aabb::from_points([]), None);
+        assert!(a.contains(Vec2(2.0, 1.0)));
+        assert!(!a.contains(Vec2(2.1, 1.0)));
+    }
+
+    #[test]
+    fn test_distances() {
+        let a = Aabb::new(Vec2(0.0, 0.0), Vec2(1.0, 1.0));
+        let b = Aabb::new(Vec2(4.0, 5.0), Vec2(5.0, 6.0));
+        assert_eq!(a.min_distance_sq(&b), 3.0*3.0 + 4.0*4.0);
+        assert_eq!(a.max_distance_sq(&b), 5.0*5.0 + 6.0*6.0);
+        assert_eq!(a.min_distance_sq(&Aabb::new(Vec2(0.5, 0.5), Vec2(2.0, 2.0))), 0.0);
+
+        let a = Aabb::new(Vec2(-1.0, -1.0), Vec2(1.0, 1.0));
+        assert_eq!(a.closest_point(Vec2(-2.0, -2.0)), Vec2(-1.0, -1.0));
+        assert_eq!(a.closest_point(Vec2(0.3, 2.0)), Vec2(0.3, 1.0));
+        assert_eq!(a.closest_point(Vec2(0.3, 0.2)), Vec2(0.3, 0.2));
+        assert_eq!(a.farthest_point(Vec2(-2.0, 0.5)), Vec2(1.0, -1.0));
+        assert_eq!(a.farthest_point(Vec2(0.5, -3.0)), Vec2(-1.0, 1.0));
+    }
+
+    #[test]
+    fn test_ray_intersection() {
+        let a = Aabb::new(Vec2(1.0, -1.0), Vec2(3.0, 1.0));
+        assert_eq!(a.ray_intersection(Vec2(0.0, 0.0), Vec2(1.0, 0.0)), Some((1.0, 3.0)));
+        assert_eq!(a.ray_intersection(Vec2(0.0, 0.0), Vec2(2.0, 0.0)), Some((0.5, 1.5)));
+        assert_eq!(a.ray_intersection(Vec2(2.0, 0.0), Vec2(0.0, 1.0)), Some((0.0, 1.0)));
+        assert_eq!(a.ray_intersection(Vec2(0.0, 0.0), Vec2(-1.0, 0.0)), None);
+        assert_eq!(a.ray_intersection(Vec2(0.0, 2.0), Vec2(1.0, 0.0)), None);
+        let (t_enter, _) = a.ray_intersection(Vec2(0.0, -2.0), Vec2(1.0, 1.0)).unwrap();
+        assert_eq!(t_enter, 1.0);
+    }
+}
@@ -0,0 +1,200 @@
+use super::vec::Vec2;
+use super::spline::{SmoothBezierSpline, BezierControl};
+
+
+/// Relative strength of the regularization keeping the least-squares system well-posed when a
+/// segment has few samples
+const REGULARIZATION: f64 = 1e-9;
+
+/// Factor by which the number of segments grows when a fit is not within tolerance
+const SEGMENT_GROWTH: f32 = 1.5;
+
+
+/// The result of fitting a spline to a sequence of points
+pub struct SplineFit {
+    pub spline: SmoothBezierSpline,
+    /// The largest distance between a sample and its corresponding point on the spline
+    pub max_error: f32,
+}
+
+
+/// Solve the dense linear system 'a*x = b' by Gaussian elimination with partial pivoting, where
+/// 'a' is n x n in row-major order and 'b' holds one right hand side per column
+fn solve_linear_system<const M: usize>(mut a: Vec<f64>, mut b: Vec<[f64; M]>) -> Vec<[f64; M]> {
+    let n = b.len();
+    for column in 0 .. n {
+        let pivot = (column .. n)
+            .max_by(|&i, &j| a[i*n + column].abs().total_cmp(&a[j*n + column].abs()))
+            .expect("at least one row");
+        if pivot != column {
+            for k in 0 .. n {
+                a.swap(column*n + k, pivot*n + k);
+            }
+            b.swap(column, pivot);
+        }
+
+        let diagonal = a[column*n + column];
+        for row in column+1 .. n {
+            let factor = a[row*n + column] / diagonal;
+            if factor == 0.0 {
+                continue;
+            }
+            for k in column .. n {
+                a[row*n + k] -= factor*a[column*n + k];
+            }
+            let pivot_b = b[column];
+            for (value, pivot_value) in b[row].iter_mut().zip(pivot_b) {
+                *value -= factor*pivot_value;
+            }
+        }
+    }
+
+    // Back substitution
+    let mut x = vec![[0.0; M]; n];
+    for row in (0 .. n).rev() {
+        for m in 0 .. M {
+            let sum: f64 = (row+1 .. n).map(|k| a[row*n + k]*x[k][m]).sum();
+            x[row][m] = (b[row][m] - sum) / a[row*n + row];
+        }
+    }
+    x
+}
+
+
+/// Least-squares fit of a spline with a fixed number of segments. The knots are placed evenly in
+/// chord length, and each sample is matched to the spline point at its chord length.
+fn fit_segments(points: &[Vec2], closed: bool, n_segments: usize) -> SplineFit {
+    // Chord length parameterization of the samples, including the closing chord of a loop
+    let mut chord = Vec::with_capacity(points.len() + 1);
+    chord.push(0.0);
+    for i in 1 .. points.len() {
+        chord.push(chord[i-1] + points[i-1].distance(points[i]));
+    }
+    if closed {
+        chord.push(chord[points.len()-1] + points[points.len()-1].distance(points[0]));
+    }
+    let total = *chord.last().expect("at least one sample");
+
+    // The unknowns are the point and velocity of each control, where a loop reuses the first
+    // control as its last
+    let n_controls = if closed { n_segments } else { n_segments + 1 };
+    let n = 2*n_controls;
+    let mut ata = vec![0.0; n*n];
+    let mut atb = vec![[0.0; 2]; n];
+
+    let parameters: Vec<f32> = points.iter().enumerate()
+        .map(|(i, _)| (chord[i] / total) * n_segments as f32)
+        .collect();
+
+    for (point, &u) in points.iter().zip(&parameters) {
+        let k = (u as usize).min(n_segments - 1);
+        let t = (u - k as f32) as f64;
+        let s = 1.0 - t;
+
+        // Bernstein basis, with B(t) = b0 P_k + b1 (P_k + V_k) + b2 (P_{k+1} - V_{k+1}) + b3 P_{k+1}
+        let (b0, b1, b2, b3) = (s*s*s, 3.0*s*s*t, 3.0*s*t*t, t*t*t);
+        let next = (k + 1) % n_controls;
+        let row = [(2*k, b0 + b1), (2*k + 1, b1), (2*next, b2 + b3), (2*next + 1, -b2)];
+
+        for &(i, a_i) in &row {
+            for &(j, a_j) in &row {
+                ata[i*n + j] += a_i*a_j;
+            }
+            atb[i][0] += a_i*point.0 as f64;
+            atb[i][1] += a_i*point.1 as f64;
+        }
+    }
+
+    let scale = (0 .. n).map(|i| ata[i*n + i]).fold(0.0, f64::max).max(1.0);
+    for i in 0 .. n {
+        ata[i*n + i] += REGULARIZATION*scale;
+    }
+
+    let solution = solve_linear_system(ata, atb);
+    let controls: Vec<BezierControl> = (0 .. n_controls)
+        .map(|k| BezierControl {
+            point: Vec2(solution[2*k][0] as f32, solution[2*k][1] as f32),
+            velocity: Vec2(solution[2*k + 1][0] as f32, solution[2*k + 1][1] as f32),
+        })
+        .collect();
+    let spline = if closed { SmoothBezierSpline::new_closed(controls) } else { SmoothBezierSpline::new(controls) };
+
+    let max_error = points.iter().zip(&parameters)
+        .map(|(&point, &u)| spline.get(u).distance(point))
+        .fold(0.0, f32::max);
+    SplineFit { spline, max_error }
+}
+
+
+/// Approximate a dense sequence of points, e.g. a recorded driving trajectory, by a spline.
+///
+/// Starting from a few segments, the number of segments is increased until the largest distance
+/// from a sample to the spline is at most 'tolerance', or 'max_segments' is reached. A 'closed'
+/// fit joins the last point back to the first, e.g. for a recorded lap.
+pub fn fit_spline(points: &[Vec2], closed: bool, max_segments: usize, tolerance: f32) -> SplineFit {
+    let min_segments = if closed { 3 } else { 1 };
+    assert!(max_segments >= min_segments, "Tried to fit spline with fewer than {} segments.", min_segments);
+    assert!(points.len() >= 2*min_segments + 2, "Tried to fit spline to only {} points.", points.len());
+
+    let mut n_segments = min_segments;
+    loop {
+        let fit = fit_segments(points, closed, n_segments);
+        if fit.max_error <= tolerance || n_segments == max_segments {
+            return fit;
+        }
+        n_segments = ((n_segments as f32 * SEGMENT_GROWTH).ceil() as usize).min(max_segments);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_system() {
+        let a = vec![0.0, 2.0, 1.0,
+                     1.0, 1.0, 0.0,
+                     3.0, 0.0, 1.0];
+        let x = solve_linear_system(a, vec![[7.0], [3.0], [6.0]]);
+        for (x, expected) in x.iter().zip([1.0, 2.0, 3.0]) {
+            assert!((x[0] - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_fit_circle() {
+        let r = 20.0;
+        let points: Vec<Vec2> = (0 .. 400)
+            .map(|i| Vec2(r, 0.0).rotate(std::f32::consts::TAU * i as f32 / 400.0))
+            .collect();
+        let SplineFit { spline, max_error } = fit_spline(&points, true, 32, 0.01);
+        assert!(max_error <= 0.01);
+        assert!(spline.is_closed());
+        assert!(spline.segments.len() <= 32);
+        assert!((spline.total_length() - std::f32::consts::TAU*r).abs() < 0.05);
+        for u in [0.1, 1.7, 2.5] {
+            assert!((spline.get(u).norm() - r).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn test_fit_open() {
+        // One segment is too few for a wave, but the error bound is reached with more segments
+        let points: Vec<Vec2> = (0 ..= 200)
+            .map(|i| {
+                let x = i as f32 * 0.1;
+                Vec2(x, 3.0*(0.5*x).sin())
+            })
+            .collect();
+        let coarse = fit_spline(&points, false, 1, 0.01);
+        assert!(coarse.max_error > 0.01);
+        assert_eq!(coarse.spline.segments.len(), 1);
+
+        let fine = fit_spline(&points, false, 50, 0.01);
+        assert!(fine.max_error <= 0.01);
+        assert!(!fine.spline.is_closed());
+        assert!((fine.spline.get(0.0) - points[0]).norm() < 0.01);
+        assert!((fine.spline.get(fine.spline.max_u) - points[200]).norm() < 0.01);
+    }
+}
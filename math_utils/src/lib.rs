@@ -1,6 +1,12 @@
 mod vec;
+mod aabb;
 pub mod spline;
 pub mod root;
+pub mod poly;
+pub mod fit;
+pub mod polyline;
+mod segment_grid;
 
 pub use vec::Vec2;
+pub use aabb::Aabb;
 
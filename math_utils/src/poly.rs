@@ -0,0 +1,253 @@
+use std::f64::consts::PI;
+
+
+/// Coefficients below this, relative to the largest coefficient, are treated as zero
+const DEGENERATE_TOLERANCE: f64 = 1e-9;
+
+
+/// Get the real roots of 'b*x + c' in increasing order. A constant polynomial has no roots.
+fn solve_linear(b: f64, c: f64) -> Vec<f64> {
+    if b == 0.0 { Vec::new() } else { vec![-c / b] }
+}
+
+
+/// Get the real roots of 'a*x^2 + b*x + c' in increasing order, where a double root is given once
+pub fn solve_quadratic(a: f32, b: f32, c: f32) -> Vec<f32> {
+    quadratic_roots(a as f64, b as f64, c as f64).into_iter().map(|x| x as f32).collect()
+}
+
+fn quadratic_roots(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let scale = a.abs().max(b.abs()).max(c.abs());
+    if a.abs() <= DEGENERATE_TOLERANCE*scale {
+        return solve_linear(b, c);
+    }
+    let discriminant = b*b - 4.0*a*c;
+    if discriminant < 0.0 {
+        return Vec::new();
+    }
+    if discriminant == 0.0 {
+        return vec![-0.5*b / a];
+    }
+    // Avoid cancellation by computing the larger root first, and the other from the product c/a
+    let q = -0.5*(b + b.signum()*discriminant.sqrt());
+    let (x0, x1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    if x0 < x1 { vec![x0, x1] } else { vec![x1, x0] }
+}
+
+
+/// Get the real roots of 'a*x^3 + b*x^2 + c*x + d' in increasing order, where repeated roots are
+/// given once. Falls back to lower degrees when the leading coefficients vanish.
+pub fn solve_cubic(a: f32, b: f32, c: f32, d: f32) -> Vec<f32> {
+    cubic_roots(a as f64, b as f64, c as f64, d as f64).into_iter().map(|x| x as f32).collect()
+}
+
+fn cubic_roots(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    let scale = a.abs().max(b.abs()).max(c.abs()).max(d.abs());
+    if a.abs() <= DEGENERATE_TOLERANCE*scale {
+        return quadratic_roots(b, c, d);
+    }
+
+    // Depressed cubic t^3 + p*t + q with x = t - b/(3a)
+    let (b, c, d) = (b / a, c / a, d / a);
+    let shift = b / 3.0;
+    let p = c - b*shift;
+    let q = 2.0*shift*shift*shift - shift*c + d;
+
+    let discriminant = 0.25*q*q + p*p*p / 27.0;
+    let mut roots = if discriminant > 0.0 {
+        // One real root, by Cardano's formula
+        let s = discriminant.sqrt();
+        vec![(-0.5*q + s).cbrt() + (-0.5*q - s).cbrt()]
+    } else if p == 0.0 {
+        vec![0.0]
+    } else {
+        // Three real roots, by the trigonometric method
+        let r = 2.0*(-p / 3.0).sqrt();
+        let phi = (3.0*q / (p*r)).clamp(-1.0, 1.0).acos() / 3.0;
+        (0 .. 3).map(|k| r*(phi - 2.0*PI*k as f64 / 3.0).cos()).collect()
+    };
+
+    // Polish the roots of the full cubic with a Newton step, which fixes rounding in the formulas
+    let f = |x: f64| ((x + b)*x + c)*x + d;
+    let fp = |x: f64| (3.0*x + 2.0*b)*x + c;
+    for root in roots.iter_mut() {
+        let x = *root - shift;
+        let slope = fp(x);
+        *root = if slope != 0.0 { x - f(x) / slope } else { x };
+    }
+    roots.sort_by(f64::total_cmp);
+    roots.dedup_by(|x, y| (*x - *y).abs() <= 1e-7*(1.0 + y.abs()));
+    roots
+}
+
+
+/// Maximum number of iterations when refining a root of a monotone piece of a polynomial
+const REFINE_MAX_ITERATIONS: usize = 100;
+
+
+/// Evaluate a polynomial, with coefficients from the highest degree, by Horner's scheme
+fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().fold(0.0, |value, &coefficient| value*x + coefficient)
+}
+
+fn derivative(coefficients: &[f64]) -> Vec<f64> {
+    let degree = coefficients.len() - 1;
+    coefficients[.. degree].iter()
+        .enumerate()
+        .map(|(i, &coefficient)| coefficient*(degree - i) as f64)
+        .collect()
+}
+
+/// Find the root of a polynomial which is monotone over [lo, hi] and changes sign there, by Newton
+/// steps safeguarded with bisection
+fn refine_root(coefficients: &[f64], slope: &[f64], mut lo: f64, mut hi: f64) -> f64 {
+    let rising = evaluate(coefficients, hi) > evaluate(coefficients, lo);
+    let mut x = 0.5*(lo + hi);
+    for _ in 0 .. REFINE_MAX_ITERATIONS {
+        let value = evaluate(coefficients, x);
+        if value == 0.0 {
+            return x;
+        }
+        if (value > 0.0) == rising { hi = x } else { lo = x }
+        let newton = x - value / evaluate(slope, x);
+        let next = if newton > lo && newton < hi { newton } else { 0.5*(lo + hi) };
+        if (next - x).abs() <= 1e-15*(1.0 + x.abs()) {
+            return next;
+        }
+        x = next;
+    }
+    x
+}
+
+/// Get the real roots in [lo, hi] of a polynomial of any degree, with no vanishing leading
+/// coefficient. Between consecutive roots of the derivative the polynomial is monotone, so it has
+/// at most one root there, which is found iteratively.
+fn roots_in(coefficients: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    let degree = coefficients.len() - 1;
+    if degree <= 3 {
+        let closed_form = match coefficients {
+            [] | [_] => Vec::new(),
+            &[b, c] => solve_linear(b, c),
+            &[a, b, c] => quadratic_roots(a, b, c),
+            &[a, b, c, d] => cubic_roots(a, b, c, d),
+            _ => unreachable!(),
+        };
+        return closed_form.into_iter().filter(|x| (lo ..= hi).contains(x)).collect();
+    }
+
+    let slope = derivative(coefficients);
+    let mut breakpoints = vec![lo];
+    breakpoints.extend(roots_in(&slope, lo, hi));
+    breakpoints.push(hi);
+
+    let mut roots: Vec<f64> = Vec::new();
+    for pair in breakpoints.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let (fa, fb) = (evaluate(coefficients, a), evaluate(coefficients, b));
+        let root = if fa == 0.0 {
+            Some(a)
+        } else if fb == 0.0 {
+            Some(b)
+        } else if fa.signum() != fb.signum() {
+            Some(refine_root(coefficients, &slope, a, b))
+        } else {
+            None
+        };
+        if let Some(root) = root && roots.last().is_none_or(|&last| (root - last).abs() > 1e-12*(1.0 + root.abs())) {
+            roots.push(root);
+        }
+    }
+    roots
+}
+
+/// Drop vanishing leading coefficients, relative to the largest coefficient
+fn trim_leading(coefficients: &[f64]) -> &[f64] {
+    let scale = coefficients.iter().fold(0.0_f64, |scale, c| scale.max(c.abs()));
+    let first = coefficients.iter()
+        .position(|c| c.abs() > DEGENERATE_TOLERANCE*scale)
+        .unwrap_or(coefficients.len());
+    &coefficients[first ..]
+}
+
+
+/// Get the real roots in [x_min, x_max] of the polynomial with the given coefficients, from the
+/// highest degree, in increasing order
+pub fn solve_polynomial_in(coefficients: &[f32], x_min: f32, x_max: f32) -> Vec<f32> {
+    let coefficients: Vec<f64> = coefficients.iter().map(|&c| c as f64).collect();
+    roots_in(trim_leading(&coefficients), x_min as f64, x_max as f64)
+        .into_iter()
+        .map(|x| x as f32)
+        .collect()
+}
+
+
+/// Get the real roots of the polynomial with the given coefficients, from the highest degree, in
+/// increasing order
+pub fn solve_polynomial(coefficients: &[f32]) -> Vec<f32> {
+    let coefficients: Vec<f64> = coefficients.iter().map(|&c| c as f64).collect();
+    let coefficients = trim_leading(&coefficients);
+    let Some((&leading, rest)) = coefficients.split_first() else {
+        return Vec::new();
+    };
+    // All roots are within Cauchy's bound
+    let bound = 1.0 + rest.iter().fold(0.0_f64, |bound, c| bound.max((c / leading).abs()));
+    roots_in(coefficients, -bound, bound).into_iter().map(|x| x as f32).collect()
+}
+
+
+/// Get the real roots of 'a*x^5 + b*x^4 + c*x^3 + d*x^2 + e*x + f' in increasing order, e.g. the
+/// stationary points of the squared distance between a point and a cubic Bezier curve
+pub fn solve_quintic(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Vec<f32> {
+    solve_polynomial(&[a, b, c, d, e, f])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(roots: Vec<f32>, expected: &[f32]) {
+        assert_eq!(roots.len(), expected.len(), "{:?} != {:?}", roots, expected);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-4, "{:?} != {:?}", roots, expected);
+        }
+    }
+
+    #[test]
+    fn test_quadratic() {
+        assert_roots(solve_quadratic(1.0, -3.0, 2.0), &[1.0, 2.0]);
+        assert_roots(solve_quadratic(2.0, 0.0, -8.0), &[-2.0, 2.0]);
+        assert_roots(solve_quadratic(1.0, 2.0, 1.0), &[-1.0]);
+        assert_roots(solve_quadratic(1.0, 0.0, 1.0), &[]);
+        assert_roots(solve_quadratic(0.0, 2.0, -1.0), &[0.5]);
+        assert_roots(solve_quadratic(0.0, 0.0, 1.0), &[]);
+    }
+
+    #[test]
+    fn test_cubic() {
+        // (x - 1)(x - 2)(x + 3)
+        assert_roots(solve_cubic(1.0, 0.0, -7.0, 6.0), &[-3.0, 1.0, 2.0]);
+        // 2(x - 0.5)(x^2 + 1)
+        assert_roots(solve_cubic(2.0, -1.0, 2.0, -1.0), &[0.5]);
+        // (x - 1)^2 (x + 1)
+        assert_roots(solve_cubic(1.0, -1.0, -1.0, 1.0), &[-1.0, 1.0]);
+        assert_roots(solve_cubic(1.0, 0.0, 0.0, -8.0), &[2.0]);
+        assert_roots(solve_cubic(1.0, 0.0, 0.0, 0.0), &[0.0]);
+        assert_roots(solve_cubic(0.0, 1.0, -3.0, 2.0), &[1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_quintic() {
+        // (x + 2)(x + 1) x (x - 1)(x - 3) = x^5 - x^4 - 7x^3 + x^2 + 6x
+        assert_roots(solve_quintic(1.0, -1.0, -7.0, 1.0, 6.0, 0.0), &[-2.0, -1.0, 0.0, 1.0, 3.0]);
+        // x^5 - 32 has the single real root 2
+        assert_roots(solve_quintic(1.0, 0.0, 0.0, 0.0, 0.0, -32.0), &[2.0]);
+        // (x - 1)^2 (x^2 + 1)(x + 4), with a double root
+        assert_roots(solve_quintic(1.0, 2.0, -6.0, 6.0, -7.0, 4.0), &[-4.0, 1.0]);
+        // Degenerate leading coefficients fall back to lower degrees
+        assert_roots(solve_quintic(0.0, 0.0, 1.0, 0.0, -7.0, 6.0), &[-3.0, 1.0, 2.0]);
+        // A quartic, (x^2 - 1)(x^2 - 4)
+        assert_roots(solve_polynomial(&[1.0, 0.0, -5.0, 0.0, 4.0]), &[-2.0, -1.0, 1.0, 2.0]);
+        assert_roots(solve_polynomial_in(&[1.0, 0.0, -5.0, 0.0, 4.0], 0.0, 1.5), &[1.0]);
+    }
+}
@@ -0,0 +1,217 @@
+use super::vec::Vec2;
+use super::aabb::Aabb;
+
+
+/// A sequence of points joined by straight segments. A closed polyline also joins its last point
+/// back to its first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    points: Vec<Vec2>,
+    closed: bool,
+    /// cumulative_length[i] is the length along the polyline from its start to the start of segment i
+    cumulative_length: Vec<f32>,
+}
+
+
+/// The closest point on a polyline to a query point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolylineProjection {
+    /// Length along the polyline from its start to the closest point
+    pub arc_length: f32,
+    pub point: Vec2,
+    pub distance_sq: f32,
+}
+
+
+/// Get the distance along the ray 'point + t*direction' to the segment from 'a' to 'b', in units
+/// of 'direction', if they intersect
+fn ray_segment_intersection(point: Vec2, direction: Vec2, a: Vec2, b: Vec2) -> Option<f32> {
+    // point + t*direction = a + s*(b-a)
+    let edge = b - a;
+    let denominator = direction.cross(edge);
+    if denominator == 0.0 {
+        return None;  // Parallel lines
+    }
+    let offset = a - point;
+    let t = offset.cross(edge) / denominator;
+    let s = offset.cross(direction) / denominator;
+    if t >= 0.0 && (0.0 ..= 1.0).contains(&s) { Some(t) } else { None }
+}
+
+/// Get the closest point on the segment from 'a' to 'b', as the fraction along the segment
+fn segment_fraction(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let edge = b - a;
+    let length_sq = edge.dot(edge);
+    if length_sq == 0.0 {
+        return 0.0;
+    }
+    ((point - a).dot(edge) / length_sq).clamp(0.0, 1.0)
+}
+
+
+impl Polyline {
+    fn with_closed(points: Vec<Vec2>, closed: bool) -> Self {
+        assert!(points.len() >= 2, "Tried to construct polyline with fewer than two points.");
+        let mut this = Self { points, closed, cumulative_length: Vec::new() };
+        let mut length = 0.0;
+        let cumulative_length = std::iter::once(length)
+            .chain(this.segments().map(|(a, b)| {
+                length += a.distance(b);
+                length
+            }))
+            .collect();
+        this.cumulative_length = cumulative_length;
+        this
+    }
+
+    /// Create an open polyline through 'points'
+    pub fn new(points: Vec<Vec2>) -> Self {
+        Self::with_closed(points, false)
+    }
+
+    /// Create a closed polyline through 'points'. A last point repeating the first is dropped, as
+    /// the polyline joins back to its first point anyway.
+    pub fn new_closed(mut points: Vec<Vec2>) -> Self {
+        if points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        Self::with_closed(points, true)
+    }
+
+    pub fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Iterate over the (start, end) points of the segments, including the closing segment of a
+    /// closed polyline
+    pub fn segments(&self) -> impl Iterator<Item=(Vec2, Vec2)> + '_ {
+        let n_segments = if self.closed { self.points.len() } else { self.points.len() - 1 };
+        (0 .. n_segments).map(|i| (self.points[i], self.points[(i + 1) % self.points.len()]))
+    }
+
+    pub fn length(&self) -> f32 {
+        *self.cumulative_length.last().expect("at least one length")
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        Aabb::from_points(self.points.iter().copied()).expect("at least two points")
+    }
+
+    pub fn closest_point(&self, point: Vec2) -> PolylineProjection {
+        self.segments()
+            .enumerate()
+            .map(|(i, (a, b))| {
+                let s = segment_fraction(point, a, b);
+                let closest = a + (b - a)*s;
+                let offset = point - closest;
+                let arc_length = self.cumulative_length[i] + s*a.distance(b);
+                PolylineProjection { arc_length, point: closest, distance_sq: offset.dot(offset) }
+            })
+            .reduce(|best, projection| if projection.distance_sq < best.distance_sq { projection } else { best })
+            .expect("at least one segment")
+    }
+
+    /// Get the point 'arc_length' along the polyline and the unit direction of its segment there.
+    /// The length wraps around for closed polylines and is clamped to the ends for open ones.
+    pub fn point_at(&self, arc_length: f32) -> (Vec2, Vec2) {
+        let arc_length = if self.closed { arc_length.rem_euclid(self.length()) } else { arc_length.clamp(0.0, self.length()) };
+        let n_segments = self.cumulative_length.len() - 1;
+        let i = self.cumulative_length.partition_point(|&length| length <= arc_length)
+            .saturating_sub(1)
+            .min(n_segments - 1);
+        let a = self.points[i];
+        let b = self.points[(i + 1) % self.points.len()];
+        let segment_length = self.cumulative_length[i+1] - self.cumulative_length[i];
+        let s = if segment_length > 0.0 { (arc_length - self.cumulative_length[i]) / segment_length } else { 0.0 };
+        (a + (b - a)*s, (b - a).normalized())
+    }
+
+    /// Takes in a point and (non-normalized) direction defining a ray and returns the distance
+    /// along the ray to the first intersection with the polyline, if any
+    pub fn ray_intersection(&self, point: Vec2, direction: Vec2) -> Option<f32> {
+        let direction = direction.normalized();
+        self.segments()
+            .filter_map(|(a, b)| ray_segment_intersection(point, direction, a, b))
+            .reduce(f32::min)
+    }
+
+    /// Checks whether the line segment between 'start' and 'end' touches the polyline
+    pub fn intersects_segment(&self, start: Vec2, end: Vec2) -> bool {
+        self.segments()
+            .any(|(a, b)| ray_segment_intersection(start, end - start, a, b).is_some_and(|t| t <= 1.0))
+    }
+
+    /// Checks whether a point is inside a closed polyline, using the even-odd rule. An open
+    /// polyline encloses no area and contains no points.
+    pub fn contains(&self, point: Vec2) -> bool {
+        if !self.closed {
+            return false;
+        }
+        let crossings = self.segments()
+            .filter(|&(a, b)| {
+                (a.1 > point.1) != (b.1 > point.1)
+                    && point.0 < a.0 + (point.1 - a.1) * (b.0 - a.0) / (b.1 - a.1)
+            })
+            .count();
+        crossings % 2 == 1
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(size: f32) -> Vec<Vec2> {
+        vec![Vec2(size, -size), Vec2(size, size), Vec2(-size, size), Vec2(-size, -size)]
+    }
+
+    #[test]
+    fn test_lengths() {
+        let closed = Polyline::new_closed(square(1.0));
+        assert_eq!(closed.segments().count(), 4);
+        assert_eq!(closed.length(), 8.0);
+        assert_eq!(closed.aabb(), Aabb::new(Vec2(-1.0, -1.0), Vec2(1.0, 1.0)));
+
+        let open = Polyline::new(square(1.0));
+        assert_eq!(open.segments().count(), 3);
+        assert_eq!(open.length(), 6.0);
+
+        // A repeated closing point is dropped
+        let mut points = square(1.0);
+        points.push(points[0]);
+        assert_eq!(Polyline::new_closed(points), closed);
+
+        assert_eq!(closed.point_at(9.0), (Vec2(1.0, 0.0), Vec2(0.0, 1.0)));
+        assert_eq!(closed.point_at(7.0), (Vec2(0.0, -1.0), Vec2(1.0, 0.0)));
+        assert_eq!(open.point_at(7.0), (Vec2(-1.0, -1.0), Vec2(0.0, -1.0)));
+    }
+
+    #[test]
+    fn test_closest_point() {
+        let closed = Polyline::new_closed(square(1.0));
+        let projection = closed.closest_point(Vec2(3.0, 0.5));
+        assert_eq!(projection, PolylineProjection { arc_length: 1.5, point: Vec2(1.0, 0.5), distance_sq: 4.0 });
+        // Only the closing segment of a closed polyline passes below the square
+        assert_eq!(closed.closest_point(Vec2(-0.5, -2.0)).point, Vec2(-0.5, -1.0));
+        assert_eq!(Polyline::new(square(1.0)).closest_point(Vec2(-0.5, -2.0)).point, Vec2(-1.0, -1.0));
+    }
+
+    #[test]
+    fn test_intersections() {
+        let closed = Polyline::new_closed(square(1.0));
+        assert_eq!(closed.ray_intersection(Vec2(0.0, 0.0), Vec2(2.0, 0.0)), Some(1.0));
+        assert_eq!(closed.ray_intersection(Vec2(-3.0, 0.0), Vec2(1.0, 0.0)), Some(2.0));
+        assert_eq!(closed.ray_intersection(Vec2(-3.0, 0.0), Vec2(-1.0, 0.0)), None);
+        assert!(closed.intersects_segment(Vec2(0.0, 0.0), Vec2(0.0, 2.0)));
+        assert!(!closed.intersects_segment(Vec2(0.0, 0.0), Vec2(0.0, 0.5)));
+
+        assert!(closed.contains(Vec2(0.5, 0.5)));
+        assert!(!closed.contains(Vec2(1.5, 0.5)));
+        assert!(!Polyline::new(square(1.0)).contains(Vec2(0.5, 0.5)));
+    }
+}
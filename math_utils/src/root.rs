@@ -104,6 +104,92 @@ where
 }
 
 
+/// Maximum number of iterations of Brent's method
+const BRENT_MAX_ITERATIONS: usize = 100;
+
+
+/// Find a root of 'f' in [x_min, x_max] to within 'tolerance' by Brent's method, given that 'f'
+/// changes sign over the interval.
+///
+/// Each iteration tries inverse quadratic interpolation or the secant method, and falls back to
+/// bisection when the interpolated point is not a sufficient improvement. This converges
+/// superlinearly for smooth functions, while never being much slower than bisection.
+pub fn find_root_brent<F>(f: F, x_min: f32, x_max: f32, tolerance: f32) -> Option<f32>
+where
+    F: Fn(f32) -> f32,
+{
+    let (mut a, mut b) = (x_min, x_max);
+    let (mut fa, mut fb) = (f(a), f(b));
+    if fa == 0.0 {
+        return Some(a);
+    }
+    if fb == 0.0 {
+        return Some(b);
+    }
+    if fa.signum() == fb.signum() {
+        return None;
+    }
+
+    // 'b' is the best estimate and 'c' the other end of the bracket, 'd' and 'e' are the latest
+    // two steps
+    let (mut c, mut fc) = (a, fa);
+    let mut d = b - a;
+    let mut e = d;
+
+    for _ in 0 .. BRENT_MAX_ITERATIONS {
+        if fb.signum() == fc.signum() {
+            (c, fc) = (a, fa);
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            (a, fa) = (b, fb);
+            (b, fb) = (c, fc);
+            (c, fc) = (a, fa);
+        }
+
+        let tol = 2.0*f32::EPSILON*b.abs() + 0.5*tolerance;
+        let half_width = 0.5*(c - b);
+        if half_width.abs() <= tol || fb == 0.0 {
+            return Some(b);
+        }
+
+        if e.abs() >= tol && fa.abs() > fb.abs() {
+            // Interpolate, with the secant method if only two distinct points are known
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                (2.0*half_width*s, 1.0 - s)
+            } else {
+                let q = fa / fc;
+                let r = fb / fc;
+                (s*(2.0*half_width*q*(q - r) - (b - a)*(r - 1.0)), (q - 1.0)*(r - 1.0)*(s - 1.0))
+            };
+            if p > 0.0 {
+                q = -q;
+            }
+            p = p.abs();
+
+            // Accept the interpolation if it stays inside the bracket and shrinks the steps
+            if 2.0*p < (3.0*half_width*q - (tol*q).abs()).min((e*q).abs()) {
+                e = d;
+                d = p / q;
+            } else {
+                d = half_width;
+                e = d;
+            }
+        } else {
+            d = half_width;
+            e = d;
+        }
+
+        (a, fa) = (b, fb);
+        b += if d.abs() > tol { d } else { tol.copysign(half_width) };
+        fb = f(b);
+    }
+    Some(b)
+}
+
+
 pub fn find_local_min_differentiable<F>(fp: F, x_min: f32, x_max: f32, width_threshold: f32) -> Option<f32>
 where
     F: Fn(f32) -> f32,
@@ -111,13 +197,60 @@ where
     // Evaluate the derivative at the start and end points
     let d_start = fp(x_min);
     let d_end = fp(x_max);
+    if !(d_start.is_finite() && d_end.is_finite()) {
+        return None  // The derivative is ill-behaved, e.g. at a cusp
+    }
     if d_start > 0.0 || d_end < 0.0 {
         return None  // The passed arguments can only guarantee a local maximum
     } 
 
     // The derivative will have a root with negative derivative to the left and positive derivative
     // to the right. The root found will constitute a local minimum
-    find_root(fp, x_min, x_max, width_threshold)
+    find_root_brent(fp, x_min, x_max, width_threshold)
+}
+
+
+/// Maximum number of iterations of the golden-section search
+const GOLDEN_SECTION_MAX_ITERATIONS: usize = 100;
+
+
+/// Find a minimum of 'f' in [x_min, x_max] to within 'width_threshold' by golden-section search,
+/// using only function values. Finds the global minimum if 'f' is unimodal over the interval,
+/// and otherwise some local minimum.
+pub fn find_min<F>(f: F, x_min: f32, x_max: f32, width_threshold: f32) -> FunctionObservation
+where
+    F: Fn(f32) -> f32,
+{
+    // Keep two interior points splitting the interval in the golden ratio, so that one of them
+    // can be reused when the interval shrinks
+    let inverse_phi = 0.5*(5.0_f32.sqrt() - 1.0);
+    let (mut a, mut b) = (x_min, x_max);
+    let mut left = FunctionObservation::new(b - inverse_phi*(b - a), f(b - inverse_phi*(b - a)));
+    let mut right = FunctionObservation::new(a + inverse_phi*(b - a), f(a + inverse_phi*(b - a)));
+
+    for _ in 0 .. GOLDEN_SECTION_MAX_ITERATIONS {
+        if b - a <= width_threshold {
+            break;
+        }
+        if left.value <= right.value {
+            b = right.x;
+            right = left;
+            let x = b - inverse_phi*(b - a);
+            left = FunctionObservation::new(x, f(x));
+        } else {
+            a = left.x;
+            left = right;
+            let x = a + inverse_phi*(b - a);
+            right = FunctionObservation::new(x, f(x));
+        }
+    }
+
+    // The interval end points may be the minimum if it is at the boundary
+    [FunctionObservation::new(x_min, f(x_min)), left, right, FunctionObservation::new(x_max, f(x_max))]
+        .into_iter()
+        .filter(|obs| obs.x >= a && obs.x <= b)
+        .min_by(|obs_a, obs_b| obs_a.value.total_cmp(&obs_b.value))
+        .expect("the interior points to be inside the interval")
 }
 
 
@@ -150,14 +283,15 @@ where
         let x_left = (obs_i.x-dx).max(x_min);
         let x_right = (obs_i.x+dx).min(x_max);
 
-        if let Some(x_lm) = find_local_min_differentiable(fp, x_left, x_right, width_threshold) {
-            let obs_lm = FunctionObservation::new(x_lm, f(x_lm));
-            match obs_lm.value.total_cmp(&obs_i.value) {
-                Ordering::Less => obs_lm,
-                Ordering::Greater | Ordering::Equal => obs_i,
-            } 
-        } else {
-            obs_i
+        // When the derivative gives no root, e.g. where it vanishes at a zero-velocity spline
+        // point, fall back to a search which only uses the function values
+        let obs_lm = match find_local_min_differentiable(fp, x_left, x_right, width_threshold) {
+            Some(x_lm) if x_lm.is_finite() => FunctionObservation::new(x_lm, f(x_lm)),
+            _ => find_min(&f, x_left, x_right, width_threshold),
+        };
+        match obs_lm.value.total_cmp(&obs_i.value) {
+            Ordering::Less => obs_lm,
+            Ordering::Greater | Ordering::Equal => obs_i,
         }
 }
 
@@ -174,6 +308,55 @@ mod tests {
         assert_eq!(find_root(f, 0.0, std::f32::consts::PI, 1e-3), Some(3.0));
     }
 
+    #[test]
+    fn test_root_brent() {
+        let f = |x| x*x - 9.0;
+        assert!((find_root_brent(f, 1.0, 4.0, 1e-6).unwrap() - 3.0).abs() < 1e-6);
+        assert!((find_root_brent(f, 4.0, 0.0, 1e-6).unwrap() - 3.0).abs() < 1e-6);
+        assert_eq!(find_root_brent(f, 4.0, 5.0, 1e-6), None);
+
+        // Converges to high accuracy on a smooth function in few evaluations
+        let evaluations = std::cell::Cell::new(0);
+        let f = |x: f32| {
+            evaluations.set(evaluations.get() + 1);
+            x.cos() - x
+        };
+        let root = find_root_brent(f, 0.0, 1.0, 1e-6).unwrap();
+        assert!((root - 0.739_085_13).abs() < 1e-6);
+        assert!(evaluations.get() < 12);
+
+        // Falls back to bisection for a function which interpolates badly
+        let root = find_root_brent(|x: f32| x.cbrt(), -1.0, 2.0, 1e-6).unwrap();
+        assert!(root.abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_golden_section() {
+        let extremum = find_min(|x: f32| (x - 0.3).abs(), 0.0, 1.0, 1e-5);
+        assert!((extremum.x - 0.3).abs() < 1e-5);
+        assert!(extremum.value < 1e-5);
+
+        // Minimum at the boundary
+        let extremum = find_min(|x: f32| x*x, 1.0, 2.0, 1e-5);
+        assert_eq!(extremum.x, 1.0);
+        assert_eq!(extremum.value, 1.0);
+        let extremum = find_min(|x: f32| -x, 1.0, 2.0, 1e-5);
+        assert_eq!(extremum.x, 2.0);
+    }
+
+    #[test]
+    fn test_min_fallback() {
+        // A derivative which is not finite everywhere still gives the minimum by golden section
+        let f = |x: f32| (x - 0.5).abs();
+        let fp = |_: f32| f32::NAN;
+        let extremum = find_min_differentiable(f, fp, 0.0, 1.0, 1e-5);
+        assert_eq!(extremum.x, 0.5);
+
+        let f = |x: f32| (x - 0.51).abs();
+        let extremum = find_min_differentiable(f, fp, 0.0, 1.0, 1e-5);
+        assert!((extremum.x - 0.51).abs() < 1e-5);
+    }
+
     #[test]
     fn test_min() {
         // Find minimum of cos(x)
@@ -182,7 +365,7 @@ mod tests {
 
         // Case when global minimum is local minimum inside the range
         let extremum = find_min_differentiable(f, fp, 3.0, 3.5, 1e-3);
-        assert_eq!(extremum.x, std::f32::consts::PI);
+        assert!((extremum.x - std::f32::consts::PI).abs() < 1e-6);
         assert_eq!(extremum.value, -1.0);
 
         // Case when global minimum is boundary value
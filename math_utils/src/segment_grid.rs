@@ -0,0 +1,88 @@
+use super::vec::Vec2;
+use super::aabb::Aabb;
+
+
+/// Get the segments which may hold the closest point to any point in the box 'region'
+pub(crate) fn box_candidates(segment_bounds: &[Aabb], region: &Aabb) -> Vec<usize> {
+    let upper_bound = segment_bounds.iter()
+        .map(|bounds| region.max_distance_sq(bounds))
+        .fold(f32::INFINITY, f32::min);
+    segment_bounds.iter()
+        .enumerate()
+        .filter(|&(_, bounds)| region.min_distance_sq(bounds) <= upper_bound)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+
+/// A uniform grid over the plane, listing for each cell the segments which may hold the closest
+/// point to any point in the cell.
+///
+/// For a cell, let U be the smallest over all segments of the largest distance between the cell
+/// and the bounding box of the segment. The closest segment to any point in the cell is then at
+/// most U away, so only segments whose bounding box is within U of the cell are candidates.
+#[derive(Clone)]
+pub(crate) struct SegmentGrid {
+    origin: Vec2,
+    cell_size: f32,
+    nx: usize,
+    ny: usize,
+    candidates: Vec<Vec<usize>>,
+}
+
+
+impl SegmentGrid {
+    /// Build a grid with a given cell size covering the segment bounding boxes, padded by 'padding'
+    pub(crate) fn new(segment_bounds: &[Aabb], cell_size: f32, padding: f32) -> Self {
+        assert!(cell_size > 0.0, "Grid cell size must be positive, got {}", cell_size);
+        let covered = segment_bounds.iter()
+            .copied()
+            .reduce(|a, b| a.union(&b))
+            .expect("at least one segment")
+            .padded(padding);
+        let origin = covered.min;
+        let extent = covered.size();
+        let nx = ((extent.0 / cell_size).ceil() as usize).max(1);
+        let ny = ((extent.1 / cell_size).ceil() as usize).max(1);
+
+        let candidates = (0 .. nx*ny)
+            .map(|idx| {
+                let cell_min = origin + Vec2((idx % nx) as f32, (idx / nx) as f32)*cell_size;
+                box_candidates(segment_bounds, &Aabb::new(cell_min, cell_min + Vec2(cell_size, cell_size)))
+            })
+            .collect();
+
+        Self { origin, cell_size, nx, ny, candidates }
+    }
+
+    /// Get the candidate segments for a point, or None if the point is outside of the grid
+    pub(crate) fn candidates(&self, point: Vec2) -> Option<&[usize]> {
+        let grid = (point - self.origin) / self.cell_size;
+        if !(grid.0 >= 0.0 && grid.1 >= 0.0) {
+            return None;
+        }
+        let (i, j) = (grid.0 as usize, grid.1 as usize);
+        if i >= self.nx || j >= self.ny {
+            return None;
+        }
+        Some(&self.candidates[j*self.nx + i])
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        // Two far apart segments; cells near one of them only list that one
+        let bounds = [Aabb::new(Vec2(0.0, 0.0), Vec2(1.0, 1.0)), Aabb::new(Vec2(20.0, 0.0), Vec2(21.0, 1.0))];
+        let grid = SegmentGrid::new(&bounds, 1.0, 2.0);
+        assert_eq!(grid.candidates(Vec2(0.5, 0.5)), Some(&[0][..]));
+        assert_eq!(grid.candidates(Vec2(20.5, -1.5)), Some(&[1][..]));
+        assert_eq!(grid.candidates(Vec2(10.5, 0.5)).map(|candidates| candidates.len()), Some(2));
+        assert_eq!(grid.candidates(Vec2(-5.0, 0.0)), None);
+        assert_eq!(grid.candidates(Vec2(0.0, 10.0)), None);
+    }
+}
@@ -1,11 +1,45 @@
 use super::vec::Vec2;
+use super::aabb::Aabb;
 use itertools::Itertools;
 
 use std::cmp::Ordering;
 
+use serde::{Serialize, Deserialize};
+
 use super::root::{FunctionObservation, find_min_differentiable};
+use super::poly::{solve_quadratic, solve_cubic};
+use super::segment_grid::{SegmentGrid, box_candidates};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+
+/// Number of intervals in the arc length lookup table of each Bezier curve
+const ARC_TABLE_INTERVALS: usize = 32;
+
+/// Number of Newton iterations used when inverting the arc length
+const ARC_INVERSE_ITERATIONS: usize = 3;
+
+/// Tolerance in the curve parameter of the bracketing search for closest points
+const CLOSEST_POINT_TOLERANCE: f32 = 1e-3;
+
+/// Number of Newton iterations refining closest points after the bracketing search
+const CLOSEST_POINT_NEWTON_ITERATIONS: usize = 2;
+
+/// Default maximum distance between an offset curve and its polyline approximation
+pub const OFFSET_TOLERANCE: f32 = 0.05;
 
+/// Number of initial subdivisions of each segment when approximating offset curves
+const OFFSET_MIN_SUBDIVISIONS: usize = 4;
 
+/// Maximum depth of the adaptive subdivision of offset curves
+const OFFSET_MAX_DEPTH: usize = 10;
+
+
+/// A cubic Bezier curve. Only the control points are serialized, the cached coefficients,
+/// arc length table and bounding box are recomputed on load.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "BezierPoints", into = "BezierPoints")]
 pub struct CubicBezier {
     pub start: Vec2,
     pub p1: Vec2,
@@ -14,76 +48,75 @@ pub struct CubicBezier {
     c1: Vec2,
     c2: Vec2,
     c3: Vec2,
-    arc_length: f32,
-    bounding_box: BoundingBox,
+    /// arc_table[i] is the arc length from t=0 to t=i/ARC_TABLE_INTERVALS
+    arc_table: [f32; ARC_TABLE_INTERVALS + 1],
+    bounding_box: Aabb,
 }
 
 
-#[derive(Debug)]
-struct BoundingBox {
-    pub min_x: f32,
-    pub max_x: f32,
-    pub min_y: f32,
-    pub max_y: f32,
-    pub corners: [Vec2; 4]
+/// The serialized form of a 'CubicBezier'
+#[derive(Serialize, Deserialize)]
+struct BezierPoints {
+    start: Vec2,
+    p1: Vec2,
+    p2: Vec2,
+    end: Vec2,
 }
 
-impl BoundingBox {
-    fn new(min_x: f32, max_x: f32, min_y: f32, max_y: f32) -> Self {
-        let corners = [Vec2(min_x, min_y), Vec2(min_x, max_y), Vec2(max_x, min_y), Vec2(max_x, max_y)];
-        Self { min_x, max_x, min_y, max_y, corners }
-    }
-
-    pub fn closest_point(&self, point: Vec2) -> Vec2 {
-        let Vec2(x, y) = point;
-        match (x <= self.min_x, x > self.max_x, y <= self.min_y, y > self.max_y) {
-            // Corner quadrants
-            (true, _, true, _) => Vec2(self.min_x, self.min_y),
-            (true, _, _, true) => Vec2(self.min_x, self.max_y),
-            (_, true, _, true) => Vec2(self.max_x, self.max_y),
-            (_, true, true, _) => Vec2(self.max_x, self.min_y),
-            
-            // Middle quadrant
-            (false, false, false, false) => Vec2(x, y),
-
-            // Edge quadrants
-            (true, false, false, false) => Vec2(self.min_x, y),
-            (false, true, false, false) => Vec2(self.max_x, y),
-            (false, false, true, false) => Vec2(x, self.min_y),
-            (false, false, false, true) => Vec2(x, self.max_y),
-
-            (true, true, _, _) => panic!("Impossible quadrant! (x,y)=({},{}), bbox={:?}", x, y, self),
-            (_, _, true, true) => panic!("Impossible quadrant! (x,y)=({},{}), bbox={:?}", x, y, self),
-        }
-    }
-
-    pub fn farthest_point(&self, point: Vec2) -> Vec2 {
-        let (corner, _d2) = self.corners.iter()
-            .map(|corner| {
-                let delta = point - *corner;
-                let d2 = delta.dot(delta);
-                (*corner, d2)
-            })
-            .reduce(|(corner, d2): (Vec2, f32), (new_corner, new_d2): (Vec2, f32)| match new_d2.total_cmp(&d2) {
-                Ordering::Greater => (new_corner, new_d2),
-                _ => (corner, d2),
-            })
-            .expect("at least one corner to exist");
-        corner
+impl From<BezierPoints> for CubicBezier {
+    fn from(BezierPoints { start, p1, p2, end }: BezierPoints) -> Self {
+        CubicBezier::new(start, p1, p2, end)
+    }
+}
+
+impl From<CubicBezier> for BezierPoints {
+    fn from(CubicBezier { start, p1, p2, end, .. }: CubicBezier) -> Self {
+        BezierPoints { start, p1, p2, end }
     }
 }
 
 
 /// Represents a single spline point and its tangent velocity specification
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct BezierControl {
     pub point: Vec2,
     pub velocity: Vec2,
 }
 
 
+/// A spline of joined cubic Bezier curves. Serialized as its segments and whether it is closed.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "SplineSegments", into = "SplineSegments")]
 pub struct SmoothBezierSpline {
     pub segments: Vec<CubicBezier>,
     pub max_u: f32,
+    /// cumulative_length[i] is the arc length from the start of the spline to the start of segment i
+    cumulative_length: Vec<f32>,
+    /// Whether the end of the spline joins its start, in which case the parameter wraps around
+    closed: bool,
+    /// Optional spatial index used to speed up closest point queries
+    index: Option<SegmentGrid>,
+}
+
+
+/// The serialized form of a 'SmoothBezierSpline'
+#[derive(Serialize, Deserialize)]
+struct SplineSegments {
+    segments: Vec<CubicBezier>,
+    #[serde(default)]
+    closed: bool,
+}
+
+impl From<SplineSegments> for SmoothBezierSpline {
+    fn from(SplineSegments { segments, closed }: SplineSegments) -> Self {
+        SmoothBezierSpline::from_segments(segments, closed)
+    }
+}
+
+impl From<SmoothBezierSpline> for SplineSegments {
+    fn from(SmoothBezierSpline { segments, closed, .. }: SmoothBezierSpline) -> Self {
+        SplineSegments { segments, closed }
+    }
 }
 
 
@@ -105,10 +138,13 @@ impl CubicBezier {
         let mut this = CubicBezier {
             start, p1, p2, end,
             c1, c2, c3,
-            arc_length: 0.0,
-            bounding_box: BoundingBox::new(0.0, 0.0, 0.0, 0.0),
+            arc_table: [0.0; ARC_TABLE_INTERVALS + 1],
+            bounding_box: Aabb::new(start, start),
         };
-        this.arc_length = this._arc_length(0.0, 1.0, 32);
+        for i in 0 .. ARC_TABLE_INTERVALS {
+            let (t_start, t_end) = (i as f32 / ARC_TABLE_INTERVALS as f32, (i+1) as f32 / ARC_TABLE_INTERVALS as f32);
+            this.arc_table[i+1] = this.arc_table[i] + this._arc_length(t_start, t_end);
+        }
         this.bounding_box = this._bounding_box();
         this
     }
@@ -125,37 +161,180 @@ impl CubicBezier {
         self.velocity(t).normalized()
     }
 
-    fn _arc_length(&self, t_start: f32, t_end: f32, steps: usize) -> f32 {
+    pub fn acceleration(&self, t: f32) -> Vec2 {
+        self.c2 * 2.0 + self.c3 * 6.0 * t
+    }
+
+    /// Get the signed curvature at 't', positive when the curve turns left (counterclockwise)
+    pub fn curvature(&self, t: f32) -> f32 {
+        // kappa = (v x a) / |v|^3
+        let v = self.velocity(t);
+        let a = self.acceleration(t);
+        let speed = v.norm();
+        v.cross(a) / (speed*speed*speed)
+    }
+
+    fn _arc_length(&self, t_start: f32, t_end: f32) -> f32 {
         // Arc length is int_{t_start}^{t_end} |velocity(t)|dt
-        // Compute it numerically using trapezoid method
-        let dt = (t_end - t_start) / steps as f32;
-        let ts = (1 .. steps).map(|i| t_start + i as f32*dt);  // [dt, 2*dt, ..., t-dt]
-                                                               //
-        ts.map(|t| self.velocity(t).norm()*dt).sum::<f32>() + 0.5*dt*(self.velocity(t_start).norm() + self.velocity(t_end).norm())
+        // Compute it numerically using three-point Gauss-Legendre quadrature, which is accurate
+        // over the short intervals of the lookup table
+        let half_width = 0.5*(t_end - t_start);
+        let center = t_start + half_width;
+        let offset = half_width*0.6_f32.sqrt();
+        half_width * (5.0/9.0*self.velocity(center - offset).norm()
+                      + 8.0/9.0*self.velocity(center).norm()
+                      + 5.0/9.0*self.velocity(center + offset).norm())
     }
 
     // Computes the tangential arc length from t=0 to t=t
     pub fn arc_length(&self, t: f32) -> f32 {
-        if t == 1.0 {
-            self.arc_length
-            
+        let t = t.clamp(0.0, 1.0);
+        let i = ((t*ARC_TABLE_INTERVALS as f32) as usize).min(ARC_TABLE_INTERVALS - 1);
+        let t_start = i as f32 / ARC_TABLE_INTERVALS as f32;
+        if t == t_start {
+            self.arc_table[i]
         } else {
-            self._arc_length(0.0, t, 32)
+            self.arc_table[i] + self._arc_length(t_start, t)
         }
     }
 
-    fn _bounding_box(&self) -> BoundingBox {
-        let fx = |t| { self.get(t).0 };
-        let fpx = |t| { self.velocity(t).0 };
-        let min_x = find_min_differentiable(fx, fpx, 0.0, 1.0, 1e-4).value;
-        let max_x = -find_min_differentiable(|t| -fx(t), |t| -fpx(t), 0.0, 1.0, 1e-4).value;
+    pub fn total_length(&self) -> f32 {
+        self.arc_table[ARC_TABLE_INTERVALS]
+    }
+
+    /// Find the parameter t at which the arc length from t=0 is 'arc', i.e. the inverse of
+    /// 'arc_length'. Uses the lookup table to find the interval, then refines with Newton's method.
+    pub fn t_at_arc_length(&self, arc: f32) -> f32 {
+        let arc = arc.clamp(0.0, self.total_length());
+        let i = self.arc_table.partition_point(|&length| length <= arc)
+            .saturating_sub(1)
+            .min(ARC_TABLE_INTERVALS - 1);
+        let (t_start, t_end) = (i as f32 / ARC_TABLE_INTERVALS as f32, (i+1) as f32 / ARC_TABLE_INTERVALS as f32);
+        let (arc_start, arc_end) = (self.arc_table[i], self.arc_table[i+1]);
+        if arc_end <= arc_start {
+            return t_start;  // Degenerate interval without length
+        }
+
+        // Initial guess by linear interpolation within the interval
+        let mut t = t_start + (t_end - t_start)*(arc - arc_start)/(arc_end - arc_start);
+        for _ in 0 .. ARC_INVERSE_ITERATIONS {
+            let speed = self.velocity(t).norm();
+            if speed <= f32::EPSILON {
+                break;
+            }
+            t = (t - (self.arc_length(t) - arc)/speed).clamp(t_start, t_end);
+        }
+        t
+    }
+
+    /// Get the exact bounding box from the end points and the extrema of each coordinate, where
+    /// the quadratic velocity component vanishes
+    fn _bounding_box(&self) -> Aabb {
+        let extent = |component: fn(Vec2) -> f32| {
+            let roots = solve_quadratic(3.0*component(self.c3), 2.0*component(self.c2), component(self.c1));
+            [0.0, 1.0].into_iter()
+                .chain(roots.into_iter().filter(|t| (0.0 ..= 1.0).contains(t)))
+                .map(|t| component(self.get(t)))
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), x| (min.min(x), max.max(x)))
+        };
+        let (min_x, max_x) = extent(|v| v.0);
+        let (min_y, max_y) = extent(|v| v.1);
+        Aabb::new(Vec2(min_x, min_y), Vec2(max_x, max_y))
+    }
+
+    /// Get the smallest axis-aligned box containing the curve
+    pub fn aabb(&self) -> Aabb {
+        self.bounding_box
+    }
+
+    /// Get the (min, max) corners of an axis-aligned box containing the curve
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        (self.bounding_box.min, self.bounding_box.max)
+    }
+
+    /// Map the curve by an affine map 'f'. Bezier curves are affine invariant, so mapping the
+    /// control points maps every point on the curve.
+    pub fn map_points<F: Fn(Vec2) -> Vec2>(&self, f: F) -> Self {
+        CubicBezier::new(f(self.start), f(self.p1), f(self.p2), f(self.end))
+    }
+
+    /// Get the same curve traversed in the opposite direction
+    pub fn reversed(&self) -> Self {
+        CubicBezier::new(self.end, self.p2, self.p1, self.start)
+    }
 
-        let fy = |t| { self.get(t).1 };
-        let fpy = |t| { self.velocity(t).1 };
+    /// Split the curve at 't' into the two curves covering [0, t] and [t, 1], using de Casteljau's
+    /// algorithm
+    pub fn split(&self, t: f32) -> (CubicBezier, CubicBezier) {
+        let (a, b, c) = (self.start.lerp(self.p1, t), self.p1.lerp(self.p2, t), self.p2.lerp(self.end, t));
+        let (d, e) = (a.lerp(b, t), b.lerp(c, t));
+        let middle = d.lerp(e, t);
+        (CubicBezier::new(self.start, a, d, middle), CubicBezier::new(middle, e, c, self.end))
+    }
 
-        let min_y = find_min_differentiable(fy, fpy, 0.0, 1.0, 1e-4).value;
-        let max_y = -find_min_differentiable(|t| -fy(t), |t| -fpy(t), 0.0, 1.0, 1e-4).value;
-        BoundingBox::new(min_x, max_x, min_y, max_y)
+    /// Get the part of the curve between 't_start' and 't_end'
+    fn sub_curve(&self, t_start: f32, t_end: f32) -> CubicBezier {
+        let (_, tail) = self.split(t_start);
+        if t_start >= 1.0 {
+            return tail;
+        }
+        let (middle, _) = tail.split((t_end - t_start) / (1.0 - t_start));
+        middle
+    }
+
+    /// Get the distances along a ray from 'origin' in the (non-normalized) 'direction' to all of
+    /// its intersections with the curve, in increasing order
+    pub fn ray_intersections(&self, origin: Vec2, direction: Vec2) -> Vec<f32> {
+        let direction = direction.normalized();
+        let normal = direction.rotate90();
+
+        // By the convex hull property, there is no intersection with the line of the ray if all
+        // control points are on the same side of it
+        let sides = [self.start, self.p1, self.p2, self.end].map(|p| normal.dot(p - origin));
+        if sides.iter().all(|&side| side > 0.0) || sides.iter().all(|&side| side < 0.0) {
+            return Vec::new();
+        }
+
+        // The curve crosses the line of the ray where normal . (B(t) - origin) = 0, a cubic in t
+        let mut distances: Vec<f32> = solve_cubic(normal.dot(self.c3), normal.dot(self.c2), normal.dot(self.c1), sides[0])
+            .into_iter()
+            .filter(|t| (0.0 ..= 1.0).contains(t))
+            .map(|t| direction.dot(self.get(t) - origin))
+            .filter(|&distance| distance >= 0.0)
+            .collect();
+        distances.sort_by(f32::total_cmp);
+        distances
+    }
+
+    /// Get the point at a signed 'distance' to the left of the curve at 't'
+    fn offset_point(&self, t: f32, distance: f32) -> Vec2 {
+        self.get(t) + self.tangent(t).rotate90()*distance
+    }
+
+    /// Approximate the offset curve at a signed 'distance' to the left of the curve between
+    /// 't_start' and 't_end' by a cubic Bezier curve with matching end points and derivatives.
+    fn offset_piece(&self, t_start: f32, t_end: f32, distance: f32) -> CubicBezier {
+        // The offset o(t) = B(t) + distance*n(t) has derivative o'(t) = B'(t)*(1 - distance*kappa(t))
+        let h = t_end - t_start;
+        let handle = |t: f32| self.velocity(t)*(1.0 - distance*self.curvature(t))*(h / 3.0);
+        let (start, end) = (self.offset_point(t_start, distance), self.offset_point(t_end, distance));
+        CubicBezier::new(start, start + handle(t_start), end - handle(t_end), end)
+    }
+
+    /// Recursively push cubic Bezier curves approximating the offset curve between 't_start' and
+    /// 't_end', splitting the interval while the approximation deviates more than 'tolerance'
+    fn subdivide_offset_curve(&self, t_start: f32, t_end: f32, distance: f32, tolerance: f32, depth: usize, pieces: &mut Vec<CubicBezier>) {
+        let piece = self.offset_piece(t_start, t_end, distance);
+        let error = [0.25, 0.5, 0.75].into_iter()
+            .map(|s| piece.get(s).distance(self.offset_point(t_start + s*(t_end - t_start), distance)))
+            .fold(0.0, f32::max);
+        if depth == 0 || error <= tolerance {
+            pieces.push(piece);
+            return;
+        }
+        let t_mid = 0.5*(t_start + t_end);
+        self.subdivide_offset_curve(t_start, t_mid, distance, tolerance, depth - 1, pieces);
+        self.subdivide_offset_curve(t_mid, t_end, distance, tolerance, depth - 1, pieces);
     }
 
     pub fn closest_point(&self, point: Vec2) -> ClosestPointOutput {
@@ -170,7 +349,31 @@ impl CubicBezier {
             (pt - point).dot(v) * 2.0
         };
 
-        let FunctionObservation { x: t, value: distance_sq, ..} = find_min_differentiable(f, fp, 0.0, 1.0, 1e-2);
+        let FunctionObservation { x: mut t, value: mut distance_sq, ..} = find_min_differentiable(f, fp, 0.0, 1.0, CLOSEST_POINT_TOLERANCE);
+
+        // Refine with Newton's method on the derivative of the squared distance, whose second
+        // derivative is 2*(|v|^2 + (B - point).a). Steps are only taken where the squared distance
+        // is convex, and kept only if they reduce the derivative, since the squared distance itself
+        // is too flat near the minimum to compare in single precision.
+        let mut derivative = fp(t);
+        for _ in 0 .. CLOSEST_POINT_NEWTON_ITERATIONS {
+            let delta = self.get(t) - point;
+            let v = self.velocity(t);
+            let second_derivative = 2.0*(v.dot(v) + delta.dot(self.acceleration(t)));
+            if second_derivative <= 0.0 || derivative == 0.0 {
+                break;
+            }
+            let t_new = (t - derivative / second_derivative).clamp(0.0, 1.0);
+            let derivative_new = fp(t_new);
+            if derivative_new.abs() >= derivative.abs() {
+                break;
+            }
+            let distance_sq_new = f(t_new);
+            if distance_sq_new > distance_sq + 1e-6*(1.0 + distance_sq) {
+                break;
+            }
+            (t, distance_sq, derivative) = (t_new, distance_sq_new, derivative_new);
+        }
         ClosestPointOutput { parameter: t, distance_sq }
     }
 }
@@ -189,11 +392,118 @@ impl SmoothBezierSpline {
             })
             .collect();
 
+        Self::from_segments(segments, false)
+    }
+
+    /// Create a closed loop through the control points. The loop is closed by joining the last
+    /// control point to the first, with matching velocities so that the seam is C1 continuous.
+    /// If the last control point is already at the first, it is replaced by the first.
+    pub fn new_closed(mut controls: Vec<BezierControl>) -> Self {
+        assert!(controls.len() >= 2, "Tried to construct closed SmoothBezierSpline with fewer than two control points.");
+        let first = &controls[0];
+        let closing = *first;
+        if controls.last().is_some_and(|last| last.point == first.point) {
+            controls.pop();
+        }
+        controls.push(closing);
+
+        let mut this = Self::new(controls);
+        this.closed = true;
+        this
+    }
+
+    /// Create a closed loop through a sequence of waypoints, deriving the control velocities from
+    /// the neighboring waypoints Catmull-Rom style. 'tension' scales the velocities, where 0.5
+    /// gives a standard Catmull-Rom spline and smaller values give tighter corners. A repeated
+    /// closing waypoint is ignored.
+    pub fn through_points(points: &[Vec2], tension: f32) -> Self {
+        let points = match points {
+            [first, .., last] if first == last => &points[.. points.len()-1],
+            _ => points,
+        };
+        assert!(points.len() >= 3, "Tried to construct closed SmoothBezierSpline through fewer than three distinct points.");
+
+        // A Catmull-Rom curve has tangent tension*(p[i+1] - p[i-1]) at p[i]. The corresponding
+        // Bezier control point is a third of the tangent away from p[i].
+        let n = points.len();
+        let controls = (0 .. n)
+            .map(|i| {
+                let velocity = (points[(i + 1) % n] - points[(i + n - 1) % n]) * (tension / 3.0);
+                BezierControl { point: points[i], velocity }
+            })
+            .collect();
+        Self::new_closed(controls)
+    }
+
+    fn from_segments(segments: Vec<CubicBezier>, closed: bool) -> Self {
         let max_u = segments.len() as f32;
-        Self { segments, max_u }
+        let mut cumulative_length = Vec::with_capacity(segments.len() + 1);
+        let mut length = 0.0;
+        cumulative_length.push(length);
+        for segment in &segments {
+            length += segment.total_length();
+            cumulative_length.push(length);
+        }
+        Self { segments, max_u, cumulative_length, closed, index: None }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Map the spline by an affine map 'f', see 'CubicBezier::map_points'
+    pub fn map_points<F: Fn(Vec2) -> Vec2>(&self, f: F) -> Self {
+        Self::from_segments(self.segments.iter().map(|segment| segment.map_points(&f)).collect(), self.closed)
+    }
+
+    /// Get a copy of the spline scaled by 'factor' about the origin
+    pub fn scaled(&self, factor: f32) -> Self {
+        self.map_points(|point| point*factor)
+    }
+
+    /// Get a copy of the spline rotated counterclockwise by 'angle' radians about the origin
+    pub fn rotated(&self, angle: f32) -> Self {
+        self.map_points(|point| point.rotate(angle))
+    }
+
+    /// Get a copy of the spline mirrored in the x axis
+    pub fn mirrored(&self) -> Self {
+        self.map_points(|Vec2(x, y)| Vec2(x, -y))
+    }
+
+    /// Get the same spline traversed in the opposite direction, i.e. 'reversed.get(u)' equals
+    /// 'self.get(max_u - u)'
+    pub fn reversed(&self) -> Self {
+        Self::from_segments(self.segments.iter().rev().map(|segment| segment.reversed()).collect(), self.closed)
+    }
+
+    /// Get the same spline split into more segments, each with arc length at most
+    /// 'max_segment_length'. Each segment is split into pieces of equal arc length, so the shape
+    /// is unchanged but the parameterization differs.
+    pub fn subdivide(&self, max_segment_length: f32) -> Self {
+        assert!(max_segment_length > 0.0, "Maximum segment length must be positive, got {}", max_segment_length);
+        let segments = self.segments.iter()
+            .flat_map(|segment| {
+                let n_pieces = ((segment.total_length() / max_segment_length).ceil() as usize).max(1);
+                let boundaries: Vec<f32> = (0 ..= n_pieces)
+                    .map(|i| match i {
+                        0 => 0.0,
+                        i if i == n_pieces => 1.0,
+                        i => segment.t_at_arc_length(segment.total_length() * i as f32 / n_pieces as f32),
+                    })
+                    .collect();
+                boundaries.windows(2)
+                    .map(|bounds| segment.sub_curve(bounds[0], bounds[1]))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Self::from_segments(segments, self.closed)
     }
 
     fn segment_and_t(&self, u: f32) -> (&CubicBezier, usize, f32) {
+        // Parameters outside of [0, max_u] wrap around a closed spline
+        let u = if self.closed && !(0.0 ..= self.max_u).contains(&u) { u.rem_euclid(self.max_u) } else { u };
+
         // Edge case were rounding would give index error otherwise
         if u >= self.max_u {
             let i = self.segments.len() - 1;
@@ -218,21 +528,187 @@ impl SmoothBezierSpline {
         self.velocity(u).normalized()
     }
 
+    pub fn acceleration(&self, u: f32) -> Vec2 {
+        let (segment, _, t) = self.segment_and_t(u);
+        segment.acceleration(t)
+    }
+
+    /// Get the signed curvature at 'u', positive when the spline turns left (counterclockwise)
+    pub fn curvature(&self, u: f32) -> f32 {
+        let (segment, _, t) = self.segment_and_t(u);
+        segment.curvature(t)
+    }
+
     pub fn arc_length(&self, u: f32) -> f32 {
         let (active_segment, i, t) = self.segment_and_t(u);
 
-        // All prior segments have the full length contribute
-        let previous_length: f32 = self.segments[0..i].iter().map(|segment| segment.arc_length(1.0)).sum();
-
         // Arc length is prior length, plus the arc length on the active segment
-        previous_length + active_segment.arc_length(t)
+        self.cumulative_length[i] + active_segment.arc_length(t)
+    }
+
+    /// Find the parameter u at which the arc length from the start of the spline is 'arc', i.e.
+    /// the inverse of 'arc_length'. Arc lengths outside of the spline wrap around a closed spline,
+    /// and are clamped to the ends of an open one.
+    pub fn u_at_arc_length(&self, arc: f32) -> f32 {
+        let total_length = self.total_length();
+        let arc = if self.closed { arc.rem_euclid(total_length) } else { arc.clamp(0.0, total_length) };
+        let i = self.cumulative_length.partition_point(|&length| length <= arc)
+            .saturating_sub(1)
+            .min(self.segments.len() - 1);
+        i as f32 + self.segments[i].t_at_arc_length(arc - self.cumulative_length[i])
+    }
+
+    /// Get the smallest axis-aligned box containing the spline
+    pub fn aabb(&self) -> Aabb {
+        self.segments.iter()
+            .map(|segment| segment.aabb())
+            .reduce(|a, b| a.union(&b))
+            .expect("at least one segment")
+    }
+
+    /// Get the (min, max) corners of an axis-aligned box containing the spline
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        let Aabb { min, max } = self.aabb();
+        (min, max)
     }
 
     pub fn total_length(&self) -> f32 {
-        self.arc_length(self.segments.len() as f32)
+        *self.cumulative_length.last().expect("at least one length")
+    }
+
+    /// Get the point displaced 'distance' to the left of the spline at 'u'
+    pub fn offset_point(&self, u: f32, distance: f32) -> Vec2 {
+        self.get(u) + self.tangent(u).rotate90()*distance
+    }
+
+    /// Approximate the curve at a signed 'distance' to the left of the spline, e.g. a road edge, by
+    /// a polyline. See 'offset_with_tolerance'.
+    pub fn offset(&self, distance: f32) -> Vec<Vec2> {
+        self.offset_with_tolerance(distance, OFFSET_TOLERANCE)
+    }
+
+    /// Approximate the curve at a signed 'distance' to the left of the spline by a polyline, which
+    /// deviates at most roughly 'tolerance' from the curve. Points are placed adaptively, densely in
+    /// sharp corners and sparsely on straights. For a closed spline, the polyline is implicitly
+    /// closed and does not repeat its first point.
+    pub fn offset_with_tolerance(&self, distance: f32, tolerance: f32) -> Vec<Vec2> {
+        assert!(tolerance > 0.0, "Offset tolerance must be positive, got {}", tolerance);
+        let mut points = Vec::new();
+        let n_intervals = self.segments.len()*OFFSET_MIN_SUBDIVISIONS;
+        let du = 1.0 / OFFSET_MIN_SUBDIVISIONS as f32;
+        for i in 0 .. n_intervals {
+            let (u_start, u_end) = (i as f32*du, (i+1) as f32*du);
+            points.push(self.offset_point(u_start, distance));
+            self.subdivide_offset(u_start, u_end, distance, tolerance, OFFSET_MAX_DEPTH, &mut points);
+        }
+        if !self.closed {
+            points.push(self.offset_point(self.max_u, distance));
+        }
+        points
+    }
+
+    /// Recursively push the interior points of the offset curve between 'u_start' and 'u_end',
+    /// splitting the interval while its midpoint is farther than 'tolerance' from the chord
+    fn subdivide_offset(&self, u_start: f32, u_end: f32, distance: f32, tolerance: f32, depth: usize, points: &mut Vec<Vec2>) {
+        let u_mid = 0.5*(u_start + u_end);
+        let mid = self.offset_point(u_mid, distance);
+        let chord_mid = (self.offset_point(u_start, distance) + self.offset_point(u_end, distance))*0.5;
+        if depth == 0 || mid.distance(chord_mid) <= tolerance {
+            return;
+        }
+        self.subdivide_offset(u_start, u_mid, distance, tolerance, depth - 1, points);
+        points.push(mid);
+        self.subdivide_offset(u_mid, u_end, distance, tolerance, depth - 1, points);
+    }
+
+    /// Approximate the curve at a signed 'distance' to the left of the spline, e.g. a road edge, by
+    /// a sequence of cubic Bezier curves deviating at most roughly 'tolerance' from it. Unlike
+    /// 'offset_with_tolerance', the approximation is smooth and can be intersected exactly.
+    pub fn offset_curves(&self, distance: f32, tolerance: f32) -> Vec<CubicBezier> {
+        assert!(tolerance > 0.0, "Offset tolerance must be positive, got {}", tolerance);
+        let mut pieces = Vec::new();
+        let dt = 1.0 / OFFSET_MIN_SUBDIVISIONS as f32;
+        for segment in &self.segments {
+            for i in 0 .. OFFSET_MIN_SUBDIVISIONS {
+                segment.subdivide_offset_curve(i as f32*dt, (i+1) as f32*dt, distance, tolerance, OFFSET_MAX_DEPTH, &mut pieces);
+            }
+        }
+        pieces
+    }
+
+    /// Get the signed arc length travelled going from arc length 'from' to arc length 'to' along
+    /// the spline. On a closed spline, this is the shortest way around the loop, so that crossing
+    /// the seam gives a small rather than a lap-sized difference.
+    pub fn arc_delta(&self, from: f32, to: f32) -> f32 {
+        if self.closed {
+            let total_length = self.total_length();
+            (to - from + 1.5*total_length).rem_euclid(total_length) - 0.5*total_length
+        } else {
+            to - from
+        }
+    }
+
+    /// Build a uniform grid of cells of side 'cell_size' over the spline, padded by 'padding',
+    /// which lists the segments that may be closest to points in each cell. Closest point queries
+    /// inside the grid then only search a few nearby segments. The results are unchanged.
+    pub fn build_index(&mut self, cell_size: f32, padding: f32) {
+        let bounds: Vec<Aabb> = self.segments.iter().map(|segment| segment.aabb()).collect();
+        self.index = Some(SegmentGrid::new(&bounds, cell_size, padding));
     }
 
     pub fn closest_point(&self, point: Vec2) -> ClosestPointOutput {
+        match self.index.as_ref().and_then(|index| index.candidates(point)) {
+            Some(candidates) => self.closest_point_among(point, candidates.iter().copied()),
+            None => self.closest_point_among(point, 0 .. self.segments.len()),
+        }
+    }
+
+    /// Find the closest points to a batch of points, e.g. all pixels of an image.
+    ///
+    /// Points in the spatial index use its candidates, while the segments which may be closest to
+    /// the remaining points are found once for the bounding box of the whole batch. The queries
+    /// run in parallel with the 'rayon' feature. The results equal those of 'closest_point'.
+    pub fn closest_points(&self, points: &[Vec2]) -> Vec<ClosestPointOutput> {
+        let indexed = |point: Vec2| self.index.as_ref().and_then(|index| index.candidates(point));
+
+        let unindexed_box = Aabb::from_points(points.iter().copied().filter(|&point| indexed(point).is_none()));
+        let batch_candidates = unindexed_box.map(|region| {
+            let bounds: Vec<Aabb> = self.segments.iter().map(|segment| segment.aabb()).collect();
+            box_candidates(&bounds, &region)
+        }).unwrap_or_default();
+
+        let query = |&point: &Vec2| match indexed(point) {
+            Some(candidates) => self.closest_point_among(point, candidates.iter().copied()),
+            None => self.closest_point_among(point, batch_candidates.iter().copied()),
+        };
+
+        #[cfg(feature = "rayon")]
+        let closest = points.par_iter().map(query).collect();
+        #[cfg(not(feature = "rayon"))]
+        let closest = points.iter().map(query).collect();
+        closest
+    }
+
+    /// Find the closest point among the segments within 'window' of the parameter 'u_hint', e.g.
+    /// the closest parameter at the previous time step. Much cheaper than 'closest_point' when
+    /// the point moves little between queries, but only finds the closest point near the hint.
+    pub fn closest_point_near(&self, point: Vec2, u_hint: f32, window: f32) -> ClosestPointOutput {
+        let n_segments = self.segments.len() as isize;
+        let first = (u_hint - window).floor() as isize;
+        let last = (u_hint + window).floor() as isize;
+        if last - first + 1 >= n_segments {
+            self.closest_point(point)
+        } else if self.closed {
+            self.closest_point_among(point, (first ..= last).map(|i| i.rem_euclid(n_segments) as usize))
+        } else {
+            let first = first.clamp(0, n_segments - 1) as usize;
+            let last = last.clamp(0, n_segments - 1) as usize;
+            self.closest_point_among(point, first ..= last)
+        }
+    }
+
+    /// Find the closest point on a subset of the segments, given by their indices
+    fn closest_point_among<I: Iterator<Item=usize> + Clone>(&self, point: Vec2, indices: I) -> ClosestPointOutput {
 
         // First inspect bounding boxes to get upper bound on distance_sq
         //
@@ -240,9 +716,10 @@ impl SmoothBezierSpline {
         let mut min_d2 = Vec::<f32>::with_capacity(self.segments.len());
         let mut upper_bound = f32::INFINITY;
 
-        for segment in &self.segments {
-            let closest_point = segment.bounding_box.closest_point(point);
-            let farthest_point = segment.bounding_box.farthest_point(point);
+        for i in indices.clone() {
+            let bounding_box = &self.segments[i].bounding_box;
+            let closest_point = bounding_box.closest_point(point);
+            let farthest_point = bounding_box.farthest_point(point);
 
             let closest_d2 = {
                 let delta = closest_point - point;
@@ -259,14 +736,13 @@ impl SmoothBezierSpline {
             min_d2.push(closest_d2)
         }
 
-        let points = self.segments
-            .iter()
-            .enumerate()
-            .filter_map(|(i, segment)| {
-                if min_d2[i] > upper_bound {
+        let points = indices
+            .zip(min_d2)
+            .filter_map(|(i, min_d2)| {
+                if min_d2 > upper_bound {
                     None
                 } else {
-                    let point_output = segment.closest_point(point);
+                    let point_output = self.segments[i].closest_point(point);
                     Some(ClosestPointOutput { 
                         parameter: i as f32 + point_output.parameter,
                         distance_sq: point_output.distance_sq
@@ -381,7 +857,28 @@ mod tests {
                                                   BezierControl{ point: Vec2(24.0, 18.0), velocity: Vec2(4.0, 3.0)}]);
         assert!(spline.arc_length(1.0 / 3.0) > 4.99);
         assert!(spline.arc_length(1.0 / 3.0) < 5.01);
-        assert_eq!(spline.arc_length(1.0 + 1.0 / 3.0), 20.0);
+        assert!((spline.arc_length(1.0 + 1.0 / 3.0) - 20.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_arc_length_inverse() {
+        let bezier = setup_bezier();
+        for t in [0.0, 0.01, 0.3, 0.5, 0.77, 1.0] {
+            assert!((bezier.t_at_arc_length(bezier.arc_length(t)) - t).abs() < 1e-4);
+        }
+
+        let spline = setup_spline();
+        for u in [0.0, 0.4, 1.0, 1.2, 1.9] {
+            assert!((spline.u_at_arc_length(spline.arc_length(u)) - u).abs() < 1e-4);
+        }
+        assert_eq!(spline.u_at_arc_length(-1.0), 0.0);
+        assert_eq!(spline.u_at_arc_length(spline.total_length() + 1.0), 2.0);
+
+        // The table agrees with a fine trapezoid integration of the speed
+        let steps = 10000;
+        let dt = 1.0 / steps as f32;
+        let reference: f32 = (0 .. steps).map(|i| 0.5*dt*(bezier.velocity(i as f32*dt).norm() + bezier.velocity((i+1) as f32*dt).norm())).sum();
+        assert!((bezier.total_length() - reference).abs() < 1e-4);
     }
 
     #[test]
@@ -401,9 +898,119 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_closest_accuracy() {
+        // A straight curve with B(t) = (3t, 0), so that the closest parameter is x/3
+        let line = CubicBezier::new(Vec2(0.0, 0.0), Vec2(1.0, 0.0), Vec2(2.0, 0.0), Vec2(3.0, 0.0));
+        for x in [0.123_456, 1.234_567, 2.987_654] {
+            let output = line.closest_point(Vec2(x, 2.0));
+            assert!((output.parameter - x / 3.0).abs() < 1e-5, "{} != {}", output.parameter, x / 3.0);
+            assert!((output.distance_sq - 4.0).abs() < 1e-5);
+        }
+
+        // On a curved segment, the closest point is where the offset is normal to the curve
+        let bezier = setup_bezier();
+        for point in [Vec2(0.3, 2.0), Vec2(-0.7, 0.2), Vec2(0.8, 1.0)] {
+            let t = bezier.closest_point(point).parameter;
+            let delta = bezier.get(t) - point;
+            assert!(delta.dot(bezier.tangent(t)).abs() < 1e-5, "{:?}", point);
+        }
+    }
+
+    #[test]
+    fn test_closest_near() {
+        let spline = setup_spline();
+        for point in [Vec2(0.5, 5.0), Vec2(1.5, -5.0), Vec2(1.2, 0.1)] {
+            let exact = spline.closest_point(point);
+            assert_eq!(spline.closest_point_near(point, exact.parameter, 0.5), exact);
+        }
+
+        // Only segments near the hint are searched; the closest point on the first segment is
+        // (0.5, 0.75), but the hint is on the second segment
+        let near = spline.closest_point_near(Vec2(0.5, 2.0), 1.7, 0.2);
+        assert!(near.parameter >= 1.0);
+        let on_second = spline.segments[1].closest_point(Vec2(0.5, 2.0));
+        assert_eq!(near, ClosestPointOutput { parameter: 1.0 + on_second.parameter, distance_sq: on_second.distance_sq });
+        assert!(near.distance_sq > spline.closest_point(Vec2(0.5, 2.0)).distance_sq);
+
+        // Windows wrap around the seam of a closed spline
+        let square = [Vec2(0.0, 0.0), Vec2(10.0, 0.0), Vec2(10.0, 10.0), Vec2(0.0, 10.0)];
+        let closed = SmoothBezierSpline::through_points(&square, 0.5);
+        let point = Vec2(-1.0, 2.0);
+        assert_eq!(closed.closest_point_near(point, 0.1, 0.5), closed.closest_point(point));
+    }
+
+    #[test]
+    fn test_index() {
+        let square = [Vec2(0.0, 0.0), Vec2(10.0, 0.0), Vec2(15.0, 10.0), Vec2(5.0, 12.0), Vec2(0.0, 10.0)];
+        let spline = SmoothBezierSpline::through_points(&square, 0.5);
+        let mut indexed = SmoothBezierSpline::through_points(&square, 0.5);
+        indexed.build_index(1.0, 3.0);
+
+        // The index gives the same results inside and outside of the grid
+        for i in -10 ..= 30 {
+            for j in -10 ..= 30 {
+                let point = Vec2(i as f32 * 0.7, j as f32 * 0.6);
+                assert_eq!(indexed.closest_point(point), spline.closest_point(point), "point {:?}", point);
+            }
+        }
+    }
+
+    #[test]
+    fn test_closest_points() {
+        let square = [Vec2(0.0, 0.0), Vec2(10.0, 0.0), Vec2(10.0, 10.0), Vec2(0.0, 10.0)];
+        let mut spline = SmoothBezierSpline::through_points(&square, 0.5);
+        let points: Vec<Vec2> = (0 .. 100)
+            .map(|i| Vec2((i % 10) as f32 * 3.0 - 10.0, (i / 10) as f32 * 3.0 - 10.0))
+            .collect();
+        assert_eq!(spline.closest_points(&points), points.iter().map(|&point| spline.closest_point(point)).collect::<Vec<_>>());
+
+        // With an index covering only some of the points
+        spline.build_index(1.0, 1.0);
+        assert_eq!(spline.closest_points(&points), points.iter().map(|&point| spline.closest_point(point)).collect::<Vec<_>>());
+        assert!(spline.closest_points(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_ray_intersections() {
+        let bezier = setup_bezier();
+        // The curve arcs from (-1, 0) up to (0, 0.75) and back down to (1, 0)
+        let distances = bezier.ray_intersections(Vec2(-5.0, 0.5), Vec2(2.0, 0.0));
+        assert_eq!(distances.len(), 2);
+        for distance in distances {
+            let hit = Vec2(-5.0 + distance, 0.5);
+            assert!(bezier.closest_point(hit).distance_sq < 1e-6);
+        }
+        // Only intersections ahead of the origin are found
+        assert_eq!(bezier.ray_intersections(Vec2(0.0, 0.5), Vec2(1.0, 0.0)).len(), 1);
+        assert!(bezier.ray_intersections(Vec2(-5.0, 0.5), Vec2(-1.0, 0.0)).is_empty());
+        assert!(bezier.ray_intersections(Vec2(-5.0, 1.0), Vec2(1.0, 0.0)).is_empty());
+    }
+
+    #[test]
+    fn test_offset_curves() {
+        let circle = SmoothBezierSpline::new_closed(
+            [(1.0, 0.0), (0.0, 1.0), (-1.0, 0.0), (0.0, -1.0)].into_iter()
+                .zip([(0.0, 1.0), (-1.0, 0.0), (0.0, -1.0), (1.0, 0.0)])
+                .map(|((x, y), (vx, vy))| BezierControl { point: Vec2(x, y)*10.0, velocity: Vec2(vx, vy)*5.5 })
+                .collect()
+        );
+        for distance in [-2.0, 3.0] {
+            let pieces = circle.offset_curves(distance, 0.01);
+            assert!(pieces.len() >= 4*OFFSET_MIN_SUBDIVISIONS);
+            for piece in &pieces {
+                for t in [0.0, 0.3, 0.5, 0.9] {
+                    let point = piece.get(t);
+                    let closest = circle.closest_point(point);
+                    assert!((closest.distance_sq.sqrt() - distance.abs()).abs() < 0.02, "{:?}", point);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_bounding_box() {
-        let bbox = BoundingBox::new(-1.0, 1.0, -1.0, 1.0);
+        let bbox = Aabb::new(Vec2(-1.0, -1.0), Vec2(1.0, 1.0));
         assert_eq!(bbox.closest_point(Vec2(-2.0, -2.0)), Vec2(-1.0, -1.0));
         assert_eq!(bbox.closest_point(Vec2(2.0, -2.0)), Vec2(1.0, -1.0));
         assert_eq!(bbox.closest_point(Vec2(2.0, 2.0)), Vec2(1.0, 1.0));
@@ -419,11 +1026,216 @@ mod tests {
         let bezier = setup_bezier();
 
         let bbox = bezier._bounding_box();
-        assert_eq!(bbox.min_x, -1.0);
-        assert_eq!(bbox.max_x, 1.0);
-        assert_eq!(bbox.min_y, 0.0);
-        assert_eq!(bbox.max_y, bezier.get(0.5).1);
-        assert!(bbox.max_y > 0.0);
-        assert!(bbox.max_y < 1.0);
+        assert_eq!(bbox.min.0, -1.0);
+        assert_eq!(bbox.max.0, 1.0);
+        assert_eq!(bbox.min.1, 0.0);
+        assert_eq!(bbox.max.1, bezier.get(0.5).1);
+        assert!(bbox.max.1 > 0.0);
+        assert!(bbox.max.1 < 1.0);
+
+        let spline = setup_spline();
+        let (min, max) = spline.bounds();
+        assert_eq!(min.0, 0.0);
+        assert_eq!(max.0, 2.0);
+        assert_eq!(min.1, spline.get(1.5).1);
+        assert_eq!(max.1, spline.get(0.5).1);
+
+        // An asymmetric curve, where y is largest at the root of 30t^2 - 36t + 9
+        let bezier = CubicBezier::new(Vec2(0.0, 0.0), Vec2(1.0, 3.0), Vec2(2.0, 0.0), Vec2(3.0, 1.0));
+        let bbox = bezier._bounding_box();
+        let t_max = (36.0 - 216.0_f32.sqrt()) / 60.0;
+        assert!((bbox.max.1 - bezier.get(t_max).1).abs() < 1e-6);
+        assert_eq!((bbox.min.0, bbox.max.0, bbox.min.1), (0.0, 3.0, 0.0));
+        for i in 0 ..= 1000 {
+            assert!(bezier.get(i as f32 / 1000.0).1 <= bbox.max.1 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_curvature() {
+        // Standard cubic Bezier approximation of a quarter circle of radius r
+        let r = 5.0;
+        let k = 0.5522848*r;
+        let arc = CubicBezier::new(Vec2(r, 0.0), Vec2(r, k), Vec2(k, r), Vec2(0.0, r));
+        for t in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            assert!((arc.curvature(t) - 1.0/r).abs() < 0.03/r, "curvature {} at t={}", arc.curvature(t), t);
+        }
+
+        // Acceleration is consistent with a finite difference of the velocity
+        let epsilon = 1e-3;
+        let a_fd = (arc.velocity(0.3 + epsilon) - arc.velocity(0.3)) / epsilon;
+        assert!((a_fd - arc.acceleration(0.3)).norm() < 0.01*arc.acceleration(0.3).norm());
+
+        // A full circle turning counterclockwise, then mirrored to turn clockwise
+        let controls = [Vec2(0.0, 1.0), Vec2(-1.0, 0.0), Vec2(0.0, -1.0), Vec2(1.0, 0.0)].iter()
+            .map(|&direction| BezierControl { point: direction.rotate90()*(-r), velocity: direction*k })
+            .collect();
+        let circle = SmoothBezierSpline::new_closed(controls);
+        assert!((circle.get(0.0) - Vec2(r, 0.0)).norm() < 1e-5);
+        assert!((circle.total_length() - std::f32::consts::TAU*r).abs() < 0.01);
+        for u in [0.1, 1.5, 2.9, 3.6] {
+            assert!((circle.curvature(u) - 1.0/r).abs() < 0.03/r);
+            assert!((circle.mirrored().curvature(u) + 1.0/r).abs() < 0.03/r);
+        }
+
+        // Straight lines have no curvature
+        assert_eq!(CubicBezier::new(Vec2(0.0, 0.0), Vec2(1.0, 1.0), Vec2(2.0, 2.0), Vec2(3.0, 3.0)).curvature(0.4), 0.0);
+    }
+
+    #[test]
+    fn test_offset() {
+        // Counterclockwise circle of radius 5, offset to the inside and outside
+        let r = 5.0;
+        let k = 0.5522848*r;
+        let controls = [Vec2(0.0, 1.0), Vec2(-1.0, 0.0), Vec2(0.0, -1.0), Vec2(1.0, 0.0)].iter()
+            .map(|&direction| BezierControl { point: direction.rotate90()*(-r), velocity: direction*k })
+            .collect();
+        let circle = SmoothBezierSpline::new_closed(controls);
+
+        for distance in [-1.0, 0.0, 2.0] {
+            let edge = circle.offset_with_tolerance(distance, 0.01);
+            assert!(edge.len() >= 16);
+            for (a, b) in edge.iter().zip(edge.iter().cycle().skip(1)) {
+                assert!((a.norm() - (r - distance)).abs() < 0.02);
+                assert!(((*a + *b)*0.5).norm() > r - distance - 0.03);
+            }
+        }
+
+        // Tighter tolerances give more points, and straights need no refinement
+        assert!(circle.offset_with_tolerance(1.0, 0.001).len() > circle.offset_with_tolerance(1.0, 0.1).len());
+        let line = SmoothBezierSpline::new(vec![BezierControl { point: Vec2(0.0, 0.0), velocity: Vec2(1.0, 0.0) },
+                                                BezierControl { point: Vec2(3.0, 0.0), velocity: Vec2(1.0, 0.0) }]);
+        let edge = line.offset(1.0);
+        assert_eq!(edge.len(), OFFSET_MIN_SUBDIVISIONS + 1);
+        assert_eq!(edge.first(), Some(&Vec2(0.0, 1.0)));
+        assert_eq!(edge.last(), Some(&Vec2(3.0, 1.0)));
+    }
+
+    #[test]
+    fn test_through_points() {
+        let square = [Vec2(0.0, 0.0), Vec2(10.0, 0.0), Vec2(10.0, 10.0), Vec2(0.0, 10.0)];
+        let spline = SmoothBezierSpline::through_points(&square, 0.5);
+        assert!(spline.is_closed());
+        assert_eq!(spline.max_u, 4.0);
+        for (i, &point) in square.iter().enumerate() {
+            assert_eq!(spline.get(i as f32), point);
+        }
+        // At each corner, the spline heads from the previous towards the next waypoint
+        assert!((spline.tangent(1.0) - Vec2(1.0, 1.0).normalized()).norm() < 1e-5);
+        assert!((spline.velocity(0.0) - Vec2(10.0, -10.0)*0.5).norm() < 1e-5);
+
+        // A repeated closing point gives the same spline, and lower tension gives tighter corners
+        let mut closed_square = square.to_vec();
+        closed_square.push(square[0]);
+        assert_eq!(SmoothBezierSpline::through_points(&closed_square, 0.5).get(0.5), spline.get(0.5));
+        let tight = SmoothBezierSpline::through_points(&square, 0.1);
+        assert!(tight.total_length() < spline.total_length());
+    }
+
+    #[test]
+    fn test_split() {
+        let bezier = setup_bezier();
+        let (first, second) = bezier.split(0.25);
+        assert_eq!(first.start, bezier.start);
+        assert_eq!(second.end, bezier.end);
+        assert_eq!(first.end, bezier.get(0.25));
+        assert!((first.get(0.5) - bezier.get(0.125)).norm() < 1e-6);
+        assert!((second.get(0.5) - bezier.get(0.625)).norm() < 1e-6);
+        assert!((first.total_length() + second.total_length() - bezier.total_length()).abs() < 1e-4);
+
+        // The halves keep the direction of the curve at the split
+        assert!((first.tangent(1.0) - bezier.tangent(0.25)).norm() < 1e-5);
+        assert!((second.tangent(0.0) - bezier.tangent(0.25)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn test_subdivide() {
+        let spline = setup_spline();
+        let subdivided = spline.subdivide(0.5);
+        assert!(subdivided.segments.len() > spline.segments.len());
+        assert!(subdivided.segments.iter().all(|segment| segment.total_length() <= 0.5 + 1e-4));
+        assert!((subdivided.total_length() - spline.total_length()).abs() < 1e-3);
+        assert_eq!(subdivided.get(0.0), spline.get(0.0));
+        assert!((subdivided.get(subdivided.max_u) - spline.get(spline.max_u)).norm() < 1e-5);
+
+        // Same shape, so points on the original lie on the subdivided spline
+        for u in [0.3, 0.9, 1.6] {
+            assert!(subdivided.closest_point(spline.get(u)).distance_sq < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        let json = serde_json::to_string(&Vec2(1.0, -2.5)).expect("serializable vector");
+        assert_eq!(json, "[1.0,-2.5]");
+
+        let control: BezierControl = serde_json::from_str(r#"{"point": [1, 2], "velocity": [0, 3]}"#).expect("valid control");
+        assert_eq!(control, BezierControl { point: Vec2(1.0, 2.0), velocity: Vec2(0.0, 3.0) });
+
+        // Cached coefficients are reconstructed on load
+        let spline = setup_spline();
+        let json = serde_json::to_string(&spline).expect("serializable spline");
+        let loaded: SmoothBezierSpline = serde_json::from_str(&json).expect("valid spline");
+        assert_eq!(loaded.max_u, spline.max_u);
+        assert!(!loaded.is_closed());
+        assert_eq!(loaded.total_length(), spline.total_length());
+        assert_eq!(loaded.get(1.3), spline.get(1.3));
+        assert_eq!(loaded.bounds(), spline.bounds());
+    }
+
+    #[test]
+    fn test_closed() {
+        let controls = || vec![BezierControl{ point: Vec2(0.0, 0.0), velocity: Vec2(1.0, 0.0)},
+                               BezierControl{ point: Vec2(2.0, 2.0), velocity: Vec2(0.0, 1.0)},
+                               BezierControl{ point: Vec2(0.0, 4.0), velocity: Vec2(-1.0, 0.0)},
+                               BezierControl{ point: Vec2(-2.0, 2.0), velocity: Vec2(0.0, -1.0)}];
+        let spline = SmoothBezierSpline::new_closed(controls());
+        assert!(spline.is_closed());
+        assert_eq!(spline.max_u, 4.0);
+
+        // The seam is C1 continuous, and the parameter wraps around it
+        assert_eq!(spline.get(4.0), spline.get(0.0));
+        assert_eq!(spline.velocity(4.0), spline.velocity(0.0));
+        assert_eq!(spline.get(4.5), spline.get(0.5));
+        assert_eq!(spline.get(-0.5), spline.get(3.5));
+
+        // A repeated closing control point is not duplicated
+        let mut repeated = controls();
+        repeated.push(BezierControl { point: Vec2(0.0, 0.0), velocity: Vec2(1.0, 0.0) });
+        assert_eq!(SmoothBezierSpline::new_closed(repeated).max_u, 4.0);
+
+        let total_length = spline.total_length();
+        assert!((spline.arc_delta(total_length - 1.0, 1.0) - 2.0).abs() < 1e-4);
+        assert!((spline.arc_delta(1.0, total_length - 1.0) + 2.0).abs() < 1e-4);
+        assert!((spline.arc_delta(1.0, 3.0) - 2.0).abs() < 1e-4);
+
+        let open = SmoothBezierSpline::new(controls());
+        assert!(!open.is_closed());
+        assert_eq!(open.arc_delta(open.total_length() - 1.0, 1.0), 2.0 - open.total_length());
+    }
+
+    #[test]
+    fn test_transformations() {
+        let spline = setup_spline();
+        let close = |a: Vec2, b: Vec2| (a - b).norm() < 1e-5;
+
+        let scaled = spline.scaled(2.0);
+        assert!((scaled.total_length() - 2.0*spline.total_length()).abs() < 1e-4);
+        assert!(close(scaled.get(0.5), spline.get(0.5)*2.0));
+
+        let rotated = spline.rotated(std::f32::consts::FRAC_PI_2);
+        assert!(close(rotated.get(0.5), spline.get(0.5).rotate90()));
+        assert!(close(rotated.tangent(1.25), spline.tangent(1.25).rotate90()));
+
+        let mirrored = spline.mirrored();
+        let Vec2(x, y) = spline.get(1.5);
+        assert!(close(mirrored.get(1.5), Vec2(x, -y)));
+
+        let reversed = spline.reversed();
+        assert_eq!(reversed.max_u, spline.max_u);
+        for u in [0.0, 0.25, 1.0, 1.75, 2.0] {
+            assert!(close(reversed.get(u), spline.get(spline.max_u - u)));
+            assert!(close(reversed.tangent(u), -spline.tangent(spline.max_u - u)));
+        }
     }
 }
@@ -1,7 +1,10 @@
 use std::ops::{Add, Sub, Mul, Div, Neg};
 
+use serde::{Serialize, Deserialize};
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+
+/// A 2D vector, serialized as an '[x, y]' array
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Vec2(pub f32, pub f32);
 
 
@@ -69,6 +72,45 @@ impl Vec2 {
         let cos = angle.cos();
         Vec2(self.0 * cos - self.1 * sin, self.0 * sin + self.1 * cos)
     }
+
+    /// The z component of the 3D cross product, positive when 'rhs' is counterclockwise of 'self'
+    pub fn cross(self, rhs: Self) -> f32 {
+        self.0 * rhs.1 - self.1 * rhs.0
+    }
+
+    /// The dot product of 'self.rotate90()' with 'rhs', which equals 'self.cross(rhs)'
+    pub fn perp_dot(self, rhs: Self) -> f32 {
+        self.rotate90().dot(rhs)
+    }
+
+    /// The counterclockwise angle from the x axis, in (-pi, pi]
+    pub fn angle(self) -> f32 {
+        self.1.atan2(self.0)
+    }
+
+    /// The signed counterclockwise angle from 'self' to 'rhs', in (-pi, pi]
+    pub fn angle_between(self, rhs: Self) -> f32 {
+        self.cross(rhs).atan2(self.dot(rhs))
+    }
+
+    /// Linear interpolation, giving 'self' at t=0 and 'rhs' at t=1
+    pub fn lerp(self, rhs: Self, t: f32) -> Self {
+        self + (rhs - self) * t
+    }
+
+    pub fn distance(self, rhs: Self) -> f32 {
+        (rhs - self).norm()
+    }
+
+    /// The component of 'self' along 'onto'
+    pub fn project_onto(self, onto: Self) -> Self {
+        onto * (self.dot(onto) / onto.dot(onto))
+    }
+
+    /// Reflect 'self' in a line with the unit normal 'normal', e.g. a velocity bouncing off a wall
+    pub fn reflect(self, normal: Self) -> Self {
+        self - normal * (2.0 * self.dot(normal))
+    }
 }
 
 
@@ -139,6 +181,47 @@ mod tests {
         let v1 = Vec2(3.0, 4.0);
         assert_eq!(v1.norm(), 5.0);
     }
+
+    #[test]
+    fn test_cross() {
+        let v1 = Vec2(1.0, 0.0);
+        let v2 = Vec2(2.0, 3.0);
+        assert_eq!(v1.cross(v2), 3.0);
+        assert_eq!(v2.cross(v1), -3.0);
+        assert_eq!(v2.perp_dot(v1), v2.cross(v1));
+        assert_eq!(v2.cross(v2 * 2.0), 0.0);
+    }
+
+    #[test]
+    fn test_angle() {
+        use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+        assert_eq!(Vec2(1.0, 0.0).angle(), 0.0);
+        assert_eq!(Vec2(0.0, 2.0).angle(), FRAC_PI_2);
+        assert_eq!(Vec2(-1.0, 0.0).angle(), PI);
+        assert!((Vec2(1.0, 0.0).angle_between(Vec2(1.0, 1.0)) - FRAC_PI_4).abs() < 1e-6);
+        assert!((Vec2(1.0, 1.0).angle_between(Vec2(1.0, 0.0)) + FRAC_PI_4).abs() < 1e-6);
+        assert!((Vec2(3.0, 1.0).rotate(2.0).angle() - Vec2(3.0, 1.0).angle() - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_lerp_distance() {
+        let v1 = Vec2(1.0, 2.0);
+        let v2 = Vec2(4.0, 6.0);
+        assert_eq!(v1.lerp(v2, 0.0), v1);
+        assert_eq!(v1.lerp(v2, 1.0), v2);
+        assert_eq!(v1.lerp(v2, 0.5), Vec2(2.5, 4.0));
+        assert_eq!(v1.distance(v2), 5.0);
+        assert_eq!(v2.distance(v1), 5.0);
+    }
+
+    #[test]
+    fn test_project_reflect() {
+        let v1 = Vec2(3.0, 4.0);
+        assert_eq!(v1.project_onto(Vec2(2.0, 0.0)), Vec2(3.0, 0.0));
+        assert_eq!(v1.project_onto(Vec2(0.0, -1.0)), Vec2(0.0, 4.0));
+        assert_eq!(v1.reflect(Vec2(0.0, 1.0)), Vec2(3.0, -4.0));
+        assert_eq!(v1.reflect(Vec2(-1.0, 0.0)), Vec2(-3.0, 4.0));
+    }
 }
 
 